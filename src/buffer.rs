@@ -62,6 +62,24 @@ impl<R: Read> BufReader<R> {
         }
     }
 
+    /// Puts `bytes` ahead of what is buffered, so they are read first, such
+    /// as bytes read from the inner reader before this was made.
+    pub fn unread(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let mut buf = Vec::with_capacity(cmp::max(self.buf.len(),
+                                                  bytes.len() + self.cap - self.pos));
+        buf.extend_from_slice(bytes);
+        buf.extend_from_slice(&self.buf[self.pos..self.cap]);
+        self.pos = 0;
+        self.cap = buf.len();
+        // the whole capacity is read into
+        let len = buf.capacity();
+        buf.resize(len, 0);
+        self.buf = buf;
+    }
+
     #[inline]
     fn maybe_reserve(&mut self) {
         let cap = self.buf.capacity();
@@ -135,6 +153,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_unread() {
+        let mut rdr = BufReader::with_capacity(SlowRead(0), 2);
+        rdr.unread(b"hello ");
+        let mut s = String::new();
+        rdr.by_ref().take(12).read_to_string(&mut s).unwrap();
+        assert_eq!(s, "hello foobar");
+    }
+
     #[test]
     fn test_consume_and_get_buf() {
         let mut rdr = BufReader::new(SlowRead(0));
@@ -0,0 +1,316 @@
+//! A C interface to the client and the request parser.
+//!
+//! This is enabled with the `capi` feature, so that applications not
+//! written in Rust can embed hyper, by building it as a `staticlib` or
+//! `cdylib` and declaring these functions in C.
+//!
+//! Handles are opaque pointers, made by one function and freed by its
+//! matching `*_free`. Strings passed in are NUL-terminated UTF-8, and bytes
+//! are passed as a pointer and a length, which are only borrowed for the
+//! call.
+//!
+//! A panic doesn't unwind into the caller: the function it happened in
+//! returns as if it failed instead.
+use std::ffi::CStr;
+use std::io::Read;
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use client::{Client, Response};
+use http::h1::{self, ColonWhitespace};
+use method::Method;
+
+/// Called with the method and request-target of a parsed request.
+///
+/// Returning anything other than `0` stops the parse.
+pub type LineCallback = extern "C" fn(userdata: *mut c_void,
+                                      method: *const u8, method_len: usize,
+                                      target: *const u8, target_len: usize) -> c_int;
+
+/// Called with the name and value of each header field.
+///
+/// Returning anything other than `0` stops the iteration.
+pub type HeaderCallback = extern "C" fn(userdata: *mut c_void,
+                                        name: *const u8, name_len: usize,
+                                        value: *const u8, value_len: usize) -> c_int;
+
+/// The callbacks registered for `hyper_parse_request`. Either can be null.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ParserCallbacks {
+    /// Passed to each callback.
+    pub userdata: *mut c_void,
+    /// Called first, with the request line.
+    pub on_request_line: Option<LineCallback>,
+    /// Called with each header field after.
+    pub on_header: Option<HeaderCallback>,
+}
+
+/// Creates a client, to be freed with `hyper_client_free`.
+#[no_mangle]
+pub extern "C" fn hyper_client_new() -> *mut Client {
+    catch(ptr::null_mut(), || {
+        Box::into_raw(Box::new(Client::new()))
+    })
+}
+
+/// Frees a client. Responses it returned stay valid.
+#[no_mangle]
+pub unsafe extern "C" fn hyper_client_free(client: *mut Client) {
+    catch((), || {
+        if !client.is_null() {
+            drop(Box::from_raw(client));
+        }
+    })
+}
+
+/// Sends a request, waiting for the head of its response, which is to be
+/// freed with `hyper_response_free`.
+///
+/// `body` can be null if `body_len` is `0`. Returns null if the method or
+/// URL are invalid, or the request failed.
+#[no_mangle]
+pub unsafe extern "C" fn hyper_client_request(client: *const Client,
+                                              method: *const c_char,
+                                              url: *const c_char,
+                                              body: *const u8,
+                                              body_len: usize) -> *mut Response {
+    catch(ptr::null_mut(), || {
+        if client.is_null() || method.is_null() || url.is_null() {
+            return ptr::null_mut();
+        }
+        let method = match CStr::from_ptr(method).to_str().map(str::parse::<Method>) {
+            Ok(Ok(method)) => method,
+            _ => return ptr::null_mut()
+        };
+        let url = match CStr::from_ptr(url).to_str() {
+            Ok(url) => url,
+            Err(_) => return ptr::null_mut()
+        };
+        let body = bytes(body, body_len);
+
+        match (*client).request(method, url).body(body).send() {
+            Ok(res) => Box::into_raw(Box::new(res)),
+            Err(e) => {
+                debug!("capi request error: {:?}", e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Gets the status code of a response.
+#[no_mangle]
+pub unsafe extern "C" fn hyper_response_status(res: *const Response) -> u16 {
+    catch(0, || {
+        if res.is_null() {
+            return 0;
+        }
+        (*res).status.to_u16()
+    })
+}
+
+/// Calls `on_header` with each header field of a response.
+///
+/// Returns `0` once every field was visited, what the callback returned
+/// to stop, or `-1` if `res` or `on_header` is null.
+#[no_mangle]
+pub unsafe extern "C" fn hyper_response_headers(res: *const Response,
+                                                on_header: Option<HeaderCallback>,
+                                                userdata: *mut c_void) -> c_int {
+    catch(-1, || {
+        let on_header = match on_header {
+            Some(on_header) if !res.is_null() => on_header,
+            _ => return -1
+        };
+        for header in (*res).headers.iter() {
+            let (name, value) = (header.name(), header.value_string());
+            let ret = on_header(userdata, name.as_ptr(), name.len(), value.as_ptr(), value.len());
+            if ret != 0 {
+                return ret;
+            }
+        }
+        0
+    })
+}
+
+/// Reads some of the body of a response into `buf`.
+///
+/// Returns how many bytes were read, `0` at the end of the body, or `-1`
+/// on an error.
+#[no_mangle]
+pub unsafe extern "C" fn hyper_response_read(res: *mut Response, buf: *mut u8,
+                                             buf_len: usize) -> isize {
+    catch(-1, || {
+        if res.is_null() || (buf.is_null() && buf_len != 0) {
+            return -1;
+        }
+        if buf_len == 0 {
+            return 0;
+        }
+        match (*res).read(slice::from_raw_parts_mut(buf, buf_len)) {
+            Ok(len) => len as isize,
+            Err(e) => {
+                debug!("capi read error: {:?}", e);
+                -1
+            }
+        }
+    })
+}
+
+/// Frees a response, closing its connection if the body wasn't read.
+#[no_mangle]
+pub unsafe extern "C" fn hyper_response_free(res: *mut Response) {
+    catch((), || {
+        if !res.is_null() {
+            drop(Box::from_raw(res));
+        }
+    })
+}
+
+/// Parses a request head from the start of `buf`, passing its parts to
+/// the callbacks.
+///
+/// Returns the length of the head, `0` if `buf` doesn't hold all of it yet,
+/// or `-1` if it is invalid or a callback stopped the parse.
+#[no_mangle]
+pub unsafe extern "C" fn hyper_parse_request(buf: *const u8, buf_len: usize,
+                                             callbacks: *const ParserCallbacks) -> isize {
+    catch(-1, || {
+        let head = match h1::try_parse_request(bytes(buf, buf_len), ColonWhitespace::Reject) {
+            Ok(Some((head, len))) => (head, len),
+            Ok(None) => return 0,
+            Err(e) => {
+                debug!("capi parse error: {:?}", e);
+                return -1;
+            }
+        };
+        if callbacks.is_null() {
+            return head.1 as isize;
+        }
+        let callbacks = &*callbacks;
+        let (ref incoming, len) = head;
+
+        if let Some(on_request_line) = callbacks.on_request_line {
+            let (ref method, ref uri) = incoming.subject;
+            let (method, target) = (method.as_ref(), uri.to_string());
+            if on_request_line(callbacks.userdata, method.as_ptr(), method.len(),
+                               target.as_ptr(), target.len()) != 0 {
+                return -1;
+            }
+        }
+        if let Some(on_header) = callbacks.on_header {
+            for header in incoming.headers.iter() {
+                let (name, value) = (header.name(), header.value_string());
+                if on_header(callbacks.userdata, name.as_ptr(), name.len(),
+                             value.as_ptr(), value.len()) != 0 {
+                    return -1;
+                }
+            }
+        }
+        len as isize
+    })
+}
+
+// Calls `f`, returning `on_panic` if it panics, since unwinding out of an
+// `extern "C"` function aborts the process.
+fn catch<T, F: FnOnce() -> T>(on_panic: T, f: F) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(ret) => ret,
+        Err(_) => {
+            error!("capi call panicked");
+            on_panic
+        }
+    }
+}
+
+unsafe fn bytes<'a>(buf: *const u8, len: usize) -> &'a [u8] {
+    if buf.is_null() || len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(buf, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::raw::{c_int, c_void};
+    use std::ptr;
+    use std::slice;
+
+    use super::{ParserCallbacks, catch, hyper_parse_request, hyper_response_headers};
+
+    extern "C" fn on_line(userdata: *mut c_void, method: *const u8, method_len: usize,
+                          target: *const u8, target_len: usize) -> c_int {
+        let seen = unsafe { &mut *(userdata as *mut Vec<Vec<u8>>) };
+        unsafe {
+            seen.push(slice::from_raw_parts(method, method_len).to_vec());
+            seen.push(slice::from_raw_parts(target, target_len).to_vec());
+        }
+        0
+    }
+
+    extern "C" fn on_header(userdata: *mut c_void, name: *const u8, name_len: usize,
+                            value: *const u8, value_len: usize) -> c_int {
+        let seen = unsafe { &mut *(userdata as *mut Vec<Vec<u8>>) };
+        let mut field = unsafe { slice::from_raw_parts(name, name_len).to_vec() };
+        field.push(b'=');
+        field.extend_from_slice(unsafe { slice::from_raw_parts(value, value_len) });
+        let stop = field.starts_with(b"Accept=");
+        seen.push(field);
+        if stop { 1 } else { 0 }
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let input = b"GET /path?q HTTP/1.1\r\nHost: example.domain\r\n\r\nbody";
+        let mut seen: Vec<Vec<u8>> = Vec::new();
+        let callbacks = ParserCallbacks {
+            userdata: &mut seen as *mut Vec<Vec<u8>> as *mut c_void,
+            on_request_line: Some(on_line),
+            on_header: Some(on_header),
+        };
+        let len = unsafe { hyper_parse_request(input.as_ptr(), input.len(), &callbacks) };
+        assert_eq!(len, input.len() as isize - 4);
+        assert_eq!(seen, vec![b"GET".to_vec(), b"/path?q".to_vec(),
+                              b"Host=example.domain".to_vec()]);
+
+        // partial, invalid, and without callbacks
+        assert_eq!(unsafe { hyper_parse_request(input.as_ptr(), 20, &callbacks) }, 0);
+        let invalid = b"GET / HTTP/9\r\n\r\n";
+        assert_eq!(unsafe { hyper_parse_request(invalid.as_ptr(), invalid.len(), &callbacks) },
+                   -1);
+        assert_eq!(unsafe { hyper_parse_request(input.as_ptr(), input.len(), ptr::null()) },
+                   input.len() as isize - 4);
+    }
+
+    #[test]
+    fn test_callback_stops_parse() {
+        let input = b"GET / HTTP/1.1\r\nHost: a\r\nAccept: b\r\n\r\n";
+        let mut seen: Vec<Vec<u8>> = Vec::new();
+        let callbacks = ParserCallbacks {
+            userdata: &mut seen as *mut Vec<Vec<u8>> as *mut c_void,
+            on_request_line: None,
+            on_header: Some(on_header),
+        };
+        assert_eq!(unsafe { hyper_parse_request(input.as_ptr(), input.len(), &callbacks) }, -1);
+        assert_eq!(seen.last().unwrap(), b"Accept=b");
+    }
+
+    #[test]
+    fn test_response_headers_null() {
+        let mut seen: Vec<Vec<u8>> = Vec::new();
+        let userdata = &mut seen as *mut Vec<Vec<u8>> as *mut c_void;
+        assert_eq!(unsafe { hyper_response_headers(ptr::null(), None, userdata) }, -1);
+        assert_eq!(unsafe { hyper_response_headers(ptr::null(), Some(on_header), userdata) },
+                   -1);
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(catch(-1, || 1), 1);
+        assert_eq!(catch(-1, || -> isize { panic!("boom") }), -1);
+    }
+}
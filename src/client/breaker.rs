@@ -0,0 +1,256 @@
+//! Per-origin circuit breaking.
+//!
+//! A `CircuitBreaker` watches the outcome of requests to each origin. When
+//! an origin keeps failing, its circuit *opens*, and requests to it fail
+//! right away with `Error::CircuitOpen` instead of waiting on a struggling
+//! server. After `open_timeout`, a single probe request is let through
+//! (*half-open*): if it succeeds the circuit closes again, otherwise it
+//! stays open for another `open_timeout`.
+//!
+//! A request counts as failed if it returned an error, or if the response
+//! had a `5xx` status.
+//!
+//! ```no_run
+//! use hyper::Client;
+//! use hyper::client::breaker::CircuitBreaker;
+//!
+//! let mut client = Client::new();
+//! client.set_circuit_breaker(Some(CircuitBreaker::new(Default::default())));
+//! ```
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use Error;
+
+/// Config options for a `CircuitBreaker`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Open the circuit after this many consecutive failures.
+    pub max_consecutive_failures: u32,
+    /// Open the circuit when at least this fraction of the recent requests
+    /// failed.
+    pub max_error_rate: f32,
+    /// How many recent requests the error rate is calculated over. The rate
+    /// is only considered once this many requests have been seen.
+    pub window: usize,
+    /// How long a circuit stays open before a probe is allowed.
+    pub open_timeout: Duration,
+}
+
+impl Default for Config {
+    #[inline]
+    fn default() -> Config {
+        Config {
+            max_consecutive_failures: 5,
+            max_error_rate: 0.5,
+            window: 20,
+            open_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The state of a single origin's circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Requests are allowed through.
+    Closed,
+    /// Requests fail immediately.
+    Open,
+    /// A single probe request is allowed through.
+    HalfOpen,
+}
+
+/// Tracks failures per origin, and trips circuits for failing origins.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: Config,
+    circuits: Mutex<HashMap<Key, Circuit>>,
+}
+
+type Key = (String, u16, String);
+
+#[derive(Debug)]
+struct Circuit {
+    state: Inner,
+    consecutive_failures: u32,
+    recent: VecDeque<bool>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Inner {
+    Closed,
+    Open(Instant),
+    HalfOpen { probing: bool },
+}
+
+impl CircuitBreaker {
+    /// Creates a `CircuitBreaker` with the given `Config`.
+    pub fn new(config: Config) -> CircuitBreaker {
+        CircuitBreaker {
+            config: config,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the current state of the circuit for an origin.
+    pub fn state(&self, host: &str, port: u16, scheme: &str) -> State {
+        let circuits = self.circuits.lock().unwrap();
+        match circuits.get(&key(host, port, scheme)).map(|c| c.state) {
+            Some(Inner::Open(until)) if until <= Instant::now() => State::HalfOpen,
+            Some(Inner::Open(..)) => State::Open,
+            Some(Inner::HalfOpen { .. }) => State::HalfOpen,
+            Some(Inner::Closed) | None => State::Closed,
+        }
+    }
+
+    /// Closes the circuit for an origin, forgetting its past failures.
+    pub fn reset(&self, host: &str, port: u16, scheme: &str) {
+        self.circuits.lock().unwrap().remove(&key(host, port, scheme));
+    }
+
+    /// Checks whether a request to an origin may be attempted.
+    ///
+    /// Returns `Error::CircuitOpen` if it may not. When `Ok` is returned,
+    /// the outcome must be reported with `record`.
+    pub fn check(&self, host: &str, port: u16, scheme: &str) -> ::Result<()> {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = match circuits.get_mut(&key(host, port, scheme)) {
+            Some(circuit) => circuit,
+            None => return Ok(())
+        };
+        match circuit.state {
+            Inner::Closed => Ok(()),
+            Inner::Open(until) if until <= Instant::now() => {
+                debug!("circuit half-open for {}:{}", host, port);
+                circuit.state = Inner::HalfOpen { probing: true };
+                Ok(())
+            },
+            Inner::HalfOpen { probing: false } => {
+                circuit.state = Inner::HalfOpen { probing: true };
+                Ok(())
+            },
+            Inner::Open(..) | Inner::HalfOpen { probing: true } => Err(Error::CircuitOpen),
+        }
+    }
+
+    /// Records the outcome of a request to an origin.
+    pub fn record(&self, host: &str, port: u16, scheme: &str, success: bool) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(key(host, port, scheme)).or_insert_with(|| Circuit {
+            state: Inner::Closed,
+            consecutive_failures: 0,
+            recent: VecDeque::new(),
+        });
+
+        if success {
+            circuit.consecutive_failures = 0;
+        } else {
+            circuit.consecutive_failures += 1;
+        }
+        circuit.recent.push_back(success);
+        while circuit.recent.len() > self.config.window {
+            circuit.recent.pop_front();
+        }
+
+        let open = match circuit.state {
+            Inner::HalfOpen { .. } => !success,
+            Inner::Closed => {
+                let failures = circuit.recent.iter().filter(|&&ok| !ok).count();
+                circuit.consecutive_failures >= self.config.max_consecutive_failures ||
+                    (circuit.recent.len() >= self.config.window &&
+                     failures as f32 >= self.config.max_error_rate * circuit.recent.len() as f32)
+            },
+            // a request that was let through before the circuit opened
+            Inner::Open(..) => return,
+        };
+
+        if open {
+            debug!("circuit open for {}:{}", host, port);
+            circuit.state = Inner::Open(Instant::now() + self.config.open_timeout);
+        } else if let Inner::HalfOpen { .. } = circuit.state {
+            debug!("circuit closed for {}:{}", host, port);
+            circuit.state = Inner::Closed;
+            circuit.recent.clear();
+        }
+    }
+}
+
+fn key(host: &str, port: u16, scheme: &str) -> Key {
+    (host.to_owned(), port, scheme.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use Error;
+    use super::{CircuitBreaker, Config, State};
+
+    fn breaker(consecutive: u32, rate: f32, window: usize, timeout: u64) -> CircuitBreaker {
+        CircuitBreaker::new(Config {
+            max_consecutive_failures: consecutive,
+            max_error_rate: rate,
+            window: window,
+            open_timeout: Duration::from_millis(timeout),
+        })
+    }
+
+    #[test]
+    fn test_consecutive_failures() {
+        let b = breaker(3, 1.0, 100, 60_000);
+        for _ in 0..2 {
+            b.check("a", 80, "http").unwrap();
+            b.record("a", 80, "http", false);
+        }
+        b.record("a", 80, "http", true);
+        b.record("a", 80, "http", false);
+        b.record("a", 80, "http", false);
+        assert_eq!(b.state("a", 80, "http"), State::Closed);
+        b.record("a", 80, "http", false);
+        assert_eq!(b.state("a", 80, "http"), State::Open);
+        match b.check("a", 80, "http") {
+            Err(Error::CircuitOpen) => (),
+            other => panic!("{:?}", other),
+        }
+        // other origins are unaffected
+        assert!(b.check("a", 443, "https").is_ok());
+    }
+
+    #[test]
+    fn test_error_rate() {
+        let b = breaker(100, 0.5, 4, 60_000);
+        b.record("a", 80, "http", false);
+        b.record("a", 80, "http", true);
+        b.record("a", 80, "http", false);
+        assert_eq!(b.state("a", 80, "http"), State::Closed);
+        b.record("a", 80, "http", true);
+        assert_eq!(b.state("a", 80, "http"), State::Open);
+    }
+
+    #[test]
+    fn test_half_open() {
+        let b = breaker(1, 1.0, 100, 0);
+        b.record("a", 80, "http", false);
+        assert_eq!(b.state("a", 80, "http"), State::HalfOpen);
+
+        // only one probe at a time
+        assert!(b.check("a", 80, "http").is_ok());
+        assert!(b.check("a", 80, "http").is_err());
+
+        // a failed probe opens it again
+        b.record("a", 80, "http", false);
+        assert!(b.check("a", 80, "http").is_ok());
+        b.record("a", 80, "http", true);
+        assert_eq!(b.state("a", 80, "http"), State::Closed);
+        assert!(b.check("a", 80, "http").is_ok());
+    }
+
+    #[test]
+    fn test_reset() {
+        let b = breaker(1, 1.0, 100, 60_000);
+        b.record("a", 80, "http", false);
+        assert_eq!(b.state("a", 80, "http"), State::Open);
+        b.reset("a", 80, "http");
+        assert!(b.check("a", 80, "http").is_ok());
+    }
+}
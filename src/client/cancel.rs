@@ -0,0 +1,239 @@
+//! Cancelling requests from another thread.
+//!
+//! A `CancelToken` given to a request with `RequestBuilder::cancel_token`
+//! aborts it when `cancel` is called: while it is written, while the
+//! response head is waited for, or while the response body is read. `send`
+//! then returns `Error::Cancelled`, and reading the body fails with an error
+//! of kind `ConnectionAborted`. The connection is shut down, so it isn't
+//! used again. A connection being made when the request is cancelled isn't
+//! interrupted, but is closed as soon as it is made.
+//!
+//! A deadline set with `Client::set_timeout` or `RequestBuilder::timeout`
+//! aborts a request the same way once it passes, and `send` returns
+//! `Error::Timeout`, or reading the body fails with an error of kind
+//! `TimedOut`.
+//!
+//! ```no_run
+//! use std::thread;
+//! use std::time::Duration;
+//! use hyper::Client;
+//! use hyper::client::CancelToken;
+//!
+//! let token = CancelToken::new();
+//! let canceller = token.clone();
+//! thread::spawn(move || {
+//!     thread::sleep(Duration::from_secs(1));
+//!     canceller.cancel();
+//! });
+//! let res = Client::new().get("http://example.domain/slow").cancel_token(token).send();
+//! ```
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use net::AbortHandle;
+use Error;
+
+/// Cancels the requests it was given to, from any thread.
+///
+/// Clones cancel the same requests. Once cancelled, a token stays
+/// cancelled, and requests given it later fail right away.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    reason: Option<Reason>,
+    // the connection of the request in progress, if any
+    abort: Option<(usize, AbortHandle)>,
+    guards: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Reason {
+    Cancelled,
+    TimedOut,
+}
+
+impl CancelToken {
+    /// Creates a token that isn't cancelled.
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Cancels the requests given this token.
+    pub fn cancel(&self) {
+        self.trip(Reason::Cancelled);
+    }
+
+    /// Whether the token was cancelled, or the deadline of a request it was
+    /// given to has passed.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.lock().unwrap().reason.is_some()
+    }
+
+    fn trip(&self, reason: Reason) {
+        let abort = {
+            let mut state = self.inner.lock().unwrap();
+            if state.reason.is_none() {
+                debug!("request {:?}", reason);
+                state.reason = Some(reason);
+            }
+            state.abort.take()
+        };
+        if let Some((_, abort)) = abort {
+            abort();
+        }
+    }
+
+    /// Returns the error for the reason the token was cancelled, if it was.
+    #[doc(hidden)]
+    pub fn check(&self) -> ::Result<()> {
+        match self.inner.lock().unwrap().reason {
+            Some(Reason::Cancelled) => Err(Error::Cancelled),
+            Some(Reason::TimedOut) => Err(Error::Timeout),
+            None => Ok(())
+        }
+    }
+
+    /// Keeps `abort` to shut down the connection of a request, until the
+    /// returned `Guard` is dropped.
+    #[doc(hidden)]
+    pub fn guard(&self, abort: Option<AbortHandle>) -> ::Result<Guard> {
+        let mut state = self.inner.lock().unwrap();
+        state.guards += 1;
+        let id = state.guards;
+        if let Some(abort) = abort {
+            state.abort = Some((id, abort));
+        }
+        drop(state);
+        let guard = Guard { token: self.clone(), id: id };
+        try!(self.check());
+        Ok(guard)
+    }
+
+    /// Cancels the token with `Error::Timeout` after `timeout`, unless the
+    /// returned `Deadline` is dropped first.
+    #[doc(hidden)]
+    pub fn deadline(&self, timeout: Duration) -> Deadline {
+        let (tx, rx) = mpsc::channel::<()>();
+        let token = self.clone();
+        thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = rx.recv_timeout(timeout) {
+                token.trip(Reason::TimedOut);
+            }
+        });
+        Deadline { _stop: tx }
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("reason", &self.inner.lock().unwrap().reason)
+            .finish()
+    }
+}
+
+/// Keeps the connection of a request abortable by its `CancelToken`.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Guard {
+    token: CancelToken,
+    id: usize,
+}
+
+impl Guard {
+    /// The error for a cancelled request, if it was cancelled.
+    pub fn error(&self) -> Option<Error> {
+        self.token.check().err()
+    }
+
+    /// The error reading the body of a cancelled request fails with, if it
+    /// was cancelled.
+    pub fn io_error(&self) -> Option<io::Error> {
+        match self.token.inner.lock().unwrap().reason {
+            Some(Reason::Cancelled) => {
+                Some(io::Error::new(io::ErrorKind::ConnectionAborted, "request cancelled"))
+            },
+            Some(Reason::TimedOut) => {
+                Some(io::Error::new(io::ErrorKind::TimedOut, "request timed out"))
+            },
+            None => None
+        }
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let mut state = self.token.inner.lock().unwrap();
+        if state.abort.as_ref().map_or(false, |&(id, _)| id == self.id) {
+            state.abort = None;
+        }
+    }
+}
+
+/// Stops a deadline from passing when dropped.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Deadline {
+    // never sent on: dropping it wakes the deadline thread, which then
+    // stops without cancelling
+    _stop: mpsc::Sender<()>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use Error;
+    use super::CancelToken;
+
+    #[test]
+    fn test_cancel_aborts_guarded() {
+        let aborted = Arc::new(AtomicUsize::new(0));
+        let token = CancelToken::new();
+        let counter = aborted.clone();
+        let first = token.guard(Some(Box::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }))).unwrap();
+        // a guard dropped after another took its place doesn't disarm it
+        let counter = aborted.clone();
+        let second = token.guard(Some(Box::new(move || {
+            counter.fetch_add(10, Ordering::SeqCst);
+        }))).unwrap();
+        drop(first);
+
+        token.clone().cancel();
+        assert_eq!(aborted.load(Ordering::SeqCst), 10);
+        assert!(token.is_cancelled());
+        match second.error() {
+            Some(Error::Cancelled) => (),
+            other => panic!("unexpected error: {:?}", other)
+        }
+        assert!(token.guard(None).is_err());
+    }
+
+    #[test]
+    fn test_deadline() {
+        let token = CancelToken::new();
+        drop(token.deadline(Duration::from_millis(10)));
+        thread::sleep(Duration::from_millis(50));
+        assert!(token.check().is_ok());
+
+        let _deadline = token.deadline(Duration::from_millis(10));
+        thread::sleep(Duration::from_millis(50));
+        match token.check() {
+            Err(Error::Timeout) => (),
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! Keeping cookies between requests.
+//!
+//! A `CookieJar` stores the cookies set by responses, and sends them back
+//! with later requests to the same site, following the rules of
+//! [RFC6265](http://tools.ietf.org/html/rfc6265#section-5.3): a cookie
+//! without a `Domain` goes back only to the host that set it, one with a
+//! `Domain` to that domain and the hosts below it, and only to paths under
+//! its `Path`. `Secure` cookies are only sent over `https`, and cookies are
+//! dropped once their `Max-Age` or `Expires` has passed.
+//!
+//! Cookies parsed from a `Set-Cookie` header without a `Path` get the path
+//! `/`, so they are sent to every path of their host.
+//!
+//! ```no_run
+//! use hyper::Client;
+//! use hyper::client::CookieJar;
+//!
+//! let mut client = Client::new();
+//! client.set_cookie_jar(Some(CookieJar::new()));
+//! client.post("http://example.domain/login").body("user=me").send().unwrap();
+//! // sent with the session cookie the login set
+//! client.get("http://example.domain/account").send().unwrap();
+//! ```
+use std::sync::{Arc, Mutex};
+
+use cookie::Cookie as CookiePair;
+use time::{Duration, Timespec};
+
+use clock::{self, Clock};
+use header::{Cookie, SetCookie};
+use Url;
+
+/// The cookies set by responses, to send with later requests.
+#[derive(Debug)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<Stored>>,
+    clock: Arc<Clock>,
+}
+
+#[derive(Debug)]
+struct Stored {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<Timespec>,
+}
+
+impl Stored {
+    fn is_expired(&self, now: Timespec) -> bool {
+        self.expires.map_or(false, |expires| expires <= now)
+    }
+
+    fn matches(&self, host: &str, path: &str, secure: bool) -> bool {
+        let domain = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(host, &self.domain)
+        };
+        domain && path_match(path, &self.path) && (secure || !self.secure)
+    }
+}
+
+impl CookieJar {
+    /// Creates an empty `CookieJar`.
+    pub fn new() -> CookieJar {
+        CookieJar::with_clock(clock::system())
+    }
+
+    /// Creates an empty `CookieJar` that expires cookies by the time of
+    /// `clock`.
+    pub fn with_clock(clock: Arc<Clock>) -> CookieJar {
+        CookieJar {
+            cookies: Mutex::new(Vec::new()),
+            clock: clock,
+        }
+    }
+
+    /// Stores the cookies of a `Set-Cookie` header, from a response to
+    /// `url`.
+    ///
+    /// Cookies for a domain `url` isn't in are ignored. A cookie replaces
+    /// the one of the same name, domain and path, and an expired one just
+    /// removes it.
+    pub fn store(&self, url: &Url, set_cookie: &SetCookie) {
+        let host = match url.serialize_host() {
+            Some(host) => host.to_lowercase(),
+            None => return
+        };
+        let now = self.clock.now_utc().to_timespec();
+        let mut cookies = self.cookies.lock().unwrap();
+        for cookie in set_cookie.iter() {
+            let (domain, host_only) = match cookie.domain {
+                Some(ref domain) => {
+                    let domain = domain.trim_left_matches('.').to_lowercase();
+                    if !domain_match(&host, &domain) {
+                        debug!("ignoring cookie {} for {} from {}", cookie.name, domain, host);
+                        continue;
+                    }
+                    (domain, false)
+                },
+                None => (host.clone(), true)
+            };
+            let path = match cookie.path {
+                Some(ref path) if path.starts_with('/') => path.clone(),
+                _ => default_path(url)
+            };
+            // Max-Age wins over Expires
+            let expires = match (cookie.max_age, cookie.expires) {
+                (Some(max_age), _) => Some(now + Duration::seconds(max_age as i64)),
+                (None, Some(expires)) => Some(expires.to_timespec()),
+                (None, None) => None
+            };
+            let stored = Stored {
+                name: cookie.name.clone(),
+                value: cookie.value.clone(),
+                domain: domain,
+                host_only: host_only,
+                path: path,
+                secure: cookie.secure,
+                expires: expires,
+            };
+            let existing = cookies.iter().position(|c| {
+                c.name == stored.name && c.domain == stored.domain && c.path == stored.path
+            });
+            match existing {
+                Some(i) if stored.is_expired(now) => { cookies.remove(i); },
+                // it keeps the place of the one it replaces
+                Some(i) => cookies[i] = stored,
+                None if stored.is_expired(now) => (),
+                None => cookies.push(stored)
+            }
+        }
+    }
+
+    /// Get the `Cookie` header to send with a request to `url`, if any
+    /// cookies are stored for it.
+    ///
+    /// Cookies with longer paths are first.
+    pub fn cookies_for(&self, url: &Url) -> Option<Cookie> {
+        let host = match url.serialize_host() {
+            Some(host) => host.to_lowercase(),
+            None => return None
+        };
+        let path = url.serialize_path().unwrap_or_else(|| "/".to_owned());
+        let secure = url.scheme == "https";
+        let now = self.clock.now_utc().to_timespec();
+
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|c| !c.is_expired(now));
+        let mut matching = cookies.iter()
+            .filter(|c| c.matches(&host, &path, secure))
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            return None;
+        }
+        // a stable sort keeps older cookies of the same path first
+        matching.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+        Some(Cookie(matching.into_iter().map(|c| {
+            CookiePair::new(c.name.clone(), c.value.clone())
+        }).collect()))
+    }
+
+    /// The number of cookies stored, including any that have expired but
+    /// weren't dropped yet.
+    pub fn len(&self) -> usize {
+        self.cookies.lock().unwrap().len()
+    }
+
+    /// Whether no cookies are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every cookie.
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+}
+
+impl Default for CookieJar {
+    fn default() -> CookieJar {
+        CookieJar::new()
+    }
+}
+
+// Whether `host` is `domain`, or a name below it. IP addresses only match
+// themselves.
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    let is_ip = host.parse::<::std::net::IpAddr>().is_ok() || host.starts_with('[');
+    !is_ip && host.len() > domain.len() && host.ends_with(domain) &&
+        host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+}
+
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path || (path.starts_with(cookie_path) &&
+        (cookie_path.ends_with('/') || path.as_bytes()[cookie_path.len()] == b'/'))
+}
+
+// The directory of the request path, which a cookie with an invalid `Path`
+// is for.
+fn default_path(url: &Url) -> String {
+    let path = url.serialize_path().unwrap_or_else(|| "/".to_owned());
+    match path.rfind('/') {
+        Some(0) | None => "/".to_owned(),
+        Some(slash) => path[..slash].to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration as StdDuration;
+
+    use cookie::Cookie as CookiePair;
+    use time::{self, Duration};
+
+    use clock::ManualClock;
+    use header::{Cookie, SetCookie};
+    use Url;
+
+    use super::CookieJar;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn set_cookie(raw: &[&str]) -> SetCookie {
+        SetCookie(raw.iter().map(|s| s.parse().unwrap()).collect())
+    }
+
+    fn names(cookie: Option<Cookie>) -> Vec<String> {
+        cookie.map_or_else(Vec::new, |c| c.0.iter().map(|c| c.name.clone()).collect())
+    }
+
+    #[test]
+    fn test_domain_and_path() {
+        let jar = CookieJar::new();
+        jar.store(&url("http://www.example.domain/account/login"), &set_cookie(&[
+            "host=1; Path=/account",
+            "wide=1; Domain=.Example.domain; Path=/",
+            "deep=1; Path=/account/settings",
+            "other=1; Domain=other.domain",
+        ]));
+        assert_eq!(jar.len(), 3);
+
+        assert_eq!(names(jar.cookies_for(&url("http://www.example.domain/account/settings/x"))),
+                   vec!["deep", "host", "wide"]);
+        assert_eq!(names(jar.cookies_for(&url("http://www.example.domain/accounts"))),
+                   vec!["wide"]);
+        assert_eq!(names(jar.cookies_for(&url("http://api.example.domain/account"))),
+                   vec!["wide"]);
+        assert_eq!(names(jar.cookies_for(&url("http://example.domain/"))), vec!["wide"]);
+        assert!(jar.cookies_for(&url("http://badexample.domain/")).is_none());
+    }
+
+    #[test]
+    fn test_replace_and_expire() {
+        let jar = CookieJar::new();
+        let site = url("https://example.domain/");
+        jar.store(&site, &set_cookie(&["a=1", "b=1; Secure", "c=1; Max-Age=60"]));
+        jar.store(&site, &set_cookie(&["a=2"]));
+
+        let cookie = jar.cookies_for(&site).unwrap();
+        assert_eq!(cookie.0, vec![CookiePair::new("a".to_owned(), "2".to_owned()),
+                                  CookiePair::new("b".to_owned(), "1".to_owned()),
+                                  CookiePair::new("c".to_owned(), "1".to_owned())]);
+        assert_eq!(names(jar.cookies_for(&url("http://example.domain/"))), vec!["a", "c"]);
+
+        let mut past = CookiePair::new("a".to_owned(), "".to_owned());
+        past.expires = Some(time::now_utc() - Duration::days(1));
+        jar.store(&site, &SetCookie(vec![past]));
+        jar.store(&site, &set_cookie(&["c=; Max-Age=0"]));
+        assert_eq!(names(jar.cookies_for(&site)), vec!["b"]);
+
+        jar.clear();
+        assert!(jar.cookies_for(&site).is_none());
+    }
+
+    #[test]
+    fn test_clock() {
+        let clock = ManualClock::at(time::strptime("Tue, 01 Jun 2021 00:00:00 GMT",
+                                                   "%a, %d %b %Y %T %Z").unwrap());
+        let jar = CookieJar::with_clock(Arc::new(clock.clone()));
+        let site = url("http://example.domain/");
+        jar.store(&site, &set_cookie(&["short=1; Max-Age=10",
+                                       "dated=1; Expires=Wed, 02 Jun 2021 00:00:00 GMT"]));
+
+        clock.advance(StdDuration::from_secs(9));
+        assert_eq!(names(jar.cookies_for(&site)), vec!["short", "dated"]);
+        clock.advance(StdDuration::from_secs(1));
+        assert_eq!(names(jar.cookies_for(&site)), vec!["dated"]);
+        clock.advance(StdDuration::from_secs(24 * 60 * 60));
+        assert!(jar.cookies_for(&site).is_none());
+        assert!(jar.is_empty());
+    }
+}
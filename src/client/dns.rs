@@ -0,0 +1,420 @@
+//! DNS result caching.
+//!
+//! Each new connection normally resolves its host again. A `DnsCache`
+//! remembers the addresses a host resolved to for a while, and also
+//! remembers failed lookups for a shorter while, so a host that doesn't
+//! resolve isn't looked up again on every request.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use hyper::Client;
+//! use hyper::client::dns::{DnsCache, DnsConnector};
+//!
+//! let dns = DnsCache::new(Duration::from_secs(60));
+//! let client = Client::with_connector(DnsConnector::new(dns.clone()));
+//! client.get("http://example.domain").send().unwrap();
+//!
+//! // forget everything, such as after a network change
+//! dns.flush();
+//! ```
+//!
+//! For HTTPS, wrap the `DnsConnector` in an `HttpsConnector` with
+//! `HttpsConnector::with_connector`.
+//!
+//! When none of the cached addresses of a host can be connected to, such as
+//! after it moved before its entry expired, the `DnsConnector` resolves it
+//! again, and tries the addresses it wasn't at before. A function set with
+//! `DnsCache::set_on_change` is told whenever a host moves:
+//!
+//! ```
+//! use std::time::Duration;
+//! use hyper::client::dns::DnsCache;
+//!
+//! let dns = DnsCache::new(Duration::from_secs(60));
+//! dns.set_on_change(|host, old, new| {
+//!     println!("{} moved from {:?} to {:?}", host, old, new);
+//! });
+//! ```
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use net::{ConnectInfo, NetworkConnector, HttpStream, ResolveOverrides, Resolver, connect_any};
+use net::ip_literal;
+
+/// A cache of resolved host addresses, shared between its clones.
+#[derive(Clone)]
+pub struct DnsCache {
+    inner: Arc<Mutex<Cache>>,
+    lookup: fn(&str) -> io::Result<Vec<IpAddr>>,
+}
+
+struct Cache {
+    entries: HashMap<String, Entry>,
+    ttl: Duration,
+    negative_ttl: Duration,
+    on_change: Option<Arc<OnChange>>,
+}
+
+type OnChange = Fn(&str, &[IpAddr], &[IpAddr]) + Send + Sync;
+
+struct Entry {
+    result: Result<Vec<IpAddr>, (io::ErrorKind, String)>,
+    expires: Instant,
+}
+
+impl DnsCache {
+    /// Creates a cache that keeps successful lookups for `ttl`.
+    ///
+    /// The system resolver doesn't report the TTLs of the records it found,
+    /// so `ttl` should be no longer than those of the hosts being resolved.
+    /// Failed lookups are kept for 5 seconds, or `ttl` if that is shorter.
+    pub fn new(ttl: Duration) -> DnsCache {
+        DnsCache {
+            inner: Arc::new(Mutex::new(Cache {
+                entries: HashMap::new(),
+                ttl: ttl,
+                negative_ttl: ::std::cmp::min(ttl, Duration::from_secs(5)),
+                on_change: None,
+            })),
+            lookup: system_lookup,
+        }
+    }
+
+    /// Sets how long successful lookups are kept.
+    pub fn set_ttl(&self, ttl: Duration) {
+        self.inner.lock().unwrap().ttl = ttl;
+    }
+
+    /// Sets how long failed lookups are kept.
+    pub fn set_negative_ttl(&self, ttl: Duration) {
+        self.inner.lock().unwrap().negative_ttl = ttl;
+    }
+
+    /// Sets a function to call with a host, the addresses it resolved to,
+    /// and those it resolves to now, whenever looking it up again finds
+    /// other addresses than before.
+    pub fn set_on_change<F>(&self, on_change: F)
+    where F: Fn(&str, &[IpAddr], &[IpAddr]) + Send + Sync + 'static {
+        self.inner.lock().unwrap().on_change = Some(Arc::new(on_change));
+    }
+
+    /// Resolves a host to socket addresses, using a cached result if there
+    /// is a fresh one.
+    pub fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        self.resolve_with(host, port, false).map(|(addrs, _)| addrs)
+    }
+
+    /// Resolves a host to socket addresses, replacing its cached result
+    /// even if it is fresh.
+    pub fn refresh(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        self.resolve_with(host, port, true).map(|(addrs, _)| addrs)
+    }
+
+    // Also returns whether the addresses came from the cache.
+    fn resolve_with(&self, host: &str, port: u16, refresh: bool)
+            -> io::Result<(Vec<SocketAddr>, bool)> {
+        let now = Instant::now();
+        if !refresh {
+            let cache = self.inner.lock().unwrap();
+            if let Some(entry) = cache.entries.get(host) {
+                if entry.expires > now {
+                    trace!("dns cache hit {:?}", host);
+                    return entry.addrs(port).map(|addrs| (addrs, true));
+                }
+            }
+        }
+
+        // The lock isn't held while resolving, since that can take a while.
+        trace!("dns cache miss {:?}", host);
+        let result = (self.lookup)(host);
+        let (addrs, changed) = {
+            let mut cache = self.inner.lock().unwrap();
+            let entry = match result {
+                Ok(ips) => Entry {
+                    result: Ok(ips),
+                    expires: now + cache.ttl,
+                },
+                Err(e) => Entry {
+                    result: Err((e.kind(), e.to_string())),
+                    expires: now + cache.negative_ttl,
+                },
+            };
+            let changed = match (cache.entries.get(host), &entry.result, &cache.on_change) {
+                (Some(&Entry { result: Ok(ref old), .. }), &Ok(ref new), &Some(ref on_change))
+                        if !same_ips(old, new) => {
+                    Some((on_change.clone(), old.clone(), new.clone()))
+                },
+                _ => None
+            };
+            let addrs = entry.addrs(port);
+            cache.entries.insert(host.to_owned(), entry);
+            (addrs, changed)
+        };
+        if let Some((on_change, old, new)) = changed {
+            debug!("{:?} moved from {:?} to {:?}", host, old, new);
+            on_change(host, &old, &new);
+        }
+        addrs.map(|addrs| (addrs, false))
+    }
+
+    /// Forgets the cached result for one host.
+    pub fn remove(&self, host: &str) {
+        self.inner.lock().unwrap().entries.remove(host);
+    }
+
+    /// Forgets all cached results.
+    pub fn flush(&self) {
+        self.inner.lock().unwrap().entries.clear();
+    }
+}
+
+impl Resolver for DnsCache {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        DnsCache::resolve(self, host, port)
+    }
+}
+
+impl Entry {
+    fn addrs(&self, port: u16) -> io::Result<Vec<SocketAddr>> {
+        match self.result {
+            Ok(ref ips) => Ok(ips.iter().map(|&ip| SocketAddr::new(ip, port)).collect()),
+            Err((kind, ref msg)) => Err(io::Error::new(kind, msg.clone())),
+        }
+    }
+}
+
+// Whether two lookups found the same addresses, in any order, since
+// resolvers rotate them.
+fn same_ips(a: &[IpAddr], b: &[IpAddr]) -> bool {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort();
+    b.sort();
+    a == b
+}
+
+fn system_lookup(host: &str) -> io::Result<Vec<IpAddr>> {
+    let addrs = try!((host, 0).to_socket_addrs());
+    Ok(addrs.map(|addr| addr.ip()).collect())
+}
+
+/// A connector that produces `HttpStream`s, resolving hosts with a
+/// `DnsCache`, and racing their addresses with `net::connect_any`.
+#[derive(Clone)]
+pub struct DnsConnector {
+    cache: DnsCache,
+    delay: Duration,
+    overrides: ResolveOverrides,
+}
+
+impl DnsConnector {
+    /// Creates a connector using the given cache.
+    pub fn new(cache: DnsCache) -> DnsConnector {
+        DnsConnector {
+            cache: cache,
+            delay: Duration::from_millis(250),
+            overrides: ResolveOverrides::new(),
+        }
+    }
+
+    /// Sets how long an attempt to connect gets before the next address is
+    /// tried alongside it.
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+
+    /// Get the cache this connector uses.
+    pub fn cache(&self) -> &DnsCache {
+        &self.cache
+    }
+
+    /// Sets the hosts to connect to without resolving them.
+    pub fn set_overrides(&mut self, overrides: ResolveOverrides) {
+        self.overrides = overrides;
+    }
+
+    /// Get the hosts this connector connects to without resolving them.
+    pub fn overrides(&self) -> &ResolveOverrides {
+        &self.overrides
+    }
+}
+
+impl NetworkConnector for DnsConnector {
+    type Stream = HttpStream;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<HttpStream> {
+        self.connect_with_info(host, port, scheme).map(|(stream, _)| stream)
+    }
+
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+            -> ::Result<(HttpStream, ConnectInfo)> {
+        match scheme {
+            "http" => {
+                let mut info = ConnectInfo::default();
+                let start = Instant::now();
+                let addr = match (self.overrides.get(host), ip_literal(host, port)) {
+                    (Some(addr), _) => Some(addr),
+                    (None, Some(addr)) => Some(try!(addr)),
+                    (None, None) => None,
+                };
+                if let Some(addr) = addr {
+                    let stream = try!(TcpStream::connect(addr));
+                    info.connect = Some(start.elapsed());
+                    return Ok((HttpStream(stream), info));
+                }
+                let (addrs, cached) = try!(self.cache.resolve_with(host, port, false));
+                info.dns = Some(start.elapsed());
+                let start = Instant::now();
+                let stream = match connect_any(&addrs, self.delay) {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        if !cached {
+                            return Err(e.into());
+                        }
+                        debug!("connecting to cached {:?} failed, resolving again: {:?}",
+                               host, e);
+                        let moved = match self.cache.refresh(host, port) {
+                            Ok(fresh) => fresh.into_iter()
+                                .filter(|addr| !addrs.contains(addr))
+                                .collect::<Vec<_>>(),
+                            Err(_) => Vec::new()
+                        };
+                        if moved.is_empty() {
+                            return Err(e.into());
+                        }
+                        try!(connect_any(&moved, self.delay))
+                    }
+                };
+                info.connect = Some(start.elapsed());
+                Ok((HttpStream(stream), info))
+            },
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                    "Invalid scheme for Http").into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener};
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+    use std::time::Duration;
+
+    use net::{NetworkConnector, NetworkStream};
+    use super::{DnsCache, DnsConnector};
+
+    static LOOKUPS: AtomicUsize = ATOMIC_USIZE_INIT;
+    static MOVES: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    fn fake_lookup(host: &str) -> io::Result<Vec<IpAddr>> {
+        LOOKUPS.fetch_add(1, Ordering::SeqCst);
+        match host {
+            "example.domain" => Ok(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]),
+            _ => Err(io::Error::new(io::ErrorKind::Other, "no such host")),
+        }
+    }
+
+    // leaves LOOKUPS alone, so it can run alongside test_dns_cache
+    fn moving_lookup(host: &str) -> io::Result<Vec<IpAddr>> {
+        match host {
+            // at ::1 the first time, and then at 127.0.0.1
+            "moved.domain" => Ok(vec![match MOVES.fetch_add(1, Ordering::SeqCst) {
+                0 => IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)),
+                _ => IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            }]),
+            _ => Err(io::Error::new(io::ErrorKind::Other, "no such host")),
+        }
+    }
+
+    fn lookups(f: &Fn()) -> usize {
+        let before = LOOKUPS.load(Ordering::SeqCst);
+        f();
+        LOOKUPS.load(Ordering::SeqCst) - before
+    }
+
+    // these all share the LOOKUPS counter, so run as one test
+    #[test]
+    fn test_dns_cache() {
+        let mut dns = DnsCache::new(Duration::from_secs(60));
+        dns.lookup = fake_lookup;
+
+        assert_eq!(lookups(&|| {
+            let addrs = dns.resolve("example.domain", 80).unwrap();
+            assert_eq!(addrs, vec!["10.0.0.1:80".parse::<SocketAddr>().unwrap()]);
+            let addrs = dns.resolve("example.domain", 443).unwrap();
+            assert_eq!(addrs, vec!["10.0.0.1:443".parse::<SocketAddr>().unwrap()]);
+        }), 1);
+
+        // negative caching
+        assert_eq!(lookups(&|| {
+            assert!(dns.resolve("missing.domain", 80).is_err());
+            let err = dns.resolve("missing.domain", 80).unwrap_err();
+            assert_eq!(err.to_string(), "no such host");
+        }), 1);
+        dns.set_negative_ttl(Duration::from_millis(0));
+        dns.remove("missing.domain");
+        assert_eq!(lookups(&|| {
+            assert!(dns.resolve("missing.domain", 80).is_err());
+            assert!(dns.resolve("missing.domain", 80).is_err());
+        }), 2);
+
+        // flushing
+        assert_eq!(lookups(&|| {
+            dns.remove("example.domain");
+            dns.resolve("example.domain", 80).unwrap();
+            dns.flush();
+            dns.resolve("example.domain", 80).unwrap();
+        }), 2);
+
+        // expiry
+        dns.set_ttl(Duration::from_millis(0));
+        dns.flush();
+        assert_eq!(lookups(&|| {
+            dns.resolve("example.domain", 80).unwrap();
+            dns.resolve("example.domain", 80).unwrap();
+        }), 2);
+    }
+
+    #[test]
+    fn test_resolve_again_on_connect_failure() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut dns = DnsCache::new(Duration::from_secs(60));
+        dns.lookup = moving_lookup;
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let seen = changes.clone();
+        dns.set_on_change(move |host, old, new| {
+            seen.lock().unwrap().push((host.to_owned(), old.to_vec(), new.to_vec()));
+        });
+
+        // nothing listens on ::1, so the host is looked up again
+        let addrs = dns.resolve("moved.domain", port).unwrap();
+        assert!(addrs[0].ip().is_ipv6());
+        let connector = DnsConnector::new(dns.clone());
+        let mut stream = connector.connect("moved.domain", port, "http").unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+        assert_eq!(dns.resolve("moved.domain", port).unwrap(),
+                   vec![listener.local_addr().unwrap()]);
+        assert_eq!(*changes.lock().unwrap(), vec![(
+            "moved.domain".to_owned(),
+            vec![IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))],
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
+        )]);
+
+        // a refresh finding the same addresses isn't a change
+        dns.refresh("moved.domain", port).unwrap();
+        assert_eq!(changes.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_dns_cache_system() {
+        let dns = DnsCache::new(Duration::from_secs(60));
+        let addrs = dns.resolve("127.0.0.1", 8080).unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:8080".parse::<SocketAddr>().unwrap()]);
+    }
+}
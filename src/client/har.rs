@@ -0,0 +1,532 @@
+//! Recording client traffic as an HTTP Archive.
+//!
+//! A `HarRecorder` set on a `Client` captures every request it makes, and
+//! the response received, and can write them out in the
+//! [HAR 1.2](http://www.softwareishard.com/blog/har-12-spec/) format that
+//! browser developer tools can import.
+//!
+//! ```no_run
+//! use std::io::Read;
+//! use hyper::Client;
+//! use hyper::client::har::HarRecorder;
+//!
+//! let har = HarRecorder::new();
+//! let mut client = Client::new();
+//! client.set_har_recorder(Some(har.clone()));
+//!
+//! let mut body = String::new();
+//! client.get("http://example.domain").send().unwrap().read_to_string(&mut body).unwrap();
+//!
+//! println!("{}", har.to_json());
+//! ```
+//!
+//! Bodies are only recorded if enabled with `set_record_bodies`. Request
+//! bodies are only available when sent from a buffer, and a response body
+//! is added to its entry once it has been read to the end.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serialize::base64::{ToBase64, STANDARD};
+use serialize::json::{self, Json, ToJson};
+use time;
+
+use header::{ContentLength, ContentType, Headers, Location};
+use method::Method;
+use version::HttpVersion;
+use Url;
+
+use super::{Response, Timings};
+
+/// Records client requests and responses, to export as HAR.
+///
+/// Clones share the same recording.
+#[derive(Clone)]
+pub struct HarRecorder {
+    inner: Arc<Mutex<Recording>>,
+}
+
+struct Recording {
+    entries: Vec<Entry>,
+    next_id: usize,
+    bodies: bool,
+}
+
+struct Entry {
+    id: usize,
+    started: time::Tm,
+    request: RequestRecord,
+    response: Option<ResponseRecord>,
+    error: Option<String>,
+    timings: Timings,
+}
+
+#[derive(Clone)]
+struct RequestRecord {
+    method: Method,
+    url: Url,
+    version: HttpVersion,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+}
+
+struct ResponseRecord {
+    status: u16,
+    status_text: String,
+    version: String,
+    headers: Vec<(String, String)>,
+    redirect_url: String,
+    mime_type: String,
+    size: Option<u64>,
+    body: Option<Vec<u8>>,
+}
+
+impl HarRecorder {
+    /// Creates an empty recorder, that doesn't record bodies.
+    pub fn new() -> HarRecorder {
+        HarRecorder {
+            inner: Arc::new(Mutex::new(Recording {
+                entries: vec![],
+                next_id: 0,
+                bodies: false,
+            }))
+        }
+    }
+
+    /// Sets whether request and response bodies are recorded.
+    pub fn set_record_bodies(&self, bodies: bool) {
+        self.inner.lock().unwrap().bodies = bodies;
+    }
+
+    /// Get the number of recorded entries.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Whether nothing has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops all recorded entries.
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().entries.clear();
+    }
+
+    /// Serializes the recording as a HAR 1.2 JSON document.
+    pub fn to_json(&self) -> String {
+        json::as_pretty_json(&self.to_har()).to_string()
+    }
+
+    /// Writes the recording as a HAR 1.2 JSON document.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(self.to_json().as_bytes())
+    }
+
+    fn to_har(&self) -> Json {
+        let recording = self.inner.lock().unwrap();
+        let mut creator = BTreeMap::new();
+        creator.insert("name".to_owned(), "hyper".to_json());
+        creator.insert("version".to_owned(), env!("CARGO_PKG_VERSION").to_json());
+
+        let mut log = BTreeMap::new();
+        log.insert("version".to_owned(), "1.2".to_json());
+        log.insert("creator".to_owned(), Json::Object(creator));
+        log.insert("entries".to_owned(),
+                   Json::Array(recording.entries.iter().map(|e| e.to_json()).collect()));
+
+        let mut har = BTreeMap::new();
+        har.insert("log".to_owned(), Json::Object(log));
+        Json::Object(har)
+    }
+
+    #[doc(hidden)]
+    pub fn capture(&self, method: &Method, url: &Url, headers: Option<&Headers>) -> Capture {
+        Capture {
+            recorder: self.clone(),
+            started: time::now_utc(),
+            started_instant: Instant::now(),
+            request: RequestRecord {
+                method: method.clone(),
+                url: url.clone(),
+                version: HttpVersion::Http11,
+                headers: headers.map(header_list).unwrap_or(vec![]),
+                body: None,
+            }
+        }
+    }
+
+    fn record_bodies(&self) -> bool {
+        self.inner.lock().unwrap().bodies
+    }
+
+    fn push(&self, entry: Entry) -> usize {
+        let mut recording = self.inner.lock().unwrap();
+        let id = recording.next_id;
+        recording.next_id += 1;
+        recording.entries.push(Entry { id: id, ..entry });
+        id
+    }
+
+    fn complete(&self, id: usize, body: Option<Vec<u8>>, total: Duration) {
+        let mut recording = self.inner.lock().unwrap();
+        if let Some(entry) = recording.entries.iter_mut().find(|e| e.id == id) {
+            entry.timings.total = Some(total);
+            if let Some(ref mut response) = entry.response {
+                if let Some(ref body) = body {
+                    response.size = Some(body.len() as u64);
+                }
+                response.body = body;
+            }
+        }
+    }
+}
+
+impl Default for HarRecorder {
+    fn default() -> HarRecorder {
+        HarRecorder::new()
+    }
+}
+
+/// A request in progress, to be recorded once it has finished.
+#[doc(hidden)]
+pub struct Capture {
+    recorder: HarRecorder,
+    started: time::Tm,
+    started_instant: Instant,
+    request: RequestRecord,
+}
+
+impl Capture {
+    #[doc(hidden)]
+    pub fn set_request(&mut self, version: HttpVersion, headers: &Headers,
+                       body: Option<&[u8]>) {
+        self.request.version = version;
+        self.request.headers = header_list(headers);
+        if self.recorder.record_bodies() {
+            self.request.body = body.map(|b| b.to_vec());
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn finish(self, res: ::Result<Response>) -> ::Result<Response> {
+        let Capture { recorder, started, started_instant, request } = self;
+        let (response, error, timings) = match res {
+            Ok(ref res) => (Some(ResponseRecord {
+                status: res.status_raw().0,
+                status_text: res.status_raw().1.to_string(),
+                version: res.version.to_string(),
+                headers: header_list(&res.headers),
+                redirect_url: match res.headers.get::<Location>() {
+                    Some(&Location(ref loc)) => loc.clone(),
+                    None => String::new(),
+                },
+                mime_type: res.headers.get::<ContentType>().map(|ct| ct.to_string())
+                    .unwrap_or(String::new()),
+                size: res.headers.get::<ContentLength>().map(|len| len.0),
+                body: None,
+            }), None, res.timings().clone()),
+            Err(ref e) => (None, Some(e.to_string()), Timings {
+                total: Some(started_instant.elapsed()),
+                ..Default::default()
+            }),
+        };
+
+        let id = recorder.push(Entry {
+            id: 0,
+            started: started,
+            request: request,
+            response: response,
+            error: error,
+            timings: timings,
+        });
+
+        res.map(|mut res| {
+            if res.timings().total.is_none() {
+                let bodies = recorder.record_bodies();
+                res.set_har_body(BodyCapture {
+                    recorder: recorder,
+                    id: id,
+                    started: started_instant,
+                    body: if bodies { Some(vec![]) } else { None },
+                });
+            }
+            res
+        })
+    }
+}
+
+/// Collects a response body as it is read, for its `HarRecorder` entry.
+#[doc(hidden)]
+pub struct BodyCapture {
+    recorder: HarRecorder,
+    id: usize,
+    started: Instant,
+    body: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for BodyCapture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BodyCapture").field("id", &self.id).finish()
+    }
+}
+
+impl BodyCapture {
+    #[doc(hidden)]
+    pub fn read(&mut self, buf: &[u8]) {
+        if let Some(ref mut body) = self.body {
+            body.extend_from_slice(buf);
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn end(self) {
+        self.recorder.complete(self.id, self.body, self.started.elapsed());
+    }
+}
+
+fn header_list(headers: &Headers) -> Vec<(String, String)> {
+    headers.iter().map(|h| (h.name().to_owned(), h.value_string())).collect()
+}
+
+fn millis(dur: Option<Duration>) -> f64 {
+    match dur {
+        Some(dur) => dur.as_secs() as f64 * 1000.0 + dur.subsec_nanos() as f64 / 1_000_000.0,
+        None => -1.0,
+    }
+}
+
+fn name_values(pairs: &[(String, String)]) -> Json {
+    Json::Array(pairs.iter().map(|&(ref name, ref value)| {
+        let mut obj = BTreeMap::new();
+        obj.insert("name".to_owned(), name.to_json());
+        obj.insert("value".to_owned(), value.to_json());
+        Json::Object(obj)
+    }).collect())
+}
+
+fn insert_body(obj: &mut BTreeMap<String, Json>, body: &[u8]) {
+    match ::std::str::from_utf8(body) {
+        Ok(text) => {
+            obj.insert("text".to_owned(), text.to_json());
+        },
+        Err(_) => {
+            obj.insert("text".to_owned(), body.to_base64(STANDARD).to_json());
+            obj.insert("encoding".to_owned(), "base64".to_json());
+        }
+    }
+}
+
+impl ToJson for Entry {
+    fn to_json(&self) -> Json {
+        let t = &self.timings;
+        let dns = millis(t.dns);
+        let connect = millis(t.connect);
+        let ssl = millis(t.tls);
+        let setup = [dns, connect, ssl].iter().filter(|&&ms| ms > 0.0).fold(0.0, |a, b| a + b);
+        let wait = if t.first_byte.is_some() {
+            (millis(t.first_byte) - setup).max(0.0)
+        } else {
+            0.0
+        };
+        let receive = match (t.first_byte, t.total) {
+            (Some(_), Some(_)) => (millis(t.total) - millis(t.first_byte)).max(0.0),
+            _ => 0.0,
+        };
+        let total = match t.total {
+            Some(_) => millis(t.total),
+            None => setup + wait,
+        };
+
+        let mut timings = BTreeMap::new();
+        timings.insert("blocked".to_owned(), (-1.0f64).to_json());
+        timings.insert("dns".to_owned(), dns.to_json());
+        timings.insert("connect".to_owned(), connect.to_json());
+        timings.insert("ssl".to_owned(), ssl.to_json());
+        timings.insert("send".to_owned(), 0.0f64.to_json());
+        timings.insert("wait".to_owned(), wait.to_json());
+        timings.insert("receive".to_owned(), receive.to_json());
+
+        let started = format!("{}.{:03}Z", self.started.strftime("%Y-%m-%dT%H:%M:%S").unwrap(),
+                              self.started.tm_nsec / 1_000_000);
+
+        let mut entry = BTreeMap::new();
+        entry.insert("startedDateTime".to_owned(), started.to_json());
+        entry.insert("time".to_owned(), total.to_json());
+        entry.insert("request".to_owned(), self.request.to_json());
+        entry.insert("response".to_owned(), match self.response {
+            Some(ref response) => response.to_json(),
+            None => ResponseRecord::failed(),
+        });
+        entry.insert("cache".to_owned(), Json::Object(BTreeMap::new()));
+        entry.insert("timings".to_owned(), Json::Object(timings));
+        if let Some(ref error) = self.error {
+            entry.insert("_error".to_owned(), error.to_json());
+        }
+        Json::Object(entry)
+    }
+}
+
+impl ToJson for RequestRecord {
+    fn to_json(&self) -> Json {
+        let query = self.url.query_pairs().unwrap_or(vec![]);
+        let mut obj = BTreeMap::new();
+        obj.insert("method".to_owned(), self.method.to_string().to_json());
+        obj.insert("url".to_owned(), self.url.serialize().to_json());
+        obj.insert("httpVersion".to_owned(), self.version.to_string().to_json());
+        obj.insert("cookies".to_owned(), Json::Array(vec![]));
+        obj.insert("headers".to_owned(), name_values(&self.headers));
+        obj.insert("queryString".to_owned(), name_values(&query));
+        obj.insert("headersSize".to_owned(), (-1i64).to_json());
+        obj.insert("bodySize".to_owned(), match self.body {
+            Some(ref body) => (body.len() as i64).to_json(),
+            None => (-1i64).to_json(),
+        });
+        if let Some(ref body) = self.body {
+            let mime = self.headers.iter()
+                .find(|&&(ref name, _)| name.eq_ignore_ascii_case("Content-Type"))
+                .map(|&(_, ref value)| value.clone())
+                .unwrap_or(String::new());
+            let mut post = BTreeMap::new();
+            post.insert("mimeType".to_owned(), mime.to_json());
+            insert_body(&mut post, body);
+            obj.insert("postData".to_owned(), Json::Object(post));
+        }
+        Json::Object(obj)
+    }
+}
+
+impl ResponseRecord {
+    fn failed() -> Json {
+        ResponseRecord {
+            status: 0,
+            status_text: String::new(),
+            version: String::new(),
+            headers: vec![],
+            redirect_url: String::new(),
+            mime_type: String::new(),
+            size: None,
+            body: None,
+        }.to_json()
+    }
+}
+
+impl ToJson for ResponseRecord {
+    fn to_json(&self) -> Json {
+        let size = self.size.map(|size| size as i64).unwrap_or(-1);
+        let mut content = BTreeMap::new();
+        content.insert("size".to_owned(), size.to_json());
+        content.insert("mimeType".to_owned(), self.mime_type.to_json());
+        if let Some(ref body) = self.body {
+            insert_body(&mut content, body);
+        }
+
+        let mut obj = BTreeMap::new();
+        obj.insert("status".to_owned(), self.status.to_json());
+        obj.insert("statusText".to_owned(), self.status_text.to_json());
+        obj.insert("httpVersion".to_owned(), self.version.to_json());
+        obj.insert("cookies".to_owned(), Json::Array(vec![]));
+        obj.insert("headers".to_owned(), name_values(&self.headers));
+        obj.insert("content".to_owned(), Json::Object(content));
+        obj.insert("redirectURL".to_owned(), self.redirect_url.to_json());
+        obj.insert("headersSize".to_owned(), (-1i64).to_json());
+        obj.insert("bodySize".to_owned(), size.to_json());
+        Json::Object(obj)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use serialize::json::Json;
+
+    use client::{Client, Pool};
+    use header::Headers;
+    use method::Method;
+    use version::HttpVersion;
+    use {Error, Url};
+    use super::HarRecorder;
+
+    mock_connector!(MockHar {
+        "http://127.0.0.1" =>       "HTTP/1.1 200 OK\r\n\
+                                     Content-Type: text/plain\r\n\
+                                     Content-Length: 5\r\n\
+                                     \r\n\
+                                     hello"
+    });
+
+    fn parse(har: &HarRecorder) -> Json {
+        Json::from_str(&har.to_json()).unwrap()
+    }
+
+    fn find<'a>(json: &'a Json, path: &[&str]) -> &'a Json {
+        json.find_path(path).unwrap_or_else(|| panic!("missing {:?}", path))
+    }
+
+    #[test]
+    fn test_har_recorder() {
+        let har = HarRecorder::new();
+        let mut client = Client::with_connector(Pool::with_connector(Default::default(), MockHar));
+        client.set_har_recorder(Some(har.clone()));
+
+        client.post("http://127.0.0.1/submit?q=1").body("data").send().unwrap();
+        assert_eq!(har.len(), 1);
+
+        let json = parse(&har);
+        assert_eq!(find(&json, &["log", "version"]).as_string(), Some("1.2"));
+        let entry = &find(&json, &["log", "entries"]).as_array().unwrap()[0];
+        assert_eq!(find(entry, &["request", "method"]).as_string(), Some("POST"));
+        assert_eq!(find(entry, &["request", "httpVersion"]).as_string(), Some("HTTP/1.1"));
+        assert_eq!(find(entry, &["request", "url"]).as_string(),
+                   Some("http://127.0.0.1/submit?q=1"));
+        let query = find(entry, &["request", "queryString"]).as_array().unwrap();
+        assert_eq!(query[0].find("name").unwrap().as_string(), Some("q"));
+        assert!(find(entry, &["request"]).find("postData").is_none());
+        assert_eq!(find(entry, &["response", "status"]).as_u64(), Some(200));
+        assert_eq!(find(entry, &["response", "content", "size"]).as_i64(), Some(5));
+        assert_eq!(find(entry, &["response", "content", "mimeType"]).as_string(),
+                   Some("text/plain"));
+        assert!(find(entry, &["response", "content"]).find("text").is_none());
+        assert!(find(entry, &["startedDateTime"]).as_string().unwrap().ends_with("Z"));
+    }
+
+    #[test]
+    fn test_har_recorder_bodies() {
+        let har = HarRecorder::new();
+        har.set_record_bodies(true);
+        let mut client = Client::with_connector(MockHar);
+        client.set_har_recorder(Some(har.clone()));
+
+        let mut res = client.post("http://127.0.0.1").body("data").send().unwrap();
+        let content = &["log", "entries"];
+        {
+            let json = parse(&har);
+            let entry = &find(&json, content).as_array().unwrap()[0];
+            assert_eq!(find(entry, &["request", "postData", "text"]).as_string(), Some("data"));
+            assert!(find(entry, &["response", "content"]).find("text").is_none());
+        }
+
+        res.read_to_string(&mut String::new()).unwrap();
+        let json = parse(&har);
+        let entry = &find(&json, content).as_array().unwrap()[0];
+        assert_eq!(find(entry, &["response", "content", "text"]).as_string(), Some("hello"));
+
+        har.clear();
+        assert!(har.is_empty());
+    }
+
+    #[test]
+    fn test_har_request_version() {
+        let har = HarRecorder::new();
+        let url = Url::parse("http://127.0.0.1/").unwrap();
+        let mut capture = har.capture(&Method::Get, &url, None);
+        capture.set_request(HttpVersion::Http10, &Headers::new(), None);
+        assert!(capture.finish(Err(Error::TooLarge)).is_err());
+
+        let json = parse(&har);
+        let entry = &find(&json, &["log", "entries"]).as_array().unwrap()[0];
+        assert_eq!(find(entry, &["request", "httpVersion"]).as_string(), Some("HTTP/1.0"));
+    }
+}
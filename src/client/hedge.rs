@@ -0,0 +1,129 @@
+//! Hedged requests, to cut down on tail latency.
+//!
+//! With `Hedging` enabled, an idempotent request without a body that hasn't
+//! received a response head within a delay is sent a second time, on
+//! another connection, from another thread. Whichever response arrives
+//! first is returned, and the other attempt is cancelled, closing its
+//! connection.
+//!
+//! The delay is the configured percentile of recently observed latencies,
+//! so only the slowest requests are hedged. Until enough latencies have
+//! been seen, requests are not hedged at all.
+//!
+//! ```no_run
+//! use hyper::Client;
+//! use hyper::client::hedge::Hedging;
+//!
+//! let mut client = Client::new();
+//! client.set_hedging(Some(Hedging::new(Default::default())));
+//! ```
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Config options for `Hedging`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The latency percentile to wait for before hedging, between 0 and 1.
+    pub percentile: f32,
+    /// How many recent latencies to keep.
+    pub window: usize,
+    /// How many latencies must have been seen before hedging starts.
+    pub min_samples: usize,
+    /// The shortest delay to ever hedge after.
+    pub min_delay: Duration,
+}
+
+impl Default for Config {
+    #[inline]
+    fn default() -> Config {
+        Config {
+            percentile: 0.95,
+            window: 100,
+            min_samples: 20,
+            min_delay: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Tracks response latencies, and decides when requests are hedged.
+#[derive(Debug)]
+pub struct Hedging {
+    config: Config,
+    samples: Mutex<VecDeque<Duration>>,
+}
+
+impl Hedging {
+    /// Creates `Hedging` with the given `Config`.
+    pub fn new(config: Config) -> Hedging {
+        Hedging {
+            samples: Mutex::new(VecDeque::with_capacity(config.window)),
+            config: config,
+        }
+    }
+
+    /// Get the delay after which a request would currently be hedged.
+    ///
+    /// Returns `None` if not enough latencies have been seen yet.
+    pub fn delay(&self) -> Option<Duration> {
+        let samples = self.samples.lock().unwrap();
+        if samples.is_empty() || samples.len() < self.config.min_samples {
+            return None;
+        }
+        let mut sorted = samples.iter().cloned().collect::<Vec<_>>();
+        sorted.sort();
+        let rank = (self.config.percentile * sorted.len() as f32).ceil() as usize;
+        let index = ::std::cmp::min(rank.saturating_sub(1), sorted.len() - 1);
+        Some(::std::cmp::max(sorted[index], self.config.min_delay))
+    }
+
+    /// Records the time it took to receive a response head.
+    pub fn record(&self, latency: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() >= self.config.window {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::{Config, Hedging};
+
+    #[test]
+    fn test_delay_percentile() {
+        let hedging = Hedging::new(Config {
+            percentile: 0.9,
+            window: 10,
+            min_samples: 5,
+            min_delay: Duration::from_millis(1),
+        });
+        for ms in 1..5 {
+            hedging.record(Duration::from_millis(ms));
+        }
+        assert_eq!(hedging.delay(), None);
+
+        for ms in 5..11 {
+            hedging.record(Duration::from_millis(ms));
+        }
+        assert_eq!(hedging.delay(), Some(Duration::from_millis(9)));
+
+        // the oldest samples fall out of the window
+        hedging.record(Duration::from_millis(100));
+        hedging.record(Duration::from_millis(100));
+        assert_eq!(hedging.delay(), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_min_delay() {
+        let hedging = Hedging::new(Config {
+            min_samples: 1,
+            min_delay: Duration::from_millis(50),
+            ..Default::default()
+        });
+        hedging.record(Duration::from_millis(1));
+        assert_eq!(hedging.delay(), Some(Duration::from_millis(50)));
+    }
+}
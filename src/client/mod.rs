@@ -58,41 +58,79 @@
 use std::default::Default;
 use std::io::{self, copy, Read};
 use std::iter::Extend;
-
-#[cfg(feature = "timeouts")]
-use std::time::Duration;
+use std::net::SocketAddr;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use url::UrlParser;
 use url::ParseError as UrlError;
 
 use header::{Headers, Header, HeaderFormat};
-use header::{ContentLength, Location};
+use header::{ContentLength, ContentType, Cookie, Expect, Location, SetCookie, TransferEncoding};
+use header::UserAgent;
 use method::Method;
-use net::{NetworkConnector, NetworkStream, Fresh};
+use status::StatusCode;
+use net::{NetworkConnector, NetworkStream, Fresh, ResolveOverrides, Resolver,
+          ResolvingConnector, Streaming, SystemResolver};
 use {Url};
 use Error;
 
+pub use self::breaker::CircuitBreaker;
+pub use self::cancel::CancelToken;
+pub use self::cookies::CookieJar;
+pub use self::har::HarRecorder;
+pub use self::hedge::Hedging;
+pub use self::pipe::WriteBody;
 pub use self::pool::Pool;
 pub use self::request::Request;
-pub use self::response::Response;
-
+pub use self::response::{Response, Timings};
+pub use http::h1::Limits;
+
+pub mod breaker;
+pub mod cancel;
+pub mod cookies;
+pub mod dns;
+pub mod har;
+pub mod hedge;
+pub mod pipe;
 pub mod pool;
+pub mod proxy;
+pub mod ratelimit;
 pub mod request;
 pub mod response;
+pub mod upstream;
 
-use http::Protocol;
+use http::{HttpMessage, Protocol};
 use http::h1::Http11Protocol;
 
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
 /// A Client to use additional features with Requests.
 ///
 /// Clients can handle things such as: redirect policy, connection pooling.
 pub struct Client {
-    protocol: Box<Protocol + Send + Sync>,
+    protocol: Arc<Protocol + Send + Sync>,
     redirect_policy: RedirectPolicy,
+    circuit_breaker: Option<CircuitBreaker>,
+    hedging: Option<Arc<Hedging>>,
+    max_redirects: usize,
+    har_recorder: Option<HarRecorder>,
+    cookie_jar: Option<Arc<CookieJar>>,
+    user_agent: Option<UserAgent>,
+    strict_headers: bool,
+    limits: Limits,
+    background: Arc<Background>,
+    shutdown_on_drop: Option<Duration>,
+    timeout: Option<Duration>,
+    rate_limit_wait: Option<Duration>,
+    resolve_overrides: Option<ResolveOverrides>,
     #[cfg(feature = "timeouts")]
     read_timeout: Option<Duration>,
     #[cfg(feature = "timeouts")]
     write_timeout: Option<Duration>,
+    #[cfg(feature = "timeouts")]
+    expect_continue_timeout: Duration,
 }
 
 impl Client {
@@ -104,7 +142,27 @@ impl Client {
 
     /// Create a new Client with a configured Pool Config.
     pub fn with_pool_config(config: pool::Config) -> Client {
-        Client::with_connector(Pool::new(config))
+        Client::resolving(config, SystemResolver)
+    }
+
+    /// Create a new Client resolving hosts with `resolver`, and otherwise
+    /// like `Client::new`.
+    ///
+    /// The addresses a host resolves to are raced as `net::connect_any`
+    /// does.
+    pub fn with_resolver<R: Resolver + 'static>(resolver: R) -> Client {
+        Client::resolving(Default::default(), resolver)
+    }
+
+    fn resolving<R: Resolver + 'static>(config: pool::Config, resolver: R) -> Client {
+        let overrides = ResolveOverrides::new();
+        let mut connector = ResolvingConnector::new(resolver);
+        connector.set_overrides(overrides.clone());
+        #[cfg(feature = "openssl")]
+        let connector = ::net::HttpsConnector::with_connector(::net::Openssl::default(), connector);
+        let mut client = Client::with_connector(Pool::with_connector(config, connector));
+        client.resolve_overrides = Some(overrides);
+        client
     }
 
     /// Create a new client with a specific connector.
@@ -117,8 +175,21 @@ impl Client {
     /// Create a new client with a specific `Protocol`.
     pub fn with_protocol<P: Protocol + Send + Sync + 'static>(protocol: P) -> Client {
         Client {
-            protocol: Box::new(protocol),
+            protocol: Arc::new(protocol),
             redirect_policy: Default::default(),
+            circuit_breaker: None,
+            hedging: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            har_recorder: None,
+            cookie_jar: None,
+            user_agent: None,
+            strict_headers: false,
+            limits: Limits::default(),
+            background: Arc::new(Background::default()),
+            shutdown_on_drop: None,
+            timeout: None,
+            rate_limit_wait: None,
+            resolve_overrides: None,
         }
     }
 
@@ -126,10 +197,24 @@ impl Client {
     /// Create a new client with a specific `Protocol`.
     pub fn with_protocol<P: Protocol + Send + Sync + 'static>(protocol: P) -> Client {
         Client {
-            protocol: Box::new(protocol),
+            protocol: Arc::new(protocol),
             redirect_policy: Default::default(),
+            circuit_breaker: None,
+            hedging: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            har_recorder: None,
+            cookie_jar: None,
+            user_agent: None,
+            strict_headers: false,
+            limits: Limits::default(),
+            background: Arc::new(Background::default()),
+            shutdown_on_drop: None,
+            timeout: None,
+            rate_limit_wait: None,
+            resolve_overrides: None,
             read_timeout: None,
             write_timeout: None,
+            expect_continue_timeout: Duration::from_secs(1),
         }
     }
 
@@ -138,6 +223,168 @@ impl Client {
         self.redirect_policy = policy;
     }
 
+    /// Set the most redirects to follow for a request, after which the
+    /// last redirect response is returned. It defaults to 10.
+    pub fn set_max_redirects(&mut self, max: usize) {
+        self.max_redirects = max;
+    }
+
+    /// Set a CircuitBreaker to guard requests to each origin.
+    pub fn set_circuit_breaker(&mut self, breaker: Option<CircuitBreaker>) {
+        self.circuit_breaker = breaker;
+    }
+
+    /// Set Hedging for idempotent requests without a body.
+    pub fn set_hedging(&mut self, hedging: Option<Hedging>) {
+        self.hedging = hedging.map(Arc::new);
+    }
+
+    /// Set a HarRecorder to record all requests and responses.
+    pub fn set_har_recorder(&mut self, recorder: Option<HarRecorder>) {
+        self.har_recorder = recorder;
+    }
+
+    /// Set a CookieJar to keep the cookies responses set, and send them
+    /// with later requests.
+    pub fn set_cookie_jar(&mut self, jar: Option<CookieJar>) {
+        self.cookie_jar = jar.map(Arc::new);
+    }
+
+    /// Get the CookieJar of this Client, if it has one.
+    pub fn cookie_jar(&self) -> Option<&CookieJar> {
+        self.cookie_jar.as_ref().map(|jar| &**jar)
+    }
+
+    /// Set a `User-Agent` to send with all requests that don't set their
+    /// own.
+    pub fn set_user_agent(&mut self, user_agent: Option<UserAgent>) {
+        self.user_agent = user_agent;
+    }
+
+    /// Set whether to fail on responses with header lines that can't be
+    /// parsed, instead of keeping them aside in `Headers::get_raw_invalid`.
+    pub fn set_strict_headers(&mut self, strict: bool) {
+        self.strict_headers = strict;
+    }
+
+    /// Set the limits on the size of responses.
+    ///
+    /// Only `max_head_size`, `max_headers` and `max_body_size` apply to
+    /// responses.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Set a timeout to `shutdown` with when this Client is dropped.
+    ///
+    /// Without one, the default, idle connections are closed once nothing
+    /// shares them anymore, and requests running on other threads are left
+    /// to finish on their own.
+    pub fn set_shutdown_on_drop(&mut self, timeout: Option<Duration>) {
+        self.shutdown_on_drop = timeout;
+    }
+
+    /// Set how long a request may take, from connecting until its response
+    /// body is read, after which it is aborted. See the `cancel` module.
+    ///
+    /// Requests with a timeout or a `CancelToken` aren't hedged.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Set the longest a request waits out a rate limit before it is sent
+    /// once more.
+    ///
+    /// A `429` or `503` response saying when to retry, with the
+    /// `RateLimit-*` headers or `Retry-After` (see `ratelimit::retry_delay`),
+    /// is then waited out if that's no longer than `max`, and the request
+    /// sent again, if it is idempotent and has no body or a buffered one.
+    /// Otherwise, and without a `max`, the default, the response is
+    /// returned.
+    pub fn set_rate_limit_wait(&mut self, max: Option<Duration>) {
+        self.rate_limit_wait = max;
+    }
+
+    /// Connect to `addr` for requests to `host`, on any port, instead of
+    /// the addresses `host` resolves to, as curl's `--resolve` does.
+    ///
+    /// The request is otherwise unchanged, keeping its `Host` header and
+    /// the name TLS verifies. Connections already kept idle for `host` are
+    /// still reused.
+    ///
+    /// Only a Client made with `new`, `with_pool_config` or `with_resolver`
+    /// knows its connector, so this returns `false` and does nothing for
+    /// others. Their connector can be given a `net::ResolveOverrides` with
+    /// `set_overrides` instead.
+    pub fn resolve_override(&mut self, host: &str, addr: SocketAddr) -> bool {
+        match self.resolve_overrides {
+            Some(ref overrides) => {
+                overrides.insert(host, addr);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Waits up to `timeout` for the requests this Client still has running
+    /// on other threads, such as the second attempts of hedged requests, and
+    /// then closes the idle connections kept for reuse. Returns whether the
+    /// requests all finished.
+    ///
+    /// The Client can still be used afterwards, making new connections.
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        let finished = self.background.wait(timeout);
+        self.protocol.close_idle();
+        finished
+    }
+
+    // A Client sharing this one's connections and timeouts, to make a
+    // single request on another thread.
+    #[cfg(not(feature = "timeouts"))]
+    fn detached(&self) -> Client {
+        Client {
+            protocol: self.protocol.clone(),
+            redirect_policy: RedirectPolicy::FollowNone,
+            circuit_breaker: None,
+            hedging: None,
+            max_redirects: self.max_redirects,
+            har_recorder: self.har_recorder.clone(),
+            cookie_jar: self.cookie_jar.clone(),
+            user_agent: self.user_agent.clone(),
+            strict_headers: self.strict_headers,
+            limits: self.limits,
+            background: self.background.clone(),
+            shutdown_on_drop: None,
+            timeout: None,
+            rate_limit_wait: self.rate_limit_wait,
+            resolve_overrides: self.resolve_overrides.clone(),
+        }
+    }
+
+    #[cfg(feature = "timeouts")]
+    fn detached(&self) -> Client {
+        Client {
+            protocol: self.protocol.clone(),
+            redirect_policy: RedirectPolicy::FollowNone,
+            circuit_breaker: None,
+            hedging: None,
+            max_redirects: self.max_redirects,
+            har_recorder: self.har_recorder.clone(),
+            cookie_jar: self.cookie_jar.clone(),
+            user_agent: self.user_agent.clone(),
+            strict_headers: self.strict_headers,
+            limits: self.limits,
+            background: self.background.clone(),
+            shutdown_on_drop: None,
+            timeout: None,
+            rate_limit_wait: self.rate_limit_wait,
+            resolve_overrides: self.resolve_overrides.clone(),
+            read_timeout: self.read_timeout,
+            write_timeout: self.write_timeout,
+            expect_continue_timeout: self.expect_continue_timeout,
+        }
+    }
+
     /// Set the read timeout value for all requests.
     #[cfg(feature = "timeouts")]
     pub fn set_read_timeout(&mut self, dur: Option<Duration>) {
@@ -150,6 +397,16 @@ impl Client {
         self.write_timeout = dur;
     }
 
+    /// Set how long requests sent with `Expect: 100-continue` wait for a
+    /// `100 Continue` before writing their body anyway. It defaults to one
+    /// second.
+    ///
+    /// Without the `timeouts` feature, the body is written without waiting.
+    #[cfg(feature = "timeouts")]
+    pub fn set_expect_continue_timeout(&mut self, dur: Duration) {
+        self.expect_continue_timeout = dur;
+    }
+
     /// Build a Get request.
     pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
         self.request(Method::Get, url)
@@ -189,6 +446,9 @@ impl Client {
             url: url.into_url(),
             body: None,
             headers: None,
+            on_response_head: None,
+            cancel_token: None,
+            timeout: None,
         }
     }
 }
@@ -216,6 +476,9 @@ pub struct RequestBuilder<'a> {
     headers: Option<Headers>,
     method: Method,
     body: Option<Body<'a>>,
+    on_response_head: Option<Box<FnMut(&Response) -> bool + 'a>>,
+    cancel_token: Option<CancelToken>,
+    timeout: Option<Duration>,
 }
 
 impl<'a> RequestBuilder<'a> {
@@ -248,13 +511,66 @@ impl<'a> RequestBuilder<'a> {
         self
     }
 
+    /// Set a function to call with the head of each response, as soon as
+    /// it is read, before any of its body.
+    ///
+    /// It sees the responses to redirects that are followed too, before
+    /// they are. If it returns `false`, the response's connection is closed
+    /// without reading its body, and `send` returns an error of kind
+    /// `ConnectionAborted`.
+    ///
+    /// ```no_run
+    /// # use hyper::Client;
+    /// # use hyper::header::ContentLength;
+    /// let res = Client::new().get("http://example.domain/video").on_response_head(|res| {
+    ///     // don't download anything too large
+    ///     res.headers.get::<ContentLength>().map_or(true, |len| **len < 1 << 20)
+    /// }).send();
+    /// ```
+    pub fn on_response_head<F>(mut self, f: F) -> RequestBuilder<'a>
+    where F: FnMut(&Response) -> bool + 'a {
+        self.on_response_head = Some(Box::new(f));
+        self
+    }
+
+    /// Set a `CancelToken` that aborts this request when cancelled.
+    pub fn cancel_token(mut self, token: CancelToken) -> RequestBuilder<'a> {
+        self.cancel_token = Some(token);
+        self
+    }
+
+    /// Set how long this request may take, instead of the timeout of the
+    /// Client.
+    pub fn timeout(mut self, timeout: Duration) -> RequestBuilder<'a> {
+        self.timeout = Some(timeout);
+        self
+    }
+
     /// Execute this request and receive a Response back.
-    pub fn send(self) -> ::Result<Response> {
-        let RequestBuilder { client, method, url, headers, body } = self;
+    pub fn send(mut self) -> ::Result<Response> {
+        let timeout = self.timeout.or(self.client.timeout);
+        let token = match timeout {
+            Some(_) => Some(self.cancel_token.take().unwrap_or_else(CancelToken::new)),
+            None => self.cancel_token.take()
+        };
+        let deadline = match (timeout, token.as_ref()) {
+            (Some(timeout), Some(token)) => Some(token.deadline(timeout)),
+            _ => None
+        };
+        let mut res = try!(self.send_cancellable(token.as_ref()));
+        if let Some(deadline) = deadline {
+            res.set_deadline(deadline);
+        }
+        Ok(res)
+    }
+
+    fn send_cancellable(self, cancel: Option<&CancelToken>) -> ::Result<Response> {
+        let RequestBuilder { client, mut method, url, mut headers, body,
+                             mut on_response_head, .. } = self;
         let mut url = try!(url);
         trace!("send {:?} {:?}", method, url);
 
-        let can_have_body = match &method {
+        let mut can_have_body = match &method {
             &Method::Get | &Method::Head => false,
             _ => true
         };
@@ -264,40 +580,73 @@ impl<'a> RequestBuilder<'a> {
         } else {
             None
         };
+        // what is sent again to a redirect that keeps the method, or after a
+        // rate limit
+        let replay = match body {
+            None => Some(None),
+            Some(Body::BufBody(bytes, len)) => Some(Some((bytes, len))),
+            Some(_) => None,
+        };
+        let mut redirects = 0;
+        // a rate limited request is only sent again once
+        let mut rate_limited = false;
 
         loop {
-            let message = {
-                let (host, port) = try!(get_host_and_port(&url));
-                try!(client.protocol.new_message(&host, port, &*url.scheme))
-            };
-            let mut req = try!(Request::with_message(method.clone(), url.clone(), message));
-            headers.as_ref().map(|headers| req.headers_mut().extend(headers.iter()));
-
-            #[cfg(not(feature = "timeouts"))]
-            fn set_timeouts(_req: &mut Request<Fresh>, _client: &Client) -> ::Result<()> {
-                Ok(())
+            let (host, port) = try!(get_host_and_port(&url));
+            if let Some(ref breaker) = client.circuit_breaker {
+                try!(breaker.check(&host, port, &url.scheme));
             }
 
-            #[cfg(feature = "timeouts")]
-            fn set_timeouts(req: &mut Request<Fresh>, client: &Client) -> ::Result<()> {
-                try!(req.set_write_timeout(client.write_timeout));
-                try!(req.set_read_timeout(client.read_timeout));
-                Ok(())
+            let hedged = method.idempotent() && body.is_none() && cancel.is_none();
+            let mut req = Outgoing {
+                method: &method,
+                url: &url,
+                host: &host,
+                port: port,
+                headers: headers.as_ref(),
+                can_have_body: can_have_body,
+                body: body.take(),
+                cancel: cancel,
+                capture: None,
+            };
+            let res = match client.hedging {
+                Some(ref hedging) if hedged => send_hedged(hedging, client, &req),
+                _ => attempt(client, &mut req)
+            };
+            if let Some(ref breaker) = client.circuit_breaker {
+                let success = match res {
+                    Ok(ref res) => !res.status.is_server_error(),
+                    Err(_) => false
+                };
+                breaker.record(&host, port, &url.scheme, success);
             }
-
-            try!(set_timeouts(&mut req, &client));
-
-            match (can_have_body, body.as_ref()) {
-                (true, Some(body)) => match body.size() {
-                    Some(size) => req.headers_mut().set(ContentLength(size)),
-                    None => (), // chunked, Request will add it automatically
-                },
-                (true, None) => req.headers_mut().set(ContentLength(0)),
-                _ => () // neither
+            let res = try!(res);
+            if let Some(ref mut on_response_head) = on_response_head {
+                if !on_response_head(&res) {
+                    debug!("response {} from {} aborted", res.status, url);
+                    return Err(io::Error::new(io::ErrorKind::ConnectionAborted,
+                                              "response aborted by on_response_head").into());
+                }
+            }
+            if !rate_limited && method.idempotent() && replay.is_some() {
+                let delay = client.rate_limit_wait.and_then(|max| {
+                    ratelimit::retry_delay(res.status, &res.headers).and_then(|delay| {
+                        if delay <= max { Some(delay) } else { None }
+                    })
+                });
+                if let Some(delay) = delay {
+                    debug!("{} for {}, retrying after {:?}", res.status, url, delay);
+                    drop(res);
+                    thread::sleep(delay);
+                    rate_limited = true;
+                    if can_have_body {
+                        body = replay.and_then(|replay| {
+                            replay.map(|(bytes, len)| Body::BufBody(bytes, len))
+                        });
+                    }
+                    continue;
+                }
             }
-            let mut streaming = try!(req.start());
-            body.take().map(|mut rdr| copy(&mut rdr, &mut streaming));
-            let res = try!(streaming.send());
             if !res.status.is_redirection() {
                 return Ok(res)
             }
@@ -333,6 +682,386 @@ impl<'a> RequestBuilder<'a> {
                 RedirectPolicy::FollowIf(cond) if cond(&url) => (), //continue
                 _ => return Ok(res),
             }
+
+            redirects += 1;
+            if redirects > client.max_redirects {
+                debug!("not following more than {} redirects", client.max_redirects);
+                return Ok(res);
+            }
+            let to_get = match res.status {
+                StatusCode::SeeOther => method != Method::Head,
+                StatusCode::MovedPermanently | StatusCode::Found => method == Method::Post,
+                _ => false
+            };
+            if to_get {
+                debug!("redirected {} becomes GET", method);
+                method = Method::Get;
+                can_have_body = false;
+                body = None;
+                if let Some(ref mut headers) = headers {
+                    headers.remove::<ContentLength>();
+                    headers.remove::<ContentType>();
+                    headers.remove::<TransferEncoding>();
+                }
+            } else if can_have_body {
+                body = match replay {
+                    Some(replay) => replay.map(|(bytes, len)| Body::BufBody(bytes, len)),
+                    None => {
+                        debug!("can't send the body of {} again for a redirect", method);
+                        return Ok(res);
+                    }
+                };
+            }
+            if !same_origin(&res.url, &url) {
+                if let Some(ref mut headers) = headers {
+                    for name in CREDENTIAL_HEADERS {
+                        headers.remove_raw(name);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// What each attempt at a request sends.
+struct Outgoing<'r, 'a> {
+    method: &'r Method,
+    url: &'r Url,
+    host: &'r str,
+    port: u16,
+    headers: Option<&'r Headers>,
+    can_have_body: bool,
+    body: Option<Body<'a>>,
+    cancel: Option<&'r CancelToken>,
+    capture: Option<har::Capture>,
+}
+
+impl<'r, 'a> Outgoing<'r, 'a> {
+    // The same request, without a body, cancelled by `cancel` instead.
+    fn bodiless<'s>(&'s self, cancel: Option<&'s CancelToken>) -> Outgoing<'s, 'a> {
+        Outgoing {
+            method: self.method,
+            url: self.url,
+            host: self.host,
+            port: self.port,
+            headers: self.headers,
+            can_have_body: self.can_have_body,
+            body: None,
+            cancel: cancel,
+            capture: None,
+        }
+    }
+}
+
+fn attempt(client: &Client, req: &mut Outgoing) -> ::Result<Response> {
+    match client.har_recorder {
+        Some(ref har) => {
+            req.capture = Some(har.capture(req.method, req.url, req.headers));
+            let res = attempt_with(client, req);
+            req.capture.take().unwrap().finish(res)
+        },
+        None => attempt_with(client, req)
+    }
+}
+
+fn attempt_with(client: &Client, req: &mut Outgoing) -> ::Result<Response> {
+    // Only a request that can be sent again as it was is retried.
+    let replay = match req.body {
+        _ if !req.method.idempotent() => None,
+        None => Some(None),
+        Some(Body::BufBody(bytes, len)) => Some(Some((bytes, len))),
+        Some(_) => None,
+    };
+    let mut reused = false;
+    let err = match send_once(client, req, &mut reused) {
+        Ok(res) => return Ok(res),
+        Err(e) => e
+    };
+    match replay {
+        // A pooled connection can be closed by the server just as a request
+        // is written on it. The request never reached the server, so it is
+        // sent once more, on a new connection.
+        Some(replay) if reused && is_keep_alive_race(&err) => {
+            debug!("reused connection was closed ({:?}), retrying {}", err, req.url);
+            req.body = replay.map(|(bytes, len)| Body::BufBody(bytes, len));
+            send_once(client, req, &mut reused)
+        },
+        _ => Err(err)
+    }
+}
+
+fn is_keep_alive_race(err: &Error) -> bool {
+    match *err {
+        Error::Io(ref e) => match e.kind() {
+            io::ErrorKind::ConnectionAborted |
+            io::ErrorKind::ConnectionReset |
+            io::ErrorKind::BrokenPipe => true,
+            _ => false
+        },
+        _ => false
+    }
+}
+
+fn send_once(client: &Client, req: &mut Outgoing, reused: &mut bool) -> ::Result<Response> {
+    if let Some(token) = req.cancel {
+        try!(token.check());
+    }
+    let started = Instant::now();
+    let message = try!(client.protocol.new_message(req.host, req.port, &*req.url.scheme));
+    let info = message.connect_info().cloned().unwrap_or_default();
+    *reused = info.reused;
+    // a connection made while the request was cancelled is closed here
+    let guard = match req.cancel {
+        Some(token) => Some(try!(token.guard(message.abort_handle()))),
+        None => None
+    };
+    match exchange(client, message, req) {
+        Ok(mut res) => {
+            res.set_connect_info(started, info);
+            if let Some(guard) = guard {
+                res.set_cancel(guard);
+            }
+            Ok(res)
+        },
+        Err(e) => Err(guard.and_then(|guard| guard.error()).unwrap_or(e))
+    }
+}
+
+// Sends a request on `message`, and reads the head of its response.
+fn exchange(client: &Client, mut message: Box<HttpMessage>,
+            outgoing: &mut Outgoing) -> ::Result<Response> {
+    let Outgoing { method, url, headers, can_have_body, ref mut body, ref mut capture, .. } =
+        *outgoing;
+    message.set_limits(client.limits);
+    let mut req = try!(Request::with_message(method.clone(), url.clone(), message));
+    headers.map(|headers| req.headers_mut().extend(headers.iter()));
+    if let Some(ref user_agent) = client.user_agent {
+        if !req.headers().has::<UserAgent>() {
+            req.headers_mut().set(user_agent.clone());
+        }
+    }
+    if let Some(ref jar) = client.cookie_jar {
+        if !req.headers().has::<Cookie>() {
+            if let Some(cookie) = jar.cookies_for(url) {
+                req.headers_mut().set(cookie);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "timeouts"))]
+    fn set_timeouts(_req: &mut Request<Fresh>, _client: &Client) -> ::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(feature = "timeouts")]
+    fn set_timeouts(req: &mut Request<Fresh>, client: &Client) -> ::Result<()> {
+        try!(req.set_write_timeout(client.write_timeout));
+        try!(req.set_read_timeout(client.read_timeout));
+        Ok(())
+    }
+
+    try!(set_timeouts(&mut req, client));
+
+    #[cfg(not(feature = "timeouts"))]
+    fn wait_for_continue(_req: &mut Request<Streaming>, _client: &Client) -> ::Result<bool> {
+        Ok(true)
+    }
+
+    #[cfg(feature = "timeouts")]
+    fn wait_for_continue(req: &mut Request<Streaming>, client: &Client) -> ::Result<bool> {
+        req.wait_for_continue(client.expect_continue_timeout)
+    }
+
+    match (can_have_body, body.as_ref()) {
+        (true, Some(body)) => match body.size() {
+            Some(size) => req.headers_mut().set(ContentLength(size)),
+            None => (), // chunked, Request will add it automatically
+        },
+        (true, None) => req.headers_mut().set(ContentLength(0)),
+        _ => () // neither
+    }
+    if let Some(ref mut capture) = *capture {
+        let bytes = match *body {
+            Some(Body::BufBody(bytes, _)) => Some(bytes),
+            _ => None
+        };
+        capture.set_request(req.version, req.headers(), bytes);
+    }
+    let expects_continue = body.is_some() &&
+        req.headers().get::<Expect>() == Some(&Expect::Continue);
+    let mut streaming = try!(req.start());
+    if expects_continue && !try!(wait_for_continue(&mut streaming, client)) {
+        body.take();
+    }
+    body.take().map(|mut rdr| copy(&mut rdr, &mut streaming));
+    let res = try!(streaming.send());
+    if let Some(ref jar) = client.cookie_jar {
+        if let Some(set_cookie) = res.headers.get::<SetCookie>() {
+            jar.store(url, set_cookie);
+        }
+    }
+    if client.strict_headers && !res.headers.get_raw_invalid().is_empty() {
+        debug!("response has invalid header lines: {:?}", res.headers.get_raw_invalid());
+        return Err(Error::Header);
+    }
+    Ok(res)
+}
+
+fn send_hedged(hedging: &Arc<Hedging>, client: &Client,
+               req: &Outgoing) -> ::Result<Response> {
+    let delay = match hedging.delay() {
+        Some(delay) => delay,
+        None => {
+            let start = Instant::now();
+            let res = attempt(client, &mut req.bodiless(None));
+            if res.is_ok() {
+                hedging.record(start.elapsed());
+            }
+            return res;
+        }
+    };
+
+    // The first attempt runs on this thread, and the second on another, if
+    // the first is still running after the delay. Whichever succeeds first
+    // claims the win, and cancels the other, closing its connection.
+    let race = Race {
+        first: CancelToken::new(),
+        second: CancelToken::new(),
+        won: Arc::new(Mutex::new(false)),
+    };
+    let (tx, rx) = mpsc::channel();
+    let (finished, waiting) = mpsc::channel::<()>();
+    spawn_hedge(hedging, client, req, delay, waiting, race.clone(), tx);
+
+    let start = Instant::now();
+    let res = attempt(client, &mut req.bodiless(Some(&race.first)));
+    // the second attempt isn't started after this
+    drop(finished);
+    let err = match res {
+        Ok(res) => {
+            hedging.record(start.elapsed());
+            if race.claim(&race.second) {
+                return Ok(res);
+            }
+            // the second attempt won, and its response is on the way
+            Error::Cancelled
+        },
+        Err(e) => e
+    };
+    rx.recv().unwrap_or(Err(err))
+}
+
+// The cancel tokens of both attempts of a hedged request.
+#[derive(Clone)]
+struct Race {
+    first: CancelToken,
+    second: CancelToken,
+    won: Arc<Mutex<bool>>,
+}
+
+impl Race {
+    // Claims the win for a successful attempt, cancelling the other, unless
+    // the other already won.
+    fn claim(&self, other: &CancelToken) -> bool {
+        let mut won = self.won.lock().unwrap();
+        if *won {
+            return false;
+        }
+        *won = true;
+        other.cancel();
+        true
+    }
+}
+
+fn spawn_hedge(hedging: &Arc<Hedging>, client: &Client, req: &Outgoing, delay: Duration,
+               waiting: mpsc::Receiver<()>, race: Race, tx: mpsc::Sender<::Result<Response>>) {
+    let hedging = hedging.clone();
+    let client = client.detached();
+    let method = req.method.clone();
+    let url = req.url.clone();
+    let host = req.host.to_owned();
+    let port = req.port;
+    let headers = req.headers.cloned();
+    let can_have_body = req.can_have_body;
+    let running = Background::enter(&client.background);
+    thread::spawn(move || {
+        let _running = running;
+        // disconnected once the first attempt is done
+        if let Err(mpsc::RecvTimeoutError::Disconnected) = waiting.recv_timeout(delay) {
+            return;
+        }
+        debug!("no response after {:?}, hedging {}", delay, url);
+        let start = Instant::now();
+        let res = attempt(&client, &mut Outgoing {
+            method: &method,
+            url: &url,
+            host: &host,
+            port: port,
+            headers: headers.as_ref(),
+            can_have_body: can_have_body,
+            body: None,
+            cancel: Some(&race.second),
+            capture: None,
+        });
+        match res {
+            Ok(res) => {
+                hedging.record(start.elapsed());
+                if race.claim(&race.first) {
+                    let _ = tx.send(Ok(res));
+                }
+            },
+            Err(e) => {
+                let _ = tx.send(Err(e));
+            }
+        }
+    });
+}
+
+// Counts the threads a Client has running requests on.
+#[derive(Default)]
+struct Background {
+    running: Mutex<usize>,
+    done: Condvar,
+}
+
+impl Background {
+    fn enter(background: &Arc<Background>) -> Running {
+        *background.running.lock().unwrap() += 1;
+        Running(background.clone())
+    }
+
+    fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut running = self.running.lock().unwrap();
+        while *running > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                debug!("{} requests still running", *running);
+                return false;
+            }
+            running = self.done.wait_timeout(running, deadline - now).unwrap().0;
+        }
+        true
+    }
+}
+
+// Counts a thread as running until it is dropped.
+struct Running(Arc<Background>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let mut running = (self.0).running.lock().unwrap();
+        *running -= 1;
+        if *running == 0 {
+            (self.0).done.notify_all();
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Some(timeout) = self.shutdown_on_drop {
+            self.shutdown(timeout);
         }
     }
 }
@@ -444,6 +1173,16 @@ impl Default for RedirectPolicy {
     }
 }
 
+// Sent only to the origin they were set for, and not on to another that a
+// request is redirected to.
+const CREDENTIAL_HEADERS: &'static [&'static str] = &["Authorization", "Cookie",
+                                                       "Proxy-Authorization"];
+
+fn same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme == b.scheme && a.serialize_host() == b.serialize_host() &&
+        a.port_or_default() == b.port_or_default()
+}
+
 fn get_host_and_port(url: &Url) -> ::Result<(String, u16)> {
     let host = match url.serialize_host() {
         Some(host) => host,
@@ -492,6 +1231,36 @@ mod tests {
         assert_eq!(res.headers.get(), Some(&Server("mock3".to_owned())));
     }
 
+    #[test]
+    fn test_on_response_head() {
+        use std::io;
+        use status::StatusCode;
+        use Error;
+
+        let mut client = Client::with_connector(MockRedirectPolicy);
+        client.set_redirect_policy(RedirectPolicy::FollowAll);
+        let mut seen = Vec::new();
+        let res = client.get("http://127.0.0.1").on_response_head(|res| {
+            seen.push((res.status, res.url.serialize()));
+            true
+        }).send().unwrap();
+        assert_eq!(res.headers.get(), Some(&Server("mock3".to_owned())));
+        assert_eq!(seen, vec![(StatusCode::MovedPermanently, "http://127.0.0.1/".to_owned()),
+                              (StatusCode::Found, "http://127.0.0.2/".to_owned()),
+                              (StatusCode::Ok, "https://127.0.0.3/".to_owned())]);
+
+        // aborting a redirect doesn't follow it
+        let mut heads = 0;
+        match client.get("http://127.0.0.1").on_response_head(|_| {
+            heads += 1;
+            heads < 2
+        }).send() {
+            Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::ConnectionAborted => (),
+            other => panic!("{:?}", other.map(|res| res.status))
+        }
+        assert_eq!(heads, 2);
+    }
+
     #[test]
     fn test_redirect_dontfollow() {
         let mut client = Client::with_connector(MockRedirectPolicy);
@@ -511,6 +1280,149 @@ mod tests {
         assert_eq!(res.headers.get(), Some(&Server("mock2".to_owned())));
     }
 
+    mock_connector!(MockRedirectLoop {
+        "http://127.0.0.1" =>       "HTTP/1.1 302 Found\r\n\
+                                     Location: http://127.0.0.1/again\r\n\
+                                     Server: loop\r\n\
+                                     Content-Length: 0\r\n\
+                                     \r\n\
+                                    "
+    });
+
+    #[test]
+    fn test_redirect_max() {
+        let mut client = Client::with_connector(MockRedirectLoop);
+        client.set_max_redirects(3);
+        let res = client.get("http://127.0.0.1").send().unwrap();
+        assert_eq!(res.status, ::status::StatusCode::Found);
+        assert_eq!(res.url.serialize(), "http://127.0.0.1/again");
+    }
+
+    #[test]
+    fn test_redirect_method_and_credentials() {
+        use std::io::{self, Write};
+        use std::net::SocketAddr;
+        use std::str::from_utf8;
+        use std::sync::{Arc, Mutex};
+        #[cfg(feature = "timeouts")]
+        use std::time::Duration;
+        use header::Authorization;
+        use mock::MockStream;
+        use net::{NetworkConnector, NetworkStream};
+
+        struct Recorded(MockStream, Arc<Mutex<Vec<String>>>);
+
+        impl Read for Recorded {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+
+        impl Write for Recorded {
+            fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
+                let mut written = self.1.lock().unwrap();
+                written.last_mut().unwrap().push_str(from_utf8(msg).unwrap());
+                Ok(msg.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        impl NetworkStream for Recorded {
+            fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+                self.0.peer_addr()
+            }
+            #[cfg(feature = "timeouts")]
+            fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+                self.0.set_read_timeout(dur)
+            }
+            #[cfg(feature = "timeouts")]
+            fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+                self.0.set_write_timeout(dur)
+            }
+        }
+
+        // 127.0.0.1 answers with `status`, sending to /next on the same
+        // host, from where 127.0.0.2 is next
+        struct Redirector(&'static str, Arc<Mutex<Vec<String>>>);
+
+        impl NetworkConnector for Redirector {
+            type Stream = Recorded;
+            fn connect(&self, host: &str, _: u16, _: &str) -> ::Result<Recorded> {
+                let mut written = self.1.lock().unwrap();
+                let res = match (host, written.len()) {
+                    ("127.0.0.1", 0) => format!("HTTP/1.1 {}\r\nLocation: /next\r\n\
+                                                 Connection: close\r\n\
+                                                 Content-Length: 0\r\n\r\n", self.0),
+                    ("127.0.0.1", _) => format!("HTTP/1.1 {}\r\n\
+                                                 Location: http://127.0.0.2/\r\n\
+                                                 Connection: close\r\n\
+                                                 Content-Length: 0\r\n\r\n", self.0),
+                    _ => "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n".to_owned()
+                };
+                written.push(String::new());
+                Ok(Recorded(MockStream::with_input(res.as_bytes()), self.1.clone()))
+            }
+        }
+
+        fn send(status: &'static str) -> Vec<String> {
+            let written = Arc::new(Mutex::new(Vec::new()));
+            let client = Client::with_connector(Redirector(status, written.clone()));
+            client.post("http://127.0.0.1")
+                .header(Authorization("secret".to_owned()))
+                .body("data")
+                .send().unwrap();
+            let written = written.lock().unwrap();
+            written.clone()
+        }
+
+        let sent = send("302 Found");
+        assert_eq!(sent.len(), 3);
+        assert!(sent[0].starts_with("POST / "), "{:?}", sent[0]);
+        assert!(sent[0].ends_with("data"));
+        assert!(sent[1].starts_with("GET /next "), "{:?}", sent[1]);
+        assert!(sent[1].contains("Authorization: secret\r\n"));
+        assert!(!sent[1].contains("Content-Length"));
+        assert!(!sent[1].ends_with("data"));
+        // another host doesn't get the credentials
+        assert!(sent[2].starts_with("GET / "), "{:?}", sent[2]);
+        assert!(!sent[2].contains("Authorization"));
+
+        let sent = send("307 Temporary Redirect");
+        assert_eq!(sent.len(), 3);
+        for req in &sent {
+            assert!(req.starts_with("POST "), "{:?}", req);
+            assert!(req.contains("Content-Length: 4\r\n"));
+            assert!(req.ends_with("data"));
+        }
+        assert!(sent[1].contains("Authorization: secret\r\n"));
+        assert!(!sent[2].contains("Authorization"));
+    }
+
+    mock_connector!(MockInvalidHeader {
+        "http://127.0.0.1" =>       "HTTP/1.1 200 OK\r\n\
+                                     Server: mock\r\n\
+                                     X Bad: 1\r\n\
+                                     Content-Length: 0\r\n\
+                                     \r\n\
+                                    "
+    });
+
+    #[test]
+    fn test_strict_headers() {
+        let mut client = Client::with_connector(MockInvalidHeader);
+        let res = client.get("http://127.0.0.1").send().unwrap();
+        assert_eq!(res.headers.get(), Some(&Server("mock".to_owned())));
+        assert_eq!(res.headers.get_raw_invalid(), &[b"X Bad: 1".to_vec()][..]);
+
+        client.set_strict_headers(true);
+        match client.get("http://127.0.0.1").send() {
+            Err(::Error::Header) => (),
+            other => panic!("unexpected result: {:?}", other.map(|res| res.status))
+        }
+    }
+
     mock_connector!(Issue640Connector {
         b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\n",
         b"GET",
@@ -537,4 +1449,502 @@ mod tests {
         client.post("http://127.0.0.1").send().unwrap().read_to_string(&mut s).unwrap();
         assert_eq!(s, "POST");
     }
+
+    #[test]
+    fn test_response_connect_info() {
+        let client = Client::with_connector(Pool::with_connector(Default::default(), Issue640Connector));
+
+        let mut res = client.get("http://127.0.0.1").send().unwrap();
+        assert!(!res.reused());
+        assert!(res.timings().first_byte.is_some());
+        assert_eq!(res.timings().total, None);
+        res.read_to_string(&mut String::new()).unwrap();
+        assert!(res.timings().total.is_some());
+        drop(res);
+
+        let res = client.get("http://127.0.0.1").send().unwrap();
+        assert!(res.reused());
+        assert_eq!(res.timings().connect, None);
+        assert_eq!(res.cipher(), None);
+    }
+
+    #[test]
+    fn test_retry_on_closed_keep_alive() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use mock::MockStream;
+        use net::NetworkConnector;
+
+        // Each connection answers one request, then is closed.
+        struct OneShot(Arc<AtomicUsize>);
+
+        impl NetworkConnector for OneShot {
+            type Stream = MockStream;
+            fn connect(&self, _: &str, _: u16, _: &str) -> ::Result<MockStream> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(MockStream::with_input(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"))
+            }
+        }
+
+        let connects = Arc::new(AtomicUsize::new(0));
+        let client = Client::with_connector(Pool::with_connector(Default::default(),
+                                                                 OneShot(connects.clone())));
+        for _ in 0..2 {
+            let mut s = String::new();
+            client.put("http://127.0.0.1").body("data").send().unwrap()
+                .read_to_string(&mut s).unwrap();
+            assert_eq!(s, "ok");
+        }
+        assert_eq!(connects.load(Ordering::SeqCst), 2);
+
+        // not idempotent, so the error is returned
+        assert!(client.post("http://127.0.0.1").body("data").send().is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_wait() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+        use mock::MockStream;
+        use net::NetworkConnector;
+        use status::StatusCode;
+
+        // Rate limited on every other connection.
+        struct Limited(Arc<AtomicUsize>, &'static str);
+
+        impl NetworkConnector for Limited {
+            type Stream = MockStream;
+            fn connect(&self, _: &str, _: u16, _: &str) -> ::Result<MockStream> {
+                Ok(MockStream::with_input(match self.0.fetch_add(1, Ordering::SeqCst) % 2 {
+                    0 => self.1.as_bytes(),
+                    _ => b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"
+                }))
+            }
+        }
+
+        let limited = "HTTP/1.1 429 Too Many Requests\r\n\
+                       RateLimit-Remaining: 0\r\nRateLimit-Reset: 0\r\n\
+                       Content-Length: 0\r\n\r\n";
+        let connects = Arc::new(AtomicUsize::new(0));
+        let mut client = Client::with_connector(Limited(connects.clone(), limited));
+        assert_eq!(client.put("http://127.0.0.1").body("data").send().unwrap().status,
+                   StatusCode::TooManyRequests);
+        client.set_rate_limit_wait(Some(Duration::from_secs(1)));
+        connects.store(0, Ordering::SeqCst);
+        assert_eq!(client.put("http://127.0.0.1").body("data").send().unwrap().status,
+                   StatusCode::Ok);
+        assert_eq!(connects.load(Ordering::SeqCst), 2);
+
+        // not idempotent, so the response is returned
+        connects.store(0, Ordering::SeqCst);
+        assert_eq!(client.post("http://127.0.0.1").send().unwrap().status,
+                   StatusCode::TooManyRequests);
+
+        // nor is a longer wait than allowed
+        let unavailable = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 60\r\n\
+                           Content-Length: 0\r\n\r\n";
+        let mut client = Client::with_connector(Limited(Arc::new(AtomicUsize::new(0)),
+                                                        unavailable));
+        client.set_rate_limit_wait(Some(Duration::from_secs(1)));
+        assert_eq!(client.get("http://127.0.0.1").send().unwrap().status,
+                   StatusCode::ServiceUnavailable);
+    }
+
+    #[test]
+    fn test_request_shutdown_write() {
+        use std::io::Write;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use method::Method;
+        use mock::MockStream;
+        use net::NetworkConnector;
+        use super::request::Request;
+
+        struct Counting(Arc<AtomicUsize>);
+
+        impl NetworkConnector for Counting {
+            type Stream = MockStream;
+            fn connect(&self, _: &str, _: u16, _: &str) -> ::Result<MockStream> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(MockStream::with_input(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"))
+            }
+        }
+
+        let connects = Arc::new(AtomicUsize::new(0));
+        let pool = Pool::with_connector(Default::default(), Counting(connects.clone()));
+        let url = Url::parse("http://127.0.0.1").unwrap();
+        for _ in 0..2 {
+            let req = Request::with_connector(Method::Post, url.clone(), &pool).unwrap();
+            let mut req = req.start().unwrap();
+            req.write_all(b"data").unwrap();
+            req.shutdown_write().unwrap();
+            assert!(req.write_all(b"more").is_err());
+            let mut s = String::new();
+            req.send().unwrap().read_to_string(&mut s).unwrap();
+            assert_eq!(s, "ok");
+        }
+        // the connection wasn't kept alive
+        assert_eq!(connects.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_shutdown() {
+        use std::thread;
+        use std::time::Duration;
+        use mock::MockStream;
+        use net::NetworkConnector;
+        use super::Background;
+
+        #[derive(Clone)]
+        struct KeepAlive;
+
+        impl NetworkConnector for KeepAlive {
+            type Stream = MockStream;
+            fn connect(&self, _: &str, _: u16, _: &str) -> ::Result<MockStream> {
+                Ok(MockStream::with_input(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"))
+            }
+        }
+
+        let pool = Pool::with_connector(Default::default(), KeepAlive);
+        let mut client = Client::with_connector(pool.clone());
+        client.get("http://127.0.0.1").send().unwrap().read_to_string(&mut String::new()).unwrap();
+        assert_eq!(pool.stats().hosts[0].idle, 1);
+
+        // a request still running elsewhere is waited for
+        let running = Background::enter(&client.background);
+        let slow = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(running);
+        });
+        assert!(!client.shutdown(Duration::from_millis(0)));
+        assert!(client.shutdown(Duration::from_secs(5)));
+        slow.join().unwrap();
+        assert_eq!(pool.stats().evicted_cleared, 1);
+        assert!(pool.stats().hosts.is_empty());
+
+        client.get("http://127.0.0.1").send().unwrap().read_to_string(&mut String::new()).unwrap();
+        client.set_shutdown_on_drop(Some(Duration::from_secs(5)));
+        drop(client);
+        assert_eq!(pool.stats().evicted_cleared, 2);
+    }
+
+    mock_connector!(MockFailing {
+        "http://127.0.0.1" =>       "HTTP/1.1 503 Service Unavailable\r\n\
+                                     Content-Length: 0\r\n\
+                                     \r\n\
+                                    "
+    });
+
+    #[test]
+    fn test_circuit_breaker() {
+        use std::time::Duration;
+        use Error;
+        use super::breaker::{CircuitBreaker, Config};
+
+        let mut client = Client::with_connector(MockFailing);
+        client.set_circuit_breaker(Some(CircuitBreaker::new(Config {
+            max_consecutive_failures: 2,
+            open_timeout: Duration::from_secs(60),
+            ..Default::default()
+        })));
+
+        for _ in 0..2 {
+            let res = client.get("http://127.0.0.1").send().unwrap();
+            assert_eq!(res.status, ::status::StatusCode::ServiceUnavailable);
+        }
+        match client.get("http://127.0.0.1").send() {
+            Err(Error::CircuitOpen) => (),
+            other => panic!("expected CircuitOpen, got {:?}", other.map(|res| res.status)),
+        }
+    }
+
+    mock_connector!(MockSetCookie {
+        "http://127.0.0.1" =>       "HTTP/1.1 200 OK\r\n\
+                                     Set-Cookie: sid=abc; Path=/\r\n\
+                                     Content-Length: 0\r\n\
+                                     \r\n\
+                                    "
+    });
+
+    #[test]
+    fn test_cookie_jar() {
+        use super::CookieJar;
+
+        let mut client = Client::with_connector(MockSetCookie);
+        client.set_cookie_jar(Some(CookieJar::new()));
+        client.get("http://127.0.0.1/login").send().unwrap();
+
+        let jar = client.cookie_jar().unwrap();
+        let cookie = jar.cookies_for(&Url::parse("http://127.0.0.1/account").unwrap());
+        let cookie = cookie.unwrap();
+        assert_eq!((&cookie[0].name[..], &cookie[0].value[..]), ("sid", "abc"));
+        assert!(jar.cookies_for(&Url::parse("http://127.0.0.2/").unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_user_agent() {
+        use std::io::{self, Write};
+        use std::net::SocketAddr;
+        use std::str::from_utf8;
+        use std::sync::{Arc, Mutex};
+        #[cfg(feature = "timeouts")]
+        use std::time::Duration;
+        use header::UserAgent;
+        use mock::MockStream;
+        use net::{NetworkConnector, NetworkStream};
+
+        struct Recorded(MockStream, Arc<Mutex<Vec<u8>>>);
+
+        impl Read for Recorded {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+
+        impl Write for Recorded {
+            fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
+                self.1.lock().unwrap().extend_from_slice(msg);
+                Ok(msg.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        impl NetworkStream for Recorded {
+            fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+                self.0.peer_addr()
+            }
+            #[cfg(feature = "timeouts")]
+            fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+                self.0.set_read_timeout(dur)
+            }
+            #[cfg(feature = "timeouts")]
+            fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+                self.0.set_write_timeout(dur)
+            }
+        }
+
+        struct Recorder(Arc<Mutex<Vec<u8>>>);
+
+        impl NetworkConnector for Recorder {
+            type Stream = Recorded;
+            fn connect(&self, _: &str, _: u16, _: &str) -> ::Result<Recorded> {
+                let res = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+                Ok(Recorded(MockStream::with_input(res), self.0.clone()))
+            }
+        }
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut client = Client::with_connector(Recorder(written.clone()));
+        client.set_user_agent(Some(UserAgent::new("myapp", "1.2")));
+
+        client.get("http://127.0.0.1").send().unwrap();
+        assert!(from_utf8(&written.lock().unwrap()).unwrap()
+                .contains("User-Agent: myapp/1.2\r\n"));
+
+        written.lock().unwrap().clear();
+        client.get("http://127.0.0.1").header(UserAgent::new("other", "2")).send().unwrap();
+        let raw = written.lock().unwrap().clone();
+        let raw = from_utf8(&raw).unwrap();
+        assert!(raw.contains("User-Agent: other/2\r\n"));
+        assert!(!raw.contains("myapp"));
+    }
+
+    #[test]
+    fn test_hedging() {
+        use std::io::{self, Write};
+        use std::net::SocketAddr;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+        use std::time::Duration;
+        use mock::MockStream;
+        use net::{NetworkConnector, NetworkStream};
+        use super::hedge::{Config, Hedging};
+
+        struct Slow(MockStream, Duration);
+
+        impl Read for Slow {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                thread::sleep(self.1);
+                self.0.read(buf)
+            }
+        }
+
+        impl Write for Slow {
+            fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
+                self.0.write(msg)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                self.0.flush()
+            }
+        }
+
+        impl NetworkStream for Slow {
+            fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+                self.0.peer_addr()
+            }
+            #[cfg(feature = "timeouts")]
+            fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+                self.0.set_read_timeout(dur)
+            }
+            #[cfg(feature = "timeouts")]
+            fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+                self.0.set_write_timeout(dur)
+            }
+        }
+
+        struct SlowFirst(AtomicUsize);
+
+        impl NetworkConnector for SlowFirst {
+            type Stream = Slow;
+            fn connect(&self, _: &str, _: u16, _: &str) -> ::Result<Slow> {
+                let (server, delay) = match self.0.fetch_add(1, Ordering::SeqCst) {
+                    0 => ("slow", 500),
+                    _ => ("fast", 0),
+                };
+                let res = format!("HTTP/1.1 200 OK\r\nServer: {}\r\n\r\n", server);
+                Ok(Slow(MockStream::with_input(res.as_bytes()), Duration::from_millis(delay)))
+            }
+        }
+
+        let hedging = Hedging::new(Config {
+            min_samples: 1,
+            min_delay: Duration::from_millis(20),
+            ..Default::default()
+        });
+        hedging.record(Duration::from_millis(20));
+
+        let mut client = Client::with_connector(SlowFirst(AtomicUsize::new(0)));
+        client.set_hedging(Some(hedging));
+        let res = client.get("http://127.0.0.1").send().unwrap();
+        assert_eq!(res.headers.get(), Some(&Server("fast".to_owned())));
+    }
+
+    #[test]
+    fn test_hedging_cancels_loser() {
+        use std::io::{ErrorKind, Write};
+        use std::net::TcpListener;
+        use std::sync::mpsc::channel;
+        use std::thread;
+        use std::time::{Duration, Instant};
+        use super::hedge::{Config, Hedging};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (closed_tx, closed_rx) = channel();
+        thread::spawn(move || {
+            let mut incoming = listener.incoming();
+            let mut slow = incoming.next().unwrap().unwrap();
+            let mut fast = incoming.next().unwrap().unwrap();
+            fast.write_all(b"HTTP/1.1 200 OK\r\nServer: fast\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            // the first connection is never answered, and is closed by the
+            // client once the second wins
+            slow.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let mut buf = [0; 1024];
+            let closed = loop {
+                match slow.read(&mut buf) {
+                    Ok(0) => break true,
+                    Ok(_) => (),
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock ||
+                                  e.kind() == ErrorKind::TimedOut => break false,
+                    Err(_) => break true,
+                }
+            };
+            closed_tx.send(closed).unwrap();
+            thread::sleep(Duration::from_millis(100));
+        });
+
+        let hedging = Hedging::new(Config {
+            min_samples: 1,
+            min_delay: Duration::from_millis(20),
+            ..Default::default()
+        });
+        hedging.record(Duration::from_millis(20));
+
+        let mut client = Client::new();
+        client.set_hedging(Some(hedging));
+        let start = Instant::now();
+        let res = client.get(&*url).send().unwrap();
+        assert_eq!(res.headers.get(), Some(&Server("fast".to_owned())));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(closed_rx.recv().unwrap());
+    }
+
+    #[test]
+    fn test_timeout_and_cancel() {
+        use std::io::{self, Write};
+        use std::net::TcpListener;
+        use std::thread;
+        use std::time::Duration;
+        use Error;
+        use super::CancelToken;
+
+        // answers each connection with the next reply, and then stalls
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let replies = ["", "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nab"];
+            let mut stalled = Vec::new();
+            for (reply, stream) in replies.iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                stream.write_all(reply.as_bytes()).unwrap();
+                stalled.push(stream);
+            }
+            thread::sleep(Duration::from_secs(5));
+        });
+
+        let mut client = Client::new();
+        client.set_timeout(Some(Duration::from_millis(50)));
+        match client.get(&*url).send() {
+            Err(Error::Timeout) => (),
+            other => panic!("expected Timeout, got {:?}", other.map(|res| res.status))
+        }
+
+        let token = CancelToken::new();
+        let mut res = Client::new().get(&*url).cancel_token(token.clone()).send().unwrap();
+        let mut buf = [0; 10];
+        assert_eq!(res.read(&mut buf).unwrap(), 2);
+        let canceller = token.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+        match res.read(&mut buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionAborted => (),
+            other => panic!("expected ConnectionAborted, got {:?}", other)
+        }
+
+        // requests given a cancelled token fail without connecting
+        match Client::new().get(&*url).cancel_token(token).send() {
+            Err(Error::Cancelled) => (),
+            other => panic!("expected Cancelled, got {:?}", other.map(|res| res.status))
+        }
+    }
+
+    #[test]
+    fn test_resolve_override() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+        use std::thread;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+            lines.next();
+            let host = lines.next().unwrap().unwrap();
+            (&stream).write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").unwrap();
+            host
+        });
+
+        let mut client = Client::new();
+        assert!(client.resolve_override("Backend.Domain", addr));
+        let res = client.get("http://backend.domain/").send().unwrap();
+        assert_eq!(res.status, ::status::StatusCode::NoContent);
+        assert_eq!(server.join().unwrap(), "Host: backend.domain");
+    }
 }
@@ -0,0 +1,165 @@
+//! Bodies written by serializers that push into an `io::Write`.
+//!
+//! Request bodies are pulled from a `Read`, but many serializers, such as
+//! CSV or XML writers, push their output into an `io::Write` instead.
+//! `WriteBody` turns one into the other, by running the serializer on its
+//! own thread, and passing what it writes over a bounded buffer.
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use hyper::Client;
+//! use hyper::client::WriteBody;
+//!
+//! let mut body = WriteBody::new(8192, |w| {
+//!     for row in 0..1000 {
+//!         try!(writeln!(w, "{},{}", row, row * row));
+//!     }
+//!     Ok(())
+//! });
+//! Client::new().post("http://example.domain/rows").body(&mut body).send().unwrap();
+//! ```
+use std::cmp::{max, min};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+
+/// A `Read` of what a function writes to an `io::Write`, on another thread.
+///
+/// The function is stopped, by its writes failing, if the body is dropped
+/// before it is read to the end. An error it returns is returned by `read`
+/// after what it wrote before.
+pub struct WriteBody {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl WriteBody {
+    /// Starts `write` on a new thread, buffering at most about `capacity`
+    /// bytes of what it writes until they are read.
+    pub fn new<F>(capacity: usize, write: F) -> WriteBody
+    where F: FnOnce(&mut Write) -> io::Result<()> + Send + 'static {
+        // one chunk waits in the channel, while the next is being filled
+        let chunk_size = max(capacity / 2, 1);
+        let (tx, rx) = sync_channel(1);
+        thread::spawn(move || {
+            let mut writer = ChunkWriter {
+                chunks: tx,
+                buf: Vec::with_capacity(chunk_size),
+                chunk_size: chunk_size,
+            };
+            // what was written before an error is still read first
+            let res = write(&mut writer);
+            let res = writer.flush().and(res);
+            if let Err(e) = res {
+                debug!("body writer error: {:?}", e);
+                let _ = writer.chunks.send(Err(e));
+            }
+        });
+        WriteBody {
+            chunks: rx,
+            chunk: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for WriteBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.chunks.recv() {
+                Ok(Ok(chunk)) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                },
+                Ok(Err(e)) => return Err(e),
+                // the writer is done
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = try!((&self.chunk[self.pos..]).read(buf));
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl fmt::Debug for WriteBody {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WriteBody {{ buffered: {} }}", self.chunk.len() - self.pos)
+    }
+}
+
+struct ChunkWriter {
+    chunks: SyncSender<io::Result<Vec<u8>>>,
+    buf: Vec<u8>,
+    chunk_size: usize,
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
+        if self.buf.len() >= self.chunk_size {
+            try!(self.flush());
+        }
+        let n = min(msg.len(), self.chunk_size - self.buf.len());
+        self.buf.extend_from_slice(&msg[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = mem::replace(&mut self.buf, Vec::with_capacity(self.chunk_size));
+        self.chunks.send(Ok(chunk)).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "body was dropped")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+    use std::sync::mpsc::channel;
+
+    use super::WriteBody;
+
+    #[test]
+    fn test_write_body() {
+        let mut body = WriteBody::new(16, |w| {
+            for i in 0..100 {
+                try!(write!(w, "{},", i));
+            }
+            Ok(())
+        });
+        let mut s = String::new();
+        body.read_to_string(&mut s).unwrap();
+        let expected = (0..100).map(|i| format!("{},", i)).collect::<String>();
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn test_write_body_error() {
+        let mut body = WriteBody::new(16, |w| {
+            try!(w.write_all(b"partial"));
+            Err(io::Error::new(io::ErrorKind::Other, "serializer failed"))
+        });
+        let mut buf = [0; 16];
+        assert_eq!(body.read(&mut buf).unwrap(), 7);
+        assert_eq!(&buf[..7], b"partial");
+        assert_eq!(body.read(&mut buf).unwrap_err().kind(), io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_write_body_dropped() {
+        let (tx, rx) = channel();
+        let body = WriteBody::new(4, move |w| {
+            let res = (0..1000).map(|_| w.write_all(b"data")).find(|res| res.is_err());
+            tx.send(res.map(|res| res.unwrap_err().kind())).unwrap();
+            Ok(())
+        });
+        drop(body);
+        assert_eq!(rx.recv().unwrap(), Some(io::ErrorKind::BrokenPipe));
+    }
+}
@@ -4,23 +4,41 @@ use std::collections::HashMap;
 use std::io::{self, Read, Write};
 use std::net::{SocketAddr, Shutdown};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-#[cfg(feature = "timeouts")]
-use std::time::Duration;
-
-use net::{NetworkConnector, NetworkStream, DefaultConnector};
+use clock::{self, Clock};
+use header::Headers;
+use net::{AbortHandle, ConnectInfo, NetworkConnector, NetworkStream, DefaultConnector};
 
 /// The `NetworkConnector` that behaves as a connection pool used by hyper's `Client`.
+///
+/// Clones share their idle connections and statistics, so a clone can be
+/// kept to read `stats` from, after giving the `Pool` to a `Client`.
 pub struct Pool<C: NetworkConnector> {
     connector: C,
     inner: Arc<Mutex<PoolImpl<<C as NetworkConnector>::Stream>>>
 }
 
+impl<C: NetworkConnector + Clone> Clone for Pool<C> {
+    fn clone(&self) -> Pool<C> {
+        Pool {
+            connector: self.connector.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
 /// Config options for the `Pool`.
 #[derive(Debug)]
 pub struct Config {
     /// The maximum idle connections *per host*.
     pub max_idle: usize,
+    /// How long after it was made a connection is reused for, after which
+    /// it is closed once idle, so that requests move on to other servers
+    /// behind a load balancer or a changed DNS name. Without one, the
+    /// default, connections are reused for as long as they stay open.
+    pub max_lifetime: Option<Duration>,
 }
 
 impl Default for Config {
@@ -28,14 +46,66 @@ impl Default for Config {
     fn default() -> Config {
         Config {
             max_idle: 5,
+            max_lifetime: None,
+        }
+    }
+}
+
+/// A snapshot of what a `Pool` has done, from `Pool::stats`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoolStats {
+    /// Connections made with the connector.
+    pub created: u64,
+    /// Connections taken from the idle ones, instead of making one.
+    pub reused: u64,
+    /// Connections closed when returned, since their host already had
+    /// `max_idle` idle connections.
+    pub evicted_max_idle: u64,
+    /// Connections not returned, since they were closed, or their response
+    /// wasn't read to the end.
+    pub evicted_closed: u64,
+    /// Idle connections closed by `clear_idle`.
+    pub evicted_cleared: u64,
+    /// Connections closed when returned, or about to be reused, since they
+    /// were older than `max_lifetime`.
+    pub evicted_expired: u64,
+    /// The connections of each host, that are idle or in use now.
+    pub hosts: Vec<HostStats>,
+}
+
+impl PoolStats {
+    /// The share of connections handed out that were reused, from `0.0` to
+    /// `1.0`, or `0.0` before any were.
+    pub fn reuse_ratio(&self) -> f64 {
+        match self.created + self.reused {
+            0 => 0.0,
+            total => self.reused as f64 / total as f64
         }
     }
 }
 
+/// The connections of one host in `PoolStats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostStats {
+    /// The host connected to.
+    pub host: String,
+    /// The port connected to.
+    pub port: u16,
+    /// The scheme the connections are for, such as `https`.
+    pub scheme: String,
+    /// Connections waiting to be reused.
+    pub idle: usize,
+    /// Connections handed out and not yet returned.
+    pub active: usize,
+}
+
 #[derive(Debug)]
 struct PoolImpl<S> {
     conns: HashMap<Key, Vec<PooledStreamInner<S>>>,
     config: Config,
+    active: HashMap<Key, usize>,
+    stats: PoolStats,
+    clock: Arc<Clock>,
 }
 
 type Key = (String, u16, Scheme);
@@ -51,6 +121,16 @@ enum Scheme {
     Other(String)
 }
 
+impl Scheme {
+    fn as_str(&self) -> &str {
+        match *self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+            Scheme::Other(ref s) => s
+        }
+    }
+}
+
 impl<'a> From<&'a str> for Scheme {
     fn from(s: &'a str) -> Scheme {
         match s {
@@ -78,23 +158,100 @@ impl<C: NetworkConnector> Pool<C> {
             inner: Arc::new(Mutex::new(PoolImpl {
                 conns: HashMap::new(),
                 config: config,
+                active: HashMap::new(),
+                stats: PoolStats::default(),
+                clock: clock::system(),
             }))
         }
     }
 
+    /// Sets the clock by which connections reach their `max_lifetime`.
+    pub fn set_clock(&mut self, clock: Arc<Clock>) {
+        self.inner.lock().unwrap().clock = clock;
+    }
+
     /// Clear all idle connections from the Pool, closing them.
     #[inline]
     pub fn clear_idle(&mut self) {
-        self.inner.lock().unwrap().conns.clear();
+        self.evict_idle();
+    }
+
+    fn evict_idle(&self) {
+        let mut locked = self.inner.lock().unwrap();
+        let cleared = locked.conns.values().fold(0, |n, conns| n + conns.len() as u64);
+        locked.stats.evicted_cleared += cleared;
+        locked.conns.clear();
+    }
+
+    /// Gets a snapshot of how many connections were made and reused, and of
+    /// the connections of each host now.
+    pub fn stats(&self) -> PoolStats {
+        let locked = self.inner.lock().unwrap();
+        let mut stats = locked.stats.clone();
+        let mut keys = locked.conns.keys().chain(locked.active.keys()).collect::<Vec<_>>();
+        keys.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+        keys.dedup();
+        stats.hosts = keys.into_iter().map(|key| HostStats {
+            host: key.0.clone(),
+            port: key.1,
+            scheme: key.2.as_str().to_owned(),
+            idle: locked.conns.get(key).map_or(0, |conns| conns.len()),
+            active: locked.active.get(key).cloned().unwrap_or(0),
+        }).collect();
+        stats
     }
 }
 
 impl<S> PoolImpl<S> {
+    // Takes an idle connection to reuse, closing those that expired.
+    fn take_idle(&mut self, key: &Key) -> Option<PooledStreamInner<S>> {
+        let max_lifetime = self.config.max_lifetime;
+        let now = self.clock.now();
+        let (conn, expired, empty) = match self.conns.get_mut(key) {
+            Some(conns) => {
+                let idle = conns.len();
+                conns.retain(|conn| !conn.is_expired(now, max_lifetime));
+                let expired = idle - conns.len();
+                (conns.pop(), expired, conns.is_empty())
+            },
+            None => return None
+        };
+        if expired > 0 {
+            debug!("closing {} expired connections to {:?}", expired, key);
+            self.stats.evicted_expired += expired as u64;
+        }
+        if empty {
+            self.conns.remove(key);
+        }
+        conn
+    }
+
     fn reuse(&mut self, key: Key, conn: PooledStreamInner<S>) {
         trace!("reuse {:?}", key);
+        self.release(&key);
+        if conn.is_expired(self.clock.now(), self.config.max_lifetime) {
+            debug!("closing expired connection to {:?}", key);
+            self.stats.evicted_expired += 1;
+            return;
+        }
         let conns = self.conns.entry(key).or_insert(vec![]);
         if conns.len() < self.config.max_idle {
             conns.push(conn);
+        } else {
+            self.stats.evicted_max_idle += 1;
+        }
+    }
+
+    fn release(&mut self, key: &Key) {
+        let remove = match self.active.get_mut(key) {
+            Some(active) => {
+                *active -= 1;
+                *active == 0
+            },
+            None => false
+        };
+        if remove {
+            self.active.remove(key);
         }
     }
 }
@@ -102,29 +259,49 @@ impl<S> PoolImpl<S> {
 impl<C: NetworkConnector<Stream=S>, S: NetworkStream + Send> NetworkConnector for Pool<C> {
     type Stream = PooledStream<S>;
     fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<PooledStream<S>> {
+        self.connect_with_info(host, port, scheme).map(|(stream, _)| stream)
+    }
+
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+            -> ::Result<(PooledStream<S>, ConnectInfo)> {
         let key = key(host, port, scheme);
         let mut locked = self.inner.lock().unwrap();
-        let mut should_remove = false;
-        let inner = match locked.conns.get_mut(&key) {
-            Some(ref mut vec) => {
+        let mut info = ConnectInfo::default();
+        let inner = match locked.take_idle(&key) {
+            Some(inner) => {
                 trace!("Pool had connection, using");
-                should_remove = vec.len() == 1;
-                vec.pop().unwrap()
+                info.reused = true;
+                inner
             }
-            _ => PooledStreamInner {
-                key: key.clone(),
-                stream: try!(self.connector.connect(host, port, scheme)),
-                previous_response_expected_no_content: false,
+            None => {
+                let (stream, connected) = try!(self.connector.connect_with_info(host, port,
+                                                                                scheme));
+                info = connected;
+                PooledStreamInner {
+                    key: key.clone(),
+                    stream: stream,
+                    previous_response_expected_no_content: false,
+                    created: locked.clock.now(),
+                }
             }
         };
-        if should_remove {
-            locked.conns.remove(&key);
+        if info.reused {
+            locked.stats.reused += 1;
+        } else {
+            locked.stats.created += 1;
         }
-        Ok(PooledStream {
+        *locked.active.entry(key).or_insert(0) += 1;
+        Ok((PooledStream {
             inner: Some(inner),
             is_closed: false,
+            aborted: Arc::new(AtomicBool::new(false)),
             pool: self.inner.clone(),
-        })
+        }, info))
+    }
+
+    #[inline]
+    fn close_idle(&self) {
+        self.evict_idle();
     }
 }
 
@@ -132,6 +309,8 @@ impl<C: NetworkConnector<Stream=S>, S: NetworkStream + Send> NetworkConnector fo
 pub struct PooledStream<S> {
     inner: Option<PooledStreamInner<S>>,
     is_closed: bool,
+    // set by abort handles, which may be called from other threads
+    aborted: Arc<AtomicBool>,
     pool: Arc<Mutex<PoolImpl<S>>>,
 }
 
@@ -140,6 +319,13 @@ struct PooledStreamInner<S> {
     key: Key,
     stream: S,
     previous_response_expected_no_content: bool,
+    created: Instant,
+}
+
+impl<S> PooledStreamInner<S> {
+    fn is_expired(&self, now: Instant, max_lifetime: Option<Duration>) -> bool {
+        max_lifetime.map_or(false, |max| now.duration_since(self.created) >= max)
+    }
 }
 
 impl<S: NetworkStream> Read for PooledStream<S> {
@@ -194,6 +380,21 @@ impl<S: NetworkStream> NetworkStream for PooledStream<S> {
         self.inner.as_mut().unwrap().stream.close(how)
     }
 
+    #[inline]
+    fn proxy_headers(&self) -> Option<&Headers> {
+        self.inner.as_ref().unwrap().stream.proxy_headers()
+    }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        let aborted = self.aborted.clone();
+        self.inner.as_ref().unwrap().stream.abort_handle().map(|abort| {
+            Box::new(move || {
+                aborted.store(true, Ordering::SeqCst);
+                abort();
+            }) as AbortHandle
+        })
+    }
+
     #[inline]
     fn set_previous_response_expected_no_content(&mut self, expected: bool) {
         trace!("set_previous_response_expected_no_content {}", expected);
@@ -210,15 +411,19 @@ impl<S: NetworkStream> NetworkStream for PooledStream<S> {
 
 impl<S> Drop for PooledStream<S> {
     fn drop(&mut self) {
-        trace!("PooledStream.drop, is_closed={}", self.is_closed);
-        if !self.is_closed {
-            self.inner.take().map(|inner| {
-                if let Ok(mut pool) = self.pool.lock() {
+        let is_closed = self.is_closed || self.aborted.load(Ordering::SeqCst);
+        trace!("PooledStream.drop, is_closed={}", is_closed);
+        self.inner.take().map(|inner| {
+            if let Ok(mut pool) = self.pool.lock() {
+                if is_closed {
+                    pool.release(&inner.key);
+                    pool.stats.evicted_closed += 1;
+                } else {
                     pool.reuse(inner.key.clone(), inner);
                 }
-                // else poisoned, give up
-            });
-        }
+            }
+            // else poisoned, give up
+        });
     }
 }
 
@@ -229,7 +434,7 @@ mod tests {
     use mock::{MockConnector};
     use net::{NetworkConnector, NetworkStream};
 
-    use super::{Pool, key};
+    use super::{Config, HostStats, Pool, key};
 
     macro_rules! mocked {
         () => ({
@@ -275,4 +480,73 @@ mod tests {
         let locked = pool.inner.lock().unwrap();
         assert_eq!(locked.conns.len(), 0);
     }
+
+    #[test]
+    fn test_stats() {
+        let mut pool = Pool::with_connector(Config { max_idle: 1, ..Default::default() },
+                                            MockConnector);
+        let a = pool.connect("127.0.0.1", 3000, "http").unwrap();
+        let b = pool.connect("127.0.0.1", 3000, "http").unwrap();
+        let mut c = pool.connect("127.0.0.1", 4000, "http").unwrap();
+        {
+            let stats = pool.stats();
+            assert_eq!((stats.created, stats.reused), (3, 0));
+            assert_eq!(stats.hosts, vec![
+                HostStats { host: "127.0.0.1".to_owned(), port: 3000, scheme: "http".to_owned(),
+                            idle: 0, active: 2 },
+                HostStats { host: "127.0.0.1".to_owned(), port: 4000, scheme: "http".to_owned(),
+                            idle: 0, active: 1 },
+            ]);
+        }
+
+        drop(a);
+        drop(b); // over max_idle
+        c.close(Shutdown::Both).unwrap();
+        drop(c);
+        pool.connect("127.0.0.1", 3000, "http").unwrap(); // reused, and returned
+        let stats = pool.stats();
+        assert_eq!((stats.created, stats.reused), (3, 1));
+        assert_eq!(stats.reuse_ratio(), 0.25);
+        assert_eq!((stats.evicted_max_idle, stats.evicted_closed), (1, 1));
+        assert_eq!(stats.hosts.len(), 1);
+        assert_eq!((stats.hosts[0].idle, stats.hosts[0].active), (1, 0));
+
+        pool.clear_idle();
+        let stats = pool.stats();
+        assert_eq!(stats.evicted_cleared, 1);
+        assert!(stats.hosts.is_empty());
+    }
+
+    #[test]
+    fn test_max_lifetime() {
+        use std::sync::Arc;
+        use std::time::Duration;
+        use clock::ManualClock;
+
+        let clock = ManualClock::new();
+        let config = Config { max_lifetime: Some(Duration::from_millis(50)), ..Default::default() };
+        let mut pool = Pool::with_connector(config, MockConnector);
+        pool.set_clock(Arc::new(clock.clone()));
+        let old = pool.connect("127.0.0.1", 3000, "http").unwrap();
+        clock.advance(Duration::from_millis(30));
+        let young = pool.connect("127.0.0.1", 3000, "http").unwrap();
+        drop(young);
+        drop(old);
+        assert_eq!(pool.stats().hosts[0].idle, 2);
+
+        // the older connection expires while idle, and isn't reused
+        clock.advance(Duration::from_millis(30));
+        drop(pool.connect("127.0.0.1", 3000, "http").unwrap());
+        let stats = pool.stats();
+        assert_eq!((stats.created, stats.reused, stats.evicted_expired), (2, 1, 1));
+        assert_eq!(stats.hosts[0].idle, 1);
+
+        // and the other once it's returned
+        let young = pool.connect("127.0.0.1", 3000, "http").unwrap();
+        clock.advance(Duration::from_millis(30));
+        drop(young);
+        let stats = pool.stats();
+        assert_eq!((stats.reused, stats.evicted_expired), (2, 2));
+        assert!(stats.hosts.is_empty());
+    }
 }
@@ -0,0 +1,154 @@
+//! Waiting out rate limits.
+//!
+//! Servers that rate limit their clients say how much of the quota is left,
+//! and when it resets, with the `RateLimit-*` headers, or the older
+//! `X-RateLimit-*` ones. `wait` reads those from a response, and says how
+//! long to back off for before sending more requests.
+//!
+//! A Client can wait out a rate limit and send the request again itself,
+//! with `Client::set_rate_limit_wait`, which uses `retry_delay`.
+//!
+//! ```no_run
+//! use std::thread;
+//! use hyper::Client;
+//! use hyper::client::ratelimit;
+//!
+//! let client = Client::new();
+//! let res = client.get("http://example.domain/items").send().unwrap();
+//! if let Some(wait) = ratelimit::wait(&res.headers) {
+//!     thread::sleep(wait);
+//! }
+//! ```
+use std::time::{Duration, Instant};
+
+use time;
+
+use header::{Headers, HttpDate, RateLimitRemaining, RateLimitReset, RetryAfter,
+             XRateLimitRemaining, XRateLimitReset};
+use status::StatusCode;
+
+// Resets at least this large are Unix timestamps, not numbers of seconds.
+const TIMESTAMP_THRESHOLD: u64 = 1_000_000_000;
+
+/// Get how long to wait before the quota resets, if it has run out.
+///
+/// Returns `None` if there is quota left, or the headers don't say when it
+/// resets. The standard headers are preferred over the legacy ones when a
+/// response has both.
+pub fn wait(headers: &Headers) -> Option<Duration> {
+    let remaining = headers.get::<RateLimitRemaining>().map(|h| **h)
+        .or_else(|| headers.get::<XRateLimitRemaining>().map(|h| **h));
+    if remaining != Some(0) {
+        return None;
+    }
+
+    if let Some(reset) = headers.get::<RateLimitReset>() {
+        return Some(Duration::from_secs(**reset));
+    }
+    headers.get::<XRateLimitReset>().map(|reset| {
+        let reset = **reset;
+        if reset >= TIMESTAMP_THRESHOLD {
+            let now = time::now_utc().to_timespec().sec as u64;
+            Duration::from_secs(reset.saturating_sub(now))
+        } else {
+            Duration::from_secs(reset)
+        }
+    })
+}
+
+/// Get when the quota resets, if it has run out.
+///
+/// See `wait`.
+pub fn wait_until(headers: &Headers) -> Option<Instant> {
+    wait(headers).map(|wait| Instant::now() + wait)
+}
+
+/// Get how long to wait before sending a request again, after a response
+/// to it with `status` and `headers`.
+///
+/// Only a `429 Too Many Requests` or `503 Service Unavailable` is retried.
+/// What `wait` says is preferred, and then `Retry-After`.
+pub fn retry_delay(status: StatusCode, headers: &Headers) -> Option<Duration> {
+    match status {
+        StatusCode::TooManyRequests | StatusCode::ServiceUnavailable => (),
+        _ => return None
+    }
+    wait(headers).or_else(|| headers.get::<RetryAfter>().map(|after| match *after {
+        RetryAfter::Delay(secs) => Duration::from_secs(secs),
+        RetryAfter::DateTime(HttpDate(date)) => {
+            let secs = (date - time::now_utc()).num_seconds();
+            Duration::from_secs(if secs > 0 { secs as u64 } else { 0 })
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use time;
+
+    use header::{Headers, HttpDate, RateLimitRemaining, RateLimitReset, RetryAfter,
+                 XRateLimitRemaining, XRateLimitReset};
+    use status::StatusCode;
+    use super::{retry_delay, wait, wait_until};
+
+    #[test]
+    fn test_wait() {
+        let mut headers = Headers::new();
+        assert_eq!(wait(&headers), None);
+
+        headers.set(RateLimitRemaining(3));
+        headers.set(RateLimitReset(30));
+        assert_eq!(wait(&headers), None);
+
+        headers.set(RateLimitRemaining(0));
+        assert_eq!(wait(&headers), Some(Duration::from_secs(30)));
+        assert!(wait_until(&headers).is_some());
+
+        headers.remove::<RateLimitReset>();
+        assert_eq!(wait(&headers), None);
+    }
+
+    #[test]
+    fn test_wait_legacy() {
+        let mut headers = Headers::new();
+        headers.set(XRateLimitRemaining(0));
+        headers.set(XRateLimitReset(60));
+        assert_eq!(wait(&headers), Some(Duration::from_secs(60)));
+
+        let now = time::now_utc().to_timespec().sec as u64;
+        headers.set(XRateLimitReset(now + 120));
+        let secs = wait(&headers).unwrap().as_secs();
+        assert!(secs <= 120 && secs >= 118, "{}", secs);
+
+        headers.set(XRateLimitReset(now - 10));
+        assert_eq!(wait(&headers), Some(Duration::from_secs(0)));
+
+        // the standard headers win
+        headers.set(RateLimitRemaining(5));
+        assert_eq!(wait(&headers), None);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let mut headers = Headers::new();
+        headers.set(RetryAfter::Delay(5));
+        assert_eq!(retry_delay(StatusCode::TooManyRequests, &headers),
+                   Some(Duration::from_secs(5)));
+        assert_eq!(retry_delay(StatusCode::ServiceUnavailable, &headers),
+                   Some(Duration::from_secs(5)));
+        assert_eq!(retry_delay(StatusCode::Ok, &headers), None);
+
+        let past = time::now_utc() - time::Duration::seconds(30);
+        headers.set(RetryAfter::DateTime(HttpDate(past)));
+        assert_eq!(retry_delay(StatusCode::TooManyRequests, &headers),
+                   Some(Duration::from_secs(0)));
+
+        // the rate limit headers win
+        headers.set(RateLimitRemaining(0));
+        headers.set(RateLimitReset(10));
+        assert_eq!(retry_delay(StatusCode::TooManyRequests, &headers),
+                   Some(Duration::from_secs(10)));
+        assert_eq!(retry_delay(StatusCode::TooManyRequests, &Headers::new()), None);
+    }
+}
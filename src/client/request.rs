@@ -9,13 +9,14 @@ use url::Url;
 
 use method::{self, Method};
 use header::Headers;
-use header::Host;
+use header::{Host, ContentLength, TransferEncoding};
+use unicase::UniCase;
 use net::{NetworkStream, NetworkConnector, DefaultConnector, Fresh, Streaming};
 use version;
 use client::{Response, get_host_and_port};
 
 use http::{HttpMessage, RequestHead};
-use http::h1::Http11Message;
+use http::h1::{Http11Message, HeadParser, Incoming};
 
 
 /// A client request to a remote server.
@@ -43,6 +44,81 @@ impl<W> Request<W> {
     #[inline]
     pub fn method(&self) -> method::Method { self.method.clone() }
 
+    /// Renders an equivalent `curl` command, to reproduce this request.
+    ///
+    /// The values of `Authorization`, `Proxy-Authorization` and `Cookie`
+    /// are replaced with `REDACTED`. If the request has a body, curl is told
+    /// to read it from stdin, since a request doesn't keep what was written.
+    ///
+    /// ```
+    /// # use hyper::client::Request;
+    /// # use hyper::header::UserAgent;
+    /// # fn curl(mut req: Request<hyper::net::Fresh>) {
+    /// req.headers_mut().set(UserAgent("my-app/1.0".to_owned()));
+    /// println!("{}", req.to_curl());
+    /// // curl -X POST 'http://example.domain/' -H 'User-Agent: my-app/1.0' --data-binary @-
+    /// # }
+    /// ```
+    pub fn to_curl(&self) -> String {
+        self.to_curl_with(|name, value| {
+            if UniCase(name) == UniCase("Authorization") ||
+                    UniCase(name) == UniCase("Proxy-Authorization") ||
+                    UniCase(name) == UniCase("Cookie") {
+                Some("REDACTED".to_owned())
+            } else {
+                Some(value.to_owned())
+            }
+        })
+    }
+
+    /// Renders an equivalent `curl` command, passing each header through
+    /// `redact` first.
+    ///
+    /// `redact` receives the header name and value, and returns the value to
+    /// render, or `None` to leave the header out.
+    pub fn to_curl_with<F>(&self, redact: F) -> String
+    where F: Fn(&str, &str) -> Option<String> {
+        let mut cmd = String::from("curl");
+        match self.method {
+            Method::Get => (),
+            Method::Head => cmd.push_str(" --head"),
+            ref method => {
+                cmd.push_str(" -X ");
+                cmd.push_str(&method.to_string());
+            }
+        }
+        cmd.push(' ');
+        cmd.push_str(&shell_quote(&self.url.serialize()));
+
+        for header in self.headers.iter() {
+            if header.is::<Host>() {
+                // curl sends its own, unless it differs from the url's
+                let url_host = get_host_and_port(&self.url).ok().map(|(host, _)| host);
+                if header.value::<Host>().map(|h| &h.hostname) == url_host.as_ref() {
+                    continue;
+                }
+            } else if header.is::<ContentLength>() || header.is::<TransferEncoding>() {
+                // curl works these out from the body itself
+                continue;
+            }
+            let value = header.value_string();
+            if let Some(value) = redact(header.name(), &value) {
+                cmd.push_str(" -H ");
+                cmd.push_str(&shell_quote(&format!("{}: {}", header.name(), value)));
+            }
+        }
+
+        // like Http11Message, only GET and HEAD requests never have a body
+        let body = match self.method {
+            Method::Get | Method::Head => false,
+            _ => self.headers.get::<ContentLength>().map_or(true, |len| **len > 0),
+        };
+        if body {
+            cmd.push_str(" --data-binary @-");
+        }
+        cmd
+    }
+
     /// Set the write timeout.
     #[cfg(feature = "timeouts")]
     #[inline]
@@ -75,7 +151,7 @@ impl Request<Fresh> {
             method: method,
             headers: headers,
             url: url,
-            version: version::HttpVersion::Http11,
+            version: message.version(),
             message: message,
             _marker: PhantomData,
         })
@@ -123,6 +199,43 @@ impl Request<Fresh> {
         })
     }
 
+    /// Consume a Fresh Request, writing `head` as its head byte for byte,
+    /// returning a Streaming Request.
+    ///
+    /// This is for gateways that forward heads unchanged, such as one kept
+    /// with `Server::set_raw_heads`. `head` is a whole request head, ending
+    /// with an empty line. Its method and headers take the place of the
+    /// request's, and decide how the body is written: chunked if
+    /// `Transfer-Encoding` ends with `chunked`, with `Content-Length` bytes,
+    /// or else not at all. Nothing is added to it, not even the headers a
+    /// `ProxyConnector` sends.
+    ///
+    /// The head of the response is kept, for `Response::raw_head`.
+    pub fn start_raw(mut self, head: &[u8]) -> ::Result<Request<Streaming>> {
+        let Incoming { subject: (method, _), headers, .. } =
+            try!(HeadParser::request().parse_whole(head));
+        let head = match self.message.set_outgoing_raw(RequestHead {
+            headers: headers,
+            method: method,
+            url: self.url,
+        }, head) {
+            Ok(head) => head,
+            Err(e) => {
+                let _ = self.message.close_connection();
+                return Err(From::from(e));
+            }
+        };
+
+        Ok(Request {
+            method: head.method,
+            headers: head.headers,
+            url: head.url,
+            version: self.version,
+            message: self.message,
+            _marker: PhantomData,
+        })
+    }
+
     /// Get a mutable reference to the Request headers.
     #[inline]
     pub fn headers_mut(&mut self) -> &mut Headers { &mut self.headers }
@@ -135,6 +248,31 @@ impl Request<Streaming> {
     pub fn send(self) -> ::Result<Response> {
         Response::with_message(self.url, self.message)
     }
+
+    /// Completes writing the request, and shuts down writing to the
+    /// connection, for servers that wait for the end of the stream.
+    ///
+    /// The response is still read with `send`, but the connection isn't
+    /// kept alive after it.
+    pub fn shutdown_write(&mut self) -> ::Result<()> {
+        self.message.shutdown_write()
+    }
+
+    /// Waits up to `timeout` for the server to answer `Expect: 100-continue`
+    /// with `100 Continue`, before writing the body.
+    ///
+    /// Returns `false` if the server sent its final response instead, such
+    /// as `417 Expectation Failed`, in which case the body shouldn't be
+    /// written, and `send` returns that response. The body is also to be
+    /// written if the server didn't answer in time.
+    #[cfg(feature = "timeouts")]
+    pub fn wait_for_continue(&mut self, timeout: Duration) -> ::Result<bool> {
+        self.message.wait_for_continue(timeout)
+    }
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace("'", "'\\''"))
 }
 
 impl Write for Request<Streaming> {
@@ -257,6 +395,26 @@ mod tests {
         assert!(s.contains("Transfer-Encoding:"));
     }
 
+    #[test]
+    fn test_start_raw() {
+        let url = Url::parse("http://example.dom").unwrap();
+        let req = Request::with_connector(Get, url.clone(), &mut MockConnector).unwrap();
+        assert!(req.start_raw(b"POST / HTTP/1.1\r\nHost: example.dom\r\n").is_err());
+
+        let head = b"POST /form HTTP/1.1\r\nhost: example.dom\r\nX-Dup: 1\r\n\
+                     CONTENT-LENGTH: 3\r\nx-dup: 2\r\n\r\n";
+        let req = Request::with_connector(Get, url, &mut MockConnector).unwrap();
+        let mut req = req.start_raw(head).unwrap();
+        assert_eq!(req.method(), Post);
+        req.write_all(b"a=b").unwrap();
+        let mut message = req.message.downcast::<Http11Message>().ok().unwrap();
+        message.flush_outgoing().unwrap();
+        let stream = *message.into_inner().downcast::<MockStream>().ok().unwrap();
+        let mut expected = head.to_vec();
+        expected.extend_from_slice(b"a=b");
+        assert_eq!(stream.write, expected);
+    }
+
     #[test]
     fn test_write_error_closes() {
         let url = Url::parse("http://hyper.rs").unwrap();
@@ -276,4 +434,40 @@ mod tests {
             .get_ref().downcast_ref::<MockStream>().unwrap()
             .is_closed);
     }
+
+    #[test]
+    fn test_to_curl() {
+        use cookie::Cookie as CookiePair;
+        use header::{Authorization, ContentType, Cookie};
+        let url = Url::parse("http://example.dom/a?b=c'd").unwrap();
+        let mut req = Request::with_connector(Get, url, &mut MockConnector).unwrap();
+        assert_eq!(req.to_curl(), "curl 'http://example.dom/a?b=c'\\''d'");
+
+        req.headers_mut().set(Authorization("secret".to_owned()));
+        req.headers_mut().set(Cookie(vec![CookiePair::new("a".to_owned(), "b".to_owned())]));
+        let curl = req.to_curl();
+        assert!(curl.contains(" -H 'Authorization: REDACTED'"));
+        assert!(curl.contains(" -H 'Cookie: REDACTED'"));
+        assert!(!curl.contains("secret"));
+
+        let curl = req.to_curl_with(|name, value| {
+            if name == "Cookie" { None } else { Some(value.to_owned()) }
+        });
+        assert!(curl.contains(" -H 'Authorization: secret'"));
+        assert!(!curl.contains("Cookie"));
+
+        let url = Url::parse("http://example.dom").unwrap();
+        let mut req = Request::with_connector(Post, url, &mut MockConnector).unwrap();
+        req.headers_mut().set(ContentType::json());
+        assert_eq!(req.to_curl(), "curl -X POST 'http://example.dom/' \
+                                   -H 'Content-Type: application/json; charset=utf-8' \
+                                   --data-binary @-");
+        req.headers_mut().set(ContentLength(0));
+        assert!(!req.to_curl().contains("--data-binary"));
+
+        let url = Url::parse("http://example.dom").unwrap();
+        let req = Request::with_connector(Head, url, &mut MockConnector).unwrap();
+        assert_eq!(req.to_curl(), "curl --head 'http://example.dom/'");
+    }
+
 }
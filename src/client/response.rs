@@ -1,13 +1,17 @@
 //! Client Responses
 use std::io::{self, Read};
+use std::time::{Duration, Instant};
 
 use url::Url;
 
 use header;
-use net::NetworkStream;
+use net::{NetworkStream, ConnectInfo};
 use http::{self, RawStatus, ResponseHead, HttpMessage};
-use http::h1::Http11Message;
+use http::h1::{Http11Message, RawHead};
 use status;
+
+use super::cancel::{Deadline, Guard};
+use super::har::BodyCapture;
 use version;
 
 /// A response for a client request to a remote server.
@@ -23,6 +27,31 @@ pub struct Response {
     pub url: Url,
     status_raw: RawStatus,
     message: Box<HttpMessage>,
+    connect_info: ConnectInfo,
+    timings: Timings,
+    started: Option<Instant>,
+    har: Option<BodyCapture>,
+    cancel: Option<Guard>,
+    deadline: Option<Deadline>,
+}
+
+/// How long the phases of a request took, much like curl's `--write-out`
+/// timing variables.
+///
+/// Each phase is `None` if it did not happen, or was not measured, such as
+/// `dns` and `connect` when a pooled connection was reused.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    /// Time spent resolving the host.
+    pub dns: Option<Duration>,
+    /// Time spent establishing the TCP connection.
+    pub connect: Option<Duration>,
+    /// Time spent on the TLS handshake.
+    pub tls: Option<Duration>,
+    /// Time from starting the request until the response head was received.
+    pub first_byte: Option<Duration>,
+    /// Time from starting the request until the body was read to the end.
+    pub total: Option<Duration>,
 }
 
 impl Response {
@@ -54,6 +83,12 @@ impl Response {
             url: url,
             status_raw: raw_status,
             message: message,
+            connect_info: ConnectInfo::default(),
+            timings: Timings::default(),
+            started: None,
+            har: None,
+            cancel: None,
+            deadline: None,
         })
     }
 
@@ -62,17 +97,109 @@ impl Response {
     pub fn status_raw(&self) -> &RawStatus {
         &self.status_raw
     }
+
+    /// The head of the response exactly as it was received, keeping the
+    /// order, case and repetition of its header fields.
+    ///
+    /// It is only kept for a request started with `Request::start_raw`.
+    #[inline]
+    pub fn raw_head(&self) -> Option<RawHead> {
+        self.message.raw_head().map(RawHead::new)
+    }
+
+    /// The trailer fields sent after a chunked body, once the body has been
+    /// read to the end.
+    #[inline]
+    pub fn trailers(&self) -> Option<&header::Headers> {
+        self.message.trailers()
+    }
+
+    /// Whether this response was received on a reused, pooled connection.
+    #[inline]
+    pub fn reused(&self) -> bool {
+        self.connect_info.reused
+    }
+
+    /// Get the TLS cipher negotiated for the connection, if known.
+    #[inline]
+    pub fn cipher(&self) -> Option<&str> {
+        self.connect_info.cipher.as_ref().map(|s| &s[..])
+    }
+
+    /// Get the timings of the request this response answered.
+    #[inline]
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    #[doc(hidden)]
+    pub fn set_connect_info(&mut self, started: Instant, info: ConnectInfo) {
+        let elapsed = started.elapsed();
+        self.timings = Timings {
+            dns: info.dns,
+            connect: info.connect,
+            tls: info.tls,
+            first_byte: Some(elapsed),
+            total: if self.message.has_body() { None } else { Some(elapsed) },
+        };
+        self.connect_info = info;
+        self.started = Some(started);
+    }
+
+    #[doc(hidden)]
+    pub fn set_har_body(&mut self, capture: BodyCapture) {
+        self.har = Some(capture);
+    }
+
+    #[doc(hidden)]
+    pub fn set_cancel(&mut self, guard: Guard) {
+        self.cancel = Some(guard);
+    }
+
+    #[doc(hidden)]
+    pub fn set_deadline(&mut self, deadline: Deadline) {
+        self.deadline = Some(deadline);
+    }
+
+    // The error to fail reading with if the request was cancelled, after
+    // closing the connection.
+    fn cancelled(&mut self) -> Option<io::Error> {
+        let err = self.cancel.as_ref().and_then(|guard| guard.io_error());
+        if err.is_some() {
+            let _ = self.message.close_connection();
+        }
+        err
+    }
 }
 
 impl Read for Response {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(e) = self.cancelled() {
+            return Err(e);
+        }
         match self.message.read(buf) {
+            Ok(0) => {
+                // an aborted connection reads as if it ended
+                if let Some(e) = self.cancelled() {
+                    return Err(e);
+                }
+                self.cancel = None;
+                self.deadline = None;
+                if let (None, Some(started)) = (self.timings.total, self.started) {
+                    self.timings.total = Some(started.elapsed());
+                }
+                self.har.take().map(|har| har.end());
+                Ok(0)
+            },
+            Ok(n) => {
+                self.har.as_mut().map(|har| har.read(&buf[..n]));
+                Ok(n)
+            },
             Err(e) => {
                 let _ = self.message.close_connection();
-                Err(e)
+                Err(self.cancelled().unwrap_or(e))
             }
-            r => r
         }
     }
 }
@@ -0,0 +1,527 @@
+//! Client-side load balancing across equivalent origins.
+//!
+//! An `UpstreamConnector` maps logical host names to a set of `Upstreams`.
+//! Requests for `http://api/users` will connect to one of the origins
+//! registered under `api`, chosen by the configured `Strategy`. Any other
+//! host is connected to directly.
+//!
+//! ```no_run
+//! use hyper::Client;
+//! use hyper::client::upstream::{UpstreamConnector, Upstreams, Origin, Strategy};
+//!
+//! let upstreams = Upstreams::new(Strategy::RoundRobin, vec![
+//!     Origin::new("10.0.0.1", 8080),
+//!     Origin::new("10.0.0.2", 8080),
+//! ]);
+//! let mut connector = UpstreamConnector::new();
+//! connector.add("api", upstreams.clone());
+//! let client = Client::with_connector(connector);
+//!
+//! client.get("http://api/users").send().unwrap();
+//!
+//! // a resolver can feed in new origins at any time
+//! upstreams.set_origins(vec![Origin::new("10.0.0.3", 8080)]);
+//! ```
+//!
+//! Origins are passively health checked: an origin that fails to connect
+//! `max_fails` times in a row is ejected for `fail_timeout`, and other
+//! origins are tried in its place.
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, Shutdown};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use header::Headers;
+use net::{AbortHandle, ConnectInfo, NetworkConnector, NetworkStream, HttpConnector};
+
+/// How an origin is picked from a set of `Upstreams`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Use each origin in turn.
+    RoundRobin,
+    /// Use the origin with the fewest open connections, relative to its
+    /// weight.
+    LeastConnections,
+    /// Use origins in proportion to their weights, spreading them evenly.
+    Weighted,
+}
+
+/// A single origin server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The host to connect to.
+    pub host: String,
+    /// The port to connect to.
+    pub port: u16,
+    /// The relative weight of this origin. Defaults to 1.
+    pub weight: u32,
+}
+
+impl Origin {
+    /// Creates an `Origin` with a weight of 1.
+    pub fn new<S: Into<String>>(host: S, port: u16) -> Origin {
+        Origin {
+            host: host.into(),
+            port: port,
+            weight: 1,
+        }
+    }
+}
+
+/// A set of equivalent origins, sharing one logical name.
+///
+/// Cloning an `Upstreams` gives another handle to the same set, so origins
+/// can be replaced while it is in use.
+#[derive(Clone)]
+pub struct Upstreams {
+    inner: Arc<Mutex<State>>,
+}
+
+struct State {
+    strategy: Strategy,
+    members: Vec<Member>,
+    cursor: usize,
+    max_fails: u32,
+    fail_timeout: Duration,
+}
+
+struct Member {
+    origin: Origin,
+    health: Arc<Mutex<Health>>,
+    current_weight: i64,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    active: usize,
+    fails: u32,
+    ejected_until: Option<Instant>,
+}
+
+impl Upstreams {
+    /// Creates a set of upstreams from a static list of origins.
+    pub fn new(strategy: Strategy, origins: Vec<Origin>) -> Upstreams {
+        let upstreams = Upstreams {
+            inner: Arc::new(Mutex::new(State {
+                strategy: strategy,
+                members: vec![],
+                cursor: 0,
+                max_fails: 1,
+                fail_timeout: Duration::from_secs(10),
+            }))
+        };
+        upstreams.set_origins(origins);
+        upstreams
+    }
+
+    /// Replaces the origins in this set.
+    ///
+    /// Origins that were already present keep their health and connection
+    /// counts.
+    pub fn set_origins(&self, origins: Vec<Origin>) {
+        let mut state = self.inner.lock().unwrap();
+        let mut old = ::std::mem::replace(&mut state.members, vec![]);
+        state.members = origins.into_iter().map(|origin| {
+            let health = match old.iter().position(|m| m.origin.host == origin.host &&
+                                                         m.origin.port == origin.port) {
+                Some(pos) => old.remove(pos).health,
+                None => Arc::new(Mutex::new(Health::default())),
+            };
+            Member {
+                origin: origin,
+                health: health,
+                current_weight: 0,
+            }
+        }).collect();
+        state.cursor = 0;
+    }
+
+    /// Get the current origins in this set.
+    pub fn origins(&self) -> Vec<Origin> {
+        self.inner.lock().unwrap().members.iter().map(|m| m.origin.clone()).collect()
+    }
+
+    /// Sets how many consecutive failures eject an origin. Defaults to 1.
+    pub fn set_max_fails(&self, max: u32) {
+        self.inner.lock().unwrap().max_fails = max;
+    }
+
+    /// Sets how long an ejected origin is skipped. Defaults to 10 seconds.
+    pub fn set_fail_timeout(&self, dur: Duration) {
+        self.inner.lock().unwrap().fail_timeout = dur;
+    }
+
+    /// Returns the origins to try, in order, for the next connection.
+    fn select(&self) -> Vec<(Origin, Arc<Mutex<Health>>)> {
+        let mut state = self.inner.lock().unwrap();
+        let len = state.members.len();
+        if len == 0 {
+            return vec![];
+        }
+
+        let now = Instant::now();
+        let healthy = state.members.iter().map(|m| {
+            match m.health.lock().unwrap().ejected_until {
+                Some(until) => until <= now,
+                None => true,
+            }
+        }).collect::<Vec<_>>();
+
+        let first = match state.strategy {
+            Strategy::RoundRobin => {
+                let start = state.cursor;
+                state.cursor = (start + 1) % len;
+                (0..len).map(|i| (start + i) % len).find(|&i| healthy[i])
+            },
+            Strategy::LeastConnections => {
+                let start = state.cursor;
+                state.cursor = (start + 1) % len;
+                let members = &state.members;
+                (0..len).map(|i| (start + i) % len).filter(|&i| healthy[i]).min_by_key(|&i| {
+                    let active = members[i].health.lock().unwrap().active as u64;
+                    // compare active / weight without losing precision
+                    active * 1000 / ::std::cmp::max(members[i].origin.weight, 1) as u64
+                })
+            },
+            Strategy::Weighted => {
+                let total = state.members.iter().enumerate()
+                    .filter(|&(i, _)| healthy[i])
+                    .fold(0, |sum, (_, m)| sum + m.origin.weight as i64);
+                let mut best = None;
+                for (i, member) in state.members.iter_mut().enumerate() {
+                    if !healthy[i] {
+                        continue;
+                    }
+                    member.current_weight += member.origin.weight as i64;
+                    best = match best {
+                        Some((_, weight)) if weight >= member.current_weight => best,
+                        _ => Some((i, member.current_weight)),
+                    };
+                }
+                best.map(|(i, _)| {
+                    state.members[i].current_weight -= total;
+                    i
+                })
+            }
+        };
+
+        // The chosen origin goes first, then any remaining healthy origins,
+        // and as a last resort the ejected ones.
+        let mut order = Vec::with_capacity(len);
+        order.extend(first);
+        order.extend((0..len).filter(|&i| healthy[i] && Some(i) != first));
+        order.extend((0..len).filter(|&i| !healthy[i]));
+        order.into_iter().map(|i| {
+            let member = &state.members[i];
+            (member.origin.clone(), member.health.clone())
+        }).collect()
+    }
+
+    fn record(&self, health: &Mutex<Health>, ok: bool) {
+        let (max_fails, fail_timeout) = {
+            let state = self.inner.lock().unwrap();
+            (state.max_fails, state.fail_timeout)
+        };
+        let mut health = health.lock().unwrap();
+        if ok {
+            health.fails = 0;
+            health.ejected_until = None;
+        } else {
+            health.fails += 1;
+            if health.fails >= max_fails {
+                debug!("ejecting origin for {:?}", fail_timeout);
+                health.ejected_until = Some(Instant::now() + fail_timeout);
+            }
+        }
+    }
+}
+
+/// A `NetworkConnector` that spreads connections to logical hosts across
+/// their `Upstreams`.
+pub struct UpstreamConnector<C = HttpConnector> {
+    connector: C,
+    upstreams: HashMap<String, Upstreams>,
+}
+
+impl UpstreamConnector<HttpConnector> {
+    /// Creates an `UpstreamConnector` using an `HttpConnector`.
+    pub fn new() -> UpstreamConnector<HttpConnector> {
+        UpstreamConnector::with_connector(HttpConnector)
+    }
+}
+
+impl<C: NetworkConnector> UpstreamConnector<C> {
+    /// Creates an `UpstreamConnector` that connects with the given connector.
+    pub fn with_connector(connector: C) -> UpstreamConnector<C> {
+        UpstreamConnector {
+            connector: connector,
+            upstreams: HashMap::new(),
+        }
+    }
+
+    /// Registers a set of upstreams under a logical host name.
+    pub fn add(&mut self, name: &str, upstreams: Upstreams) {
+        self.upstreams.insert(name.to_owned(), upstreams);
+    }
+}
+
+impl<C: NetworkConnector<Stream=S>, S: NetworkStream + Send> NetworkConnector for UpstreamConnector<C> {
+    type Stream = UpstreamStream<S>;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<UpstreamStream<S>> {
+        self.connect_with_info(host, port, scheme).map(|(stream, _)| stream)
+    }
+
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+            -> ::Result<(UpstreamStream<S>, ConnectInfo)> {
+        let upstreams = match self.upstreams.get(host) {
+            Some(upstreams) => upstreams,
+            None => {
+                let (stream, info) = try!(self.connector.connect_with_info(host, port, scheme));
+                return Ok((UpstreamStream {
+                    stream: stream,
+                    health: None,
+                }, info));
+            }
+        };
+
+        let mut last_err = None;
+        for (origin, health) in upstreams.select() {
+            trace!("upstream {} -> {}:{}", host, origin.host, origin.port);
+            match self.connector.connect_with_info(&origin.host, origin.port, scheme) {
+                Ok((stream, info)) => {
+                    upstreams.record(&health, true);
+                    health.lock().unwrap().active += 1;
+                    return Ok((UpstreamStream {
+                        stream: stream,
+                        health: Some(health),
+                    }, info));
+                },
+                Err(e) => {
+                    debug!("upstream {}:{} failed: {:?}", origin.host, origin.port, e);
+                    upstreams.record(&health, false);
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no origins for upstream").into()
+        }))
+    }
+
+    #[inline]
+    fn close_idle(&self) {
+        self.connector.close_idle()
+    }
+}
+
+/// A stream connected through an `UpstreamConnector`.
+///
+/// Counts as an open connection to its origin until dropped.
+pub struct UpstreamStream<S> {
+    stream: S,
+    health: Option<Arc<Mutex<Health>>>,
+}
+
+impl<S: NetworkStream> Read for UpstreamStream<S> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl<S: NetworkStream> Write for UpstreamStream<S> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl<S: NetworkStream> NetworkStream for UpstreamStream<S> {
+    #[inline]
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    #[cfg(feature = "timeouts")]
+    #[inline]
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(dur)
+    }
+
+    #[cfg(feature = "timeouts")]
+    #[inline]
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_write_timeout(dur)
+    }
+
+    #[inline]
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.stream.close(how)
+    }
+
+    #[inline]
+    fn proxy_headers(&self) -> Option<&Headers> {
+        self.stream.proxy_headers()
+    }
+
+    #[inline]
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        self.stream.abort_handle()
+    }
+
+    #[inline]
+    fn set_previous_response_expected_no_content(&mut self, expected: bool) {
+        self.stream.set_previous_response_expected_no_content(expected)
+    }
+
+    #[inline]
+    fn previous_response_expected_no_content(&self) -> bool {
+        self.stream.previous_response_expected_no_content()
+    }
+}
+
+impl<S> Drop for UpstreamStream<S> {
+    fn drop(&mut self) {
+        if let Some(ref health) = self.health {
+            if let Ok(mut health) = health.lock() {
+                health.active -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use mock::MockStream;
+    use net::NetworkConnector;
+
+    use super::{UpstreamConnector, Upstreams, Origin, Strategy};
+
+    #[derive(Default)]
+    struct Recorder {
+        connects: Mutex<Vec<String>>,
+        down: Vec<&'static str>,
+    }
+
+    impl NetworkConnector for Recorder {
+        type Stream = MockStream;
+
+        fn connect(&self, host: &str, port: u16, _scheme: &str) -> ::Result<MockStream> {
+            self.connects.lock().unwrap().push(format!("{}:{}", host, port));
+            if self.down.contains(&host) {
+                Err(io::Error::new(io::ErrorKind::ConnectionRefused, "down").into())
+            } else {
+                Ok(MockStream::new())
+            }
+        }
+    }
+
+    fn connector(strategy: Strategy, origins: Vec<Origin>, down: Vec<&'static str>)
+            -> UpstreamConnector<Recorder> {
+        let mut connector = UpstreamConnector::with_connector(Recorder {
+            connects: Mutex::new(vec![]),
+            down: down,
+        });
+        connector.add("api", Upstreams::new(strategy, origins));
+        connector
+    }
+
+    fn connects(connector: &UpstreamConnector<Recorder>) -> Vec<String> {
+        ::std::mem::replace(&mut *connector.connector.connects.lock().unwrap(), vec![])
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let c = connector(Strategy::RoundRobin,
+                          vec![Origin::new("a", 1), Origin::new("b", 2)], vec![]);
+        for _ in 0..3 {
+            c.connect("api", 80, "http").unwrap();
+        }
+        assert_eq!(connects(&c), vec!["a:1", "b:2", "a:1"]);
+    }
+
+    #[test]
+    fn test_other_hosts_pass_through() {
+        let c = connector(Strategy::RoundRobin, vec![Origin::new("a", 1)], vec![]);
+        c.connect("example.domain", 80, "http").unwrap();
+        assert_eq!(connects(&c), vec!["example.domain:80"]);
+    }
+
+    #[test]
+    fn test_least_connections() {
+        let c = connector(Strategy::LeastConnections,
+                          vec![Origin::new("a", 1), Origin::new("b", 1)], vec![]);
+        let first = c.connect("api", 80, "http").unwrap();
+        let _second = c.connect("api", 80, "http").unwrap();
+        drop(first);
+        let _third = c.connect("api", 80, "http").unwrap();
+        let _fourth = c.connect("api", 80, "http").unwrap();
+        assert_eq!(connects(&c), vec!["a:1", "b:1", "a:1", "b:1"]);
+    }
+
+    #[test]
+    fn test_weighted() {
+        let mut heavy = Origin::new("a", 1);
+        heavy.weight = 3;
+        let c = connector(Strategy::Weighted, vec![heavy, Origin::new("b", 1)], vec![]);
+        for _ in 0..4 {
+            c.connect("api", 80, "http").unwrap();
+        }
+        let connects = connects(&c);
+        assert_eq!(connects.iter().filter(|c| *c == "a:1").count(), 3);
+        assert_eq!(connects.iter().filter(|c| *c == "b:1").count(), 1);
+        assert!(connects[0] == "a:1" && connects[3] == "a:1");
+    }
+
+    #[test]
+    fn test_passive_ejection() {
+        let c = connector(Strategy::RoundRobin,
+                          vec![Origin::new("a", 1), Origin::new("b", 1)], vec!["a"]);
+        c.connect("api", 80, "http").unwrap();
+        assert_eq!(connects(&c), vec!["a:1", "b:1"]);
+        // a is ejected now, so it isn't tried again
+        c.connect("api", 80, "http").unwrap();
+        c.connect("api", 80, "http").unwrap();
+        assert_eq!(connects(&c), vec!["b:1", "b:1"]);
+    }
+
+    #[test]
+    fn test_ejection_expires() {
+        let c = connector(Strategy::RoundRobin,
+                          vec![Origin::new("a", 1), Origin::new("b", 1)], vec!["a"]);
+        c.upstreams["api"].set_fail_timeout(Duration::from_millis(0));
+        c.connect("api", 80, "http").unwrap();
+        c.connect("api", 80, "http").unwrap();
+        c.connect("api", 80, "http").unwrap();
+        assert_eq!(connects(&c), vec!["a:1", "b:1", "b:1", "a:1", "b:1"]);
+    }
+
+    #[test]
+    fn test_all_down() {
+        let c = connector(Strategy::RoundRobin,
+                          vec![Origin::new("a", 1), Origin::new("b", 1)], vec!["a", "b"]);
+        assert!(c.connect("api", 80, "http").is_err());
+        assert!(c.connect("api", 80, "http").is_err());
+        assert_eq!(connects(&c), vec!["a:1", "b:1", "a:1", "b:1"]);
+    }
+
+    #[test]
+    fn test_set_origins() {
+        let c = connector(Strategy::RoundRobin, vec![Origin::new("a", 1)], vec![]);
+        c.upstreams["api"].set_origins(vec![Origin::new("b", 1)]);
+        c.connect("api", 80, "http").unwrap();
+        assert_eq!(connects(&c), vec!["b:1"]);
+        assert_eq!(c.upstreams["api"].origins(), vec![Origin::new("b", 1)]);
+    }
+}
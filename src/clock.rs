@@ -0,0 +1,143 @@
+//! Sources of the current time.
+//!
+//! The parts of hyper that depend on the time ask a `Clock` for it, instead
+//! of the system: the `Date` header a `Server` sends, its head timeout, the
+//! expiry of cookies in a `CookieJar`, the `max_lifetime` of connections in
+//! a client `Pool` and the freshness of entries in a server `Cache`. They
+//! use a `SystemClock` unless given another, such as a `ManualClock`, which
+//! only moves when told to, so that tests of them don't have to sleep:
+//!
+//! ```
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//! use hyper::clock::{Clock, ManualClock};
+//! use hyper::client::CookieJar;
+//! use hyper::header::SetCookie;
+//! use hyper::Url;
+//!
+//! let clock = ManualClock::new();
+//! let jar = CookieJar::with_clock(Arc::new(clock.clone()));
+//! let url = Url::parse("http://example.domain/").unwrap();
+//! jar.store(&url, &SetCookie(vec!["sid=1; Max-Age=60".parse().unwrap()]));
+//! assert!(jar.cookies_for(&url).is_some());
+//!
+//! clock.advance(Duration::from_secs(60));
+//! assert!(jar.cookies_for(&url).is_none());
+//! ```
+//!
+//! Timeouts set on sockets are still measured by the system.
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use time::{self, Timespec, Tm};
+
+/// A source of the current time.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current instant, to measure how much time has passed.
+    fn now(&self) -> Instant;
+
+    /// The current date and time, in UTC.
+    fn now_utc(&self) -> Tm;
+}
+
+/// The time of the system, as `Instant::now` and `time::now_utc` tell it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    #[inline]
+    fn now_utc(&self) -> Tm {
+        time::now_utc()
+    }
+}
+
+/// A clock that stands still until it is advanced.
+///
+/// Clones share the same time, so one can be given away and another kept
+/// to move it.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    inner: Arc<Mutex<Manual>>,
+}
+
+#[derive(Debug)]
+struct Manual {
+    start: Instant,
+    start_utc: Tm,
+    elapsed: Duration,
+}
+
+impl ManualClock {
+    /// Creates a clock at the Unix epoch.
+    pub fn new() -> ManualClock {
+        ManualClock::at(time::at_utc(Timespec::new(0, 0)))
+    }
+
+    /// Creates a clock at a date and time.
+    pub fn at(utc: Tm) -> ManualClock {
+        ManualClock {
+            inner: Arc::new(Mutex::new(Manual {
+                start: Instant::now(),
+                start_utc: utc.to_utc(),
+                elapsed: Duration::from_secs(0),
+            })),
+        }
+    }
+
+    /// Moves the clock forward.
+    pub fn advance(&self, dur: Duration) {
+        self.inner.lock().unwrap().elapsed += dur;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        let inner = self.inner.lock().unwrap();
+        inner.start + inner.elapsed
+    }
+
+    fn now_utc(&self) -> Tm {
+        let inner = self.inner.lock().unwrap();
+        let elapsed = time::Duration::seconds(inner.elapsed.as_secs() as i64) +
+            time::Duration::nanoseconds(inner.elapsed.subsec_nanos() as i64);
+        inner.start_utc + elapsed
+    }
+}
+
+/// The `SystemClock`, shared.
+pub fn system() -> Arc<Clock> {
+    Arc::new(SystemClock)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use time::{self, Timespec};
+
+    use super::{Clock, ManualClock};
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::at(time::at_utc(Timespec::new(1000, 0)));
+        let other = clock.clone();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        other.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now() - start, Duration::from_millis(1500));
+        assert_eq!(clock.now_utc().to_timespec(), Timespec::new(1001, 500_000_000));
+    }
+}
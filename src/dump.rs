@@ -0,0 +1,422 @@
+//! Dumping the raw bytes of connections, for debugging.
+//!
+//! A `WireDump` logs every read and write on the streams it wraps as a hex
+//! and ASCII listing, tagged with a connection number. Wrap a client's
+//! connector in a `DumpConnector`, or a server's listener in a
+//! `DumpListener`:
+//!
+//! ```no_run
+//! use hyper::Client;
+//! use hyper::dump::{WireDump, DumpConnector};
+//! use hyper::net::HttpConnector;
+//!
+//! let dump = WireDump::new();
+//! let client = Client::with_connector(DumpConnector::new(HttpConnector, dump.clone()));
+//! client.get("http://example.domain").send().unwrap();
+//!
+//! // dumping can be switched off and on again at any time
+//! dump.set_enabled(false);
+//! ```
+//!
+//! By default the listing is logged at the `debug` level. The values of
+//! `Authorization`, `Proxy-Authorization`, `Cookie` and `Set-Cookie`
+//! headers are masked with `*` before anything is dumped; `set_redact`
+//! picks which headers are masked instead.
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, Shutdown};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+#[cfg(feature = "timeouts")]
+use std::time::Duration;
+
+use unicase::UniCase;
+
+use header::Headers;
+use net::{AbortHandle, ConnectInfo, NetworkConnector, NetworkListener, NetworkStream,
+          HttpConnector};
+
+/// Dumps the bytes of the streams it wraps, shared between its clones.
+#[derive(Clone)]
+pub struct WireDump {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    enabled: AtomicBool,
+    next_id: AtomicUsize,
+    redact: RwLock<Box<Fn(&str) -> bool + Send + Sync>>,
+    sink: RwLock<Box<Fn(&str) + Send + Sync>>,
+}
+
+impl WireDump {
+    /// Creates an enabled `WireDump` that logs at the `debug` level.
+    pub fn new() -> WireDump {
+        WireDump::with_sink(|dump| debug!("{}", dump))
+    }
+
+    /// Creates an enabled `WireDump` that passes each listing to `sink`.
+    pub fn with_sink<F>(sink: F) -> WireDump
+    where F: Fn(&str) + Send + Sync + 'static {
+        WireDump {
+            inner: Arc::new(Inner {
+                enabled: AtomicBool::new(true),
+                next_id: AtomicUsize::new(1),
+                redact: RwLock::new(Box::new(default_redact)),
+                sink: RwLock::new(Box::new(sink)),
+            })
+        }
+    }
+
+    /// Switches dumping on or off.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.inner.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Get whether dumping is switched on.
+    pub fn is_enabled(&self) -> bool {
+        self.inner.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Sets which headers have their values masked, by name.
+    pub fn set_redact<F>(&self, redact: F)
+    where F: Fn(&str) -> bool + Send + Sync + 'static {
+        *self.inner.redact.write().unwrap() = Box::new(redact);
+    }
+
+    /// Wraps a stream, so its reads and writes are dumped.
+    pub fn wrap<S: NetworkStream>(&self, stream: S) -> DumpStream<S> {
+        DumpStream {
+            stream: stream,
+            dump: self.clone(),
+            id: self.inner.next_id.fetch_add(1, Ordering::SeqCst),
+            read: Arc::new(Mutex::new(Masker::new())),
+            written: Arc::new(Mutex::new(Masker::new())),
+        }
+    }
+
+    fn dump(&self, id: usize, arrow: &str, masker: &Mutex<Masker>, data: &[u8]) {
+        let redact = self.inner.redact.read().unwrap();
+        let data = masker.lock().unwrap().mask(data, &**redact);
+        // mask even while disabled, so the header state stays in sync
+        if data.is_empty() || !self.is_enabled() {
+            return;
+        }
+        let listing = format!("conn {} {} {} bytes\n{}", id, arrow, data.len(), hex_dump(&data));
+        (self.inner.sink.read().unwrap())(&listing);
+    }
+}
+
+fn default_redact(name: &str) -> bool {
+    let name = UniCase(name);
+    name == UniCase("Authorization") || name == UniCase("Proxy-Authorization") ||
+        name == UniCase("Cookie") || name == UniCase("Set-Cookie")
+}
+
+/// Finds header lines in a stream of bytes, and masks their values.
+///
+/// Header lines can be split across reads, so where a line is left off is
+/// kept between calls. Lines in bodies that look like headers are masked
+/// too, which is harmless for a dump.
+struct Masker {
+    name: Vec<u8>,
+    in_name: bool,
+    masking: bool,
+}
+
+impl Masker {
+    fn new() -> Masker {
+        Masker { name: Vec::new(), in_name: true, masking: false }
+    }
+
+    fn mask(&mut self, data: &[u8], redact: &Fn(&str) -> bool) -> Vec<u8> {
+        let mut out = data.to_vec();
+        for (i, &b) in data.iter().enumerate() {
+            if b == b'\r' || b == b'\n' {
+                self.masking = false;
+                self.in_name = b == b'\n';
+                self.name.clear();
+            } else if self.masking {
+                out[i] = b'*';
+            } else if self.in_name {
+                if b == b':' {
+                    self.in_name = false;
+                    self.masking = ::std::str::from_utf8(&self.name).map_or(false, |n| redact(n));
+                } else if self.name.len() < 64 && b > b' ' && b < 0x7f {
+                    self.name.push(b);
+                } else {
+                    self.in_name = false;
+                }
+            }
+        }
+        out
+    }
+}
+
+fn hex_dump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (n, line) in data.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x} ", n * 16);
+        for i in 0..16 {
+            if i == 8 {
+                out.push(' ');
+            }
+            match line.get(i) {
+                Some(b) => { let _ = write!(out, " {:02x}", b); },
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str("  |");
+        for &b in line {
+            out.push(if b >= b' ' && b < 0x7f { b as char } else { '.' });
+        }
+        out.push_str("|\n");
+    }
+    out
+}
+
+/// A stream whose reads and writes are dumped by a `WireDump`.
+///
+/// Clones share the connection number, and are dumped as one connection.
+#[derive(Clone)]
+pub struct DumpStream<S> {
+    stream: S,
+    dump: WireDump,
+    id: usize,
+    read: Arc<Mutex<Masker>>,
+    written: Arc<Mutex<Masker>>,
+}
+
+impl<S> DumpStream<S> {
+    /// Get the connection number this stream is dumped with.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Get a reference to the wrapped stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S: NetworkStream> Read for DumpStream<S> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = try!(self.stream.read(buf));
+        self.dump.dump(self.id, "<<", &self.read, &buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<S: NetworkStream> Write for DumpStream<S> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = try!(self.stream.write(buf));
+        self.dump.dump(self.id, ">>", &self.written, &buf[..n]);
+        Ok(n)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl<S: NetworkStream> NetworkStream for DumpStream<S> {
+    #[inline]
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    #[cfg(feature = "timeouts")]
+    #[inline]
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(dur)
+    }
+
+    #[cfg(feature = "timeouts")]
+    #[inline]
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_write_timeout(dur)
+    }
+
+    #[inline]
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.stream.close(how)
+    }
+
+    #[inline]
+    fn proxy_headers(&self) -> Option<&Headers> {
+        self.stream.proxy_headers()
+    }
+
+    #[inline]
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        self.stream.abort_handle()
+    }
+
+    #[inline]
+    fn set_previous_response_expected_no_content(&mut self, expected: bool) {
+        self.stream.set_previous_response_expected_no_content(expected)
+    }
+
+    #[inline]
+    fn previous_response_expected_no_content(&self) -> bool {
+        self.stream.previous_response_expected_no_content()
+    }
+}
+
+/// A connector whose streams are dumped by a `WireDump`.
+pub struct DumpConnector<C = HttpConnector> {
+    connector: C,
+    dump: WireDump,
+}
+
+impl<C> DumpConnector<C> {
+    /// Wraps a connector, dumping its streams with `dump`.
+    pub fn new(connector: C, dump: WireDump) -> DumpConnector<C> {
+        DumpConnector {
+            connector: connector,
+            dump: dump,
+        }
+    }
+
+    /// Get the `WireDump` used by this connector.
+    pub fn dump(&self) -> &WireDump {
+        &self.dump
+    }
+}
+
+impl<C: NetworkConnector<Stream=S>, S: NetworkStream + Send> NetworkConnector for DumpConnector<C> {
+    type Stream = DumpStream<S>;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<DumpStream<S>> {
+        let stream = try!(self.connector.connect(host, port, scheme));
+        Ok(self.dump.wrap(stream))
+    }
+
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+            -> ::Result<(DumpStream<S>, ConnectInfo)> {
+        let (stream, info) = try!(self.connector.connect_with_info(host, port, scheme));
+        Ok((self.dump.wrap(stream), info))
+    }
+
+    #[inline]
+    fn close_idle(&self) {
+        self.connector.close_idle()
+    }
+}
+
+/// A listener whose accepted streams are dumped by a `WireDump`.
+#[derive(Clone)]
+pub struct DumpListener<L> {
+    listener: L,
+    dump: WireDump,
+}
+
+impl<L: NetworkListener> DumpListener<L> {
+    /// Wraps a listener, dumping its streams with `dump`.
+    pub fn new(listener: L, dump: WireDump) -> DumpListener<L> {
+        DumpListener {
+            listener: listener,
+            dump: dump,
+        }
+    }
+
+    /// Get the `WireDump` used by this listener.
+    pub fn dump(&self) -> &WireDump {
+        &self.dump
+    }
+}
+
+impl<L: NetworkListener> NetworkListener for DumpListener<L> {
+    type Stream = DumpStream<L::Stream>;
+
+    fn accept(&mut self) -> ::Result<DumpStream<L::Stream>> {
+        let stream = try!(self.listener.accept());
+        Ok(self.dump.wrap(stream))
+    }
+
+    #[inline]
+    fn local_addr(&mut self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::sync::{Arc, Mutex};
+
+    use mock::MockStream;
+    use super::{WireDump, hex_dump};
+
+    fn recording() -> (WireDump, Arc<Mutex<Vec<String>>>) {
+        let out = Arc::new(Mutex::new(Vec::new()));
+        let sink = out.clone();
+        let dump = WireDump::with_sink(move |s| sink.lock().unwrap().push(s.to_owned()));
+        (dump, out)
+    }
+
+    // the ASCII column of a listing
+    fn ascii(listing: &str) -> String {
+        listing.lines().skip(1).map(|line| {
+            let start = line.find('|').unwrap() + 1;
+            &line[start..line.len() - 1]
+        }).collect()
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        assert_eq!(hex_dump(b"GET / HTTP/1.1\r\nHost: a\r\n"),
+            "00000000  47 45 54 20 2f 20 48 54  54 50 2f 31 2e 31 0d 0a  |GET / HTTP/1.1..|\n\
+             00000010  48 6f 73 74 3a 20 61 0d  0a                       |Host: a..|\n");
+    }
+
+    #[test]
+    fn test_dump_read_write() {
+        let (dump, out) = recording();
+        let mut stream = dump.wrap(MockStream::with_input(b"HTTP/1.1 200 OK\r\n"));
+        stream.write_all(b"GET /").unwrap();
+        let mut buf = [0; 64];
+        let n = stream.read(&mut buf).unwrap();
+        assert_eq!(n, 17);
+
+        let out = out.lock().unwrap();
+        assert_eq!(out.len(), 2);
+        assert!(out[0].starts_with(&format!("conn {} >> 5 bytes\n", stream.id())));
+        assert!(out[0].contains("|GET /|"));
+        assert!(out[1].starts_with(&format!("conn {} << 17 bytes\n", stream.id())));
+        assert!(out[1].contains("|HTTP/1.1 200 OK.|"));
+    }
+
+    #[test]
+    fn test_dump_redacts_split_headers() {
+        let (dump, out) = recording();
+        let mut stream = dump.wrap(MockStream::new());
+        stream.write_all(b"GET / HTTP/1.1\r\nauthoriz").unwrap();
+        stream.write_all(b"ation: Basic c2Vj").unwrap();
+        stream.write_all(b"cmV0\r\nAccept: */*\r\nCookie: a=b\r\n\r\n").unwrap();
+
+        let out = out.lock().unwrap().iter().map(|s| ascii(s)).collect::<String>();
+        assert_eq!(out, "GET / HTTP/1.1..authorization:***************..\
+                         Accept: */*..Cookie:****....");
+    }
+
+    #[test]
+    fn test_dump_set_redact_and_disable() {
+        let (dump, out) = recording();
+        dump.set_redact(|name| name == "X-Token");
+        let mut stream = dump.wrap(MockStream::new());
+        stream.write_all(b"X-Token: abc\r\nCookie: a=b\r\n").unwrap();
+        assert_eq!(ascii(&out.lock().unwrap()[0]), "X-Token:****..Cookie: a=b..");
+
+        dump.set_enabled(false);
+        stream.write_all(b"X-Token: abc\r\n").unwrap();
+        assert_eq!(out.lock().unwrap().len(), 1);
+        dump.set_enabled(true);
+        stream.write_all(b"\r\n").unwrap();
+        assert_eq!(out.lock().unwrap().len(), 2);
+    }
+}
@@ -6,6 +6,7 @@ use std::str::Utf8Error;
 use std::string::FromUtf8Error;
 
 use httparse;
+use http::h1::Diagnostic;
 use url;
 use solicit::http::HttpError as Http2Error;
 
@@ -21,8 +22,15 @@ use self::Error::{
     Io,
     Ssl,
     TooLarge,
+    UriTooLong,
+    UriForm,
+    BodyTooLarge,
+    Malformed,
     Http2,
-    Utf8
+    Utf8,
+    CircuitOpen,
+    Timeout,
+    Cancelled
 };
 
 
@@ -42,6 +50,16 @@ pub enum Error {
     Header,
     /// A message head is too large to be reasonable.
     TooLarge,
+    /// A request-target is longer than `Limits::max_uri_len`.
+    UriTooLong,
+    /// A request-target in a form its method doesn't allow, such as `*`
+    /// for a `GET`.
+    UriForm,
+    /// A message body is larger than `Limits::max_body_size`.
+    BodyTooLarge,
+    /// A message head failed to parse with the error, such as `Header`, at
+    /// the place the `Diagnostic` points to.
+    Malformed(Box<Error>, Diagnostic),
     /// An invalid `Status`, such as `1337 ELITE`.
     Status,
     /// An `io::Error` that occurred while trying to read or write to a network stream.
@@ -52,6 +70,12 @@ pub enum Error {
     Http2(Http2Error),
     /// Parsing a field as string failed
     Utf8(Utf8Error),
+    /// The circuit breaker for the origin is open, so no request was made.
+    CircuitOpen,
+    /// The deadline of a request passed before it was done.
+    Timeout,
+    /// A request was cancelled with its `CancelToken`.
+    Cancelled,
 
     #[doc(hidden)]
     __Nonexhaustive(Void)
@@ -68,7 +92,10 @@ impl fmt::Debug for Void {
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(self.description())
+        match *self {
+            Malformed(ref e, ref diagnostic) => write!(f, "{}: {}", e, diagnostic),
+            _ => f.write_str(self.description())
+        }
     }
 }
 
@@ -79,8 +106,15 @@ impl StdError for Error {
             Version => "Invalid HTTP version specified",
             Header => "Invalid Header provided",
             TooLarge => "Message head is too large",
+            UriTooLong => "Request-target is too long",
+            UriForm => "Request-target is in a form not allowed for the method",
+            BodyTooLarge => "Message body is too large",
             Status => "Invalid Status provided",
+            CircuitOpen => "Circuit breaker is open for this origin",
+            Timeout => "Request timed out",
+            Cancelled => "Request was cancelled",
             Uri(ref e) => e.description(),
+            Malformed(ref e, _) => e.description(),
             Io(ref e) => e.description(),
             Ssl(ref e) => e.description(),
             Http2(ref e) => e.description(),
@@ -94,6 +128,7 @@ impl StdError for Error {
             Io(ref error) => Some(error),
             Ssl(ref error) => Some(&**error),
             Uri(ref error) => Some(error),
+            Malformed(ref error, _) => Some(&**error),
             Http2(ref error) => Some(error),
             _ => None,
         }
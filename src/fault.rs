@@ -0,0 +1,446 @@
+//! Injecting faults into connections, for testing resilience.
+//!
+//! A `Faults` makes the streams it wraps misbehave as its `Config` says:
+//! waiting before reads and writes, reading and writing only part of what
+//! was asked, resetting the connection after some bytes, or corrupting
+//! bytes. Wrap a client's connector in a `FaultConnector`, or a server's
+//! listener in a `FaultListener`:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use hyper::Client;
+//! use hyper::fault::{Config, Faults, FaultConnector};
+//! use hyper::net::HttpConnector;
+//!
+//! let faults = Faults::new(Config {
+//!     write_delay: Some(Duration::from_millis(100)),
+//!     reset_after_read: Some(1024),
+//!     ..Config::default()
+//! });
+//! let client = Client::with_connector(FaultConnector::new(HttpConnector, faults.clone()));
+//! client.get("http://example.domain").send().unwrap();
+//!
+//! // faults can be switched off and on again at any time
+//! faults.set_enabled(false);
+//! ```
+//!
+//! A slowloris client, trickling out its requests, is a `max_write` of a
+//! few bytes with a `write_delay`.
+//!
+//! Which bytes are corrupted is decided by a generator seeded with
+//! `Config::seed` and the number of the connection, so a run can be
+//! repeated.
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, Shutdown};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use header::Headers;
+use net::{AbortHandle, ConnectInfo, NetworkConnector, NetworkListener, NetworkStream,
+          HttpConnector};
+
+/// The faults to inject.
+///
+/// The default injects none.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// How long to wait before each read.
+    pub read_delay: Option<Duration>,
+    /// How long to wait before each write.
+    pub write_delay: Option<Duration>,
+    /// The most bytes each read returns.
+    pub max_read: Option<usize>,
+    /// The most bytes each write takes.
+    pub max_write: Option<usize>,
+    /// The bytes read from a connection before it is reset.
+    pub reset_after_read: Option<u64>,
+    /// The bytes written to a connection before it is reset.
+    pub reset_after_write: Option<u64>,
+    /// The chance of each byte read or written being corrupted, from `0.0`
+    /// to `1.0`.
+    pub corrupt_rate: f64,
+    /// The seed of the choice of bytes to corrupt.
+    pub seed: u64,
+}
+
+/// Injects faults into the streams it wraps, shared between its clones.
+#[derive(Clone)]
+pub struct Faults {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    enabled: AtomicBool,
+    next_id: AtomicUsize,
+    config: RwLock<Config>,
+}
+
+impl Faults {
+    /// Creates an enabled `Faults` injecting those of `config`.
+    pub fn new(config: Config) -> Faults {
+        Faults {
+            inner: Arc::new(Inner {
+                enabled: AtomicBool::new(true),
+                next_id: AtomicUsize::new(1),
+                config: RwLock::new(config),
+            })
+        }
+    }
+
+    /// Switches injecting faults on or off.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.inner.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Get whether injecting faults is switched on.
+    pub fn is_enabled(&self) -> bool {
+        self.inner.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Replaces the faults to inject, including in streams already wrapped.
+    pub fn set_config(&self, config: Config) {
+        *self.inner.config.write().unwrap() = config;
+    }
+
+    /// Get the faults being injected.
+    pub fn config(&self) -> Config {
+        self.inner.config.read().unwrap().clone()
+    }
+
+    /// Wraps a stream, so faults are injected into its reads and writes.
+    pub fn wrap<S: NetworkStream>(&self, stream: S) -> FaultStream<S> {
+        let id = self.inner.next_id.fetch_add(1, Ordering::SeqCst);
+        let seed = self.inner.config.read().unwrap().seed;
+        FaultStream {
+            stream: stream,
+            faults: self.clone(),
+            state: Arc::new(Mutex::new(State {
+                read: 0,
+                written: 0,
+                rng: seed ^ (id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15),
+            })),
+        }
+    }
+
+    fn current(&self) -> Option<Config> {
+        if self.is_enabled() {
+            Some(self.config())
+        } else {
+            None
+        }
+    }
+}
+
+// What a connection has done so far, shared by the clones of its stream.
+struct State {
+    read: u64,
+    written: u64,
+    rng: u64,
+}
+
+impl State {
+    // splitmix64, which is good enough to pick bytes and works from any seed
+    fn next(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn corrupt(&mut self, data: &mut [u8], rate: f64) {
+        if rate <= 0.0 {
+            return;
+        }
+        for b in data {
+            let roll = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+            if roll < rate {
+                *b ^= 1 << (self.next() % 8);
+            }
+        }
+    }
+}
+
+// How much of `len` bytes can pass, with `done` passed already.
+fn allowed(len: usize, max: Option<usize>, done: u64, reset_after: Option<u64>)
+           -> io::Result<usize> {
+    let len = max.map_or(len, |max| ::std::cmp::min(len, max));
+    match reset_after {
+        Some(limit) if done >= limit && len > 0 => {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "connection reset by fault"))
+        },
+        Some(limit) => Ok(::std::cmp::min(len as u64, limit - done) as usize),
+        None => Ok(len)
+    }
+}
+
+fn delay(dur: Option<Duration>) {
+    if let Some(dur) = dur {
+        thread::sleep(dur);
+    }
+}
+
+/// A stream whose reads and writes have faults injected by a `Faults`.
+///
+/// Clones share what has been read and written, and are treated as one
+/// connection.
+#[derive(Clone)]
+pub struct FaultStream<S> {
+    stream: S,
+    faults: Faults,
+    state: Arc<Mutex<State>>,
+}
+
+impl<S> FaultStream<S> {
+    /// Get a reference to the wrapped stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S: NetworkStream> Read for FaultStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let config = match self.faults.current() {
+            Some(config) => config,
+            None => return self.stream.read(buf)
+        };
+        delay(config.read_delay);
+        let len = {
+            let state = self.state.lock().unwrap();
+            try!(allowed(buf.len(), config.max_read, state.read, config.reset_after_read))
+        };
+        let n = try!(self.stream.read(&mut buf[..len]));
+        let mut state = self.state.lock().unwrap();
+        state.read += n as u64;
+        state.corrupt(&mut buf[..n], config.corrupt_rate);
+        Ok(n)
+    }
+}
+
+impl<S: NetworkStream> Write for FaultStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let config = match self.faults.current() {
+            Some(config) => config,
+            None => return self.stream.write(buf)
+        };
+        delay(config.write_delay);
+        let data = {
+            let mut state = self.state.lock().unwrap();
+            let len = try!(allowed(buf.len(), config.max_write, state.written,
+                                   config.reset_after_write));
+            let mut data = buf[..len].to_vec();
+            state.corrupt(&mut data, config.corrupt_rate);
+            data
+        };
+        let n = try!(self.stream.write(&data));
+        self.state.lock().unwrap().written += n as u64;
+        Ok(n)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl<S: NetworkStream> NetworkStream for FaultStream<S> {
+    #[inline]
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    #[cfg(feature = "timeouts")]
+    #[inline]
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(dur)
+    }
+
+    #[cfg(feature = "timeouts")]
+    #[inline]
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_write_timeout(dur)
+    }
+
+    #[inline]
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.stream.close(how)
+    }
+
+    #[inline]
+    fn proxy_headers(&self) -> Option<&Headers> {
+        self.stream.proxy_headers()
+    }
+
+    #[inline]
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        self.stream.abort_handle()
+    }
+
+    #[inline]
+    fn set_previous_response_expected_no_content(&mut self, expected: bool) {
+        self.stream.set_previous_response_expected_no_content(expected)
+    }
+
+    #[inline]
+    fn previous_response_expected_no_content(&self) -> bool {
+        self.stream.previous_response_expected_no_content()
+    }
+}
+
+/// A connector whose streams have faults injected by a `Faults`.
+pub struct FaultConnector<C = HttpConnector> {
+    connector: C,
+    faults: Faults,
+}
+
+impl<C> FaultConnector<C> {
+    /// Wraps a connector, injecting faults into its streams with `faults`.
+    pub fn new(connector: C, faults: Faults) -> FaultConnector<C> {
+        FaultConnector {
+            connector: connector,
+            faults: faults,
+        }
+    }
+
+    /// Get the `Faults` used by this connector.
+    pub fn faults(&self) -> &Faults {
+        &self.faults
+    }
+}
+
+impl<C: NetworkConnector<Stream=S>, S: NetworkStream + Send> NetworkConnector for FaultConnector<C> {
+    type Stream = FaultStream<S>;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<FaultStream<S>> {
+        let stream = try!(self.connector.connect(host, port, scheme));
+        Ok(self.faults.wrap(stream))
+    }
+
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+            -> ::Result<(FaultStream<S>, ConnectInfo)> {
+        let (stream, info) = try!(self.connector.connect_with_info(host, port, scheme));
+        Ok((self.faults.wrap(stream), info))
+    }
+
+    #[inline]
+    fn close_idle(&self) {
+        self.connector.close_idle()
+    }
+}
+
+/// A listener whose accepted streams have faults injected by a `Faults`.
+#[derive(Clone)]
+pub struct FaultListener<L> {
+    listener: L,
+    faults: Faults,
+}
+
+impl<L: NetworkListener> FaultListener<L> {
+    /// Wraps a listener, injecting faults into its streams with `faults`.
+    pub fn new(listener: L, faults: Faults) -> FaultListener<L> {
+        FaultListener {
+            listener: listener,
+            faults: faults,
+        }
+    }
+
+    /// Get the `Faults` used by this listener.
+    pub fn faults(&self) -> &Faults {
+        &self.faults
+    }
+}
+
+impl<L: NetworkListener> NetworkListener for FaultListener<L> {
+    type Stream = FaultStream<L::Stream>;
+
+    fn accept(&mut self) -> ::Result<FaultStream<L::Stream>> {
+        let stream = try!(self.listener.accept());
+        Ok(self.faults.wrap(stream))
+    }
+
+    #[inline]
+    fn local_addr(&mut self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Read, Write};
+    use std::time::{Duration, Instant};
+
+    use mock::MockStream;
+    use super::{Config, Faults};
+
+    #[test]
+    fn test_partial_and_reset() {
+        let faults = Faults::new(Config {
+            max_write: Some(4),
+            reset_after_write: Some(6),
+            max_read: Some(3),
+            reset_after_read: Some(5),
+            ..Config::default()
+        });
+        let mut stream = faults.wrap(MockStream::with_input(b"abcdefgh"));
+
+        assert_eq!(stream.write(b"0123456789").unwrap(), 4);
+        assert_eq!(stream.write(b"456789").unwrap(), 2);
+        assert_eq!(stream.write(b"6789").unwrap_err().kind(), ErrorKind::ConnectionReset);
+        assert_eq!(stream.get_ref().write, b"012345");
+
+        let mut buf = [0; 8];
+        assert_eq!(stream.read(&mut buf).unwrap(), 3);
+        assert_eq!(stream.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"de");
+        assert_eq!(stream.read(&mut buf).unwrap_err().kind(), ErrorKind::ConnectionReset);
+
+        // switched off, the stream is left alone
+        faults.set_enabled(false);
+        assert_eq!(stream.write(b"6789").unwrap(), 4);
+        assert_eq!(stream.read(&mut buf).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_corrupt() {
+        let config = Config {
+            corrupt_rate: 0.5,
+            seed: 7,
+            ..Config::default()
+        };
+        let written = |config: &Config| {
+            let mut stream = Faults::new(config.clone()).wrap(MockStream::new());
+            stream.write_all(&[0; 64]).unwrap();
+            stream.get_ref().write.clone()
+        };
+        let out = written(&config);
+        let flipped = out.iter().filter(|&&b| b != 0).count();
+        assert!(flipped > 8 && flipped < 56, "{} of 64 corrupted", flipped);
+        assert!(out.iter().all(|b| b.count_ones() <= 1));
+        assert_eq!(written(&config), out);
+
+        let none = written(&Config::default());
+        assert_eq!(none, vec![0; 64]);
+
+        let mut stream = Faults::new(Config {
+            corrupt_rate: 1.0,
+            ..Config::default()
+        }).wrap(MockStream::with_input(&[0; 16]));
+        let mut buf = [0; 16];
+        stream.read(&mut buf).unwrap();
+        assert!(buf.iter().all(|&b| b.count_ones() == 1));
+    }
+
+    #[test]
+    fn test_delay() {
+        let faults = Faults::new(Config {
+            write_delay: Some(Duration::from_millis(20)),
+            ..Config::default()
+        });
+        let mut stream = faults.wrap(MockStream::new());
+        let start = Instant::now();
+        stream.write_all(b"slow").unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
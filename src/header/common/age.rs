@@ -0,0 +1,29 @@
+header! {
+    /// `Age` header, defined in [RFC7234](https://tools.ietf.org/html/rfc7234#section-5.1)
+    ///
+    /// The `Age` header field conveys the sender's estimate of the amount
+    /// of time since the response was generated or successfully validated
+    /// at the origin server.
+    ///
+    /// # ABNF
+    /// ```plain
+    /// Age = delta-seconds
+    /// ```
+    ///
+    /// # Example values
+    /// * `60`
+    ///
+    /// # Example
+    /// ```
+    /// use hyper::header::{Headers, Age};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(Age(1024u32));
+    /// ```
+    (Age, "Age") => [u32]
+
+    test_age {
+        test_header!(test1, vec![b"60"]);
+        test_header!(test2, vec![b"-1"], None);
+    }
+}
@@ -0,0 +1,32 @@
+header! {
+    /// `API-Version` header
+    ///
+    /// The `API-Version` header field names the version of an API that a
+    /// request asks for, or that a response was produced by. It isn't
+    /// standardized, but is widely used for header based API versioning.
+    ///
+    /// # ABNF
+    /// ```plain
+    /// API-Version = token
+    /// ```
+    ///
+    /// # Example values
+    /// * `2`
+    /// * `2024-01-01`
+    ///
+    /// # Example
+    /// ```
+    /// use hyper::header::{Headers, ApiVersion};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(ApiVersion("2024-01-01".to_owned()));
+    /// ```
+    (ApiVersion, "API-Version") => [String]
+
+    test_api_version {
+        test_header!(test1, vec![b"2024-01-01"]);
+        test_header!(test2, vec![b"v2"], Some(HeaderField("v2".to_owned())));
+    }
+}
+
+bench_header!(bench, ApiVersion, { vec![b"2024-01-01".to_vec()] });
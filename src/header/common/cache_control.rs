@@ -1,7 +1,8 @@
+use std::ascii::AsciiExt;
 use std::fmt;
-use std::str::FromStr;
+use std::str::{self, FromStr};
 use header::{Header, HeaderFormat};
-use header::parsing::{from_one_comma_delimited, fmt_comma_delimited};
+use header::parsing::{fmt_comma_delimited, is_token};
 
 /// `Cache-Control` header, defined in [RFC7234](https://tools.ietf.org/html/rfc7234#section-5.2)
 ///
@@ -21,6 +22,11 @@ use header::parsing::{from_one_comma_delimited, fmt_comma_delimited};
 /// * `private, community="UCI"`
 /// * `max-age=30`
 ///
+/// Directive names are case-insensitive, and quoted arguments can hold
+/// commas, such as the header names of `no-cache="Set-Cookie, Set-Cookie2"`.
+/// A delta-seconds too large for a `u32` is taken as `u32::MAX`, as RFC 7234
+/// allows.
+///
 /// # Examples
 /// ```
 /// use hyper::header::{Headers, CacheControl, CacheDirective};
@@ -56,9 +62,10 @@ impl Header for CacheControl {
 
     fn parse_header(raw: &[Vec<u8>]) -> ::Result<CacheControl> {
         let directives = raw.iter()
-            .filter_map(|line| from_one_comma_delimited(&line[..]).ok())
-            .collect::<Vec<Vec<CacheDirective>>>()
-            .concat();
+            .filter_map(|line| str::from_utf8(&line[..]).ok())
+            .flat_map(split_directives)
+            .filter_map(|directive| directive.parse().ok())
+            .collect::<Vec<CacheDirective>>();
         if !directives.is_empty() {
             Ok(CacheControl(directives))
         } else {
@@ -73,6 +80,30 @@ impl HeaderFormat for CacheControl {
     }
 }
 
+// Splits a header value at the commas between directives, which aren't
+// those in quoted arguments.
+fn split_directives(s: &str) -> Vec<&str> {
+    let mut directives = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                directives.push(s[start..i].trim());
+                start = i + 1;
+            },
+            _ => ()
+        }
+    }
+    directives.push(s[start..].trim());
+    directives.retain(|directive| !directive.is_empty());
+    directives
+}
+
 /// CacheControl contains a list of these directives.
 #[derive(PartialEq, Clone, Debug)]
 pub enum CacheDirective {
@@ -104,6 +135,12 @@ pub enum CacheDirective {
     ProxyRevalidate,
     /// "s-maxage=delta"
     SMaxAge(u32),
+    /// "immutable", from RFC 8246
+    Immutable,
+    /// "stale-while-revalidate=delta", from RFC 5861
+    StaleWhileRevalidate(u32),
+    /// "stale-if-error=delta", from RFC 5861
+    StaleIfError(u32),
 
     /// Extension directives. Optionally include an argument.
     Extension(String, Option<String>)
@@ -127,9 +164,17 @@ impl fmt::Display for CacheDirective {
             Private => "private",
             ProxyRevalidate => "proxy-revalidate",
             SMaxAge(secs) => return write!(f, "s-maxage={}", secs),
+            Immutable => "immutable",
+            StaleWhileRevalidate(secs) => return write!(f, "stale-while-revalidate={}", secs),
+            StaleIfError(secs) => return write!(f, "stale-if-error={}", secs),
 
             Extension(ref name, None) => &name[..],
-            Extension(ref name, Some(ref arg)) => return write!(f, "{}={}", name, arg),
+            Extension(ref name, Some(ref arg)) if is_token(arg) => {
+                return write!(f, "{}={}", name, arg)
+            },
+            Extension(ref name, Some(ref arg)) => {
+                return write!(f, "{}=\"{}\"", name, arg.replace('\\', "\\\\").replace('"', "\\\""))
+            },
 
         }, f)
     }
@@ -139,7 +184,7 @@ impl FromStr for CacheDirective {
     type Err = Option<<u32 as FromStr>::Err>;
     fn from_str(s: &str) -> Result<CacheDirective, Option<<u32 as FromStr>::Err>> {
         use self::CacheDirective::*;
-        match s {
+        match &s.to_ascii_lowercase()[..] {
             "no-cache" => Ok(NoCache),
             "no-store" => Ok(NoStore),
             "no-transform" => Ok(NoTransform),
@@ -148,14 +193,17 @@ impl FromStr for CacheDirective {
             "public" => Ok(Public),
             "private" => Ok(Private),
             "proxy-revalidate" => Ok(ProxyRevalidate),
+            "immutable" => Ok(Immutable),
             "" => Err(None),
-            _ => match s.find('=') {
-                Some(idx) if idx+1 < s.len() => match (&s[..idx], (&s[idx+1..]).trim_matches('"')) {
-                    ("max-age" , secs) => secs.parse().map(MaxAge).map_err(Some),
-                    ("max-stale", secs) => secs.parse().map(MaxStale).map_err(Some),
-                    ("min-fresh", secs) => secs.parse().map(MinFresh).map_err(Some),
-                    ("s-maxage", secs) => secs.parse().map(SMaxAge).map_err(Some),
-                    (left, right) => Ok(Extension(left.to_owned(), Some(right.to_owned())))
+            lower => match s.find('=') {
+                Some(idx) if idx+1 < s.len() => match (&lower[..idx], unquote(&s[idx+1..])) {
+                    ("max-age" , secs) => delta_seconds(&secs).map(MaxAge),
+                    ("max-stale", secs) => delta_seconds(&secs).map(MaxStale),
+                    ("min-fresh", secs) => delta_seconds(&secs).map(MinFresh),
+                    ("s-maxage", secs) => delta_seconds(&secs).map(SMaxAge),
+                    ("stale-while-revalidate", secs) => delta_seconds(&secs).map(StaleWhileRevalidate),
+                    ("stale-if-error", secs) => delta_seconds(&secs).map(StaleIfError),
+                    (_, right) => Ok(Extension(s[..idx].to_owned(), Some(right)))
                 },
                 Some(_) => Err(None),
                 None => Ok(Extension(s.to_owned(), None))
@@ -164,9 +212,33 @@ impl FromStr for CacheDirective {
     }
 }
 
+// Takes the quotes and escapes out of a quoted-string.
+fn unquote(s: &str) -> String {
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        return s.to_owned();
+    }
+    let mut unquoted = String::with_capacity(s.len() - 2);
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+fn delta_seconds(s: &str) -> Result<u32, Option<<u32 as FromStr>::Err>> {
+    match s.parse() {
+        Ok(secs) => Ok(secs),
+        Err(_) if !s.is_empty() && s.bytes().all(|b| b'0' <= b && b <= b'9') => Ok(u32::max_value()),
+        Err(e) => Err(Some(e))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use header::Header;
+    use header::{Header, HeaderFormatter};
     use super::*;
 
     #[test]
@@ -197,6 +269,35 @@ mod tests {
             CacheDirective::Extension("bar".to_owned(), Some("baz".to_owned()))])))
     }
 
+    #[test]
+    fn test_parse_quoted_commas() {
+        let cache = Header::parse_header(&[b"no-cache=\"Set-Cookie, Set-Cookie2\", Max-Age=60".to_vec()]);
+        assert_eq!(cache.ok(), Some(CacheControl(vec![
+            CacheDirective::Extension("no-cache".to_owned(),
+                                      Some("Set-Cookie, Set-Cookie2".to_owned())),
+            CacheDirective::MaxAge(60)])))
+    }
+
+    #[test]
+    fn test_parse_rfc5861_and_immutable() {
+        let cache = Header::parse_header(&[b"immutable, stale-while-revalidate=30, \
+                                           stale-if-error=99999999999".to_vec()]);
+        assert_eq!(cache.ok(), Some(CacheControl(vec![CacheDirective::Immutable,
+                                                 CacheDirective::StaleWhileRevalidate(30),
+                                                 CacheDirective::StaleIfError(u32::max_value())])))
+    }
+
+    #[test]
+    fn test_fmt_quoted_extension() {
+        let cache = CacheControl(vec![
+            CacheDirective::Extension("private".to_owned(), Some("Set-Cookie, \"X\"".to_owned())),
+            CacheDirective::Extension("community".to_owned(), Some("UCI".to_owned()))]);
+        assert_eq!(HeaderFormatter(&cache).to_string(),
+                   "private=\"Set-Cookie, \\\"X\\\"\", community=UCI");
+        let parsed: CacheControl = Header::parse_header(&[HeaderFormatter(&cache).to_string().into_bytes()]).unwrap();
+        assert_eq!(parsed, cache);
+    }
+
     #[test]
     fn test_parse_bad_syntax() {
         let cache: ::Result<CacheControl> = Header::parse_header(&[b"foo=".to_vec()]);
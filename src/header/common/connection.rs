@@ -25,26 +25,44 @@ pub enum ConnectionOption {
     ConnectionHeader(UniCase<String>),
 }
 
-impl FromStr for ConnectionOption {
-    type Err = ();
-    fn from_str(s: &str) -> Result<ConnectionOption, ()> {
+impl ConnectionOption {
+    /// Get the token of this option, such as `close` or `upgrade`.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            KeepAlive => "keep-alive",
+            Close => "close",
+            ConnectionHeader(UniCase(ref s)) => s.as_ref()
+        }
+    }
+
+    /// Checks if this option is `token`, ignoring case.
+    pub fn is(&self, token: &str) -> bool {
+        UniCase(self.as_str()) == UniCase(token)
+    }
+}
+
+impl<'a> From<&'a str> for ConnectionOption {
+    fn from(s: &'a str) -> ConnectionOption {
         if UniCase(s) == KEEP_ALIVE {
-            Ok(KeepAlive)
+            KeepAlive
         } else if UniCase(s) == CLOSE {
-            Ok(Close)
+            Close
         } else {
-            Ok(ConnectionHeader(UniCase(s.to_owned())))
+            ConnectionHeader(UniCase(s.to_owned()))
         }
     }
 }
 
+impl FromStr for ConnectionOption {
+    type Err = ();
+    fn from_str(s: &str) -> Result<ConnectionOption, ()> {
+        Ok(ConnectionOption::from(s))
+    }
+}
+
 impl Display for ConnectionOption {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(match *self {
-            KeepAlive => "keep-alive",
-            Close => "close",
-            ConnectionHeader(UniCase(ref s)) => s.as_ref()
-        })
+        f.write_str(self.as_str())
     }
 }
 
@@ -93,6 +111,13 @@ header! {
     /// );
     /// # }
     /// ```
+    /// ```
+    /// use hyper::header::{Headers, Connection};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(Connection(vec!["keep-alive".into(), "x-trace".into()]));
+    /// assert!(headers.get::<Connection>().unwrap().has("X-Trace"));
+    /// ```
     (Connection, "Connection") => (ConnectionOption)+
 
     test_connection {
@@ -114,6 +139,11 @@ impl Connection {
     pub fn keep_alive() -> Connection {
         Connection(vec![ConnectionOption::KeepAlive])
     }
+
+    /// Checks if `token` is one of the options, ignoring case.
+    pub fn has(&self, token: &str) -> bool {
+        self.iter().any(|option| option.is(token))
+    }
 }
 
 bench_header!(close, Connection, { vec![b"close".to_vec()] });
@@ -122,7 +152,7 @@ bench_header!(header, Connection, { vec![b"authorization".to_vec()] });
 
 #[cfg(test)]
 mod tests {
-    use super::{Connection,ConnectionHeader};
+    use super::{Connection, ConnectionHeader, ConnectionOption};
     use header::Header;
     use unicase::UniCase;
 
@@ -140,4 +170,14 @@ mod tests {
         assert_eq!(Connection(vec![ConnectionHeader(UniCase("upgrade".to_owned()))]),
             parse_option(b"upgrade".to_vec()));
     }
+
+    #[test]
+    fn test_tokens() {
+        let conn = parse_option(b"Close, X-Foo".to_vec());
+        assert_eq!(conn, Connection(vec![ConnectionOption::Close, "x-foo".into()]));
+        assert!(conn.has("close"));
+        assert!(conn.has("x-FOO"));
+        assert!(!conn.has("keep-alive"));
+        assert_eq!(conn[1].as_str(), "X-Foo");
+    }
 }
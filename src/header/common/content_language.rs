@@ -55,5 +55,13 @@ header! {
     test_content_language {
         test_header!(test1, vec![b"da"]);
         test_header!(test2, vec![b"mi, en"]);
+
+        #[test]
+        fn test_ill_formed() {
+            // tags that aren't well-formed BCP 47 are skipped
+            let raw = vec![b"en-, 12-US, de-CH-1901, en-a, zh-Hant-TW".to_vec()];
+            let value = HeaderField::parse_header(&raw[..]).unwrap();
+            assert_eq!(value.to_string(), "de-CH-1901, zh-Hant-TW");
+        }
     }
 }
@@ -0,0 +1,43 @@
+header! {
+    /// `Content-Location` header, defined in
+    /// [RFC7231](https://tools.ietf.org/html/rfc7231#section-3.1.4.2)
+    ///
+    /// The `Content-Location` header field references a URI that can be used
+    /// as an identifier for a specific resource corresponding to the
+    /// representation in this message's payload.  In other words, if one
+    /// were to perform a GET request on this URI at the time of this
+    /// message's generation, then a 200 (OK) response would contain the same
+    /// representation that is enclosed as payload in this message.
+    ///
+    /// # ABNF
+    /// ```plain
+    /// Content-Location = absolute-URI / partial-URI
+    /// ```
+    ///
+    /// # Example values
+    /// * `/hypertext/Overview.html`
+    /// * `http://www.example.org/hypertext/Overview.html`
+    ///
+    /// # Examples
+    /// ```
+    /// use hyper::header::{Headers, ContentLocation};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(ContentLocation("/hypertext/Overview.html".to_owned()));
+    /// ```
+    /// ```
+    /// use hyper::header::{Headers, ContentLocation};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(ContentLocation("http://www.example.org/hypertext/Overview.html".to_owned()));
+    /// ```
+    // TODO: Use URL
+    (ContentLocation, "Content-Location") => [String]
+
+    test_content_location {
+        test_header!(partial_query, vec![b"/hypertext/Overview.html?q=tim"]);
+        test_header!(absolute, vec![b"http://www.example.org/hypertext/Overview.html"]);
+    }
+}
+
+bench_header!(bench, ContentLocation, { vec![b"/hypertext/Overview.html".to_vec()] });
@@ -0,0 +1,122 @@
+use std::fmt;
+use std::str::FromStr;
+
+use time::{self, Timespec};
+
+use header::{Header, HeaderFormat, HttpDate};
+use header::parsing::from_one_raw_str;
+
+/// `Deprecation` header, defined in [RFC9745](https://tools.ietf.org/html/rfc9745)
+///
+/// The `Deprecation` response header field signals that the resource the
+/// request targeted is, or will be, deprecated, and since when.
+///
+/// The date is sent as a Structured Field Date, the number of seconds since
+/// the epoch after an `@`. The `true` value and the HTTP-date form from
+/// earlier drafts are still parsed.
+///
+/// # ABNF
+/// ```plain
+/// Deprecation = sf-date
+/// sf-date     = "@" sf-integer
+/// ```
+///
+/// # Example values
+/// * `@1688169599`
+///
+/// # Example
+/// ```
+/// # extern crate hyper;
+/// # extern crate time;
+/// # fn main() {
+/// // extern crate time;
+///
+/// use hyper::header::{Headers, Deprecation, HttpDate};
+///
+/// let mut headers = Headers::new();
+/// headers.set(Deprecation::Since(HttpDate(time::now_utc())));
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Deprecation {
+    /// Deprecated, without saying since when.
+    Deprecated,
+    /// Deprecated since, or from, the date.
+    Since(HttpDate),
+}
+
+impl Header for Deprecation {
+    fn header_name() -> &'static str {
+        "Deprecation"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> ::Result<Deprecation> {
+        from_one_raw_str(raw)
+    }
+}
+
+impl HeaderFormat for Deprecation {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Deprecation::Deprecated => f.write_str("true"),
+            Deprecation::Since(HttpDate(ref tm)) => write!(f, "@{}", tm.to_timespec().sec),
+        }
+    }
+}
+
+impl FromStr for Deprecation {
+    type Err = ::Error;
+    fn from_str(s: &str) -> ::Result<Deprecation> {
+        if s == "true" {
+            Ok(Deprecation::Deprecated)
+        } else if s.starts_with('@') {
+            match s[1..].parse() {
+                Ok(secs) => Ok(Deprecation::Since(HttpDate(time::at_utc(Timespec::new(secs, 0))))),
+                Err(_) => Err(::Error::Header),
+            }
+        } else {
+            s.parse().map(Deprecation::Since)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Header, HttpDate};
+    use super::Deprecation;
+
+    fn parse(raw: &[u8]) -> Option<Deprecation> {
+        Header::parse_header(&[raw.to_vec()]).ok()
+    }
+
+    fn since(dep: Option<Deprecation>) -> Option<i64> {
+        match dep {
+            Some(Deprecation::Since(HttpDate(tm))) => Some(tm.to_timespec().sec),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(since(parse(b"@1688169599")), Some(1688169599));
+        assert_eq!(since(parse(b"Fri, 30 Jun 2023 23:59:59 GMT")), Some(1688169599));
+        assert_eq!(parse(b"true"), Some(Deprecation::Deprecated));
+        assert_eq!(parse(b"@soon"), None);
+        assert_eq!(parse(b"false"), None);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(parse(b"@1688169599").unwrap().to_string(), "@1688169599");
+        assert_eq!(parse(b"Fri, 30 Jun 2023 23:59:59 GMT").unwrap().to_string(), "@1688169599");
+        assert_eq!(Deprecation::Deprecated.to_string(), "true");
+    }
+}
+
+bench_header!(date, Deprecation, { vec![b"@1688169599".to_vec()] });
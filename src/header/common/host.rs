@@ -8,7 +8,8 @@ use header::parsing::from_one_raw_str;
 /// client requests add one automatically.
 ///
 /// Currently is just a String, but it should probably become a better type,
-/// like url::Host or something.
+/// like url::Host or something. An IPv6 address is parsed with its
+/// brackets, as in URIs, and is given them when formatted if it hasn't.
 ///
 /// # Examples
 /// ```
@@ -47,41 +48,26 @@ impl Header for Host {
     }
 
     fn parse_header(raw: &[Vec<u8>]) -> ::Result<Host> {
-        from_one_raw_str(raw).and_then(|mut s: String| {
-            // FIXME: use rust-url to parse this
-            // https://github.com/servo/rust-url/issues/42
-            let idx = {
-                let slice = &s[..];
-                let mut chars = slice.chars();
-                chars.next();
-                if chars.next().unwrap() == '[' {
-                    match slice.rfind(']') {
-                        Some(idx) => {
-                            if slice.len() > idx + 2 {
-                                Some(idx + 1)
-                            } else {
-                                None
-                            }
-                        }
-                        None => return Err(::Error::Header) // this is a bad ipv6 address...
-                    }
-                } else {
-                    slice.rfind(':')
+        from_one_raw_str(raw).and_then(|s: String| {
+            // an IPv6 address is bracketed, since it has colons of its own
+            let host_end = if s.starts_with('[') {
+                match s.find(']') {
+                    Some(idx) => idx + 1,
+                    None => return Err(::Error::Header) // this is a bad ipv6 address...
                 }
+            } else {
+                s.find(':').unwrap_or(s.len())
             };
-
-            let port = match idx {
-                Some(idx) => s[idx + 1..].parse().ok(),
-                None => None
+            let port = match &s[host_end..] {
+                "" | ":" => None,
+                rest if rest.starts_with(':') => match rest[1..].parse() {
+                    Ok(port) => Some(port),
+                    Err(_) => return Err(::Error::Header)
+                },
+                _ => return Err(::Error::Header)
             };
-
-            match idx {
-                Some(idx) => s.truncate(idx),
-                None => ()
-            }
-
             Ok(Host {
-                hostname: s,
+                hostname: s[..host_end].to_owned(),
                 port: port
             })
         })
@@ -90,9 +76,16 @@ impl Header for Host {
 
 impl HeaderFormat for Host {
     fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // an IPv6 address given without brackets gets them, and the `%`
+        // before its zone ID is encoded
+        if self.hostname.contains(':') && !self.hostname.starts_with('[') {
+            try!(write!(f, "[{}]", self.hostname.replace('%', "%25")));
+        } else {
+            try!(f.write_str(&self.hostname[..]));
+        }
         match self.port {
-            None | Some(80) | Some(443) => f.write_str(&self.hostname[..]),
-            Some(port) => write!(f, "{}:{}", self.hostname, port)
+            None | Some(80) | Some(443) => Ok(()),
+            Some(port) => write!(f, ":{}", port)
         }
     }
 }
@@ -100,7 +93,7 @@ impl HeaderFormat for Host {
 #[cfg(test)]
 mod tests {
     use super::Host;
-    use header::Header;
+    use header::{Header, HeaderFormatter};
 
 
     #[test]
@@ -117,6 +110,39 @@ mod tests {
             hostname: "foo.com".to_owned(),
             port: Some(8080)
         }));
+
+        let host = Header::parse_header([b"[::1]:8080".to_vec()].as_ref());
+        assert_eq!(host.ok(), Some(Host {
+            hostname: "[::1]".to_owned(),
+            port: Some(8080)
+        }));
+
+        let host = Header::parse_header([b"[fe80::1%25eth0]".to_vec()].as_ref());
+        assert_eq!(host.ok(), Some(Host {
+            hostname: "[fe80::1%25eth0]".to_owned(),
+            port: None
+        }));
+
+        let host = Header::parse_header([b"a".to_vec()].as_ref());
+        assert_eq!(host.ok(), Some(Host {
+            hostname: "a".to_owned(),
+            port: None
+        }));
+
+        for bad in &["[::1", "[::1]x", "::1", "foo.com:http"] {
+            assert!(Host::parse_header([bad.as_bytes().to_vec()].as_ref()).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_host_fmt() {
+        let host = |hostname: &str, port| {
+            HeaderFormatter(&Host { hostname: hostname.to_owned(), port: port }).to_string()
+        };
+        assert_eq!(host("foo.com", Some(80)), "foo.com");
+        assert_eq!(host("[::1]", Some(8080)), "[::1]:8080");
+        assert_eq!(host("::1", Some(8080)), "[::1]:8080");
+        assert_eq!(host("fe80::1%eth0", None), "[fe80::1%25eth0]");
     }
 }
 
@@ -14,10 +14,12 @@ pub use self::access_control_expose_headers::AccessControlExposeHeaders;
 pub use self::access_control_max_age::AccessControlMaxAge;
 pub use self::access_control_request_headers::AccessControlRequestHeaders;
 pub use self::access_control_request_method::AccessControlRequestMethod;
+pub use self::api_version::ApiVersion;
 pub use self::accept_charset::AcceptCharset;
 pub use self::accept_encoding::AcceptEncoding;
 pub use self::accept_language::AcceptLanguage;
 pub use self::accept_ranges::{AcceptRanges, RangeUnit};
+pub use self::age::Age;
 pub use self::allow::Allow;
 pub use self::authorization::{Authorization, Scheme, Basic, Bearer};
 pub use self::cache_control::{CacheControl, CacheDirective};
@@ -25,10 +27,12 @@ pub use self::connection::{Connection, ConnectionOption};
 pub use self::content_length::ContentLength;
 pub use self::content_encoding::ContentEncoding;
 pub use self::content_language::ContentLanguage;
+pub use self::content_location::ContentLocation;
 pub use self::content_range::{ContentRange, ContentRangeSpec};
 pub use self::content_type::ContentType;
 pub use self::cookie::Cookie;
 pub use self::date::Date;
+pub use self::deprecation::Deprecation;
 pub use self::etag::ETag;
 pub use self::expect::Expect;
 pub use self::expires::Expires;
@@ -42,13 +46,21 @@ pub use self::if_range::IfRange;
 pub use self::last_modified::LastModified;
 pub use self::location::Location;
 pub use self::pragma::Pragma;
+pub use self::prefer::{Prefer, Preference};
+pub use self::preference_applied::PreferenceApplied;
 pub use self::range::{Range, ByteRangeSpec};
+pub use self::ratelimit_limit::{RateLimitLimit, XRateLimitLimit};
+pub use self::ratelimit_remaining::{RateLimitRemaining, XRateLimitRemaining};
+pub use self::ratelimit_reset::{RateLimitReset, XRateLimitReset};
 pub use self::referer::Referer;
+pub use self::retry_after::RetryAfter;
 pub use self::server::Server;
 pub use self::set_cookie::SetCookie;
 pub use self::strict_transport_security::StrictTransportSecurity;
+pub use self::sunset::Sunset;
 pub use self::transfer_encoding::TransferEncoding;
 pub use self::upgrade::{Upgrade, Protocol, ProtocolName};
+pub use self::upgrade_insecure_requests::UpgradeInsecureRequests;
 pub use self::user_agent::UserAgent;
 pub use self::vary::Vary;
 
@@ -360,11 +372,13 @@ mod access_control_expose_headers;
 mod access_control_max_age;
 mod access_control_request_headers;
 mod access_control_request_method;
+mod api_version;
 mod accept_charset;
 mod accept_encoding;
 mod accept_language;
 mod accept_ranges;
 mod allow;
+mod age;
 mod authorization;
 mod cache_control;
 mod cookie;
@@ -372,9 +386,11 @@ mod connection;
 mod content_encoding;
 mod content_language;
 mod content_length;
+mod content_location;
 mod content_range;
 mod content_type;
 mod date;
+mod deprecation;
 mod etag;
 mod expect;
 mod expires;
@@ -388,12 +404,20 @@ mod if_unmodified_since;
 mod last_modified;
 mod location;
 mod pragma;
+mod prefer;
+mod preference_applied;
 mod range;
+mod ratelimit_limit;
+mod ratelimit_remaining;
+mod ratelimit_reset;
 mod referer;
+mod retry_after;
 mod server;
 mod set_cookie;
 mod strict_transport_security;
+mod sunset;
 mod transfer_encoding;
 mod upgrade;
+mod upgrade_insecure_requests;
 mod user_agent;
 mod vary;
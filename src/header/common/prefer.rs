@@ -0,0 +1,186 @@
+use std::ascii::AsciiExt;
+use std::fmt;
+use std::str::FromStr;
+
+use header::parsing::is_tchar;
+
+header! {
+    /// `Prefer` header, defined in [RFC7240](http://tools.ietf.org/html/rfc7240)
+    ///
+    /// The `Prefer` header field can be used by a client to request that
+    /// certain behaviors be employed by a server while processing a
+    /// request.
+    ///
+    /// # ABNF
+    /// ```plain
+    /// Prefer     = "Prefer" ":" 1#preference
+    /// preference = token [ BWS "=" BWS word ]
+    ///              *( OWS ";" [ OWS parameter ] )
+    /// parameter  = token [ BWS "=" BWS word ]
+    /// ```
+    ///
+    /// # Example values
+    /// * `respond-async`
+    /// * `return=minimal`
+    /// * `wait=30`
+    /// * `foo; bar`
+    ///
+    /// # Examples
+    /// ```
+    /// use hyper::header::{Headers, Prefer, Preference};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(
+    ///     Prefer(vec![Preference::RespondAsync])
+    /// );
+    /// ```
+    /// ```
+    /// use hyper::header::{Headers, Prefer, Preference};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(
+    ///     Prefer(vec![
+    ///         Preference::RespondAsync,
+    ///         Preference::ReturnRepresentation,
+    ///         Preference::Wait(10u32),
+    ///         Preference::Extension("foo".to_owned(),
+    ///                               "bar".to_owned(),
+    ///                               vec![]),
+    ///     ])
+    /// );
+    /// ```
+    (Prefer, "Prefer") => (Preference)+
+
+    test_prefer {
+        test_header!(test1, vec![b"respond-async, return=representation"]);
+        test_header!(test2, vec![b"return=minimal, wait=30; foo=\"a b\""]);
+        test_header!(
+            test3,
+            vec![b"respond-async, return=representation, wait=100"],
+            Some(HeaderField(vec![
+                Preference::RespondAsync,
+                Preference::ReturnRepresentation,
+                Preference::Wait(100),
+            ])));
+        test_header!(
+            test4,
+            vec![b"foo=bar; baz, handling=lenient, wait=10; x=1"],
+            Some(HeaderField(vec![
+                Preference::Extension("foo".to_owned(), "bar".to_owned(),
+                                      vec![("baz".to_owned(), "".to_owned())]),
+                Preference::HandlingLenient,
+                Preference::Extension("wait".to_owned(), "10".to_owned(),
+                                      vec![("x".to_owned(), "1".to_owned())]),
+            ])));
+        // names are case-insensitive
+        test_header!(test5, vec![b"Respond-Async, RETURN=minimal"]);
+    }
+}
+
+/// Prefer contains a list of these preferences.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Preference {
+    /// "respond-async"
+    RespondAsync,
+    /// "return=representation"
+    ReturnRepresentation,
+    /// "return=minimal"
+    ReturnMinimal,
+    /// "handling=strict"
+    HandlingStrict,
+    /// "handling=lenient"
+    HandlingLenient,
+    /// "wait=delta"
+    Wait(u32),
+
+    /// Extension preferences, and known preferences with parameters. The
+    /// value is empty if there is none, as are the values of parameters.
+    Extension(String, String, Vec<(String, String)>)
+}
+
+impl fmt::Display for Preference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Preference::*;
+        fmt::Display::fmt(match *self {
+            RespondAsync => "respond-async",
+            ReturnRepresentation => "return=representation",
+            ReturnMinimal => "return=minimal",
+            HandlingStrict => "handling=strict",
+            HandlingLenient => "handling=lenient",
+
+            Wait(secs) => return write!(f, "wait={}", secs),
+
+            Extension(ref name, ref value, ref params) => {
+                try!(fmt_pair(f, name, value));
+                for &(ref name, ref value) in params {
+                    try!(f.write_str("; "));
+                    try!(fmt_pair(f, name, value));
+                }
+                return Ok(());
+            }
+        }, f)
+    }
+}
+
+/// Writes `name`, and `=value` if there is a value, quoting it if needed.
+pub fn fmt_pair(f: &mut fmt::Formatter, name: &str, value: &str) -> fmt::Result {
+    try!(f.write_str(name));
+    if value.is_empty() {
+        return Ok(());
+    }
+    if value.bytes().all(is_tchar) {
+        write!(f, "={}", value)
+    } else {
+        try!(f.write_str("=\""));
+        for c in value.chars() {
+            if c == '"' || c == '\\' {
+                try!(write!(f, "\\"));
+            }
+            try!(write!(f, "{}", c));
+        }
+        f.write_str("\"")
+    }
+}
+
+fn parse_pair(s: &str) -> (String, String) {
+    let mut parts = s.splitn(2, '=');
+    let name = parts.next().unwrap_or("").trim();
+    let value = parts.next().unwrap_or("").trim();
+    let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        value[1..value.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\")
+    } else {
+        value.to_owned()
+    };
+    (name.to_owned(), value)
+}
+
+impl FromStr for Preference {
+    type Err = Option<<u32 as FromStr>::Err>;
+    fn from_str(s: &str) -> Result<Preference, Option<<u32 as FromStr>::Err>> {
+        use self::Preference::*;
+        let mut pairs = s.split(';').map(parse_pair);
+        let (name, value) = match pairs.next() {
+            Some((ref name, _)) if name.is_empty() || !name.bytes().all(is_tchar) => {
+                return Err(None)
+            },
+            Some(pair) => pair,
+            None => return Err(None),
+        };
+        let params = pairs.filter(|&(ref name, _)| !name.is_empty()).collect::<Vec<_>>();
+        if !params.is_empty() {
+            return Ok(Extension(name, value, params));
+        }
+        match (&name.to_ascii_lowercase()[..], &value[..]) {
+            ("respond-async", "") => Ok(RespondAsync),
+            ("return", "representation") => Ok(ReturnRepresentation),
+            ("return", "minimal") => Ok(ReturnMinimal),
+            ("handling", "strict") => Ok(HandlingStrict),
+            ("handling", "lenient") => Ok(HandlingLenient),
+            ("wait", secs) => secs.parse().map(Wait).map_err(Some),
+            _ => Ok(Extension(name, value, params)),
+        }
+    }
+}
+
+bench_header!(normal,
+    Prefer, { vec![b"respond-async, return=representation, wait=100".to_vec()] });
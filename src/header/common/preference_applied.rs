@@ -0,0 +1,126 @@
+use std::fmt;
+use header::{Header, HeaderFormat, Preference};
+use header::parsing::{from_comma_delimited, fmt_comma_delimited};
+use super::prefer::fmt_pair;
+
+/// `Preference-Applied` header, defined in [RFC7240](http://tools.ietf.org/html/rfc7240)
+///
+/// The `Preference-Applied` response header may be included within a
+/// response message as an indication as to which `Prefer` header tokens were
+/// honored by the server and applied to the processing of a request.
+///
+/// # ABNF
+/// ```plain
+/// Preference-Applied = "Preference-Applied" ":" 1#applied-pref
+/// applied-pref = token [ BWS "=" BWS word ]
+/// ```
+///
+/// # Example values
+/// * `respond-async`
+/// * `return=minimal`
+/// * `wait=30`
+///
+/// # Examples
+/// ```
+/// use hyper::header::{Headers, PreferenceApplied, Preference};
+///
+/// let mut headers = Headers::new();
+/// headers.set(
+///     PreferenceApplied(vec![Preference::RespondAsync])
+/// );
+/// ```
+/// ```
+/// use hyper::header::{Headers, PreferenceApplied, Preference};
+///
+/// let mut headers = Headers::new();
+/// headers.set(
+///     PreferenceApplied(vec![
+///         Preference::RespondAsync,
+///         Preference::ReturnRepresentation,
+///         Preference::Wait(10u32),
+///         Preference::Extension("foo".to_owned(),
+///                               "bar".to_owned(),
+///                               vec![]),
+///     ])
+/// );
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct PreferenceApplied(pub Vec<Preference>);
+
+__hyper__deref!(PreferenceApplied => Vec<Preference>);
+
+impl Header for PreferenceApplied {
+    fn header_name() -> &'static str {
+        "Preference-Applied"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> ::Result<PreferenceApplied> {
+        let preferences = try!(from_comma_delimited(raw));
+        if !preferences.is_empty() {
+            Ok(PreferenceApplied(preferences))
+        } else {
+            Err(::Error::Header)
+        }
+    }
+}
+
+impl HeaderFormat for PreferenceApplied {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // applied preferences don't have parameters
+        let preferences = self.0.iter().map(|pref| match *pref {
+            Preference::Extension(ref name, ref value, _) => Applied::Extension(name, value),
+            ref pref => Applied::Known(pref),
+        }).collect::<Vec<_>>();
+        fmt_comma_delimited(f, &preferences[..])
+    }
+}
+
+impl fmt::Display for PreferenceApplied {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_header(f)
+    }
+}
+
+enum Applied<'a> {
+    Known(&'a Preference),
+    Extension(&'a str, &'a str),
+}
+
+impl<'a> fmt::Display for Applied<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Applied::Known(pref) => fmt::Display::fmt(pref, f),
+            Applied::Extension(name, value) => fmt_pair(f, name, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Header, Preference};
+    use super::*;
+
+    #[test]
+    fn test_format_ignore_parameters() {
+        assert_eq!(
+            format!("{}", PreferenceApplied(vec![Preference::Extension(
+                "foo".to_owned(),
+                "bar".to_owned(),
+                vec![("bar".to_owned(), "foo".to_owned()), ("buz".to_owned(), "".to_owned())]
+            )])),
+            "foo=bar".to_owned()
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let applied = Header::parse_header(&[b"return=minimal, wait=5".to_vec()]);
+        assert_eq!(applied.ok(), Some(PreferenceApplied(vec![Preference::ReturnMinimal,
+                                                             Preference::Wait(5)])));
+        let empty: ::Result<PreferenceApplied> = Header::parse_header(&[b"".to_vec()]);
+        assert!(empty.is_err());
+    }
+}
+
+bench_header!(normal,
+    PreferenceApplied, { vec![b"respond-async, return=representation, wait=100".to_vec()] });
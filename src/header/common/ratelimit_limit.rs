@@ -0,0 +1,49 @@
+header! {
+    /// `RateLimit-Limit` header, defined in
+    /// [draft-ietf-httpapi-ratelimit-headers](https://tools.ietf.org/html/draft-ietf-httpapi-ratelimit-headers)
+    ///
+    /// The `RateLimit-Limit` response header field contains
+    /// the number of requests the client is allowed to make in the current time window.
+    ///
+    /// # ABNF
+    /// ```plain
+    /// RateLimit-Limit = 1*DIGIT
+    /// ```
+    ///
+    /// # Example values
+    /// * `100`
+    ///
+    /// # Examples
+    /// ```
+    /// use hyper::header::{Headers, RateLimitLimit};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(RateLimitLimit(100u64));
+    /// ```
+    (RateLimitLimit, "RateLimit-Limit") => [u64]
+
+    test_ratelimit_limit {
+        test_header!(test1, vec![b"100"]);
+        test_header!(test2, vec![b"-1"], None::<HeaderField>);
+    }
+}
+
+header! {
+    /// `X-RateLimit-Limit` header
+    ///
+    /// The legacy, non-standard form of `RateLimit-Limit`, still sent by
+    /// many APIs.
+    ///
+    /// # Examples
+    /// ```
+    /// use hyper::header::{Headers, XRateLimitLimit};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(XRateLimitLimit(100u64));
+    /// ```
+    (XRateLimitLimit, "X-RateLimit-Limit") => [u64]
+
+    test_x_ratelimit_limit {
+        test_header!(test1, vec![b"100"]);
+    }
+}
@@ -0,0 +1,49 @@
+header! {
+    /// `RateLimit-Remaining` header, defined in
+    /// [draft-ietf-httpapi-ratelimit-headers](https://tools.ietf.org/html/draft-ietf-httpapi-ratelimit-headers)
+    ///
+    /// The `RateLimit-Remaining` response header field contains
+    /// the number of requests the client may still make in the current time window.
+    ///
+    /// # ABNF
+    /// ```plain
+    /// RateLimit-Remaining = 1*DIGIT
+    /// ```
+    ///
+    /// # Example values
+    /// * `42`
+    ///
+    /// # Examples
+    /// ```
+    /// use hyper::header::{Headers, RateLimitRemaining};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(RateLimitRemaining(42u64));
+    /// ```
+    (RateLimitRemaining, "RateLimit-Remaining") => [u64]
+
+    test_ratelimit_remaining {
+        test_header!(test1, vec![b"42"]);
+        test_header!(test2, vec![b"-1"], None::<HeaderField>);
+    }
+}
+
+header! {
+    /// `X-RateLimit-Remaining` header
+    ///
+    /// The legacy, non-standard form of `RateLimit-Remaining`, still sent by
+    /// many APIs.
+    ///
+    /// # Examples
+    /// ```
+    /// use hyper::header::{Headers, XRateLimitRemaining};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(XRateLimitRemaining(42u64));
+    /// ```
+    (XRateLimitRemaining, "X-RateLimit-Remaining") => [u64]
+
+    test_x_ratelimit_remaining {
+        test_header!(test1, vec![b"42"]);
+    }
+}
@@ -0,0 +1,52 @@
+header! {
+    /// `RateLimit-Reset` header, defined in
+    /// [draft-ietf-httpapi-ratelimit-headers](https://tools.ietf.org/html/draft-ietf-httpapi-ratelimit-headers)
+    ///
+    /// The `RateLimit-Reset` response header field contains
+    /// the number of seconds until the quota of the current time window resets.
+    ///
+    /// # ABNF
+    /// ```plain
+    /// RateLimit-Reset = delta-seconds
+    /// ```
+    ///
+    /// # Example values
+    /// * `30`
+    ///
+    /// # Examples
+    /// ```
+    /// use hyper::header::{Headers, RateLimitReset};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(RateLimitReset(30u64));
+    /// ```
+    (RateLimitReset, "RateLimit-Reset") => [u64]
+
+    test_ratelimit_reset {
+        test_header!(test1, vec![b"30"]);
+        test_header!(test2, vec![b"-1"], None::<HeaderField>);
+    }
+}
+
+header! {
+    /// `X-RateLimit-Reset` header
+    ///
+    /// The legacy, non-standard form of `RateLimit-Reset`, still sent by
+    /// many APIs.
+    ///
+    /// Many servers send a Unix timestamp instead of a number of seconds in
+    /// this header; `hyper::client::ratelimit` handles both.
+    ///
+    /// # Examples
+    /// ```
+    /// use hyper::header::{Headers, XRateLimitReset};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(XRateLimitReset(30u64));
+    /// ```
+    (XRateLimitReset, "X-RateLimit-Reset") => [u64]
+
+    test_x_ratelimit_reset {
+        test_header!(test1, vec![b"30"]);
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt::{self, Display};
+use header::{self, Header, HeaderFormat, HttpDate};
+
+/// `Retry-After` header, defined in [RFC7231](http://tools.ietf.org/html/rfc7231#section-7.1.3)
+///
+/// The `Retry-After` response header field indicates how long the user
+/// agent ought to wait before making a follow-up request. When sent with
+/// a 503 (Service Unavailable) response, Retry-After indicates how long
+/// the service is expected to be unavailable to the client.
+///
+/// # ABNF
+/// ```plain
+/// Retry-After = HTTP-date / delay-seconds
+/// ```
+///
+/// # Example values
+/// * `Fri, 31 Dec 1999 23:59:59 GMT`
+/// * `120`
+///
+/// # Examples
+/// ```
+/// use hyper::header::{Headers, RetryAfter};
+///
+/// let mut headers = Headers::new();
+/// headers.set(RetryAfter::Delay(120));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum RetryAfter {
+    /// The number of seconds to wait
+    Delay(u64),
+    /// The date after which to retry
+    DateTime(HttpDate),
+}
+
+impl Header for RetryAfter {
+    fn header_name() -> &'static str {
+        "Retry-After"
+    }
+    fn parse_header(raw: &[Vec<u8>]) -> ::Result<RetryAfter> {
+        let delay: ::Result<u64> = header::parsing::from_one_raw_str(raw);
+        if let Ok(delay) = delay {
+            return Ok(RetryAfter::Delay(delay));
+        }
+        let date: ::Result<HttpDate> = header::parsing::from_one_raw_str(raw);
+        if let Ok(date) = date {
+            return Ok(RetryAfter::DateTime(date));
+        }
+        Err(::Error::Header)
+    }
+}
+
+impl HeaderFormat for RetryAfter {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RetryAfter::Delay(ref x) => Display::fmt(x, f),
+            RetryAfter::DateTime(ref x) => Display::fmt(x, f),
+        }
+    }
+}
+
+impl Display for RetryAfter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_header(f)
+    }
+}
+
+#[cfg(test)]
+mod test_retry_after {
+    use std::str;
+    use header::*;
+    use super::RetryAfter as HeaderField;
+    test_header!(test1, vec![b"Fri, 31 Dec 1999 23:59:59 GMT"]);
+    test_header!(test2, vec![b"120"]);
+    test_header!(test3, vec![b"soon"], None::<RetryAfter>);
+}
@@ -1,3 +1,6 @@
+use header::ProductToken;
+use super::user_agent::append;
+
 header! {
     /// `Server` header, defined in [RFC7231](http://tools.ietf.org/html/rfc7231#section-7.4.2)
     ///
@@ -24,12 +27,47 @@ header! {
     /// let mut headers = Headers::new();
     /// headers.set(Server("hyper/0.5.2".to_owned()));
     /// ```
-    // TODO: Maybe parse as defined in the spec?
+    /// ```
+    /// use hyper::header::{Headers, Server};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(Server::new("myserver", "1.2").with_comment("Unix"));
+    /// ```
     (Server, "Server") => [String]
 
     test_server {
         // Testcase from RFC
         test_header!(test1, vec![b"CERN/3.0 libwww/2.17"]);
+
+        #[test]
+        fn test_products() {
+            let server = Server::new("CERN", "3.0").with_product("libwww", "2.17");
+            assert_eq!(server.0, "CERN/3.0 libwww/2.17");
+            assert_eq!(server.products().unwrap().len(), 2);
+            assert!(Server("(unclosed".to_owned()).products().is_err());
+        }
+    }
+}
+
+impl Server {
+    /// Creates a `Server` of a single product and version.
+    pub fn new(product: &str, version: &str) -> Server {
+        Server(format!("{}/{}", product, version))
+    }
+
+    /// Appends another product.
+    pub fn with_product(self, product: &str, version: &str) -> Server {
+        Server(append(self.0, &format!("{}/{}", product, version)))
+    }
+
+    /// Appends a comment.
+    pub fn with_comment(self, comment: &str) -> Server {
+        Server(append(self.0, &format!("({})", comment)))
+    }
+
+    /// Parses the value into its products and comments.
+    pub fn products(&self) -> ::Result<Vec<ProductToken>> {
+        ProductToken::parse_all(&self.0)
     }
 }
 
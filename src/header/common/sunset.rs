@@ -0,0 +1,42 @@
+use header::HttpDate;
+
+header! {
+    /// `Sunset` header, defined in [RFC8594](https://tools.ietf.org/html/rfc8594)
+    ///
+    /// The `Sunset` header field indicates that the resource is expected
+    /// to become unresponsive at a specific point in time.
+    ///
+    /// It is often sent along with `Deprecation`, and a `Link` to
+    /// documentation about the change.
+    ///
+    /// # ABNF
+    /// ```plain
+    /// Sunset = HTTP-date
+    /// ```
+    ///
+    /// # Example values
+    /// * `Sat, 31 Dec 2018 23:59:59 GMT`
+    ///
+    /// # Example
+    /// ```
+    /// # extern crate hyper;
+    /// # extern crate time;
+    /// # fn main() {
+    /// // extern crate time;
+    ///
+    /// use hyper::header::{Headers, Sunset, HttpDate};
+    /// use time::{self, Duration};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(Sunset(HttpDate(time::now() + Duration::days(90))));
+    /// # }
+    /// ```
+    (Sunset, "Sunset") => [HttpDate]
+
+    test_sunset {
+        // Testcase from RFC
+        test_header!(test1, vec![b"Sat, 31 Dec 2018 23:59:59 GMT"]);
+    }
+}
+
+bench_header!(imf_fixdate, Sunset, { vec![b"Sat, 31 Dec 2018 23:59:59 GMT".to_vec()] });
@@ -0,0 +1,70 @@
+use std::fmt;
+
+use header::{Header, HeaderFormat};
+use header::parsing::from_one_raw_str;
+
+/// `Upgrade-Insecure-Requests` header, defined in
+/// [Upgrade Insecure Requests](https://www.w3.org/TR/upgrade-insecure-requests/#preference)
+///
+/// The `Upgrade-Insecure-Requests` request header field tells a server
+/// that the client would prefer to be sent to a secure version of the
+/// resource, such as by a redirect to its `https` URL.
+///
+/// # ABNF
+/// ```plain
+/// Upgrade-Insecure-Requests = "1"
+/// ```
+///
+/// # Example
+/// ```
+/// use hyper::header::{Headers, UpgradeInsecureRequests};
+///
+/// let mut headers = Headers::new();
+/// headers.set(UpgradeInsecureRequests);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UpgradeInsecureRequests;
+
+impl Header for UpgradeInsecureRequests {
+    fn header_name() -> &'static str {
+        "Upgrade-Insecure-Requests"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> ::Result<UpgradeInsecureRequests> {
+        match try!(from_one_raw_str::<String>(raw)).trim() {
+            "1" => Ok(UpgradeInsecureRequests),
+            _ => Err(::Error::Header)
+        }
+    }
+}
+
+impl HeaderFormat for UpgradeInsecureRequests {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("1")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Header, Headers};
+    use super::UpgradeInsecureRequests;
+
+    #[test]
+    fn test_parse() {
+        let parse = |raw: &[u8]| -> Option<UpgradeInsecureRequests> {
+            Header::parse_header(&[raw.to_vec()]).ok()
+        };
+        assert_eq!(parse(b"1"), Some(UpgradeInsecureRequests));
+        assert_eq!(parse(b"0"), None);
+        assert_eq!(parse(b""), None);
+    }
+
+    #[test]
+    fn test_format() {
+        let mut headers = Headers::new();
+        headers.set(UpgradeInsecureRequests);
+        assert_eq!(headers.to_string(), "Upgrade-Insecure-Requests: 1\r\n");
+    }
+}
+
+bench_header!(bench, UpgradeInsecureRequests, { vec![b"1".to_vec()] });
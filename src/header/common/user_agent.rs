@@ -1,3 +1,5 @@
+use header::ProductToken;
+
 header! {
     /// `User-Agent` header, defined in
     /// [RFC7231](http://tools.ietf.org/html/rfc7231#section-5.5.3)
@@ -22,7 +24,7 @@ header! {
     /// * `Bunnies`
     ///
     /// # Notes
-    /// * The parser does not split the value
+    /// * The parser does not split the value, use `products()` for that
     ///
     /// # Example
     /// ```
@@ -31,6 +33,16 @@ header! {
     /// let mut headers = Headers::new();
     /// headers.set(UserAgent("hyper/0.5.2".to_owned()));
     /// ```
+    /// ```
+    /// use hyper::header::{Headers, UserAgent, ProductToken};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(UserAgent::new("myapp", "1.2"));
+    /// let products = headers.get::<UserAgent>().unwrap().products().unwrap();
+    /// assert_eq!(products, vec![
+    ///     ProductToken::Product("myapp".to_owned(), Some("1.2".to_owned())),
+    /// ]);
+    /// ```
     (UserAgent, "User-Agent") => [String]
 
     test_user_agent {
@@ -38,5 +50,48 @@ header! {
         test_header!(test1, vec![b"CERN-LineMode/2.15 libwww/2.17b3"]);
         // Own testcase
         test_header!(test2, vec![b"Bunnies"], Some(UserAgent("Bunnies".to_owned())));
+
+        #[test]
+        fn test_products() {
+            let ua = UserAgent::new("myapp", "1.2").with_comment("+http://example.domain");
+            assert_eq!(ua.0, "myapp/1.2 (+http://example.domain)");
+            assert_eq!(ua.products().unwrap(), vec![
+                ProductToken::Product("myapp".to_owned(), Some("1.2".to_owned())),
+                ProductToken::Comment("+http://example.domain".to_owned()),
+            ]);
+        }
     }
 }
+
+impl UserAgent {
+    /// Creates a `User-Agent` of a single product and version.
+    pub fn new(product: &str, version: &str) -> UserAgent {
+        UserAgent(format!("{}/{}", product, version))
+    }
+
+    /// Appends another product.
+    pub fn with_product(self, product: &str, version: &str) -> UserAgent {
+        UserAgent(append(self.0, &format!("{}/{}", product, version)))
+    }
+
+    /// Appends a comment, such as a contact URL.
+    pub fn with_comment(self, comment: &str) -> UserAgent {
+        UserAgent(append(self.0, &format!("({})", comment)))
+    }
+
+    /// Parses the value into its products and comments.
+    pub fn products(&self) -> ::Result<Vec<ProductToken>> {
+        ProductToken::parse_all(&self.0)
+    }
+}
+
+// joins the parts of a product list
+pub fn append(mut value: String, part: &str) -> String {
+    if !value.is_empty() {
+        value.push(' ');
+    }
+    value.push_str(part);
+    value
+}
+
+bench_header!(bench, UserAgent, { vec![b"CERN-LineMode/2.15 libwww/2.17b3".to_vec()] });
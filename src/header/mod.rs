@@ -106,6 +106,7 @@ mod common;
 mod internals;
 mod shared;
 pub mod parsing;
+pub mod sfv;
 
 type HeaderName = UniCase<CowStr>;
 
@@ -177,10 +178,17 @@ fn header_name<T: Header>() -> &'static str {
     <T as Header>::header_name()
 }
 
+// The headers that only apply to a single connection, from RFC7230 and
+// RFC2616, besides the ones listed in `Connection`.
+const HOP_BY_HOP: [&'static str; 8] = ["Connection", "Keep-Alive", "Proxy-Authenticate",
+                                       "Proxy-Authorization", "TE", "Trailer",
+                                       "Transfer-Encoding", "Upgrade"];
+
 /// A map of header fields on requests and responses.
 #[derive(Clone)]
 pub struct Headers {
-    data: HashMap<HeaderName, Item>
+    data: HashMap<HeaderName, Item>,
+    invalid: Vec<Vec<u8>>,
 }
 
 impl Headers {
@@ -188,7 +196,8 @@ impl Headers {
     /// Creates a new, empty headers map.
     pub fn new() -> Headers {
         Headers {
-            data: HashMap::new()
+            data: HashMap::new(),
+            invalid: Vec::new(),
         }
     }
 
@@ -235,6 +244,19 @@ impl Headers {
             .map(Item::raw)
     }
 
+    /// Get the received header lines that couldn't be parsed, such as ones
+    /// with an invalid name, as their original bytes.
+    ///
+    /// These lines are otherwise left out of the map.
+    pub fn get_raw_invalid(&self) -> &[Vec<u8>] {
+        &self.invalid
+    }
+
+    #[doc(hidden)]
+    pub fn set_raw_invalid(&mut self, lines: Vec<Vec<u8>>) {
+        self.invalid = lines;
+    }
+
     /// Set the raw value of a header, bypassing any typed headers.
     ///
     /// Example:
@@ -258,6 +280,22 @@ impl Headers {
         );
     }
 
+    /// Remove the hop-by-hop headers, as a proxy must before forwarding a
+    /// message: the headers named in `Connection`, and the standard ones such
+    /// as `Keep-Alive`, `TE` and `Upgrade`.
+    pub fn remove_hop_by_hop(&mut self) {
+        // read the raw lines, as the typed header only takes one line
+        let listed = self.get_raw("Connection").map_or(vec![], |lines| {
+            lines.iter()
+                .filter_map(|line| parsing::from_one_comma_delimited::<String>(line).ok())
+                .flat_map(|names| names)
+                .collect()
+        });
+        for name in listed.iter().map(|name| &name[..]).chain(HOP_BY_HOP.iter().cloned()) {
+            self.remove_raw(name);
+        }
+    }
+
     /// Get a reference to the header field's value, if it exists.
     pub fn get<H: Header + HeaderFormat>(&self) -> Option<&H> {
         self.data.get(&UniCase(CowStr(Cow::Borrowed(header_name::<H>()))))
@@ -305,7 +343,8 @@ impl Headers {
 
     /// Remove all headers from the map.
     pub fn clear(&mut self) {
-        self.data.clear()
+        self.data.clear();
+        self.invalid.clear();
     }
 }
 
@@ -711,6 +750,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_remove_hop_by_hop() {
+        let mut headers = Headers::new();
+        headers.set_raw("Connection", vec![b"close, X-Trace".to_vec(), b"x-debug".to_vec()]);
+        headers.set_raw("X-Trace", vec![b"1".to_vec()]);
+        headers.set_raw("X-Debug", vec![b"1".to_vec()]);
+        headers.set_raw("Keep-Alive", vec![b"timeout=5".to_vec()]);
+        headers.set_raw("Upgrade", vec![b"websocket".to_vec()]);
+        headers.set(ContentLength(11));
+        headers.remove_hop_by_hop();
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get(), Some(&ContentLength(11)));
+    }
+
     #[test]
     fn test_eq() {
         let mut headers1 = Headers::new();
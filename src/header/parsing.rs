@@ -38,6 +38,22 @@ pub fn from_one_comma_delimited<T: str::FromStr>(raw: &[u8]) -> ::Result<Vec<T>>
         .collect())
 }
 
+/// Whether a byte is a `tchar`, which makes up a `token`, defined in
+/// [RFC7230](https://tools.ietf.org/html/rfc7230#section-3.2.6).
+pub fn is_tchar(b: u8) -> bool {
+    match b {
+        b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' |
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' |
+        b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => true,
+        _ => false
+    }
+}
+
+/// Whether a string is a `token`, of one or more `tchar`s.
+pub fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(is_tchar)
+}
+
 /// Format an array into a comma-delimited string.
 pub fn fmt_comma_delimited<T: Display>(f: &mut fmt::Formatter, parts: &[T]) -> fmt::Result {
     for (i, part) in parts.iter().enumerate() {
@@ -0,0 +1,706 @@
+//! Structured Field Values, from RFC 8941.
+//!
+//! Newer headers, such as `Priority` or `Cache-Status`, are defined as an
+//! `Item`, a `List` or a `Dictionary` of structured values instead of with
+//! their own grammar. This module parses and serializes those, so such
+//! headers only have to map them to their own types.
+//!
+//! ```
+//! use hyper::header::sfv::{Dictionary, BareItem};
+//!
+//! let dict = Dictionary::parse(b"u=2, i").unwrap();
+//! assert_eq!(dict.get("u").and_then(|m| m.as_item()).map(|i| &i.bare),
+//!            Some(&BareItem::Integer(2)));
+//! assert_eq!(dict.to_string(), "u=2, i");
+//! ```
+//!
+//! Serializing a value that can't be represented, such as a `Token` with a
+//! space in it, returns a `fmt::Error`.
+use std::fmt::{self, Display, Write};
+
+use serialize::base64::{ToBase64, FromBase64, STANDARD};
+
+use header::parsing::is_tchar;
+
+/// A bare value, without parameters.
+#[derive(Clone, PartialEq, Debug)]
+pub enum BareItem {
+    /// An integer, of at most 15 digits.
+    Integer(i64),
+    /// A decimal, with at most 12 integer and 3 fractional digits.
+    Decimal(f64),
+    /// A string of printable ASCII.
+    String(String),
+    /// A token, such as `text/html`.
+    Token(String),
+    /// A sequence of bytes, sent as base64.
+    ByteSequence(Vec<u8>),
+    /// A boolean.
+    Boolean(bool),
+}
+
+/// Parameters of an `Item` or `InnerList`, in order.
+pub type Parameters = Vec<(String, BareItem)>;
+
+/// A `BareItem` with its parameters.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Item {
+    /// The value.
+    pub bare: BareItem,
+    /// The parameters.
+    pub params: Parameters,
+}
+
+/// A list of `Item`s in parentheses, with its parameters.
+#[derive(Clone, PartialEq, Debug)]
+pub struct InnerList {
+    /// The items.
+    pub items: Vec<Item>,
+    /// The parameters of the whole list.
+    pub params: Parameters,
+}
+
+/// A member of a `List` or `Dictionary`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Member {
+    /// A single item.
+    Item(Item),
+    /// An inner list.
+    InnerList(InnerList),
+}
+
+/// A list of members, such as `a, (b c);d=1`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct List(pub Vec<Member>);
+
+/// An ordered map of keys to members, such as `a=1, b;x=?0`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Dictionary(pub Vec<(String, Member)>);
+
+impl Item {
+    /// Creates an `Item` without parameters.
+    pub fn new(bare: BareItem) -> Item {
+        Item { bare: bare, params: Vec::new() }
+    }
+
+    /// Parses an `Item` from a field value.
+    pub fn parse(raw: &[u8]) -> ::Result<Item> {
+        let mut parser = Parser::new(raw);
+        parser.skip_sp();
+        let item = try!(parser.item());
+        try!(parser.finish());
+        Ok(item)
+    }
+
+    /// Get the value of a parameter.
+    pub fn param(&self, key: &str) -> Option<&BareItem> {
+        param(&self.params, key)
+    }
+}
+
+impl Member {
+    /// Get the member as an `Item`, if it is one.
+    pub fn as_item(&self) -> Option<&Item> {
+        match *self {
+            Member::Item(ref item) => Some(item),
+            Member::InnerList(..) => None,
+        }
+    }
+
+    /// Get the member as an `InnerList`, if it is one.
+    pub fn as_inner_list(&self) -> Option<&InnerList> {
+        match *self {
+            Member::InnerList(ref list) => Some(list),
+            Member::Item(..) => None,
+        }
+    }
+}
+
+impl List {
+    /// Parses a `List` from a field value.
+    pub fn parse(raw: &[u8]) -> ::Result<List> {
+        let mut parser = Parser::new(raw);
+        parser.skip_sp();
+        let mut members = Vec::new();
+        while !parser.is_empty() {
+            members.push(try!(parser.member()));
+            if !try!(parser.next_member()) {
+                break;
+            }
+        }
+        Ok(List(members))
+    }
+
+    /// Parses a `List` from the raw lines of a header, as if they were one
+    /// line joined with commas.
+    pub fn from_raw(raw: &[Vec<u8>]) -> ::Result<List> {
+        List::parse(&join(raw))
+    }
+}
+
+impl Dictionary {
+    /// Parses a `Dictionary` from a field value.
+    ///
+    /// A key that appears more than once keeps its first position, and its
+    /// last value.
+    pub fn parse(raw: &[u8]) -> ::Result<Dictionary> {
+        let mut parser = Parser::new(raw);
+        parser.skip_sp();
+        let mut members: Vec<(String, Member)> = Vec::new();
+        while !parser.is_empty() {
+            let key = try!(parser.key());
+            let member = if parser.eat(b'=') {
+                try!(parser.member())
+            } else {
+                Member::Item(Item {
+                    bare: BareItem::Boolean(true),
+                    params: try!(parser.params()),
+                })
+            };
+            insert(&mut members, key, member);
+            if !try!(parser.next_member()) {
+                break;
+            }
+        }
+        Ok(Dictionary(members))
+    }
+
+    /// Parses a `Dictionary` from the raw lines of a header, as if they were
+    /// one line joined with commas.
+    pub fn from_raw(raw: &[Vec<u8>]) -> ::Result<Dictionary> {
+        Dictionary::parse(&join(raw))
+    }
+
+    /// Get the member for a key.
+    pub fn get(&self, key: &str) -> Option<&Member> {
+        self.0.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref member)| member)
+    }
+}
+
+fn param<'a>(params: &'a Parameters, key: &str) -> Option<&'a BareItem> {
+    params.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref value)| value)
+}
+
+fn insert<T>(entries: &mut Vec<(String, T)>, key: String, value: T) {
+    match entries.iter().position(|&(ref k, _)| *k == key) {
+        Some(i) => entries[i].1 = value,
+        None => entries.push((key, value)),
+    }
+}
+
+fn join(raw: &[Vec<u8>]) -> Vec<u8> {
+    let mut joined = Vec::new();
+    for (i, line) in raw.iter().enumerate() {
+        if i != 0 {
+            joined.push(b',');
+        }
+        joined.extend_from_slice(line);
+    }
+    joined
+}
+
+fn is_key_start(b: u8) -> bool {
+    (b >= b'a' && b <= b'z') || b == b'*'
+}
+
+fn is_key_char(b: u8) -> bool {
+    is_key_start(b) || (b >= b'0' && b <= b'9') || b == b'_' || b == b'-' || b == b'.'
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a [u8]) -> Parser<'a> {
+        Parser { input: input, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_sp(&mut self) {
+        while self.peek() == Some(b' ') {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_ows(&mut self) {
+        while self.peek() == Some(b' ') || self.peek() == Some(b'\t') {
+            self.pos += 1;
+        }
+    }
+
+    fn finish(&mut self) -> ::Result<()> {
+        self.skip_sp();
+        if self.is_empty() { Ok(()) } else { Err(::Error::Header) }
+    }
+
+    // After a list or dictionary member: true if another one follows.
+    fn next_member(&mut self) -> ::Result<bool> {
+        self.skip_ows();
+        if self.is_empty() {
+            return Ok(false);
+        }
+        if !self.eat(b',') {
+            return Err(::Error::Header);
+        }
+        self.skip_ows();
+        if self.is_empty() {
+            // a trailing comma
+            return Err(::Error::Header);
+        }
+        Ok(true)
+    }
+
+    fn member(&mut self) -> ::Result<Member> {
+        if self.peek() == Some(b'(') {
+            self.inner_list().map(Member::InnerList)
+        } else {
+            self.item().map(Member::Item)
+        }
+    }
+
+    fn inner_list(&mut self) -> ::Result<InnerList> {
+        if !self.eat(b'(') {
+            return Err(::Error::Header);
+        }
+        let mut items = Vec::new();
+        loop {
+            self.skip_sp();
+            if self.eat(b')') {
+                return Ok(InnerList {
+                    items: items,
+                    params: try!(self.params()),
+                });
+            }
+            items.push(try!(self.item()));
+            match self.peek() {
+                Some(b' ') | Some(b')') => (),
+                _ => return Err(::Error::Header),
+            }
+        }
+    }
+
+    fn item(&mut self) -> ::Result<Item> {
+        let bare = try!(self.bare_item());
+        Ok(Item {
+            bare: bare,
+            params: try!(self.params()),
+        })
+    }
+
+    fn params(&mut self) -> ::Result<Parameters> {
+        let mut params = Vec::new();
+        while self.eat(b';') {
+            self.skip_sp();
+            let key = try!(self.key());
+            let value = if self.eat(b'=') {
+                try!(self.bare_item())
+            } else {
+                BareItem::Boolean(true)
+            };
+            insert(&mut params, key, value);
+        }
+        Ok(params)
+    }
+
+    fn key(&mut self) -> ::Result<String> {
+        let start = self.pos;
+        match self.peek() {
+            Some(b) if is_key_start(b) => self.pos += 1,
+            _ => return Err(::Error::Header),
+        }
+        while self.peek().map_or(false, is_key_char) {
+            self.pos += 1;
+        }
+        Ok(self.slice(start))
+    }
+
+    fn slice(&self, start: usize) -> String {
+        // only ever called over ASCII that was already checked
+        String::from_utf8_lossy(&self.input[start..self.pos]).into_owned()
+    }
+
+    fn bare_item(&mut self) -> ::Result<BareItem> {
+        match self.peek() {
+            Some(b'-') => self.number(),
+            Some(b) if b >= b'0' && b <= b'9' => self.number(),
+            Some(b'"') => self.string(),
+            Some(b) if (b as char).is_ascii_alphabetic() || b == b'*' => self.token(),
+            Some(b':') => self.byte_sequence(),
+            Some(b'?') => self.boolean(),
+            _ => Err(::Error::Header),
+        }
+    }
+
+    fn number(&mut self) -> ::Result<BareItem> {
+        let start = self.pos;
+        self.eat(b'-');
+        let digits_start = self.pos;
+        let mut point = None;
+        loop {
+            match self.peek() {
+                Some(b) if b >= b'0' && b <= b'9' => self.pos += 1,
+                Some(b'.') if point.is_none() => {
+                    if self.pos - digits_start > 12 {
+                        return Err(::Error::Header);
+                    }
+                    point = Some(self.pos);
+                    self.pos += 1;
+                },
+                _ => break,
+            }
+            let len = self.pos - digits_start;
+            if (point.is_none() && len > 15) || (point.is_some() && len > 16) {
+                return Err(::Error::Header);
+            }
+        }
+        if self.pos == digits_start {
+            return Err(::Error::Header);
+        }
+        let text = self.slice(start);
+        match point {
+            None => text.parse().map(BareItem::Integer).map_err(|_| ::Error::Header),
+            Some(point) => {
+                let fraction = self.pos - point - 1;
+                if fraction == 0 || fraction > 3 || point == digits_start {
+                    return Err(::Error::Header);
+                }
+                text.parse().map(BareItem::Decimal).map_err(|_| ::Error::Header)
+            }
+        }
+    }
+
+    fn string(&mut self) -> ::Result<BareItem> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(b) if b == b'"' || b == b'\\' => s.push(b as char),
+                        _ => return Err(::Error::Header),
+                    }
+                },
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(BareItem::String(s));
+                },
+                Some(b) if b >= 0x20 && b < 0x7f => s.push(b as char),
+                _ => return Err(::Error::Header),
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn token(&mut self) -> ::Result<BareItem> {
+        let start = self.pos;
+        self.pos += 1;
+        while self.peek().map_or(false, |b| is_tchar(b) || b == b':' || b == b'/') {
+            self.pos += 1;
+        }
+        Ok(BareItem::Token(self.slice(start)))
+    }
+
+    fn byte_sequence(&mut self) -> ::Result<BareItem> {
+        self.pos += 1;
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some(b':') => break,
+                Some(b) if (b as char).is_ascii_alphanumeric() ||
+                    b == b'+' || b == b'/' || b == b'=' => self.pos += 1,
+                _ => return Err(::Error::Header),
+            }
+        }
+        let encoded = &self.input[start..self.pos];
+        self.pos += 1;
+        encoded.from_base64().map(BareItem::ByteSequence).map_err(|_| ::Error::Header)
+    }
+
+    fn boolean(&mut self) -> ::Result<BareItem> {
+        self.pos += 1;
+        let value = match self.peek() {
+            Some(b'1') => true,
+            Some(b'0') => false,
+            _ => return Err(::Error::Header),
+        };
+        self.pos += 1;
+        Ok(BareItem::Boolean(value))
+    }
+}
+
+impl Display for BareItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BareItem::Integer(n) => {
+                if n < -999_999_999_999_999 || n > 999_999_999_999_999 {
+                    return Err(fmt::Error);
+                }
+                write!(f, "{}", n)
+            },
+            BareItem::Decimal(n) => {
+                let rounded = round_half_even(n * 1000.0) / 1000.0;
+                if !rounded.is_finite() || rounded.abs() >= 1_000_000_000_000.0 {
+                    return Err(fmt::Error);
+                }
+                // at most 3 fractional digits, without trailing zeros
+                let s = format!("{:.3}", rounded);
+                let s = s.trim_right_matches('0');
+                if s.ends_with('.') {
+                    write!(f, "{}0", s)
+                } else {
+                    f.write_str(s)
+                }
+            },
+            BareItem::String(ref s) => {
+                try!(f.write_char('"'));
+                for c in s.chars() {
+                    match c {
+                        '"' | '\\' => {
+                            try!(f.write_char('\\'));
+                            try!(f.write_char(c));
+                        },
+                        ' ' ... '~' => try!(f.write_char(c)),
+                        _ => return Err(fmt::Error),
+                    }
+                }
+                f.write_char('"')
+            },
+            BareItem::Token(ref s) => {
+                let bytes = s.as_bytes();
+                let valid = match bytes.first() {
+                    Some(&b) => (b as char).is_ascii_alphabetic() || b == b'*',
+                    None => false,
+                } && bytes.iter().all(|&b| is_tchar(b) || b == b':' || b == b'/');
+                if !valid {
+                    return Err(fmt::Error);
+                }
+                f.write_str(s)
+            },
+            BareItem::ByteSequence(ref bytes) => write!(f, ":{}:", bytes.to_base64(STANDARD)),
+            BareItem::Boolean(b) => f.write_str(if b { "?1" } else { "?0" }),
+        }
+    }
+}
+
+fn round_half_even(n: f64) -> f64 {
+    let rounded = n.round();
+    if (n - n.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+        rounded - n.signum()
+    } else {
+        rounded
+    }
+}
+
+fn fmt_key(f: &mut fmt::Formatter, key: &str) -> fmt::Result {
+    let bytes = key.as_bytes();
+    let valid = bytes.first().map_or(false, |&b| is_key_start(b)) &&
+        bytes.iter().all(|&b| is_key_char(b));
+    if !valid {
+        return Err(fmt::Error);
+    }
+    f.write_str(key)
+}
+
+fn fmt_params(f: &mut fmt::Formatter, params: &Parameters) -> fmt::Result {
+    for &(ref key, ref value) in params {
+        try!(f.write_char(';'));
+        try!(fmt_key(f, key));
+        if *value != BareItem::Boolean(true) {
+            try!(write!(f, "={}", value));
+        }
+    }
+    Ok(())
+}
+
+impl Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(Display::fmt(&self.bare, f));
+        fmt_params(f, &self.params)
+    }
+}
+
+impl Display for InnerList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(f.write_char('('));
+        for (i, item) in self.items.iter().enumerate() {
+            if i != 0 {
+                try!(f.write_char(' '));
+            }
+            try!(Display::fmt(item, f));
+        }
+        try!(f.write_char(')'));
+        fmt_params(f, &self.params)
+    }
+}
+
+impl Display for Member {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Member::Item(ref item) => Display::fmt(item, f),
+            Member::InnerList(ref list) => Display::fmt(list, f),
+        }
+    }
+}
+
+impl Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, member) in self.0.iter().enumerate() {
+            if i != 0 {
+                try!(f.write_str(", "));
+            }
+            try!(Display::fmt(member, f));
+        }
+        Ok(())
+    }
+}
+
+impl Display for Dictionary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &(ref key, ref member)) in self.0.iter().enumerate() {
+            if i != 0 {
+                try!(f.write_str(", "));
+            }
+            try!(fmt_key(f, key));
+            match *member {
+                Member::Item(Item { bare: BareItem::Boolean(true), ref params }) => {
+                    try!(fmt_params(f, params));
+                },
+                ref member => try!(write!(f, "={}", member)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+    use super::{BareItem, Item, InnerList, Member, List, Dictionary};
+    use super::BareItem::*;
+
+    fn item(raw: &str) -> Option<BareItem> {
+        Item::parse(raw.as_bytes()).ok().map(|item| item.bare)
+    }
+
+    #[test]
+    fn test_parse_bare_items() {
+        assert_eq!(item("42"), Some(Integer(42)));
+        assert_eq!(item("-42"), Some(Integer(-42)));
+        assert_eq!(item("999999999999999"), Some(Integer(999_999_999_999_999)));
+        assert_eq!(item("1000000000000000"), None);
+        assert_eq!(item("4.5"), Some(Decimal(4.5)));
+        assert_eq!(item("-0.125"), Some(Decimal(-0.125)));
+        assert_eq!(item("1.2345"), None);
+        assert_eq!(item("1."), None);
+        assert_eq!(item("1234567890123.0"), None);
+        assert_eq!(item("\"a \\\"b\\\\\""), Some(String("a \"b\\".to_owned())));
+        assert_eq!(item("\"\\n\""), None);
+        assert_eq!(item("\"unterminated"), None);
+        assert_eq!(item("text/html"), Some(Token("text/html".to_owned())));
+        assert_eq!(item("*foo:bar"), Some(Token("*foo:bar".to_owned())));
+        assert_eq!(item(":aGVsbG8=:"), Some(ByteSequence(b"hello".to_vec())));
+        assert_eq!(item(":aGVs bG8=:"), None);
+        assert_eq!(item("?1"), Some(Boolean(true)));
+        assert_eq!(item("?0"), Some(Boolean(false)));
+        assert_eq!(item("?2"), None);
+        assert_eq!(item("  42  "), Some(Integer(42)));
+        assert_eq!(item("42 43"), None);
+        assert_eq!(item(""), None);
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let item = Item::parse(b"abc;a=1;b=?0;c;a=2").unwrap();
+        assert_eq!(item.bare, Token("abc".to_owned()));
+        assert_eq!(item.params, vec![
+            ("a".to_owned(), Integer(2)),
+            ("b".to_owned(), Boolean(false)),
+            ("c".to_owned(), Boolean(true)),
+        ]);
+        assert_eq!(item.param("c"), Some(&Boolean(true)));
+        assert!(Item::parse(b"abc;A=1").is_err());
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let list = List::parse(b"a, (b \"c\");d=1 , ()").unwrap();
+        assert_eq!(list, List(vec![
+            Member::Item(Item::new(Token("a".to_owned()))),
+            Member::InnerList(InnerList {
+                items: vec![
+                    Item::new(Token("b".to_owned())),
+                    Item::new(String("c".to_owned())),
+                ],
+                params: vec![("d".to_owned(), Integer(1))],
+            }),
+            Member::InnerList(InnerList { items: vec![], params: vec![] }),
+        ]));
+        assert_eq!(List::parse(b"").unwrap(), List(vec![]));
+        assert!(List::parse(b"a,").is_err());
+        assert!(List::parse(b"a b").is_err());
+        assert!(List::parse(b"(a b").is_err());
+        assert!(List::parse(b"(a,b)").is_err());
+
+        let raw = vec![b"a".to_vec(), b"b, c".to_vec()];
+        assert_eq!(List::from_raw(&raw).unwrap().0.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_dictionary() {
+        let dict = Dictionary::parse(b"a=1, b, c=(x y);z, a=3").unwrap();
+        assert_eq!(dict.0.iter().map(|&(ref k, _)| &k[..]).collect::<Vec<_>>(),
+                   vec!["a", "b", "c"]);
+        assert_eq!(dict.get("a"), Some(&Member::Item(Item::new(Integer(3)))));
+        assert_eq!(dict.get("b"), Some(&Member::Item(Item::new(Boolean(true)))));
+        assert_eq!(dict.get("c").and_then(|m| m.as_inner_list()).map(|l| l.items.len()),
+                   Some(2));
+        assert!(Dictionary::parse(b"A=1").is_err());
+        assert!(Dictionary::parse(b"a=").is_err());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let raw = "a=1, b;x=?0, c=(\"s\" :AQID: 1.5);y=tok";
+        assert_eq!(Dictionary::parse(raw.as_bytes()).unwrap().to_string(), raw);
+        let raw = "?0, (a b);q, -7;z=\"\\\"\"";
+        assert_eq!(List::parse(raw.as_bytes()).unwrap().to_string(), raw);
+
+        assert_eq!(Decimal(1.0).to_string(), "1.0");
+        assert_eq!(Decimal(0.0025).to_string(), "0.002");
+        assert_eq!(Decimal(0.0035).to_string(), "0.004");
+        assert_eq!(Decimal(-12.3456).to_string(), "-12.346");
+    }
+
+    #[test]
+    fn test_serialize_invalid() {
+        fn fails<T: ::std::fmt::Display>(value: T) -> bool {
+            write!(&mut ::std::string::String::new(), "{}", value).is_err()
+        }
+        assert!(fails(Integer(1_000_000_000_000_000)));
+        assert!(fails(Integer(::std::i64::MIN)));
+        assert!(fails(Decimal(1e12)));
+        assert!(fails(String("caf\u{e9}".to_owned())));
+        assert!(fails(Token("two words".to_owned())));
+        assert!(fails(Token("1abc".to_owned())));
+        assert!(fails(Item { bare: Integer(1), params: vec![("Key".to_owned(), Integer(1))] }));
+    }
+}
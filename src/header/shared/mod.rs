@@ -2,10 +2,12 @@ pub use self::charset::Charset;
 pub use self::encoding::Encoding;
 pub use self::entity::EntityTag;
 pub use self::httpdate::HttpDate;
+pub use self::product::ProductToken;
 pub use self::quality_item::{Quality, QualityItem, qitem, q};
 
 mod charset;
 mod encoding;
 mod entity;
 mod httpdate;
+mod product;
 mod quality_item;
@@ -0,0 +1,133 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use header::parsing::is_token;
+
+/// A product token or comment, the parts of a `User-Agent` or `Server`
+/// value, defined in [RFC7231](https://tools.ietf.org/html/rfc7231#section-5.5.3)
+///
+/// # ABNF
+/// ```plain
+/// product         = token ["/" product-version]
+/// product-version = token
+/// comment         = "(" *( ctext / quoted-pair / comment ) ")"
+/// ```
+///
+/// Comments keep their text as sent, without the outer parentheses, so
+/// nested comments and escapes are left as they are.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProductToken {
+    /// A product name, with an optional version.
+    Product(String, Option<String>),
+    /// A comment.
+    Comment(String),
+}
+
+impl ProductToken {
+    /// Parses a whole `User-Agent` or `Server` value.
+    pub fn parse_all(s: &str) -> ::Result<Vec<ProductToken>> {
+        let mut tokens = Vec::new();
+        let mut rest = s.trim();
+        while !rest.is_empty() {
+            let len = if rest.starts_with('(') {
+                try!(comment_len(rest))
+            } else {
+                rest.find(|c: char| c == ' ' || c == '\t' || c == '(').unwrap_or(rest.len())
+            };
+            tokens.push(try!(rest[..len].parse()));
+            rest = rest[len..].trim_left();
+        }
+        if tokens.is_empty() {
+            return Err(::Error::Header);
+        }
+        Ok(tokens)
+    }
+}
+
+impl Display for ProductToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProductToken::Product(ref name, Some(ref version)) => {
+                write!(f, "{}/{}", name, version)
+            },
+            ProductToken::Product(ref name, None) => f.write_str(name),
+            ProductToken::Comment(ref comment) => write!(f, "({})", comment),
+        }
+    }
+}
+
+impl FromStr for ProductToken {
+    type Err = ::Error;
+    fn from_str(s: &str) -> ::Result<ProductToken> {
+        if s.starts_with('(') {
+            if try!(comment_len(s)) != s.len() {
+                return Err(::Error::Header);
+            }
+            return Ok(ProductToken::Comment(s[1..s.len() - 1].to_owned()));
+        }
+        let mut parts = s.splitn(2, '/');
+        let name = parts.next().unwrap_or("");
+        let version = parts.next();
+        if !is_token(name) || !version.map_or(true, is_token) {
+            return Err(::Error::Header);
+        }
+        Ok(ProductToken::Product(name.to_owned(), version.map(|v| v.to_owned())))
+    }
+}
+
+// the length of the comment at the start of `s`, with its parentheses
+fn comment_len(s: &str) -> ::Result<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, b) in s.bytes().enumerate() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match b {
+            b'\\' => escaped = true,
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i + 1);
+                }
+            },
+            _ => ()
+        }
+    }
+    Err(::Error::Header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProductToken;
+    use super::ProductToken::{Product, Comment};
+
+    fn product(name: &str, version: Option<&str>) -> ProductToken {
+        Product(name.to_owned(), version.map(|v| v.to_owned()))
+    }
+
+    #[test]
+    fn test_parse_all() {
+        let s = "Mozilla/5.0 (X11; Linux x86_64 (nested \\) paren)) Gecko/20100101 curl";
+        let tokens = ProductToken::parse_all(s).unwrap();
+        assert_eq!(tokens, vec![
+            product("Mozilla", Some("5.0")),
+            Comment("X11; Linux x86_64 (nested \\) paren)".to_owned()),
+            product("Gecko", Some("20100101")),
+            product("curl", None),
+        ]);
+        let display = tokens.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" ");
+        assert_eq!(display, s);
+    }
+
+    #[test]
+    fn test_parse_all_invalid() {
+        assert!(ProductToken::parse_all("").is_err());
+        assert!(ProductToken::parse_all("a (unclosed").is_err());
+        assert!(ProductToken::parse_all("a/").is_err());
+        assert!(ProductToken::parse_all("a/b/c").is_err());
+        assert!(ProductToken::parse_all("a\"b").is_err());
+    }
+}
@@ -0,0 +1,1132 @@
+//! A server connection driven by other code.
+//!
+//! `Conn` is the HTTP/1 state machine of a server connection, without any
+//! IO or threads: the bytes read from a connection are passed to
+//! `poll_read`, and the bytes to write to it are taken with `poll_write`.
+//! This lets hyper's parsing and framing run where `Server` can't, such
+//! as in an event loop, or on a device without threads.
+//!
+//! ```
+//! use std::io::Write;
+//! use hyper::header::Headers;
+//! use hyper::http::{Conn, ReadStatus};
+//! use hyper::status::StatusCode;
+//!
+//! let mut conn = Conn::new();
+//! let input = b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n";
+//! match conn.poll_read(input).unwrap() {
+//!     ReadStatus::Complete(len, _) => assert_eq!(len, input.len()),
+//!     other => panic!("{:?}", other)
+//! }
+//! assert_eq!(conn.take_head().unwrap().headers.len(), 1);
+//!
+//! let mut body = conn.write_head(StatusCode::Ok, &mut Headers::new()).unwrap();
+//! body.write_all(b"hello").unwrap();
+//! body.end().unwrap();
+//!
+//! let mut buf = [0; 1024];
+//! let len = conn.poll_write(&mut buf);
+//! assert!(buf[..len].starts_with(b"HTTP/1.1 200 OK\r\n"));
+//! ```
+//!
+//! Instead of copying into a buffer of their own with `poll_write`, callers
+//! can write `pending_write` to the connection as it is, head and body
+//! together in one slice, and then `consume_write` what was written. With a
+//! `BufferPool`, connections that have nothing to write don't hold on to a
+//! buffer, and the buffers of earlier responses are reused.
+//!
+//! An `Observer` set on a `Conn` is told when each request is read, when
+//! its response starts and ends, and when the connection is closed, along
+//! with how long they took and how many bytes they were, to keep metrics or
+//! write an access log:
+//!
+//! ```
+//! use std::sync::Arc;
+//! use hyper::http::{Conn, Observer, RequestInfo};
+//!
+//! #[derive(Debug)]
+//! struct AccessLog;
+//!
+//! impl Observer for AccessLog {
+//!     fn response_complete(&self, req: &RequestInfo) {
+//!         // much like the Common Log Format, with the time taken instead
+//!         // of the client's address and the date
+//!         println!("{:?} \"{} {} {}\" {} {}", req.started.elapsed(), req.method,
+//!                  req.uri, req.version, req.status.map_or(0, |s| s.to_u16()),
+//!                  req.bytes_written);
+//!     }
+//! }
+//!
+//! let mut conn = Conn::new();
+//! conn.set_observer(Some(Arc::new(AccessLog)));
+//! ```
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use time::now_utc;
+
+use Error;
+use header::{self, Headers, ContentLength, TransferEncoding, Encoding, Connection, Upgrade};
+use http::h1::{self, Incoming, ColonWhitespace, CR, LF, LINE_ENDING};
+use http::should_keep_alive;
+use method::Method;
+use status::{StatusCode, StatusClass};
+use uri::RequestUri;
+use version::HttpVersion;
+
+// the most bytes a request head can take
+const MAX_HEAD_SIZE: usize = 8192 + 4096 * 100;
+// the most bytes a chunk size line, or trailer line, can take
+const MAX_LINE_SIZE: usize = 4096;
+// the most requests read ahead of their responses
+const MAX_PIPELINED: usize = 16;
+// the largest buffer put back in a `BufferPool`
+const MAX_POOLED_CAPACITY: usize = 64 * 1024;
+
+/// What `Conn::poll_read` did with the bytes it was given.
+#[derive(Debug, PartialEq)]
+pub enum ReadStatus<'a> {
+    /// The first `usize` bytes were consumed, and the rest should be
+    /// passed again. The slice is the part of them that was request body,
+    /// which is empty when they were the head, or chunk framing.
+    Consumed(usize, &'a [u8]),
+    /// Nothing could be consumed, until more bytes arrive after these.
+    NeedsMore,
+    /// The request ended after the first `usize` bytes, the last of its
+    /// body being the slice.
+    Complete(usize, &'a [u8]),
+}
+
+/// The HTTP/1 state of a server connection, driven by other code.
+///
+/// Requests can be read ahead of the responses to those before them, as
+/// clients pipelining requests send them, and responses are written in
+/// the order the requests came in. A few requests are read ahead at most,
+/// and none after one that closes the connection, or asks to upgrade it,
+/// until its response has ended.
+///
+/// A `101 Switching Protocols` response hands the connection over to the
+/// protocol the client asked to upgrade to: once it has ended, nothing more
+/// is read or written, and `is_upgraded` is true.
+#[derive(Debug)]
+pub struct Conn {
+    colon: ColonWhitespace,
+    reading: Reading,
+    writing: Writing,
+    heads: VecDeque<Incoming<(Method, RequestUri)>>,
+    // the requests read that weren't answered yet, the first being the
+    // one the response being written is to
+    exchanges: VecDeque<Exchange>,
+    upgrade: bool,
+    out: Vec<u8>,
+    // how much of `out` was taken already
+    out_pos: usize,
+    pool: Option<BufferPool>,
+    observer: Option<Arc<Observer>>,
+    info: ConnInfo,
+    // when the first bytes of the head being read were given
+    head_started: Option<Instant>,
+}
+
+/// Told about the requests on a `Conn`, for access logs and metrics.
+///
+/// Each method does nothing unless implemented. They are called from
+/// `Conn`'s methods, so they should be quick.
+pub trait Observer: fmt::Debug + Send + Sync {
+    /// The `Observer` was set on a new connection.
+    fn connection_opened(&self) {}
+
+    /// The head of a request was read.
+    fn request_parsed(&self, _request: &RequestInfo) {}
+
+    /// The head of the response to a request was written.
+    fn response_started(&self, _request: &RequestInfo) {}
+
+    /// The response to a request has ended, and the request was read whole.
+    fn response_complete(&self, _request: &RequestInfo) {}
+
+    /// The `Conn` was dropped, as its connection is closed.
+    fn connection_closed(&self, _conn: &ConnInfo) {}
+}
+
+/// A request read by a `Conn`, and its response so far.
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    /// The method of the request.
+    pub method: Method,
+    /// The target of the request.
+    pub uri: RequestUri,
+    /// The version of the request.
+    pub version: HttpVersion,
+    /// How many requests were read on the connection before this one, so
+    /// that more than 0 means the connection was kept alive for it.
+    pub index: usize,
+    /// When the first bytes of its head were given to `poll_read`.
+    pub started: Instant,
+    /// The bytes of the request consumed so far: its head, and its body
+    /// with any chunk framing.
+    pub bytes_read: u64,
+    /// The status of the response, once its head was written.
+    pub status: Option<StatusCode>,
+    /// When the head of the response was written.
+    pub response_started: Option<Instant>,
+    /// The bytes of the response written so far, head and body.
+    pub bytes_written: u64,
+}
+
+/// A connection driven by a `Conn`, as it is closed.
+#[derive(Debug, Clone)]
+pub struct ConnInfo {
+    /// When the `Conn` was created.
+    pub opened: Instant,
+    /// The number of requests read.
+    pub requests: usize,
+    /// The bytes consumed by `poll_read`.
+    pub bytes_read: u64,
+    /// The bytes written, to be taken with `poll_write`.
+    pub bytes_written: u64,
+    /// Whether the connection was handed over to another protocol.
+    pub upgraded: bool,
+}
+
+/// Buffers for `Conn`s to write into, shared by its clones.
+///
+/// A `Conn` using a pool takes a buffer from it once it has bytes to write,
+/// and puts it back once they have all been taken. Buffers grown past 64KiB,
+/// such as by a large body written at once, are dropped instead.
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    buffers: Arc<Mutex<Vec<Vec<u8>>>>,
+    max_idle: usize,
+}
+
+impl BufferPool {
+    /// Creates a pool that keeps at most `max_idle` buffers that aren't in
+    /// use.
+    pub fn new(max_idle: usize) -> BufferPool {
+        BufferPool {
+            buffers: Arc::new(Mutex::new(Vec::new())),
+            max_idle: max_idle,
+        }
+    }
+
+    /// The number of buffers kept that aren't in use.
+    pub fn idle(&self) -> usize {
+        self.buffers.lock().unwrap().len()
+    }
+
+    fn take(&self) -> Vec<u8> {
+        self.buffers.lock().unwrap().pop().unwrap_or_else(Vec::new)
+    }
+
+    fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() == 0 || buf.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+        buf.clear();
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.max_idle {
+            buffers.push(buf);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Exchange {
+    info: RequestInfo,
+    keep_alive: bool,
+    upgrade: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reading {
+    Head,
+    Sized(u64),
+    Chunked(Chunk),
+    Done,
+    Closed,
+    Upgraded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chunk {
+    Size,
+    Data(u64),
+    DataEnd,
+    Trailer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Writing {
+    Waiting,
+    Head,
+    Sized(u64),
+    Chunked,
+    Empty,
+    Done,
+    Closed,
+    Upgraded,
+}
+
+impl Conn {
+    /// Creates the state of a new connection, which rejects whitespace
+    /// before header colons.
+    pub fn new() -> Conn {
+        Conn::with_colon_whitespace(ColonWhitespace::Reject)
+    }
+
+    /// Creates the state of a new connection, choosing what to do with
+    /// whitespace before header colons.
+    pub fn with_colon_whitespace(colon: ColonWhitespace) -> Conn {
+        Conn {
+            colon: colon,
+            reading: Reading::Head,
+            writing: Writing::Waiting,
+            heads: VecDeque::new(),
+            exchanges: VecDeque::new(),
+            upgrade: false,
+            out: Vec::new(),
+            out_pos: 0,
+            pool: None,
+            observer: None,
+            info: ConnInfo {
+                opened: Instant::now(),
+                requests: 0,
+                bytes_read: 0,
+                bytes_written: 0,
+                upgraded: false,
+            },
+            head_started: None,
+        }
+    }
+
+    /// Sets a pool to take buffers to write into from.
+    pub fn set_buffer_pool(&mut self, pool: Option<BufferPool>) {
+        self.release_out();
+        self.pool = pool;
+    }
+
+    /// Sets an `Observer` to tell about the requests on the connection, and
+    /// tells it the connection was opened.
+    pub fn set_observer(&mut self, observer: Option<Arc<Observer>>) {
+        if let Some(ref observer) = observer {
+            observer.connection_opened();
+        }
+        self.observer = observer;
+    }
+
+    /// Reads from the bytes received on the connection, that weren't
+    /// consumed yet.
+    pub fn poll_read<'a>(&mut self, bytes: &'a [u8]) -> ::Result<ReadStatus<'a>> {
+        match self.reading {
+            Reading::Head => self.read_head(bytes),
+            Reading::Sized(remaining) => {
+                if bytes.is_empty() {
+                    return Ok(ReadStatus::NeedsMore);
+                }
+                let len = min(remaining, bytes.len() as u64);
+                self.reading = Reading::Sized(remaining - len);
+                self.count_read(len as usize);
+                let body = &bytes[..len as usize];
+                if remaining == len {
+                    self.read_done();
+                    Ok(ReadStatus::Complete(body.len(), body))
+                } else {
+                    Ok(ReadStatus::Consumed(body.len(), body))
+                }
+            },
+            Reading::Chunked(chunk) => self.read_chunked(chunk, bytes),
+            Reading::Done | Reading::Closed | Reading::Upgraded => {
+                Ok(ReadStatus::Consumed(0, b""))
+            },
+        }
+    }
+
+    /// Takes the head of the next request read, in the order they were
+    /// read.
+    #[inline]
+    pub fn take_head(&mut self) -> Option<Incoming<(Method, RequestUri)>> {
+        self.heads.pop_front()
+    }
+
+    /// Whether `poll_read` can consume more bytes now.
+    #[inline]
+    pub fn wants_read(&self) -> bool {
+        match self.reading {
+            Reading::Done | Reading::Closed | Reading::Upgraded => false,
+            _ => true
+        }
+    }
+
+    /// Whether there are bytes waiting to be taken with `poll_write`.
+    #[inline]
+    pub fn wants_write(&self) -> bool {
+        self.out_pos < self.out.len()
+    }
+
+    /// Whether the connection should be closed, once the bytes from
+    /// `poll_write` are written.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.reading == Reading::Closed && self.writing == Writing::Closed
+    }
+
+    /// Whether a `101 Switching Protocols` response has ended, so that the
+    /// connection belongs to another protocol.
+    ///
+    /// The bytes from `poll_write` are still to be written first. Bytes
+    /// received after the request, which `poll_read` didn't consume, are
+    /// the start of the new protocol.
+    #[inline]
+    pub fn is_upgraded(&self) -> bool {
+        self.reading == Reading::Upgraded && self.writing == Writing::Upgraded
+    }
+
+    /// Writes the head of the response to the first request read that
+    /// wasn't answered yet, returning its body to write.
+    ///
+    /// Like `server::Response`, a `Date` header is added if missing, and the
+    /// body is chunked unless there is a `Content-Length`.
+    pub fn write_head(&mut self, status: StatusCode, headers: &mut Headers)
+        -> io::Result<ResponseBody> {
+        if self.writing != Writing::Head {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "no response head to write"));
+        }
+        let (method, version) = {
+            let exchange = self.exchanges.front_mut().expect("response without a request");
+            exchange.info.status = Some(status);
+            exchange.info.response_started = Some(Instant::now());
+            self.upgrade = status == StatusCode::SwitchingProtocols;
+            if self.upgrade {
+                // the connection is kept, but not for HTTP
+            } else if !exchange.keep_alive {
+                headers.set(Connection::close());
+            } else if !should_keep_alive(exchange.info.version, headers) {
+                exchange.keep_alive = false;
+            }
+            (exchange.info.method.clone(), exchange.info.version)
+        };
+        if !headers.has::<header::Date>() {
+            headers.set(header::Date(header::HttpDate(now_utc())));
+        }
+
+        self.writing = match status {
+            _ if method == Method::Head => Writing::Empty,
+            StatusCode::NoContent | StatusCode::NotModified => Writing::Empty,
+            c if c.class() == StatusClass::Informational => Writing::Empty,
+            _ => match headers.get::<ContentLength>() {
+                Some(&ContentLength(0)) => Writing::Empty,
+                Some(&ContentLength(len)) => Writing::Sized(len),
+                None => Writing::Chunked,
+            }
+        };
+        if self.writing == Writing::Chunked {
+            let mut encodings = headers.get::<TransferEncoding>()
+                .map_or_else(Vec::new, |te| te.0.clone());
+            encodings.push(Encoding::Chunked);
+            headers.set(TransferEncoding(encodings));
+        }
+
+        debug!("writing head: {:?} {:?}", version, status);
+        let written = {
+            let out = self.out();
+            let start = out.len();
+            try!(write!(out, "{} {}{}{}", version, status, CR as char, LF as char));
+            try!(write!(out, "{}{}", headers, LINE_ENDING));
+            out.len() - start
+        };
+        self.count_written(written);
+        if let (Some(observer), Some(exchange)) = (self.observer.as_ref(), self.exchanges.front()) {
+            observer.response_started(&exchange.info);
+        }
+        Ok(ResponseBody {
+            conn: self,
+            ended: false,
+        })
+    }
+
+    fn write_body(&mut self, msg: &[u8]) -> io::Result<usize> {
+        let start = self.out.len();
+        let res = match self.writing {
+            Writing::Chunked => {
+                if !msg.is_empty() {
+                    let out = self.out();
+                    try!(write!(out, "{:X}{}", msg.len(), LINE_ENDING));
+                    out.extend_from_slice(msg);
+                    out.extend_from_slice(LINE_ENDING.as_bytes());
+                }
+                Ok(msg.len())
+            },
+            Writing::Sized(remaining) => {
+                let len = min(remaining, msg.len() as u64);
+                self.out().extend_from_slice(&msg[..len as usize]);
+                self.writing = Writing::Sized(remaining - len);
+                Ok(len as usize)
+            },
+            Writing::Empty => {
+                if !msg.is_empty() {
+                    error!("Cannot include a body with this kind of message");
+                }
+                Ok(0)
+            },
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "no response body to write"))
+        };
+        let written = self.out.len() - start;
+        self.count_written(written);
+        res
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        match self.writing {
+            Writing::Chunked => {
+                self.out().extend_from_slice(b"0\r\n\r\n");
+                self.count_written(5);
+            },
+            Writing::Sized(0) | Writing::Empty => (),
+            Writing::Sized(remaining) => {
+                // the client would wait for the rest
+                debug!("response ended {} bytes short", remaining);
+                if let Some(exchange) = self.exchanges.front_mut() {
+                    exchange.keep_alive = false;
+                }
+            },
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no response to end"))
+        }
+        self.writing = Writing::Done;
+        self.next();
+        Ok(())
+    }
+
+    /// Takes bytes to write to the connection into `buf`, returning how
+    /// many.
+    pub fn poll_write(&mut self, buf: &mut [u8]) -> usize {
+        let len = min(buf.len(), self.pending_write().len());
+        buf[..len].copy_from_slice(&self.pending_write()[..len]);
+        self.consume_write(len);
+        len
+    }
+
+    /// The bytes waiting to be written to the connection, which can be
+    /// written from here instead of being taken with `poll_write`.
+    #[inline]
+    pub fn pending_write(&self) -> &[u8] {
+        &self.out[self.out_pos..]
+    }
+
+    /// Marks the first `len` bytes of `pending_write` as written.
+    pub fn consume_write(&mut self, len: usize) {
+        self.out_pos = min(self.out_pos + len, self.out.len());
+        if self.out_pos == self.out.len() {
+            self.out.clear();
+            self.out_pos = 0;
+            self.release_out();
+        } else if self.out_pos > self.out.len() / 2 {
+            // a writer that never quite catches up would otherwise grow the
+            // buffer without end
+            self.out.drain(..self.out_pos);
+            self.out_pos = 0;
+        }
+    }
+
+    // The buffer to write into, taken from the pool if there is one.
+    fn out(&mut self) -> &mut Vec<u8> {
+        if self.out.capacity() == 0 {
+            if let Some(ref pool) = self.pool {
+                self.out = pool.take();
+            }
+        }
+        &mut self.out
+    }
+
+    // Gives the buffer back to the pool, once everything in it was taken.
+    fn release_out(&mut self) {
+        if self.out.is_empty() {
+            if let Some(ref pool) = self.pool {
+                pool.put(mem::replace(&mut self.out, Vec::new()));
+            }
+        }
+    }
+
+    fn read_head<'a>(&mut self, bytes: &'a [u8]) -> ::Result<ReadStatus<'a>> {
+        if self.head_started.is_none() && !bytes.is_empty() {
+            self.head_started = Some(Instant::now());
+        }
+        let (head, len) = match try!(h1::try_parse_request(bytes, self.colon)) {
+            Some(parsed) => parsed,
+            None if bytes.len() >= MAX_HEAD_SIZE => return Err(Error::TooLarge),
+            None => return Ok(ReadStatus::NeedsMore)
+        };
+        debug!("Request Line: {:?} {:?}", head.subject, head.version);
+
+        let (ref method, _) = head.subject;
+        self.reading = if *method == Method::Get || *method == Method::Head {
+            Reading::Done
+        } else if let Some(&TransferEncoding(ref codings)) = head.headers.get() {
+            if codings.last() == Some(&Encoding::Chunked) {
+                Reading::Chunked(Chunk::Size)
+            } else {
+                Reading::Done
+            }
+        } else {
+            match head.headers.get::<ContentLength>() {
+                Some(&ContentLength(0)) | None => Reading::Done,
+                Some(&ContentLength(len)) => Reading::Sized(len),
+            }
+        };
+        let info = RequestInfo {
+            method: method.clone(),
+            uri: head.subject.1.clone(),
+            version: head.version,
+            index: self.info.requests,
+            started: self.head_started.take().unwrap_or_else(Instant::now),
+            bytes_read: len as u64,
+            status: None,
+            response_started: None,
+            bytes_written: 0,
+        };
+        self.info.requests += 1;
+        self.info.bytes_read += len as u64;
+        if let Some(ref observer) = self.observer {
+            observer.request_parsed(&info);
+        }
+        self.exchanges.push_back(Exchange {
+            info: info,
+            keep_alive: should_keep_alive(head.version, &head.headers),
+            upgrade: head.headers.has::<Upgrade>() || *method == Method::Connect,
+        });
+        if self.writing == Writing::Waiting {
+            self.writing = Writing::Head;
+        }
+        self.heads.push_back(head);
+
+        if self.reading == Reading::Done {
+            self.read_done();
+            Ok(ReadStatus::Complete(len, b""))
+        } else {
+            Ok(ReadStatus::Consumed(len, b""))
+        }
+    }
+
+    fn read_chunked<'a>(&mut self, chunk: Chunk, bytes: &'a [u8]) -> ::Result<ReadStatus<'a>> {
+        match chunk {
+            Chunk::Size | Chunk::Trailer => {
+                let end = match bytes.iter().position(|&b| b == LF) {
+                    Some(end) => end,
+                    None if bytes.len() >= MAX_LINE_SIZE => return Err(invalid_chunk()),
+                    None => return Ok(ReadStatus::NeedsMore)
+                };
+                let mut line = &bytes[..end];
+                if line.last() == Some(&CR) {
+                    line = &line[..line.len() - 1];
+                }
+                self.count_read(end + 1);
+                if chunk == Chunk::Trailer {
+                    if line.is_empty() {
+                        self.read_done();
+                        return Ok(ReadStatus::Complete(end + 1, b""));
+                    }
+                    trace!("ignoring trailer {:?}", String::from_utf8_lossy(line));
+                } else {
+                    let size = try!(parse_chunk_size(line));
+                    trace!("chunk size={:?}", size);
+                    self.reading = Reading::Chunked(if size == 0 {
+                        Chunk::Trailer
+                    } else {
+                        Chunk::Data(size)
+                    });
+                }
+                Ok(ReadStatus::Consumed(end + 1, b""))
+            },
+            Chunk::Data(remaining) => {
+                if bytes.is_empty() {
+                    return Ok(ReadStatus::NeedsMore);
+                }
+                let len = min(remaining, bytes.len() as u64);
+                self.count_read(len as usize);
+                self.reading = Reading::Chunked(if remaining == len {
+                    Chunk::DataEnd
+                } else {
+                    Chunk::Data(remaining - len)
+                });
+                let body = &bytes[..len as usize];
+                Ok(ReadStatus::Consumed(body.len(), body))
+            },
+            Chunk::DataEnd => {
+                if bytes.len() < 2 {
+                    return Ok(ReadStatus::NeedsMore);
+                }
+                if &bytes[..2] != LINE_ENDING.as_bytes() {
+                    return Err(invalid_chunk());
+                }
+                self.reading = Reading::Chunked(Chunk::Size);
+                self.count_read(2);
+                Ok(ReadStatus::Consumed(2, b""))
+            }
+        }
+    }
+
+    // Counts bytes of the body of the request being read, framing and all.
+    fn count_read(&mut self, len: usize) {
+        self.info.bytes_read += len as u64;
+        if let Some(exchange) = self.exchanges.back_mut() {
+            exchange.info.bytes_read += len as u64;
+        }
+    }
+
+    // Counts bytes of the response being written.
+    fn count_written(&mut self, len: usize) {
+        self.info.bytes_written += len as u64;
+        if let Some(exchange) = self.exchanges.front_mut() {
+            exchange.info.bytes_written += len as u64;
+        }
+    }
+
+    fn read_done(&mut self) {
+        self.reading = if self.reads_ahead() {
+            Reading::Head
+        } else {
+            Reading::Done
+        };
+        self.next();
+    }
+
+    // Whether the next request can be read before the responses to those
+    // read so far.
+    fn reads_ahead(&self) -> bool {
+        match self.exchanges.back() {
+            Some(last) => {
+                last.keep_alive && !last.upgrade && self.exchanges.len() < MAX_PIPELINED
+            },
+            None => true
+        }
+    }
+
+    // Moves on to the next response, once both halves of the first request
+    // not answered yet are done.
+    fn next(&mut self) {
+        let reading_first = self.exchanges.len() == 1 && match self.reading {
+            Reading::Sized(..) | Reading::Chunked(..) => true,
+            _ => false
+        };
+        if self.writing != Writing::Done || reading_first {
+            return;
+        }
+        let answered = self.exchanges.pop_front().expect("response without a request");
+        if let Some(ref observer) = self.observer {
+            observer.response_complete(&answered.info);
+        }
+        if !self.upgrade && answered.keep_alive {
+            self.writing = if self.exchanges.is_empty() {
+                Writing::Waiting
+            } else {
+                Writing::Head
+            };
+            if self.reading == Reading::Done && self.reads_ahead() {
+                self.reading = Reading::Head;
+            }
+            return;
+        }
+
+        if self.upgrade {
+            debug!("connection upgraded");
+            self.reading = Reading::Upgraded;
+            self.writing = Writing::Upgraded;
+        } else {
+            debug!("closing connection");
+            self.reading = Reading::Closed;
+            self.writing = Writing::Closed;
+        }
+        // requests read after this one won't be answered
+        self.exchanges.clear();
+        self.heads.clear();
+    }
+}
+
+/// The body of a response being written to a `Conn`, made by
+/// `Conn::write_head`.
+///
+/// It holds on to the `Conn` until it is ended, so that no other head, nor
+/// more body, can be written out of turn. Dropping it ends it too.
+#[derive(Debug)]
+pub struct ResponseBody<'a> {
+    conn: &'a mut Conn,
+    ended: bool,
+}
+
+impl<'a> ResponseBody<'a> {
+    /// Takes bytes written so far into `buf`, like `Conn::poll_write`, so a
+    /// long body doesn't have to be buffered whole.
+    #[inline]
+    pub fn poll_write(&mut self, buf: &mut [u8]) -> usize {
+        self.conn.poll_write(buf)
+    }
+
+    /// The bytes written so far, like `Conn::pending_write`.
+    #[inline]
+    pub fn pending_write(&self) -> &[u8] {
+        self.conn.pending_write()
+    }
+
+    /// Marks bytes of `pending_write` as written, like
+    /// `Conn::consume_write`.
+    #[inline]
+    pub fn consume_write(&mut self, len: usize) {
+        self.conn.consume_write(len)
+    }
+
+    /// Ends the response, after which the next request can be read, unless
+    /// the connection is closing.
+    pub fn end(mut self) -> io::Result<()> {
+        self.ended = true;
+        self.conn.end()
+    }
+}
+
+impl<'a> Write for ResponseBody<'a> {
+    #[inline]
+    fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
+        self.conn.write_body(msg)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Drop for ResponseBody<'a> {
+    fn drop(&mut self) {
+        if !self.ended {
+            if let Err(e) = self.conn.end() {
+                debug!("error ending response: {:?}", e);
+            }
+        }
+    }
+}
+
+impl Default for Conn {
+    fn default() -> Conn {
+        Conn::new()
+    }
+}
+
+impl Drop for Conn {
+    fn drop(&mut self) {
+        // what wasn't written is dropped with the connection
+        self.out.clear();
+        self.release_out();
+        self.info.upgraded = self.is_upgraded();
+        if let Some(ref observer) = self.observer {
+            observer.connection_closed(&self.info);
+        }
+    }
+}
+
+fn parse_chunk_size(line: &[u8]) -> ::Result<u64> {
+    let size = match line.iter().position(|&b| b == b';') {
+        Some(ext) => &line[..ext],
+        None => line
+    };
+    let size = match ::std::str::from_utf8(size) {
+        Ok(size) => size.trim_right_matches(|c| c == ' ' || c == '\t'),
+        Err(_) => return Err(invalid_chunk())
+    };
+    if size.is_empty() {
+        return Err(invalid_chunk());
+    }
+    u64::from_str_radix(size, 16).map_err(|_| invalid_chunk())
+}
+
+fn invalid_chunk() -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidInput, "Invalid chunk size line"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use header::{Headers, ContentLength};
+    use method::Method;
+    use status::StatusCode;
+
+    use super::{BufferPool, Conn, ConnInfo, Observer, ReadStatus, RequestInfo};
+
+    // feeds `input` a few bytes at a time, like it arrived from a socket,
+    // returning the request body
+    fn read_all(conn: &mut Conn, input: &[u8], step: usize) -> Vec<u8> {
+        let mut body = Vec::new();
+        let mut buf = Vec::new();
+        let mut fed = 0;
+        loop {
+            match conn.poll_read(&buf).unwrap() {
+                ReadStatus::Consumed(len, part) => {
+                    body.extend_from_slice(part);
+                    buf.drain(..len);
+                },
+                ReadStatus::Complete(len, part) => {
+                    body.extend_from_slice(part);
+                    buf.drain(..len);
+                    assert!(buf.is_empty());
+                    return body;
+                },
+                ReadStatus::NeedsMore => {
+                    assert!(fed < input.len(), "ran out of input");
+                    let end = ::std::cmp::min(fed + step, input.len());
+                    buf.extend_from_slice(&input[fed..end]);
+                    fed = end;
+                }
+            }
+        }
+    }
+
+    fn written(conn: &mut Conn) -> String {
+        let mut out = Vec::new();
+        let mut buf = [0; 7];
+        while conn.wants_write() {
+            let len = conn.poll_write(&mut buf);
+            out.extend_from_slice(&buf[..len]);
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_chunked_request() {
+        let mut conn = Conn::new();
+        let body = read_all(&mut conn, b"\
+            POST /upload HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n\
+            3;ext=1\r\n\
+            foo\r\n\
+            4\r\n\
+            \x20bar\r\n\
+            0\r\n\
+            Trailer: x\r\n\
+            \r\n\
+        ", 3);
+        assert_eq!(body, b"foo bar");
+        assert_eq!(conn.take_head().unwrap().subject.0, Method::Post);
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        let mut conn = Conn::new();
+        let body = read_all(&mut conn, b"\
+            PUT / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Content-Length: 5\r\n\
+            \r\n\
+            hello\
+        ", 4);
+        assert_eq!(body, b"hello");
+
+        let mut headers = Headers::new();
+        headers.set(ContentLength(2));
+        {
+            let mut body = conn.write_head(StatusCode::Created, &mut headers).unwrap();
+            assert_eq!(body.write(b"okay").unwrap(), 2);
+            body.end().unwrap();
+        }
+        assert!(conn.write_head(StatusCode::Ok, &mut Headers::new()).is_err());
+        let res = written(&mut conn);
+        assert!(res.starts_with("HTTP/1.1 201 Created\r\n"), "{:?}", res);
+        assert!(res.ends_with("\r\n\r\nok"), "{:?}", res);
+
+        // the next request can be read, and this one closes the connection
+        assert!(conn.wants_read());
+        read_all(&mut conn, b"HEAD / HTTP/1.0\r\n\r\n", 100);
+        // dropping the body ends it
+        conn.write_head(StatusCode::Ok, &mut Headers::new()).unwrap();
+        let res = written(&mut conn);
+        assert!(res.starts_with("HTTP/1.0 200 OK\r\n"), "{:?}", res);
+        assert!(res.contains("Connection: close\r\n"));
+        assert!(res.ends_with("\r\n\r\n"));
+        assert!(conn.is_closed());
+    }
+
+    #[test]
+    fn test_pipelining() {
+        let mut conn = Conn::new();
+        let input = b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\nConnection: close\r\n\r\n\
+                      GET /c HTTP/1.1\r\n\r\n";
+        let mut read = 0;
+        for _ in 0..2 {
+            match conn.poll_read(&input[read..]).unwrap() {
+                ReadStatus::Complete(len, b"") => read += len,
+                other => panic!("{:?}", other)
+            }
+        }
+        // nothing is read after a request closing the connection
+        assert!(!conn.wants_read());
+        assert_eq!(conn.poll_read(&input[read..]).unwrap(), ReadStatus::Consumed(0, b""));
+
+        let paths = (0..2).map(|_| conn.take_head().unwrap().subject.1.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/a", "/b"]);
+        assert!(conn.take_head().is_none());
+
+        let mut headers = Headers::new();
+        headers.set(ContentLength(1));
+        conn.write_head(StatusCode::Ok, &mut headers).unwrap().write_all(b"a").unwrap();
+        conn.write_head(StatusCode::Ok, &mut headers).unwrap().write_all(b"b").unwrap();
+        assert!(conn.write_head(StatusCode::Ok, &mut headers).is_err());
+        let res = written(&mut conn);
+        assert_eq!(res.matches("HTTP/1.1 200 OK\r\n").count(), 2, "{:?}", res);
+        assert!(res.ends_with("\r\n\r\nb"), "{:?}", res);
+        assert!(res.contains("Connection: close\r\n"));
+        assert!(conn.is_closed());
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let mut conn = Conn::new();
+        let input = b"GET /chat HTTP/1.1\r\nHost: example.domain\r\n\
+                      Connection: Upgrade\r\nUpgrade: websocket\r\n\r\n\x81\x00";
+        let len = match conn.poll_read(input).unwrap() {
+            ReadStatus::Complete(len, b"") => len,
+            other => panic!("{:?}", other)
+        };
+        assert!(!conn.is_upgraded());
+
+        let mut headers = Headers::new();
+        headers.set_raw("Connection", vec![b"Upgrade".to_vec()]);
+        headers.set_raw("Upgrade", vec![b"websocket".to_vec()]);
+        conn.write_head(StatusCode::SwitchingProtocols, &mut headers).unwrap();
+        let res = written(&mut conn);
+        assert!(res.starts_with("HTTP/1.1 101 Switching Protocols\r\n"), "{:?}", res);
+        assert!(!res.contains("close"));
+
+        // the frame after the head is left for the new protocol
+        assert!(conn.is_upgraded());
+        assert!(!conn.wants_read());
+        assert!(!conn.is_closed());
+        assert_eq!(&input[len..], b"\x81\x00");
+        assert_eq!(conn.poll_read(&input[len..]).unwrap(), ReadStatus::Consumed(0, b""));
+        assert!(conn.write_head(StatusCode::Ok, &mut Headers::new()).is_err());
+    }
+
+    #[test]
+    fn test_buffer_pool() {
+        let pool = BufferPool::new(1);
+        let mut conns = (0..2).map(|_| {
+            let mut conn = Conn::new();
+            conn.set_buffer_pool(Some(pool.clone()));
+            read_all(&mut conn, b"GET / HTTP/1.1\r\n\r\n", 100);
+            conn
+        }).collect::<Vec<_>>();
+
+        let mut headers = Headers::new();
+        headers.set(ContentLength(5));
+        for conn in &mut conns {
+            conn.write_head(StatusCode::Ok, &mut headers).unwrap().write_all(b"hello").unwrap();
+        }
+        assert_eq!(pool.idle(), 0);
+
+        // written straight from the buffer, which is put back once empty
+        let len = conns[0].pending_write().len();
+        assert!(conns[0].pending_write().ends_with(b"\r\n\r\nhello"));
+        conns[0].consume_write(len - 5);
+        assert_eq!(conns[0].pending_write(), b"hello");
+        assert_eq!(pool.idle(), 0);
+        // what was written is dropped once it is most of the buffer
+        assert_eq!(conns[0].out.len(), 5);
+        conns[0].consume_write(5);
+        assert!(!conns[0].wants_write());
+        assert_eq!(pool.idle(), 1);
+
+        // the next response reuses it
+        read_all(&mut conns[0], b"GET / HTTP/1.1\r\n\r\n", 100);
+        conns[0].write_head(StatusCode::Ok, &mut headers).unwrap().write_all(b"again").unwrap();
+        assert_eq!(pool.idle(), 0);
+        assert!(written(&mut conns[0]).ends_with("again"));
+        assert!(written(&mut conns[1]).ends_with("hello"));
+        // only one idle buffer is kept
+        assert_eq!(pool.idle(), 1);
+        drop(conns);
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn test_invalid_chunk() {
+        let mut conn = Conn::new();
+        let head = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+        match conn.poll_read(head).unwrap() {
+            ReadStatus::Consumed(len, b"") => assert_eq!(len, head.len()),
+            other => panic!("{:?}", other)
+        }
+        assert!(conn.poll_read(b"X\r\n").is_err());
+    }
+
+    #[derive(Debug, Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Observer for Recorder {
+        fn connection_opened(&self) {
+            self.0.lock().unwrap().push("opened".to_owned());
+        }
+
+        fn request_parsed(&self, req: &RequestInfo) {
+            self.0.lock().unwrap().push(format!("parsed {} {} #{}", req.method, req.uri,
+                                                req.index));
+        }
+
+        fn response_started(&self, req: &RequestInfo) {
+            assert!(req.response_started.is_some());
+            self.0.lock().unwrap().push(format!("started {}", req.status.unwrap()));
+        }
+
+        fn response_complete(&self, req: &RequestInfo) {
+            self.0.lock().unwrap().push(format!("complete {} {} {}", req.uri, req.bytes_read,
+                                                req.bytes_written));
+        }
+
+        fn connection_closed(&self, conn: &ConnInfo) {
+            self.0.lock().unwrap().push(format!("closed {} {} {}", conn.requests,
+                                                conn.bytes_read, conn.bytes_written));
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let recorder = Arc::new(Recorder::default());
+        let mut conn = Conn::new();
+        conn.set_observer(Some(recorder.clone()));
+
+        let first = b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                      5\r\nhello\r\n0\r\n\r\n";
+        read_all(&mut conn, first, 5);
+        let mut headers = Headers::new();
+        headers.set(ContentLength(2));
+        conn.write_head(StatusCode::Created, &mut headers).unwrap().write_all(b"ok").unwrap();
+        let res_len = written(&mut conn).len();
+
+        let second = b"GET /b HTTP/1.1\r\nConnection: close\r\n\r\n";
+        read_all(&mut conn, second, 100);
+        {
+            let mut body = conn.write_head(StatusCode::Ok, &mut Headers::new()).unwrap();
+            body.write_all(b"hi").unwrap();
+        }
+        let second_len = written(&mut conn).len();
+        drop(conn);
+
+        let events = recorder.0.lock().unwrap().clone();
+        assert_eq!(events, vec![
+            "opened".to_owned(),
+            "parsed POST /a #0".to_owned(),
+            "started 201 Created".to_owned(),
+            format!("complete /a {} {}", first.len(), res_len),
+            "parsed GET /b #1".to_owned(),
+            "started 200 OK".to_owned(),
+            format!("complete /b {} {}", second.len(), second_len),
+            format!("closed 2 {} {}", first.len() + second.len(), res_len + second_len),
+        ]);
+    }
+}
@@ -0,0 +1,171 @@
+//! Using HTTP/2 with origins that speak it, and HTTP/1.1 with the rest.
+//!
+//! A `FallbackProtocol` first tries HTTP/2 with prior knowledge, and if the
+//! server doesn't answer the connection preface, it sends the request with
+//! HTTP/1.1 instead. What worked for each origin is kept in a
+//! `ProtocolCache`, so that later requests to an origin that only speaks
+//! HTTP/1.1 don't try HTTP/2 again first.
+//!
+//! ```no_run
+//! use hyper::Client;
+//! use hyper::http::{h1, h2};
+//! use hyper::http::fallback::FallbackProtocol;
+//!
+//! let client = Client::with_protocol(FallbackProtocol::new(
+//!     h2::new_protocol(),
+//!     h1::Http11Protocol::with_connector(hyper::net::HttpConnector)));
+//! ```
+//!
+//! hyper has no ALPN support, so the protocol of an origin is only learned
+//! by trying HTTP/2 on a fresh connection. Only the HTTP/1.1 connections
+//! are kept in a `Pool`, so it holds connections of a single protocol.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use http::{HttpMessage, Protocol};
+use Error;
+
+/// The protocol an origin is known to speak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preference {
+    /// HTTP/2 with prior knowledge worked.
+    Http2,
+    /// HTTP/2 with prior knowledge failed, so HTTP/1.1 is used.
+    Http11,
+}
+
+/// The protocols origins are known to speak.
+///
+/// A cache can be shared by several `FallbackProtocol`s.
+#[derive(Debug, Default)]
+pub struct ProtocolCache {
+    origins: Mutex<HashMap<Key, Preference>>,
+}
+
+type Key = (String, u16, String);
+
+impl ProtocolCache {
+    /// Creates an empty `ProtocolCache`.
+    pub fn new() -> ProtocolCache {
+        ProtocolCache::default()
+    }
+
+    /// Get the protocol an origin is known to speak, if it is known.
+    pub fn get(&self, host: &str, port: u16, scheme: &str) -> Option<Preference> {
+        self.origins.lock().unwrap().get(&key(host, port, scheme)).cloned()
+    }
+
+    /// Records the protocol an origin speaks.
+    pub fn set(&self, host: &str, port: u16, scheme: &str, preference: Preference) {
+        self.origins.lock().unwrap().insert(key(host, port, scheme), preference);
+    }
+
+    /// Forgets the protocol of an origin, so HTTP/2 is tried again.
+    pub fn forget(&self, host: &str, port: u16, scheme: &str) {
+        self.origins.lock().unwrap().remove(&key(host, port, scheme));
+    }
+
+    /// Forgets the protocols of every origin.
+    pub fn clear(&self) {
+        self.origins.lock().unwrap().clear();
+    }
+}
+
+fn key(host: &str, port: u16, scheme: &str) -> Key {
+    (host.to_owned(), port, scheme.to_owned())
+}
+
+/// A `Protocol` that tries HTTP/2 first, and falls back to HTTP/1.1.
+///
+/// The HTTP/2 protocol has failed when it returns an `Error::Http2`. Other
+/// errors, such as failing to connect at all, are returned as they are.
+#[derive(Debug)]
+pub struct FallbackProtocol<H2, H1> {
+    h2: H2,
+    h1: H1,
+    cache: Arc<ProtocolCache>,
+}
+
+impl<H2: Protocol, H1: Protocol> FallbackProtocol<H2, H1> {
+    /// Creates a `FallbackProtocol`, with its own cache.
+    pub fn new(h2: H2, h1: H1) -> FallbackProtocol<H2, H1> {
+        FallbackProtocol::with_cache(h2, h1, Arc::new(ProtocolCache::new()))
+    }
+
+    /// Creates a `FallbackProtocol` using a shared cache.
+    pub fn with_cache(h2: H2, h1: H1, cache: Arc<ProtocolCache>) -> FallbackProtocol<H2, H1> {
+        FallbackProtocol {
+            h2: h2,
+            h1: h1,
+            cache: cache,
+        }
+    }
+
+    /// Get the cache of the protocols origins speak.
+    pub fn cache(&self) -> &Arc<ProtocolCache> {
+        &self.cache
+    }
+}
+
+impl<H2: Protocol, H1: Protocol> Protocol for FallbackProtocol<H2, H1> {
+    fn new_message(&self, host: &str, port: u16, scheme: &str) -> ::Result<Box<HttpMessage>> {
+        if self.cache.get(host, port, scheme) == Some(Preference::Http11) {
+            return self.h1.new_message(host, port, scheme);
+        }
+        match self.h2.new_message(host, port, scheme) {
+            Ok(msg) => {
+                self.cache.set(host, port, scheme, Preference::Http2);
+                Ok(msg)
+            },
+            Err(Error::Http2(e)) => {
+                debug!("HTTP/2 failed for {}:{}, using HTTP/1.1: {:?}", host, port, e);
+                self.cache.set(host, port, scheme, Preference::Http11);
+                self.h1.new_message(host, port, scheme)
+            },
+            Err(e) => Err(e)
+        }
+    }
+
+    fn close_idle(&self) {
+        self.h2.close_idle();
+        self.h1.close_idle();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Headers;
+    use http::Protocol;
+    use http::h1::{Http11Message, Http11Protocol};
+    use http::h2::{Http2Message, Http2Protocol};
+    use mock::{CloneableMockStream, MockConnector, MockHttp2Connector, MockStream};
+
+    use super::{FallbackProtocol, Preference};
+
+    #[test]
+    fn test_fallback() {
+        let mut h2 = MockHttp2Connector::new();
+        h2.new_response_stream(b"200", &Headers::new(), None);
+        h2.add_stream(CloneableMockStream::with_stream(
+            MockStream::with_input(b"HTTP/1.1 400 Bad Request\r\n\r\n")));
+        let protocol = FallbackProtocol::new(Http2Protocol::with_connector(h2),
+                                             Http11Protocol::with_connector(MockConnector));
+
+        let msg = protocol.new_message("h2.example.domain", 80, "http").unwrap();
+        assert!(msg.is::<Http2Message<CloneableMockStream>>());
+        assert_eq!(protocol.cache().get("h2.example.domain", 80, "http"),
+                   Some(Preference::Http2));
+
+        let msg = protocol.new_message("h1.example.domain", 80, "http").unwrap();
+        assert!(msg.is::<Http11Message>());
+        assert_eq!(protocol.cache().get("h1.example.domain", 80, "http"),
+                   Some(Preference::Http11));
+
+        // HTTP/2 isn't tried again, since the connector has no more streams
+        let msg = protocol.new_message("h1.example.domain", 80, "http").unwrap();
+        assert!(msg.is::<Http11Message>());
+
+        protocol.cache().forget("h1.example.domain", 80, "http");
+        assert_eq!(protocol.cache().get("h1.example.domain", 80, "http"), None);
+    }
+}
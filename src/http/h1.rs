@@ -1,10 +1,14 @@
 //! Adapts the HTTP/1.1 implementation into the `HttpMessage` API.
 use std::borrow::Cow;
 use std::cmp::min;
+use std::error::Error as StdError;
 use std::fmt;
 use std::io::{self, Write, BufWriter, BufRead, Read};
+use std::mem;
 use std::net::Shutdown;
 #[cfg(feature = "timeouts")]
+use std::cell::Cell;
+#[cfg(feature = "timeouts")]
 use std::time::Duration;
 
 use httparse;
@@ -12,9 +16,10 @@ use httparse;
 use buffer::BufReader;
 use Error;
 use header::{Headers, ContentLength, TransferEncoding};
+use header::parsing::is_tchar;
 use header::Encoding::Chunked;
 use method::{Method};
-use net::{NetworkConnector, NetworkStream};
+use net::{AbortHandle, ConnectInfo, NetworkConnector, NetworkStream};
 use status::StatusCode;
 use version::HttpVersion;
 use version::HttpVersion::{Http10, Http11};
@@ -93,6 +98,19 @@ impl Stream {
 pub struct Http11Message {
     method: Option<Method>,
     stream: Wrapper<Stream>,
+    trailers: Option<Headers>,
+    // Bytes of the response read while waiting for `100 Continue`.
+    pending: Vec<u8>,
+    limits: Limits,
+    // bytes of the response body read so far
+    body_read: u64,
+    // whether to keep the raw head of the response, for a request whose
+    // raw head was sent
+    keep_raw_head: bool,
+    raw_head: Option<Vec<u8>>,
+    connect_info: Option<ConnectInfo>,
+    #[cfg(feature = "timeouts")]
+    read_timeout: Cell<Option<Duration>>,
 }
 
 impl Write for Http11Message {
@@ -120,7 +138,15 @@ impl Read for Http11Message {
         match self.stream.as_mut().reader_mut() {
             None => Err(io::Error::new(io::ErrorKind::Other,
                                           "Not in a readable state")),
-            Some(ref mut reader) => reader.read(buf),
+            Some(ref mut reader) => {
+                let n = try!(reader.read(buf));
+                if n == 0 && !buf.is_empty() && self.trailers.is_none() {
+                    self.trailers = Some(try!(reader.read_trailers()));
+                }
+                self.body_read += n as u64;
+                try!(self.limits.check_body_read(self.body_read));
+                Ok(n)
+            },
         }
     }
 }
@@ -141,13 +167,20 @@ impl HttpMessage for Http11Message {
                     return stream;
                 },
             };
+            // a proxy is sent the whole url, and headers of its own
+            let proxy_headers = stream.proxy_headers().cloned();
             let mut stream = BufWriter::new(stream);
 
-            let mut uri = head.url.serialize_path().unwrap();
-            if let Some(ref q) = head.url.query {
-                uri.push('?');
-                uri.push_str(&q[..]);
-            }
+            let uri = if proxy_headers.is_some() {
+                head.url.serialize_no_fragment()
+            } else {
+                let mut uri = head.url.serialize_path().unwrap();
+                if let Some(ref q) = head.url.query {
+                    uri.push('?');
+                    uri.push_str(&q[..]);
+                }
+                uri
+            };
 
             let version = version::HttpVersion::Http11;
             debug!("request line: {:?} {:?} {:?}", head.method, uri, version);
@@ -165,7 +198,12 @@ impl HttpMessage for Http11Message {
             let stream = {
                 let write_headers = |mut stream: BufWriter<Box<NetworkStream + Send>>, head: &RequestHead| {
                     debug!("headers={:?}", head.headers);
-                    match write!(&mut stream, "{}{}", head.headers, LINE_ENDING) {
+                    let written = match proxy_headers {
+                        Some(ref proxy) => write!(&mut stream, "{}{}{}", head.headers, proxy,
+                                                  LINE_ENDING),
+                        None => write!(&mut stream, "{}{}", head.headers, LINE_ENDING)
+                    };
+                    match written {
                         Ok(_) => Ok(stream),
                         Err(e) => {
                             Err((e, stream.into_inner().unwrap()))
@@ -222,6 +260,8 @@ impl HttpMessage for Http11Message {
 
                         if chunked {
                             ChunkedWriter(stream)
+                        } else if len == 0 {
+                            EmptyWriter(stream)
                         } else {
                             SizedWriter(stream, len)
                         }
@@ -238,8 +278,53 @@ impl HttpMessage for Http11Message {
         res
     }
 
+    fn set_outgoing_raw(&mut self, head: RequestHead, raw: &[u8]) -> ::Result<RequestHead> {
+        let mut res = Err(Error::from(io::Error::new(
+                            io::ErrorKind::Other,
+                            "Message not idle, cannot start new outgoing")));
+        let mut method = None;
+        self.stream.map_in_place(|stream: Stream| -> Stream {
+            let stream = match stream {
+                Stream::Idle(stream) => stream,
+                stream => return stream
+            };
+            let mut stream = BufWriter::new(stream);
+            debug!("raw head: {:?}", String::from_utf8_lossy(raw));
+            if let Err(e) = stream.write_all(raw) {
+                res = Err(From::from(e));
+                return Stream::Idle(stream.into_inner().ok().unwrap());
+            }
+
+            // the body is framed as the head says
+            let chunked = match head.headers.get::<TransferEncoding>() {
+                Some(&TransferEncoding(ref codings)) => codings.last() == Some(&Chunked),
+                None => false
+            };
+            let writer = match head.headers.get::<ContentLength>() {
+                _ if chunked => ChunkedWriter(stream),
+                Some(&ContentLength(len)) if len > 0 => SizedWriter(stream, len),
+                _ => EmptyWriter(stream)
+            };
+
+            method = Some(head.method.clone());
+            res = Ok(head);
+            Stream::Writing(writer)
+        });
+
+        self.method = method;
+        self.keep_raw_head = res.is_ok();
+        res
+    }
+
     fn get_incoming(&mut self) -> ::Result<ResponseHead> {
         try!(self.flush_outgoing());
+        self.trailers = None;
+        self.body_read = 0;
+        self.raw_head = None;
+        let keep_raw_head = mem::replace(&mut self.keep_raw_head, false);
+        let mut raw_head = None;
+        let limits = self.limits;
+        let pending = mem::replace(&mut self.pending, Vec::new());
         let method = self.method.take().unwrap_or(Method::Get);
         let mut res = Err(From::from(
                         io::Error::new(io::ErrorKind::Other,
@@ -261,14 +346,27 @@ impl HttpMessage for Http11Message {
             trace!("previous_response_expected_no_content = {}", expected_no_content);
 
             let mut stream = BufReader::new(stream);
+            stream.unread(&pending);
 
             let mut invalid_bytes_read = 0;
             let head;
             loop {
-                head = match parse_response(&mut stream) {
+                let parsed = parse::<_, httparse::Response, RawStatus, _>(
+                    &mut stream, ColonWhitespace::Strip, &limits, None, |head| {
+                        if keep_raw_head {
+                            raw_head = Some(head.as_bytes().to_vec());
+                        }
+                    });
+                head = match parsed {
+                    // interim responses, such as a `100 Continue`, come before
+                    // the final one
+                    Ok(ref head) if is_interim(head.subject.0) => {
+                        trace!("skipping interim response {}", head.subject.0);
+                        continue;
+                    },
                     Ok(head) => head,
-                    Err(::Error::Version)
-                        if expected_no_content && invalid_bytes_read < MAX_INVALID_RESPONSE_BYTES => {
+                    Err(ref e)
+                        if is_version_error(e) && expected_no_content && invalid_bytes_read < MAX_INVALID_RESPONSE_BYTES => {
                             trace!("expected_no_content, found content");
                             invalid_bytes_read += 1;
                             stream.consume(1);
@@ -305,7 +403,14 @@ impl HttpMessage for Http11Message {
                         trace!("not chuncked. read till eof");
                         EofReader(stream)
                     }
+                } else if let Some(&ContentLength(0)) = headers.get() {
+                    EmptyReader(stream)
                 } else if let Some(&ContentLength(len)) =  headers.get() {
+                    if let Err(e) = limits.check_body(len) {
+                        trace!("Content-Length {} over the limit", len);
+                        res = Err(e);
+                        return Stream::Idle(stream.into_inner());
+                    }
                     SizedReader(stream, len)
                 } else if headers.has::<ContentLength>() {
                     trace!("illegal Content-Length: {:?}", headers.get_raw("Content-Length"));
@@ -328,9 +433,22 @@ impl HttpMessage for Http11Message {
 
             Stream::Reading(reader)
         });
+        self.raw_head = raw_head;
         res
     }
 
+    fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    fn raw_head(&self) -> Option<&[u8]> {
+        self.raw_head.as_ref().map(|head| &head[..])
+    }
+
+    fn connect_info(&self) -> Option<&ConnectInfo> {
+        self.connect_info.as_ref()
+    }
+
     fn has_body(&self) -> bool {
         match self.stream.as_ref().reader_ref() {
             Some(&EmptyReader(..)) |
@@ -344,9 +462,30 @@ impl HttpMessage for Http11Message {
     #[cfg(feature = "timeouts")]
     #[inline]
     fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.read_timeout.set(dur);
         self.get_ref().set_read_timeout(dur)
     }
 
+    #[cfg(feature = "timeouts")]
+    fn wait_for_continue(&mut self, timeout: Duration) -> ::Result<bool> {
+        match self.stream.as_mut().writer_mut() {
+            Some(writer) => try!(writer.flush()),
+            None => return Err(Error::from(io::Error::new(io::ErrorKind::Other,
+                                                          "Not in a writable state")))
+        }
+        try!(self.get_ref().set_read_timeout(Some(timeout)));
+        let res = self.read_interim();
+        try!(self.get_ref().set_read_timeout(self.read_timeout.get()));
+        if !try!(res) {
+            // the body won't be sent, and the server may still expect it,
+            // so the connection can't be reused
+            debug!("response came before the body, not sending it");
+            try!(self.shutdown_write());
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
     #[cfg(feature = "timeouts")]
     #[inline]
     fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
@@ -358,6 +497,21 @@ impl HttpMessage for Http11Message {
         try!(self.get_mut().close(Shutdown::Both));
         Ok(())
     }
+
+    #[inline]
+    fn trailers(&self) -> Option<&Headers> {
+        self.trailers.as_ref()
+    }
+
+    fn shutdown_write(&mut self) -> ::Result<()> {
+        try!(self.flush_outgoing());
+        try!(self.get_mut().close(Shutdown::Write));
+        Ok(())
+    }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        self.get_ref().abort_handle()
+    }
 }
 
 impl Http11Message {
@@ -390,12 +544,71 @@ impl Http11Message {
         }
     }
 
+    // Reads until a response head arrives, or the read times out. Returns
+    // whether to send the body, which isn't the case if a final response
+    // came instead of `100 Continue`. Its bytes are left for `get_incoming`.
+    #[cfg(feature = "timeouts")]
+    fn read_interim(&mut self) -> ::Result<bool> {
+        let mut buf = [0; 1024];
+        loop {
+            let n = match self.get_mut().read(&mut buf) {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                              e.kind() == io::ErrorKind::TimedOut => {
+                    debug!("no 100 Continue before the timeout, sending the body");
+                    return Ok(true);
+                },
+                Err(e) => return Err(Error::from(e))
+            };
+            if n == 0 {
+                // closed, which reading the response will report
+                return Ok(false);
+            }
+            self.pending.extend_from_slice(&buf[..n]);
+
+            loop {
+                let parsed = {
+                    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+                    let mut res = httparse::Response::new(&mut headers);
+                    match res.parse(&self.pending) {
+                        Ok(httparse::Status::Complete(len)) => Some((len, res.code)),
+                        Ok(httparse::Status::Partial) => None,
+                        Err(_) => return Ok(false)
+                    }
+                };
+                match parsed {
+                    Some((len, Some(100))) => {
+                        trace!("100 Continue");
+                        self.pending.drain(..len);
+                        return Ok(true);
+                    },
+                    Some((len, Some(code))) if is_interim(code) => {
+                        trace!("skipping interim response {}", code);
+                        self.pending.drain(..len);
+                    },
+                    Some(_) => return Ok(false),
+                    None if self.pending.len() > MAX_INTERIM_SIZE => return Ok(false),
+                    None => break
+                }
+            }
+        }
+    }
+
     /// Creates a new `Http11Message` that will use the given `NetworkStream` for communicating to
     /// the peer.
     pub fn with_stream(stream: Box<NetworkStream + Send>) -> Http11Message {
         Http11Message {
             method: None,
             stream: Wrapper::new(Stream::new(stream)),
+            trailers: None,
+            pending: Vec::new(),
+            limits: Limits::default(),
+            body_read: 0,
+            keep_raw_head: false,
+            raw_head: None,
+            connect_info: None,
+            #[cfg(feature = "timeouts")]
+            read_timeout: Cell::new(None),
         }
     }
 
@@ -433,9 +646,15 @@ pub struct Http11Protocol {
 
 impl Protocol for Http11Protocol {
     fn new_message(&self, host: &str, port: u16, scheme: &str) -> ::Result<Box<HttpMessage>> {
-        let stream = try!(self.connector.connect(host, port, scheme)).into();
+        let (stream, info) = try!(self.connector.connect_with_info(host, port, scheme));
+
+        let mut message = Http11Message::with_stream(stream);
+        message.connect_info = Some(info);
+        Ok(Box::new(message))
+    }
 
-        Ok(Box::new(Http11Message::with_stream(stream)))
+    fn close_idle(&self) {
+        self.connector.close_idle()
     }
 }
 
@@ -461,6 +680,18 @@ impl<C: NetworkConnector<Stream=S> + Send + Sync, S: NetworkStream + Send>
         -> ::Result<Box<NetworkStream + Send>> {
         Ok(try!(self.0.connect(host, port, scheme)).into())
     }
+
+    #[inline]
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+        -> ::Result<(Box<NetworkStream + Send>, ConnectInfo)> {
+        let (stream, info) = try!(self.0.connect_with_info(host, port, scheme));
+        Ok((stream.into(), info))
+    }
+
+    #[inline]
+    fn close_idle(&self) {
+        self.0.close_idle()
+    }
 }
 
 struct Connector(Box<NetworkConnector<Stream=Box<NetworkStream + Send>> + Send + Sync>);
@@ -472,6 +703,17 @@ impl NetworkConnector for Connector {
         -> ::Result<Box<NetworkStream + Send>> {
         Ok(try!(self.0.connect(host, port, scheme)).into())
     }
+
+    #[inline]
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+        -> ::Result<(Box<NetworkStream + Send>, ConnectInfo)> {
+        self.0.connect_with_info(host, port, scheme)
+    }
+
+    #[inline]
+    fn close_idle(&self) {
+        self.0.close_idle()
+    }
 }
 
 
@@ -536,6 +778,19 @@ impl<R: Read> HttpReader<R> {
             EmptyReader(ref mut r) => r,
         }
     }
+
+    /// Reads the trailer fields after the last chunk of a chunked body.
+    ///
+    /// They are left unread by `read`, so this is to be called once, after
+    /// `read` returned the end of the body. Other bodies have no trailers.
+    pub fn read_trailers(&mut self) -> io::Result<Headers> {
+        match *self {
+            ChunkedReader(ref mut body, Some(0)) => read_trailers(body),
+            ChunkedReader(..) => Err(io::Error::new(io::ErrorKind::Other,
+                                                    "chunked body not read to the end")),
+            _ => Ok(Headers::new())
+        }
+    }
 }
 
 impl<R> fmt::Debug for HttpReader<R> {
@@ -630,17 +885,33 @@ fn eat<R: Read>(rdr: &mut R, bytes: &[u8]) -> io::Result<()> {
 
 /// Chunked chunks start with 1*HEXDIGIT, indicating the size of the chunk.
 fn read_chunk_size<R: Read>(rdr: &mut R) -> io::Result<u64> {
+    // the start of the line, for the diagnostic if it turns out invalid
+    let mut line = Vec::new();
+    let mut read = 0;
     macro_rules! byte (
         ($rdr:ident) => ({
             let mut buf = [0];
             match try!($rdr.read(&mut buf)) {
-                1 => buf[0],
+                1 => {
+                    if line.len() < MAX_FRAGMENT {
+                        line.push(buf[0]);
+                    }
+                    read += 1;
+                    buf[0]
+                },
                 _ => return Err(io::Error::new(io::ErrorKind::InvalidInput,
                                                   "Invalid chunk size line")),
 
             }
         })
     );
+    macro_rules! invalid (
+        () => ({
+            let diagnostic = Diagnostic::new(ParseElement::ChunkSize, &line, read - 1, 0);
+            trace!("{}", diagnostic);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, diagnostic));
+        })
+    );
     let mut size = 0u64;
     let radix = 16;
     let mut in_ext = false;
@@ -662,9 +933,7 @@ fn read_chunk_size<R: Read>(rdr: &mut R) -> io::Result<u64> {
             CR => {
                 match byte!(rdr) {
                     LF => break,
-                    _ => return Err(io::Error::new(io::ErrorKind::InvalidInput,
-                                                  "Invalid chunk size line"))
-
+                    _ => invalid!()
                 }
             },
             // If we weren't in the extension yet, the ";" signals its start
@@ -687,16 +956,47 @@ fn read_chunk_size<R: Read>(rdr: &mut R) -> io::Result<u64> {
             },
             // Finally, if we aren't in the extension and we're reading any
             // other octet, the chunk size line is invalid!
-            _ => {
-                return Err(io::Error::new(io::ErrorKind::InvalidInput,
-                                         "Invalid chunk size line"));
-            }
+            _ => invalid!()
         }
     }
     trace!("chunk size={:?}", size);
     Ok(size)
 }
 
+/// The trailer section can be at most this large.
+const MAX_TRAILERS_SIZE: usize = 8192;
+
+/// Reads the trailer section, up to and including the empty line ending it.
+fn read_trailers<R: Read>(rdr: &mut R) -> io::Result<Headers> {
+    let mut section = Vec::new();
+    // one byte at a time, so nothing after the section is read
+    while section != LINE_ENDING.as_bytes() && !section.ends_with(b"\r\n\r\n") {
+        if section.len() > MAX_TRAILERS_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Trailer section too large"));
+        }
+        let mut buf = [0];
+        match try!(rdr.read(&mut buf)) {
+            1 => section.push(buf[0]),
+            _ => return Err(io::Error::new(io::ErrorKind::Other, "early eof")),
+        }
+    }
+    trace!("trailers={:?}", section);
+
+    let mut raw = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    match httparse::parse_headers(&section, &mut raw) {
+        Ok(httparse::Status::Complete((_, raw))) => Headers::from_raw(raw).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Invalid trailer section")
+        }),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid trailer section")),
+    }
+}
+
+// 1xx responses other than `101 Switching Protocols` are followed by the
+// final response.
+fn is_interim(code: u16) -> bool {
+    code >= 100 && code < 200 && code != 101
+}
+
 fn should_have_response_body(method: &Method, status: u16) -> bool {
     trace!("should_have_response_body({:?}, {})", method, status);
     match (method, status) {
@@ -776,6 +1076,28 @@ impl<W: Write> HttpWriter<W> {
             Err(e) => Err(EndError(e, self))
         }
     }
+
+    /// Ends a chunked body with the last chunk and the `trailers`, and then
+    /// flushes, like `end`.
+    ///
+    /// Only chunked bodies can have trailers, so the other writers fail.
+    pub fn end_with_trailers(mut self, trailers: &Headers) -> Result<W, EndError<W>> {
+        fn inner<W: Write>(w: &mut W, trailers: &Headers) -> io::Result<()> {
+            trace!("chunked end, trailers = {:?}", trailers);
+            try!(write!(w, "0{}{}{}", LINE_ENDING, trailers, LINE_ENDING));
+            w.flush()
+        }
+
+        let res = match self {
+            ChunkedWriter(ref mut w) => inner(w, trailers),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                    "Trailers need a chunked body")),
+        };
+        match res {
+            Ok(..) => Ok(self.into_inner()),
+            Err(e) => Err(EndError(e, self))
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -846,27 +1168,165 @@ impl<W: Write> fmt::Debug for HttpWriter<W> {
 
 const MAX_HEADERS: usize = 100;
 
+// the most bytes read for an interim response head, while waiting to send
+// a body
+#[cfg(feature = "timeouts")]
+const MAX_INTERIM_SIZE: usize = 8192;
+
+/// What to do with whitespace between a header field name and its colon,
+/// such as `Host : example.domain`.
+///
+/// [RFC7230](https://tools.ietf.org/html/rfc7230#section-3.2.4) says
+/// servers must reject requests with it, since other implementations may
+/// read the field differently, which can be used to smuggle requests.
+/// Proxies must remove it from responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColonWhitespace {
+    /// Fail to parse the message head.
+    Reject,
+    /// Remove the whitespace, and keep the field.
+    Strip,
+}
+
+/// Limits on the size of messages read, so that a peer can't make a
+/// connection buffer without end.
+///
+/// A server answers a request over a limit with `414 URI Too Long`,
+/// `431 Request Header Fields Too Large` or `413 Payload Too Large`, and
+/// closes the connection. A client fails with `Error::TooLarge` for a
+/// response head over a limit, and with `Error::BodyTooLarge` for a body
+/// said to be larger than `max_body_size`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The longest request-target a request line may have.
+    ///
+    /// Default is 8192 bytes.
+    pub max_uri_len: usize,
+    /// The most bytes a head may have, including its start line and the
+    /// empty line ending it.
+    ///
+    /// Default is about 400KiB, which is also as large as a head can be.
+    pub max_head_size: usize,
+    /// The most header fields a head may have.
+    ///
+    /// Default is 100.
+    pub max_headers: usize,
+    /// The most bytes a body may have, if any limit.
+    ///
+    /// A body with a larger `Content-Length` is refused before any of it is
+    /// read, and reading a chunked or close-delimited body fails with an
+    /// error of kind `InvalidData` once it goes over. Default is no limit.
+    pub max_body_size: Option<u64>,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_uri_len: MAX_URI_LEN,
+            max_head_size: MAX_HEAD_SIZE,
+            max_headers: MAX_HEADERS,
+            max_body_size: None,
+        }
+    }
+}
+
+impl Limits {
+    /// Checks a body of `len` bytes, as its `Content-Length` says, against
+    /// `max_body_size`.
+    #[doc(hidden)]
+    pub fn check_body(&self, len: u64) -> ::Result<()> {
+        match self.max_body_size {
+            Some(max) if len > max => Err(Error::BodyTooLarge),
+            _ => Ok(())
+        }
+    }
+
+    /// The error reading a body fails with once `read` bytes of it are over
+    /// `max_body_size`.
+    #[doc(hidden)]
+    pub fn check_body_read(&self, read: u64) -> io::Result<()> {
+        match self.max_body_size {
+            Some(max) if read > max => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "message body is too large"))
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+const MAX_URI_LEN: usize = 8192;
+
 /// Parses a request into an Incoming message head.
 #[inline]
 pub fn parse_request<R: Read>(buf: &mut BufReader<R>) -> ::Result<Incoming<(Method, RequestUri)>> {
-    parse::<R, httparse::Request, (Method, RequestUri)>(buf)
+    parse_request_with(buf, ColonWhitespace::Reject, |_| ())
+}
+
+/// Parses a request into an Incoming message head, first passing the raw
+/// head to `inspect`.
+#[inline]
+pub fn parse_request_with<R, F>(buf: &mut BufReader<R>, colon: ColonWhitespace, inspect: F)
+    -> ::Result<Incoming<(Method, RequestUri)>>
+where R: Read, F: FnOnce(&RawHead) {
+    parse_request_limited(buf, colon, &Limits::default(), inspect)
+}
+
+/// Parses a request into an Incoming message head within `limits`, first
+/// passing the raw head to `inspect`.
+///
+/// Fails with `Error::UriTooLong` if the request-target is over its limit,
+/// and with `Error::TooLarge` if the head is.
+#[inline]
+pub fn parse_request_limited<R, F>(buf: &mut BufReader<R>, colon: ColonWhitespace,
+                                   limits: &Limits, inspect: F)
+    -> ::Result<Incoming<(Method, RequestUri)>>
+where R: Read, F: FnOnce(&RawHead) {
+    parse::<R, httparse::Request, (Method, RequestUri), F>(buf, colon, limits,
+                                                           Some(limits.max_uri_len), inspect)
 }
 
 /// Parses a response into an Incoming message head.
+///
+/// Whitespace before the colon of a header field is removed.
 #[inline]
 pub fn parse_response<R: Read>(buf: &mut BufReader<R>) -> ::Result<Incoming<RawStatus>> {
-    parse::<R, httparse::Response, RawStatus>(buf)
+    parse_response_limited(buf, &Limits::default())
+}
+
+/// Parses a response into an Incoming message head within `limits`.
+///
+/// Whitespace before the colon of a header field is removed.
+#[inline]
+pub fn parse_response_limited<R: Read>(buf: &mut BufReader<R>, limits: &Limits)
+    -> ::Result<Incoming<RawStatus>> {
+    parse::<R, httparse::Response, RawStatus, _>(buf, ColonWhitespace::Strip, limits, None,
+                                                 |_| ())
 }
 
-fn parse<R: Read, T: TryParse<Subject=I>, I>(rdr: &mut BufReader<R>) -> ::Result<Incoming<I>> {
+fn parse<R, T, I, F>(rdr: &mut BufReader<R>, colon: ColonWhitespace, limits: &Limits,
+                     max_uri_len: Option<usize>, inspect: F)
+    -> ::Result<Incoming<I>>
+where R: Read, T: TryParse<Subject=I>, F: FnOnce(&RawHead) {
     loop {
-        match try!(try_parse::<R, T, I>(rdr)) {
+        if let Some(max) = max_uri_len {
+            if request_target_len(rdr.get_buf()) > max {
+                return Err(Error::UriTooLong);
+            }
+        }
+        match try!(try_parse::<R, T, I>(rdr, colon, limits.max_headers)) {
+            httparse::Status::Complete((_, len)) if len > limits.max_head_size => {
+                return Err(Error::TooLarge);
+            },
             httparse::Status::Complete((inc, len)) => {
+                inspect(&RawHead { bytes: &rdr.get_buf()[..len] });
                 rdr.consume(len);
                 return Ok(inc);
             },
             _partial => ()
         }
+        if rdr.get_buf().len() >= limits.max_head_size {
+            return Err(Error::TooLarge);
+        }
         match try!(rdr.read_into_buf()) {
             0 if rdr.get_buf().is_empty() => {
                 return Err(Error::Io(io::Error::new(
@@ -880,14 +1340,624 @@ fn parse<R: Read, T: TryParse<Subject=I>, I>(rdr: &mut BufReader<R>) -> ::Result
     }
 }
 
-fn try_parse<R: Read, T: TryParse<Subject=I>, I>(rdr: &mut BufReader<R>) -> TryParseResult<I> {
+// The length of the request-target in the request line at the start of
+// `buf`, as much of it as has arrived. Empty lines before the request line
+// are skipped, as httparse skips them.
+fn request_target_len(buf: &[u8]) -> usize {
+    let start = buf.iter().position(|&b| b != CR && b != LF).unwrap_or(buf.len());
+    let line = &buf[start..];
+    let line = &line[..line.iter().position(|&b| b == LF).unwrap_or(line.len())];
+    match line.iter().position(|&b| b == b' ') {
+        Some(sp) => {
+            let target = &line[sp + 1..];
+            target.iter().position(|&b| b == b' ').unwrap_or(target.len())
+        },
+        None => 0
+    }
+}
+
+/// Parses a request head from the start of `buf`, without reading any more,
+/// such as for a connection driven by other code.
+///
+/// Returns the head and its length once `buf` holds all of it, and `None`
+/// until then.
+pub fn try_parse_request(buf: &[u8], colon: ColonWhitespace)
+    -> ::Result<Option<(Incoming<(Method, RequestUri)>, usize)>> {
+    match try!(try_parse_buf::<httparse::Request, (Method, RequestUri)>(buf, colon, MAX_HEADERS)) {
+        httparse::Status::Complete(parsed) => Ok(Some(parsed)),
+        httparse::Status::Partial => Ok(None)
+    }
+}
+
+// the largest head a `HeadParser` takes by default, as large as a
+// `BufReader` grows
+const MAX_HEAD_SIZE: usize = 8192 + 4096 * 100;
+
+/// Parses a message head from bytes as they arrive, such as for a
+/// connection driven by other code.
+///
+/// Unlike `try_parse_request`, the bytes are passed in pieces, which the
+/// parser keeps between calls. It remembers how far it has looked for the
+/// end of the head, so each piece is only scanned once, and the head is
+/// only parsed once all of it has arrived. The size of the head and its
+/// number of header fields are limited, and checked as the bytes arrive.
+///
+/// ```
+/// use hyper::http::h1::HeadParser;
+///
+/// let mut parser = HeadParser::request();
+/// assert!(parser.parse(b"GET /echo HTTP/1.1\r\nHo").unwrap().is_none());
+/// let head = parser.parse(b"st: example.domain\r\n\r\nbody").unwrap().unwrap();
+/// assert_eq!(head.subject.1.to_string(), "/echo");
+/// assert_eq!(parser.rest(), b"body");
+/// ```
+pub struct HeadParser<S> {
+    buf: Vec<u8>,
+    // the start of the first line not scanned yet
+    scanned: usize,
+    started: bool,
+    fields: usize,
+    // the length of the head last parsed, which starts `buf`
+    parsed: Option<usize>,
+    max_headers: usize,
+    max_size: usize,
+    colon: ColonWhitespace,
+    parse: fn(&[u8], ColonWhitespace, usize) -> TryParseResult<S>,
+}
+
+impl HeadParser<(Method, RequestUri)> {
+    /// Creates a parser of request heads.
+    ///
+    /// Whitespace before the colon of a header field is rejected.
+    pub fn request() -> HeadParser<(Method, RequestUri)> {
+        HeadParser::new(ColonWhitespace::Reject,
+                        parse_head::<httparse::Request, (Method, RequestUri)>)
+    }
+}
+
+impl HeadParser<RawStatus> {
+    /// Creates a parser of response heads.
+    ///
+    /// Whitespace before the colon of a header field is removed.
+    pub fn response() -> HeadParser<RawStatus> {
+        HeadParser::new(ColonWhitespace::Strip, parse_head::<httparse::Response, RawStatus>)
+    }
+}
+
+impl<S> HeadParser<S> {
+    fn new(colon: ColonWhitespace, parse: fn(&[u8], ColonWhitespace, usize) -> TryParseResult<S>)
+            -> HeadParser<S> {
+        HeadParser {
+            buf: Vec::new(),
+            scanned: 0,
+            started: false,
+            fields: 0,
+            parsed: None,
+            max_headers: MAX_HEADERS,
+            max_size: MAX_HEAD_SIZE,
+            colon: colon,
+            parse: parse,
+        }
+    }
+
+    /// Set the most header fields a head may have.
+    ///
+    /// Default is 100.
+    pub fn set_max_headers(&mut self, max: usize) {
+        self.max_headers = max;
+    }
+
+    /// Set the most bytes a head may have, including its start line and
+    /// the empty line ending it.
+    ///
+    /// Default is about 400KiB.
+    pub fn set_max_head_size(&mut self, max: usize) {
+        self.max_size = max;
+    }
+
+    /// Set what to do with whitespace before the colon of a header field.
+    pub fn set_colon_whitespace(&mut self, colon: ColonWhitespace) {
+        self.colon = colon;
+    }
+
+    /// Passes the next bytes of the message to the parser.
+    ///
+    /// Returns the head once all of it has arrived, and `None` until then.
+    /// Bytes after the head are kept, and are the start of the next head if
+    /// `parse` is called again, so pipelined messages can be parsed one
+    /// after another. After an error, the parser shouldn't be used again.
+    pub fn parse(&mut self, bytes: &[u8]) -> Result<Option<Incoming<S>>, HeadError> {
+        if let Some(len) = self.parsed.take() {
+            self.buf.drain(..len);
+            self.scanned = 0;
+            self.started = false;
+            self.fields = 0;
+        }
+        self.buf.extend_from_slice(bytes);
+        let end = match try!(self.scan()) {
+            Some(end) => end,
+            None => return Ok(None)
+        };
+        match (self.parse)(&self.buf[..end], self.colon, self.fields) {
+            Ok(httparse::Status::Complete((head, len))) => {
+                self.parsed = Some(len);
+                Ok(Some(head))
+            },
+            // the whole head was there, so it can't be partial
+            Ok(httparse::Status::Partial) => Err(HeadError::Malformed(Error::Header)),
+            Err(e) => Err(HeadError::Malformed(e))
+        }
+    }
+
+    /// Parses `bytes` as one whole head with nothing after it, such as a
+    /// head kept to be forwarded.
+    pub fn parse_whole(mut self, bytes: &[u8]) -> ::Result<Incoming<S>> {
+        match self.parse(bytes) {
+            Ok(Some(head)) => if self.rest().is_empty() {
+                Ok(head)
+            } else {
+                Err(Error::Header)
+            },
+            Ok(None) => Err(Error::Header),
+            Err(e) => Err(From::from(e))
+        }
+    }
+
+    /// Get the bytes after the head last parsed, or none if no head has
+    /// been parsed since the last call to `parse`.
+    pub fn rest(&self) -> &[u8] {
+        match self.parsed {
+            Some(len) => &self.buf[len..],
+            None => &[]
+        }
+    }
+
+    /// Takes the bytes after the head last parsed, such as the start of its
+    /// body.
+    pub fn into_rest(mut self) -> Vec<u8> {
+        match self.parsed {
+            Some(len) => self.buf.split_off(len),
+            None => Vec::new()
+        }
+    }
+
+    // Looks for the end of the head in the lines not scanned yet, counting
+    // the header fields on the way, and returns the length of the head once
+    // it is found.
+    fn scan(&mut self) -> Result<Option<usize>, HeadError> {
+        while let Some(lf) = self.buf[self.scanned..].iter().position(|&b| b == LF) {
+            let start = self.scanned;
+            self.scanned += lf + 1;
+            if self.scanned > self.max_size {
+                return Err(HeadError::TooLarge);
+            }
+            let empty = lf == 0 || (lf == 1 && self.buf[start] == CR);
+            if !empty && !self.started {
+                self.started = true;
+            } else if !empty {
+                self.fields += 1;
+                if self.fields > self.max_headers {
+                    return Err(HeadError::TooManyHeaders);
+                }
+            } else if self.started {
+                return Ok(Some(self.scanned));
+            }
+            // empty lines before the start line are skipped, as httparse
+            // skips them
+        }
+        if self.buf.len() > self.max_size {
+            return Err(HeadError::TooLarge);
+        }
+        Ok(None)
+    }
+}
+
+impl<S> fmt::Debug for HeadParser<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HeadParser")
+            .field("buffered", &self.buf.len())
+            .field("scanned", &self.scanned)
+            .field("fields", &self.fields)
+            .field("parsed", &self.parsed)
+            .field("max_headers", &self.max_headers)
+            .field("max_size", &self.max_size)
+            .field("colon", &self.colon)
+            .finish()
+    }
+}
+
+// Parses a whole head, with room for `fields` header fields.
+fn parse_head<T, I>(buf: &[u8], colon: ColonWhitespace, fields: usize) -> TryParseResult<I>
+where T: TryParse<Subject=I> {
+    let mut headers = vec![httparse::EMPTY_HEADER; fields];
+    try_parse_with::<T, I>(&mut headers, buf, colon)
+}
+
+/// Why a `HeadParser` failed to parse a head.
+#[derive(Debug)]
+pub enum HeadError {
+    /// The head is larger than the parser's limit.
+    TooLarge,
+    /// The head has more header fields than the parser's limit.
+    TooManyHeaders,
+    /// The head arrived whole, but isn't a valid message head.
+    Malformed(Error),
+}
+
+impl fmt::Display for HeadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl StdError for HeadError {
+    fn description(&self) -> &str {
+        match *self {
+            HeadError::TooLarge => "Message head is too large",
+            HeadError::TooManyHeaders => "Message head has too many header fields",
+            HeadError::Malformed(ref e) => e.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            HeadError::Malformed(ref e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<HeadError> for Error {
+    fn from(err: HeadError) -> Error {
+        match err {
+            HeadError::TooLarge | HeadError::TooManyHeaders => Error::TooLarge,
+            HeadError::Malformed(e) => e
+        }
+    }
+}
+
+/// What part of a message a `Diagnostic` is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseElement {
+    /// The request-line, such as `GET / HTTP/1.1`.
+    RequestLine,
+    /// The status-line, such as `HTTP/1.1 200 OK`.
+    StatusLine,
+    /// The name of a header field, up to its colon.
+    HeaderName,
+    /// The value of a header field.
+    HeaderValue,
+    /// The size line of a chunk in a chunked body.
+    ChunkSize,
+}
+
+impl fmt::Display for ParseElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ParseElement::RequestLine => "request-line",
+            ParseElement::StatusLine => "status-line",
+            ParseElement::HeaderName => "header name",
+            ParseElement::HeaderValue => "header value",
+            ParseElement::ChunkSize => "chunk size",
+        })
+    }
+}
+
+/// Where a message failed to parse.
+///
+/// Parsing a malformed head fails with an `Error::Malformed` carrying one,
+/// and reading an invalid chunk size with an `io::Error` carrying one. Both
+/// are logged at the `trace` level as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The offset of the offending byte, from the start of the head, or of
+    /// the chunk size line.
+    pub offset: usize,
+    /// The line with the offending byte, or the part of it around that byte
+    /// if the line is long.
+    pub fragment: String,
+    /// What was being parsed.
+    pub element: ParseElement,
+}
+
+/// At most this much of a line is kept in a `Diagnostic`.
+const MAX_FRAGMENT: usize = 64;
+
+impl Diagnostic {
+    /// Finds where the request head at the start of `buf` is malformed, if
+    /// anywhere.
+    pub fn request(buf: &[u8]) -> Option<Diagnostic> {
+        diagnose(buf, ParseElement::RequestLine, request_line_error)
+    }
+
+    /// Finds where the response head at the start of `buf` is malformed, if
+    /// anywhere.
+    pub fn response(buf: &[u8]) -> Option<Diagnostic> {
+        diagnose(buf, ParseElement::StatusLine, status_line_error)
+    }
+
+    fn new(element: ParseElement, line: &[u8], at: usize, line_offset: usize) -> Diagnostic {
+        let start = if line.len() <= MAX_FRAGMENT {
+            0
+        } else {
+            min(at.saturating_sub(MAX_FRAGMENT / 2), line.len() - MAX_FRAGMENT)
+        };
+        let end = min(start + MAX_FRAGMENT, line.len());
+        Diagnostic {
+            offset: line_offset + at,
+            fragment: String::from_utf8_lossy(&line[start..end]).into_owned(),
+            element: element,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {} at byte {}: {:?}", self.element, self.offset, self.fragment)
+    }
+}
+
+impl StdError for Diagnostic {
+    fn description(&self) -> &str {
+        match self.element {
+            ParseElement::ChunkSize => "Invalid chunk size line",
+            _ => "Invalid message head",
+        }
+    }
+}
+
+fn diagnose(buf: &[u8], start: ParseElement, start_line_error: fn(&[u8]) -> Option<usize>)
+    -> Option<Diagnostic> {
+    let mut lines = RawLines(buf);
+    let mut offset = 0;
+    // empty lines before the start line are skipped, as httparse does
+    let line = loop {
+        match lines.next() {
+            Some(line) if line.is_empty() => offset = buf.len() - lines.0.len(),
+            Some(line) => break line,
+            None => return None
+        }
+    };
+    if let Some(at) = start_line_error(line) {
+        return Some(Diagnostic::new(start, line, at, offset));
+    }
+    loop {
+        offset = buf.len() - lines.0.len();
+        match lines.next() {
+            Some(line) if !line.is_empty() => if let Some((element, at)) = header_line_error(line) {
+                return Some(Diagnostic::new(element, line, at, offset));
+            },
+            _ => return None
+        }
+    }
+}
+
+// The index of the first byte of a request-line that doesn't fit
+// `method SP request-target SP HTTP-version`.
+fn request_line_error(line: &[u8]) -> Option<usize> {
+    let method = line.iter().position(|&b| !is_tchar(b)).unwrap_or(line.len());
+    if method == 0 || line.get(method) != Some(&b' ') {
+        return Some(method);
+    }
+    let target = method + 1;
+    let end = line[target..].iter().position(|&b| b <= b' ' || b == 0x7f)
+        .map_or(line.len(), |i| target + i);
+    if end == target || line.get(end) != Some(&b' ') {
+        return Some(end);
+    }
+    match version_error(line, end + 1) {
+        None if line.len() > end + 1 + VERSION_LEN => Some(end + 1 + VERSION_LEN),
+        res => res
+    }
+}
+
+// The index of the first byte of a status-line that doesn't fit
+// `HTTP-version SP status-code SP reason-phrase`.
+fn status_line_error(line: &[u8]) -> Option<usize> {
+    if let Some(at) = version_error(line, 0) {
+        return Some(at);
+    }
+    if line.get(VERSION_LEN) != Some(&b' ') {
+        return Some(VERSION_LEN);
+    }
+    let code = VERSION_LEN + 1;
+    for at in code..code + 3 {
+        match line.get(at) {
+            Some(&b) if b'0' <= b && b <= b'9' => (),
+            _ => return Some(at)
+        }
+    }
+    match line.get(code + 3) {
+        None => None,
+        Some(&b' ') => line[code + 4..].iter().position(|&b| !is_reason(b))
+            .map(|i| code + 4 + i),
+        Some(_) => Some(code + 3)
+    }
+}
+
+const VERSION_LEN: usize = 8;
+
+// The index of the first byte of the `HTTP/1.x` at `at` that is wrong.
+fn version_error(line: &[u8], at: usize) -> Option<usize> {
+    for (i, &b) in b"HTTP/1.".iter().enumerate() {
+        if line.get(at + i) != Some(&b) {
+            return Some(at + i);
+        }
+    }
+    match line.get(at + 7) {
+        Some(&b'0') | Some(&b'1') => None,
+        _ => Some(at + 7)
+    }
+}
+
+// What is wrong with a header line, and the index of the first byte that is.
+fn header_line_error(line: &[u8]) -> Option<(ParseElement, usize)> {
+    let name = line.iter().position(|&b| !is_tchar(b)).unwrap_or(line.len());
+    if name == 0 || line.get(name) != Some(&b':') {
+        return Some((ParseElement::HeaderName, name));
+    }
+    line[name + 1..].iter().position(|&b| !is_reason(b))
+        .map(|i| (ParseElement::HeaderValue, name + 1 + i))
+}
+
+// Whether a byte can be in a reason-phrase or a header value.
+fn is_reason(b: u8) -> bool {
+    b == b'\t' || (b >= b' ' && b != 0x7f)
+}
+
+fn try_parse<R, T, I>(rdr: &mut BufReader<R>, colon: ColonWhitespace, max_headers: usize)
+    -> TryParseResult<I>
+where R: Read, T: TryParse<Subject=I> {
+    try_parse_buf::<T, I>(rdr.get_buf(), colon, max_headers)
+}
+
+fn try_parse_buf<T, I>(buf: &[u8], colon: ColonWhitespace, max_headers: usize)
+    -> TryParseResult<I>
+where T: TryParse<Subject=I> {
+    if max_headers > MAX_HEADERS {
+        return parse_head::<T, I>(buf, colon, max_headers);
+    }
     let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
-    let buf = rdr.get_buf();
+    try_parse_with::<T, I>(&mut headers[..max_headers], buf, colon)
+}
+
+fn try_parse_with<'a, T, I>(headers: &'a mut [httparse::Header<'a>], buf: &'a [u8],
+                            colon: ColonWhitespace) -> TryParseResult<I>
+where T: TryParse<Subject=I> {
     if buf.len() == 0 {
         return Ok(httparse::Status::Partial);
     }
     trace!("try_parse({:?})", buf);
-    <T as TryParse>::try_parse(&mut headers, buf)
+    let max_headers = headers.len();
+    let res = match <T as TryParse>::try_parse(headers, buf) {
+        Err(Error::Header) => try_parse_invalid::<T, I>(buf, colon, max_headers),
+        res => res
+    };
+    res.map_err(|e| diagnosed::<T>(buf, e))
+}
+
+// The error with where in `buf` it happened, if that can be found.
+fn diagnosed<T: TryParse>(buf: &[u8], e: Error) -> Error {
+    let diagnostic = match e {
+        Error::Method | Error::Version | Error::Header | Error::Status => T::diagnose(buf),
+        // the request-target is well-formed, but not right for the method
+        Error::Uri(_) | Error::UriForm => T::diagnose(buf).or_else(|| {
+            let start = buf.iter().position(|&b| b != CR && b != LF).unwrap_or(0);
+            let line = RawLines(&buf[start..]).next().unwrap_or(b"");
+            let at = line.iter().position(|&b| b == b' ').map_or(0, |sp| sp + 1);
+            Some(Diagnostic::new(ParseElement::RequestLine, line, at, start))
+        }),
+        _ => None
+    };
+    match diagnostic {
+        Some(diagnostic) => {
+            trace!("{}", diagnostic);
+            Error::Malformed(Box::new(e), diagnostic)
+        },
+        None => e
+    }
+}
+
+// Whether parsing failed at the HTTP version.
+fn is_version_error(e: &Error) -> bool {
+    match *e {
+        Error::Version => true,
+        Error::Malformed(ref e, _) => is_version_error(e),
+        _ => false
+    }
+}
+
+// Parses a head that has invalid header lines, by leaving them out of the
+// head, and keeping them in `Headers` instead. Whitespace before colons is
+// rejected or stripped first.
+fn try_parse_invalid<T, I>(buf: &[u8], colon: ColonWhitespace, max_headers: usize)
+    -> TryParseResult<I>
+where T: TryParse<Subject=I> {
+    let mut lines = RawLines(buf);
+    let start = match lines.next() {
+        Some(line) => line,
+        None => return Ok(httparse::Status::Partial)
+    };
+    let mut head = start.to_vec();
+    head.extend_from_slice(LINE_ENDING.as_bytes());
+    let mut invalid = Vec::new();
+    let mut stripped_any = false;
+    loop {
+        let line = match lines.next() {
+            Some(line) => line,
+            None => return Ok(httparse::Status::Partial)
+        };
+        if line.is_empty() {
+            break;
+        }
+        if is_valid_header_line(line) {
+            head.extend_from_slice(line);
+            head.extend_from_slice(LINE_ENDING.as_bytes());
+        } else if let Some(stripped) = strip_colon_whitespace(line) {
+            match colon {
+                ColonWhitespace::Reject => {
+                    debug!("whitespace before colon: {:?}", String::from_utf8_lossy(line));
+                    return Err(Error::Header);
+                },
+                ColonWhitespace::Strip => {
+                    head.extend_from_slice(&stripped);
+                    head.extend_from_slice(LINE_ENDING.as_bytes());
+                    stripped_any = true;
+                }
+            }
+        } else {
+            debug!("invalid header line: {:?}", String::from_utf8_lossy(line));
+            invalid.push(line.to_vec());
+        }
+    }
+    // the head may end right at the buffer's end, without a final newline
+    if lines.0.len() == 0 && buf.last() != Some(&LF) {
+        return Ok(httparse::Status::Partial);
+    }
+    if invalid.is_empty() && !stripped_any {
+        return Err(Error::Header);
+    }
+    head.extend_from_slice(LINE_ENDING.as_bytes());
+    let len = buf.len() - lines.0.len();
+
+    let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
+    match try!(<T as TryParse>::try_parse(&mut headers, &head)) {
+        httparse::Status::Complete((mut inc, _)) => {
+            inc.headers.set_raw_invalid(invalid);
+            Ok(httparse::Status::Complete((inc, len)))
+        },
+        httparse::Status::Partial => Err(Error::Header)
+    }
+}
+
+fn is_valid_header_line(line: &[u8]) -> bool {
+    let colon = match line.iter().position(|&b| b == b':') {
+        Some(colon) => colon,
+        None => return false
+    };
+    let (name, value) = (&line[..colon], &line[colon + 1..]);
+    !name.is_empty() && name.iter().all(|&b| is_tchar(b)) && value.iter().all(|&b| is_reason(b))
+}
+
+// if a header line is only invalid because of whitespace before its
+// colon, the line without it
+fn strip_colon_whitespace(line: &[u8]) -> Option<Vec<u8>> {
+    let colon = match line.iter().position(|&b| b == b':') {
+        Some(colon) => colon,
+        None => return None
+    };
+    let name_len = line[..colon].iter().rposition(|&b| b != b' ' && b != b'\t')
+        .map_or(0, |i| i + 1);
+    if name_len == colon {
+        return None;
+    }
+    let mut stripped = line[..name_len].to_vec();
+    stripped.extend_from_slice(&line[colon..]);
+    if is_valid_header_line(&stripped) {
+        Some(stripped)
+    } else {
+        None
+    }
 }
 
 #[doc(hidden)]
@@ -895,6 +1965,7 @@ trait TryParse {
     type Subject;
     fn try_parse<'a>(headers: &'a mut [httparse::Header<'a>], buf: &'a [u8]) ->
         TryParseResult<Self::Subject>;
+    fn diagnose(buf: &[u8]) -> Option<Diagnostic>;
 }
 
 type TryParseResult<T> = Result<httparse::Status<(Incoming<T>, usize)>, Error>;
@@ -909,18 +1980,21 @@ impl<'a> TryParse for httparse::Request<'a, 'a> {
         Ok(match try!(req.parse(buf)) {
             httparse::Status::Complete(len) => {
                 trace!("Request.try_parse Complete({})", len);
+                let method = try!(req.method.unwrap().parse());
+                let uri = try!(RequestUri::from_target(&method, req.path.unwrap()));
                 httparse::Status::Complete((Incoming {
                     version: if req.version.unwrap() == 1 { Http11 } else { Http10 },
-                    subject: (
-                        try!(req.method.unwrap().parse()),
-                        try!(req.path.unwrap().parse())
-                    ),
+                    subject: (method, uri),
                     headers: try!(Headers::from_raw(req.headers))
                 }, len))
             },
             httparse::Status::Partial => httparse::Status::Partial
         })
     }
+
+    fn diagnose(buf: &[u8]) -> Option<Diagnostic> {
+        Diagnostic::request(buf)
+    }
 }
 
 impl<'a> TryParse for httparse::Response<'a, 'a> {
@@ -947,6 +2021,10 @@ impl<'a> TryParse for httparse::Response<'a, 'a> {
             httparse::Status::Partial => httparse::Status::Partial
         })
     }
+
+    fn diagnose(buf: &[u8]) -> Option<Diagnostic> {
+        Diagnostic::response(buf)
+    }
 }
 
 /// An Incoming Message head. Includes request/status line, and headers.
@@ -960,6 +2038,90 @@ pub struct Incoming<S> {
     pub headers: Headers
 }
 
+/// The head of an incoming message, exactly as it was received.
+///
+/// Unlike `Headers`, this keeps the order, case and repetition of the
+/// header fields, which can be useful for fingerprinting clients.
+#[derive(Debug, Clone, Copy)]
+pub struct RawHead<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> RawHead<'a> {
+    /// Wraps the bytes of a whole head, such as one kept to be forwarded.
+    #[inline]
+    pub fn new(bytes: &'a [u8]) -> RawHead<'a> {
+        RawHead { bytes: bytes }
+    }
+
+    /// Get the bytes of the whole head, including the empty line ending it.
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Get the start line, without its line ending.
+    pub fn start_line(&self) -> &'a [u8] {
+        RawLines(self.bytes).next().unwrap_or(b"")
+    }
+
+    /// Get an iterator over the header fields, as name and value pairs.
+    ///
+    /// Values have surrounding whitespace removed, and nothing else.
+    pub fn headers(&self) -> RawHeaders<'a> {
+        let mut lines = RawLines(self.bytes);
+        lines.next();
+        RawHeaders(lines)
+    }
+}
+
+/// An iterator over the header fields of a `RawHead`, in the order they
+/// were received.
+#[derive(Debug, Clone)]
+pub struct RawHeaders<'a>(RawLines<'a>);
+
+impl<'a> Iterator for RawHeaders<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<(&'a [u8], &'a [u8])> {
+        let line = match self.0.next() {
+            Some(line) if !line.is_empty() => line,
+            _ => return None
+        };
+        let colon = line.iter().position(|&b| b == b':').unwrap_or(line.len());
+        let mut value = &line[min(colon + 1, line.len())..];
+        while let Some((&b, rest)) = value.split_first() {
+            if b != b' ' && b != b'\t' { break; }
+            value = rest;
+        }
+        while let Some((&b, rest)) = value.split_last() {
+            if b != b' ' && b != b'\t' { break; }
+            value = rest;
+        }
+        Some((&line[..colon], value))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RawLines<'a>(&'a [u8]);
+
+impl<'a> Iterator for RawLines<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let end = self.0.iter().position(|&b| b == LF).unwrap_or(self.0.len());
+        let mut line = &self.0[..end];
+        self.0 = &self.0[min(end + 1, self.0.len())..];
+        if line.last() == Some(&CR) {
+            line = &line[..line.len() - 1];
+        }
+        Some(line)
+    }
+}
+
 /// The `\r` byte.
 pub const CR: u8 = b'\r';
 /// The `\n` byte.
@@ -974,10 +2136,12 @@ mod tests {
 
 
     use buffer::BufReader;
+    use header::Headers;
     use mock::MockStream;
     use http::HttpMessage;
 
-    use super::{read_chunk_size, parse_request, parse_response, Http11Message};
+    use super::{read_chunk_size, parse_request, parse_request_with, parse_response, Http11Message};
+    use super::{is_version_error, Diagnostic, ParseElement, MAX_FRAGMENT};
 
     #[test]
     fn test_write_chunked() {
@@ -990,6 +2154,136 @@ mod tests {
         assert_eq!(s, "7\r\nfoo bar\r\nD\r\nbaz quux herp\r\n0\r\n\r\n");
     }
 
+    #[test]
+    fn test_write_chunked_trailers() {
+        use std::str::from_utf8;
+        let mut trailers = Headers::new();
+        trailers.set_raw("Grpc-Status", vec![b"0".to_vec()]);
+
+        let mut w = super::HttpWriter::ChunkedWriter(Vec::new());
+        w.write_all(b"foo").unwrap();
+        let buf = w.end_with_trailers(&trailers).unwrap();
+        let s = from_utf8(buf.as_ref()).unwrap();
+        assert_eq!(s, "3\r\nfoo\r\n0\r\nGrpc-Status: 0\r\n\r\n");
+
+        let w = super::HttpWriter::SizedWriter(Vec::new(), 0);
+        assert!(w.end_with_trailers(&trailers).is_err());
+    }
+
+    #[test]
+    fn test_message_skips_interim_responses() {
+        let raw = MockStream::with_input(b"\
+            HTTP/1.1 100 Continue\r\n\
+            \r\n\
+            HTTP/1.1 200 OK\r\n\
+            Content-Length: 2\r\n\
+            \r\n\
+            ok");
+        let mut msg = Http11Message::with_stream(Box::new(raw));
+        assert_eq!(msg.get_incoming().unwrap().raw_status.0, 200);
+        let mut s = String::new();
+        msg.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "ok");
+    }
+
+    #[cfg(feature = "timeouts")]
+    #[test]
+    fn test_message_wait_for_continue() {
+        use std::time::Duration;
+        use header::ContentLength;
+        use http::RequestHead;
+        use method::Method;
+        use url::Url;
+
+        fn outgoing(input: &[u8]) -> Http11Message {
+            let mut msg = Http11Message::with_stream(Box::new(MockStream::with_input(input)));
+            let mut headers = Headers::new();
+            headers.set(ContentLength(4));
+            msg.set_outgoing(RequestHead {
+                headers: headers,
+                method: Method::Put,
+                url: Url::parse("http://example.domain/").unwrap(),
+            }).unwrap();
+            msg
+        }
+
+        let mut msg = outgoing(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 201 Created\r\n\r\n");
+        assert!(msg.wait_for_continue(Duration::from_secs(1)).unwrap());
+        msg.write_all(b"body").unwrap();
+        assert_eq!(msg.get_incoming().unwrap().raw_status.0, 201);
+        {
+            let raw = msg.get_ref().downcast_ref::<MockStream>().unwrap();
+            assert!(raw.write.ends_with(b"\r\n\r\nbody"));
+            assert!(!raw.is_closed);
+        }
+
+        let mut msg = outgoing(b"HTTP/1.1 417 Expectation Failed\r\nContent-Length: 0\r\n\r\n");
+        assert!(!msg.wait_for_continue(Duration::from_secs(1)).unwrap());
+        assert_eq!(msg.get_incoming().unwrap().raw_status.0, 417);
+        let raw = msg.get_ref().downcast_ref::<MockStream>().unwrap();
+        assert!(raw.write.ends_with(b"\r\n\r\n"));
+        assert!(raw.is_closed);
+    }
+
+    #[test]
+    fn test_message_raw_head() {
+        use http::RequestHead;
+        use method::Method;
+        use url::Url;
+
+        let head = b"HTTP/1.1 200 OK\r\nx-b: 1\r\nX-A:  2\r\nX-B: 3\r\nContent-Length: 2\r\n\r\n";
+        let mut input = head.to_vec();
+        input.extend_from_slice(b"ok");
+        let mut msg = Http11Message::with_stream(Box::new(MockStream::with_input(&input)));
+        let raw = b"PUT /up HTTP/1.1\r\nhost: a\r\nX-Y:  z\r\ntransfer-encoding: chunked\r\n\r\n";
+        let mut headers = Headers::new();
+        headers.set_raw("Transfer-Encoding", vec![b"chunked".to_vec()]);
+        msg.set_outgoing_raw(RequestHead {
+            headers: headers,
+            method: Method::Put,
+            url: Url::parse("http://a/up").unwrap(),
+        }, raw).unwrap();
+        msg.write_all(b"body").unwrap();
+        msg.get_incoming().unwrap();
+        assert_eq!(msg.raw_head(), Some(&head[..]));
+        let mut body = String::new();
+        msg.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "ok");
+
+        let mut written = raw.to_vec();
+        written.extend_from_slice(b"4\r\nbody\r\n0\r\n\r\n");
+        assert_eq!(msg.get_ref().downcast_ref::<MockStream>().unwrap().write, written);
+
+        // only kept for a request sent raw
+        let mut msg = Http11Message::with_stream(Box::new(MockStream::with_input(&input)));
+        msg.get_incoming().unwrap();
+        assert_eq!(msg.raw_head(), None);
+    }
+
+    #[test]
+    fn test_message_response_trailers() {
+        let raw = MockStream::with_input(b"\
+            HTTP/1.1 200 OK\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n\
+            2\r\n\
+            ok\r\n\
+            0\r\n\
+            Grpc-Status: 0\r\n\
+            Grpc-Message: done\r\n\
+            \r\n");
+        let mut msg = Http11Message::with_stream(Box::new(raw));
+        msg.get_incoming().unwrap();
+        assert!(msg.trailers().is_none());
+
+        let mut s = String::new();
+        msg.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "ok");
+        let trailers = msg.trailers().unwrap();
+        assert_eq!(trailers.get_raw("Grpc-Status").unwrap(), &[b"0".to_vec()][..]);
+        assert_eq!(trailers.get_raw("Grpc-Message").unwrap(), &[b"done".to_vec()][..]);
+    }
+
     #[test]
     fn test_write_sized() {
         use std::str::from_utf8;
@@ -1044,6 +2338,40 @@ mod tests {
         read_err("1;no CRLF");
     }
 
+    #[test]
+    fn test_diagnostic() {
+        fn check(diagnostic: Option<Diagnostic>, element: ParseElement, offset: usize,
+                 fragment: &str) {
+            let diagnostic = diagnostic.unwrap();
+            assert_eq!(diagnostic.element, element);
+            assert_eq!(diagnostic.offset, offset);
+            assert_eq!(diagnostic.fragment, fragment);
+        }
+
+        assert_eq!(Diagnostic::request(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n"), None);
+        check(Diagnostic::request(b"\r\nGE,T / HTTP/1.1\r\n\r\n"),
+              ParseElement::RequestLine, 4, "GE,T / HTTP/1.1");
+        check(Diagnostic::request(b"GET / HTTP/2.0\r\n\r\n"),
+              ParseElement::RequestLine, 11, "GET / HTTP/2.0");
+        check(Diagnostic::request(b"GET / HTTP/1.1\r\nHost : a\r\n\r\n"),
+              ParseElement::HeaderName, 20, "Host : a");
+        check(Diagnostic::request(b"GET / HTTP/1.1\r\nHost: a\r\nX: b\x01c\r\n\r\n"),
+              ParseElement::HeaderValue, 29, "X: b\u{1}c");
+        check(Diagnostic::response(b"HTTP/1.1 2OO OK\r\n\r\n"),
+              ParseElement::StatusLine, 10, "HTTP/1.1 2OO OK");
+
+        let long = format!("GET /{} HTTP/1.1 \r\n\r\n", "a".repeat(100));
+        let diagnostic = Diagnostic::request(long.as_bytes()).unwrap();
+        assert_eq!(diagnostic.offset, 114);
+        assert_eq!(diagnostic.fragment.len(), MAX_FRAGMENT);
+        assert!(diagnostic.fragment.ends_with(" HTTP/1.1 "));
+
+        let err = read_chunk_size(&mut &b"1X\r\n"[..]).unwrap_err();
+        check(err.get_ref().and_then(|e| e.downcast_ref::<Diagnostic>()).cloned(),
+              ParseElement::ChunkSize, 1, "1X");
+        assert_eq!(err.to_string(), "invalid chunk size at byte 1: \"1X\"");
+    }
+
     #[test]
     fn test_read_sized_early_eof() {
         let mut r = super::HttpReader::SizedReader(MockStream::with_input(b"foo bar"), 10);
@@ -1084,6 +2412,218 @@ mod tests {
         parse_request(&mut buf).unwrap();
     }
 
+    #[test]
+    fn test_parse_request_with_raw_head() {
+        use super::ColonWhitespace;
+
+        let head = b"GET /echo HTTP/1.1\r\nhost: hyper.rs\r\nAccept:  */*  \r\n\
+                     accept: text/plain\r\nX-Empty:\r\n\r\n";
+        let mut input = head.to_vec();
+        input.extend_from_slice(b"body");
+        let mut raw = MockStream::with_input(&input);
+        let mut buf = BufReader::new(&mut raw);
+        let mut seen = Vec::new();
+        let mut start = Vec::new();
+        parse_request_with(&mut buf, ColonWhitespace::Reject, |raw| {
+            assert_eq!(raw.as_bytes(), &head[..]);
+            start = raw.start_line().to_vec();
+            seen = raw.headers().map(|(n, v)| (n.to_vec(), v.to_vec())).collect();
+        }).unwrap();
+        assert_eq!(start, b"GET /echo HTTP/1.1");
+        assert_eq!(seen, vec![
+            (b"host".to_vec(), b"hyper.rs".to_vec()),
+            (b"Accept".to_vec(), b"*/*".to_vec()),
+            (b"accept".to_vec(), b"text/plain".to_vec()),
+            (b"X-Empty".to_vec(), b"".to_vec()),
+        ]);
+        assert_eq!(buf.get_buf(), b"body");
+    }
+
+    #[test]
+    fn test_try_parse_invalid() {
+        use httparse;
+        use super::{try_parse_invalid, MAX_HEADERS};
+        use super::ColonWhitespace::Strip;
+
+        let head = b"HTTP/1.1 200 OK\r\nBad\x01: 1\r\nno colon\r\nServer: x\r\n\r\n";
+        for end in 0..head.len() {
+            match try_parse_invalid::<httparse::Response, _>(&head[..end], Strip, MAX_HEADERS) {
+                Ok(httparse::Status::Partial) => (),
+                other => panic!("unexpected result at {}: {:?}", end, other)
+            }
+        }
+        let mut buf = head.to_vec();
+        buf.extend_from_slice(b"body");
+        match try_parse_invalid::<httparse::Response, _>(&buf, Strip, MAX_HEADERS) {
+            Ok(httparse::Status::Complete((inc, len))) => {
+                assert_eq!(len, head.len());
+                assert_eq!(inc.headers.get_raw("server"), Some(&[b"x".to_vec()][..]));
+                assert_eq!(inc.headers.get_raw_invalid(),
+                           &[b"Bad\x01: 1".to_vec(), b"no colon".to_vec()][..]);
+            },
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_head_parser() {
+        use super::{HeadParser, HeadError};
+        use error::Error;
+        use method::Method;
+
+        let head = b"\r\nGET /echo HTTP/1.1\r\nHost: hyper.rs\r\nAccept: */*\r\n\r\n";
+        let mut parser = HeadParser::request();
+        for b in &head[..head.len() - 1] {
+            assert!(parser.parse(&[*b]).unwrap().is_none());
+        }
+        let req = parser.parse(b"\nPUT / HTTP/1.1\n\n").unwrap().unwrap();
+        assert_eq!(req.subject.0, Method::Get);
+        assert_eq!(req.headers.get_raw("accept"), Some(&[b"*/*".to_vec()][..]));
+        assert_eq!(parser.rest(), b"PUT / HTTP/1.1\n\n");
+
+        // the next head starts where the last one ended
+        let req = parser.parse(b"").unwrap().unwrap();
+        assert_eq!(req.subject.0, Method::Put);
+        assert!(parser.rest().is_empty());
+        match parser.parse(b"GET / HTTP/x\r\n\r\n") {
+            Err(HeadError::Malformed(Error::Malformed(ref e, ref diagnostic))) => {
+                assert!(is_version_error(e));
+                assert_eq!(diagnostic.offset, 11);
+            },
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        let mut parser = HeadParser::response();
+        parser.set_max_headers(1);
+        assert!(parser.parse(b"HTTP/1.1 200 OK\r\nServer : x\r\n").unwrap().is_none());
+        match parser.parse(b"Date: now\r\n") {
+            Err(HeadError::TooManyHeaders) => (),
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        let mut parser = HeadParser::response();
+        parser.set_max_headers(1);
+        let res = parser.parse(b"HTTP/1.1 200 OK\r\nServer : x\r\n\r\n").unwrap().unwrap();
+        assert_eq!(res.headers.get_raw("server"), Some(&[b"x".to_vec()][..]));
+        assert!(parser.into_rest().is_empty());
+
+        let mut parser = HeadParser::request();
+        parser.set_max_head_size(31);
+        assert!(parser.parse(b"GET / HTTP/1.1\r\nHost: hyper.rs").unwrap().is_none());
+        match parser.parse(b"\r\n") {
+            Err(HeadError::TooLarge) => (),
+            other => panic!("unexpected result: {:?}", other)
+        }
+        let mut parser = HeadParser::request();
+        parser.set_max_head_size(32);
+        match parser.parse(&[b'a'; 33]) {
+            Err(HeadError::TooLarge) => (),
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_parse_colon_whitespace() {
+        use super::ColonWhitespace;
+        use error::Error;
+
+        let head = b"GET / HTTP/1.1\r\nHost: hyper.rs\r\nContent-Length : 5\r\n\r\nhello";
+        let mut raw = MockStream::with_input(head);
+        let mut buf = BufReader::new(&mut raw);
+        match parse_request(&mut buf) {
+            Err(Error::Malformed(ref e, ref diagnostic)) => {
+                match **e {
+                    Error::Header => (),
+                    ref e => panic!("unexpected error: {:?}", e)
+                }
+                assert_eq!(diagnostic.element, ParseElement::HeaderName);
+                assert_eq!(diagnostic.offset, 46);
+                assert_eq!(diagnostic.fragment, "Content-Length : 5");
+            },
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        let mut raw = MockStream::with_input(head);
+        let mut buf = BufReader::new(&mut raw);
+        let req = parse_request_with(&mut buf, ColonWhitespace::Strip, |_| ()).unwrap();
+        assert_eq!(req.headers.get_raw("content-length"), Some(&[b"5".to_vec()][..]));
+        assert!(req.headers.get_raw_invalid().is_empty());
+        assert_eq!(buf.get_buf(), b"hello");
+
+        let mut raw = MockStream::with_input(b"HTTP/1.1 200 OK\r\nServer\t: x\r\n\r\n");
+        let mut buf = BufReader::new(&mut raw);
+        let res = parse_response(&mut buf).unwrap();
+        assert_eq!(res.headers.get_raw("server"), Some(&[b"x".to_vec()][..]));
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        use super::{ColonWhitespace, Limits, parse_request_limited, parse_response_limited};
+        use error::Error;
+
+        let limits = Limits { max_uri_len: 8, max_head_size: 64, max_headers: 2,
+                              max_body_size: None };
+        let parse = |input: &[u8]| {
+            let mut raw = MockStream::with_input(input);
+            let mut buf = BufReader::new(&mut raw);
+            parse_request_limited(&mut buf, ColonWhitespace::Reject, &limits, |_| ())
+        };
+        assert!(parse(b"GET /8-bytes HTTP/1.1\r\nHost: a\r\nAccept: */*\r\n\r\n").is_ok());
+        match parse(b"\r\nGET /9-bytes! HTTP/1.1\r\n\r\n") {
+            Err(Error::UriTooLong) => (),
+            other => panic!("unexpected result: {:?}", other)
+        }
+        match parse(b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n") {
+            Err(Error::TooLarge) => (),
+            other => panic!("unexpected result: {:?}", other)
+        }
+        let mut long = b"GET / HTTP/1.1\r\nCookie: ".to_vec();
+        long.extend_from_slice(&[b'a'; 64]);
+        long.extend_from_slice(b"\r\n\r\n");
+        match parse(&long) {
+            Err(Error::TooLarge) => (),
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        // more headers than fit on the stack
+        let mut many = b"HTTP/1.1 200 OK\r\n".to_vec();
+        for _ in 0..150 {
+            many.extend_from_slice(b"X: y\r\n");
+        }
+        many.extend_from_slice(b"\r\n");
+        let mut raw = MockStream::with_input(&many);
+        let mut buf = BufReader::new(&mut raw);
+        assert!(parse_response(&mut buf).is_err());
+        let limits = Limits { max_headers: 200, ..Limits::default() };
+        let mut raw = MockStream::with_input(&many);
+        let mut buf = BufReader::new(&mut raw);
+        let res = parse_response_limited(&mut buf, &limits).unwrap();
+        assert_eq!(res.headers.get_raw("x").unwrap().len(), 150);
+    }
+
+    #[test]
+    fn test_message_body_limit() {
+        use super::Limits;
+        use error::Error;
+
+        let limits = Limits { max_body_size: Some(4), ..Limits::default() };
+        let raw = MockStream::with_input(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+        let mut msg = Http11Message::with_stream(Box::new(raw));
+        msg.set_limits(limits);
+        match msg.get_incoming() {
+            Err(Error::BodyTooLarge) => (),
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        let raw = MockStream::with_input(b"HTTP/1.1 200 OK\r\n\r\nhello");
+        let mut msg = Http11Message::with_stream(Box::new(raw));
+        msg.set_limits(limits);
+        msg.get_incoming().unwrap();
+        let mut body = Vec::new();
+        let e = msg.read_to_end(&mut body).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn test_parse_raw_status() {
         let mut raw = MockStream::with_input(b"HTTP/1.1 200 OK\r\n\r\n");
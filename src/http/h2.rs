@@ -276,13 +276,28 @@ fn prepare_path(url: Url) -> Vec<u8> {
 /// A helper function that prepares the headers that should be sent in an HTTP/2 message.
 ///
 /// Adapts the `Headers` into a list of octet string pairs.
+///
+/// Connection-specific headers are left out, as HTTP/2 forbids them, except
+/// for `TE: trailers`, which gRPC servers require. So are headers named like
+/// pseudo-headers, which can't be set this way.
 fn prepare_headers(mut headers: Headers) -> Vec<Http2Header> {
-    if headers.remove::<header::Connection>() {
+    if headers.has::<header::Connection>() {
         warn!("The `Connection` header is not valid for an HTTP/2 connection.");
     }
+    let te_trailers = match headers.get_raw("TE") {
+        Some(lines) if lines.len() == 1 => ::std::str::from_utf8(&lines[0])
+            .map(|te| te.trim().eq_ignore_ascii_case("trailers"))
+            .unwrap_or(false),
+        _ => false
+    };
+    headers.remove_hop_by_hop();
+
     let mut http2_headers: Vec<_> = headers.iter().filter_map(|h| {
         if h.is::<header::SetCookie>() {
             None
+        } else if h.name().starts_with(':') {
+            warn!("The pseudo-header {:?} can't be set as a header.", h.name());
+            None
         } else {
             // HTTP/2 header names MUST be lowercase.
             Some((h.name().to_ascii_lowercase().into_bytes(), h.value_string().into_bytes()))
@@ -296,10 +311,34 @@ fn prepare_headers(mut headers: Headers) -> Vec<Http2Header> {
             http2_headers.push((b"set-cookie".to_vec(), cookie.to_string().into_bytes()));
         }
     }
+    if te_trailers {
+        http2_headers.push((b"te".to_vec(), b"trailers".to_vec()));
+    }
 
     http2_headers
 }
 
+// The size of a header block that `solicit` can send, which can't be split
+// into CONTINUATION frames: the default SETTINGS_MAX_FRAME_SIZE.
+const MAX_HEADER_BLOCK: usize = 16_384;
+
+/// Checks that the headers fit in a single HEADERS frame once encoded, so
+/// that a request with too many, or too large, headers fails here, instead
+/// of being refused by the server.
+fn check_header_block(headers: &[Http2Header]) -> ::Result<()> {
+    // without Huffman coding, a literal field takes its name and value, and
+    // at most a few bytes for their lengths
+    let size = headers.iter().fold(0, |size, &(ref name, ref value)| {
+        size + name.len() + value.len() + 8
+    });
+    if size > MAX_HEADER_BLOCK {
+        debug!("HTTP/2 header block of about {} bytes is too large", size);
+        Err(::Error::TooLarge)
+    } else {
+        Ok(())
+    }
+}
+
 /// A helper function that prepares the body for sending in an HTTP/2 request.
 #[inline]
 fn prepare_body(body: Vec<u8>) -> Option<Vec<u8>> {
@@ -315,6 +354,10 @@ fn parse_headers(http2_headers: Vec<Http2Header>) -> ::Result<Headers> {
     // Adapt the header name from `Vec<u8>` to `String`, without making any copies.
     let mut headers = Vec::new();
     for (name, value) in http2_headers.into_iter() {
+        // pseudo-headers, such as `:status`, aren't header fields
+        if name.first() == Some(&b':') {
+            continue;
+        }
         let name = match String::from_utf8(name) {
             Ok(name) => name,
             Err(_) => return Err(From::from(Http2Error::MalformedResponse)),
@@ -377,6 +420,7 @@ impl<S> HttpMessage for Http2Message<S> where S: CloneableStream {
         let method = method.as_ref().as_bytes();
         let path = prepare_path(url);
         let extra_headers = prepare_headers(headers);
+        try!(check_header_block(&extra_headers));
         let body = prepare_body(body);
 
         // Finally, everything is ready and we issue the request.
@@ -400,6 +444,10 @@ impl<S> HttpMessage for Http2Message<S> where S: CloneableStream {
         Ok(head)
     }
 
+    fn version(&self) -> version::HttpVersion {
+        version::HttpVersion::Http20
+    }
+
     fn has_body(&self) -> bool {
         true
     }
@@ -431,10 +479,12 @@ pub fn new_protocol() -> Http2Protocol<HttpConnector, HttpStream> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Http2Protocol, prepare_headers, parse_headers, parse_response};
+    use super::{Http2Protocol, prepare_headers, parse_headers, parse_response,
+                check_header_block};
 
     use std::io::{Read};
 
+    use client::Request;
     use mock::{MockHttp2Connector, MockStream};
     use http::{RequestHead, ResponseHead, Protocol};
 
@@ -695,6 +745,71 @@ mod tests {
         assert_eq!(expected, prepare_headers(headers));
     }
 
+    /// Tests that connection-specific headers are left out, except for `TE: trailers`, and that
+    /// a gRPC content type is kept as it is.
+    #[test]
+    fn test_http2_prepare_headers_grpc() {
+        let mut headers = Headers::new();
+        headers.set_raw("Content-Type", vec![b"application/grpc+proto".to_vec()]);
+        headers.set_raw("TE", vec![b"trailers".to_vec()]);
+        headers.set(header::Connection::keep_alive());
+        headers.set_raw(":path", vec![b"/evil".to_vec()]);
+        let mut h2headers = prepare_headers(headers);
+        h2headers.sort();
+
+        assert_eq!(h2headers, vec![
+            (b"content-type".to_vec(), b"application/grpc+proto".to_vec()),
+            (b"te".to_vec(), b"trailers".to_vec()),
+        ]);
+
+        let mut headers = Headers::new();
+        headers.set_raw("TE", vec![b"gzip".to_vec()]);
+        assert_eq!(prepare_headers(headers), vec![]);
+    }
+
+    /// Tests that a header block too large for one frame is refused before it's sent.
+    #[test]
+    fn test_http2_header_block_too_large() {
+        let mut headers = Headers::new();
+        headers.set_raw("x-large", vec![vec![b'a'; 16_000]]);
+        assert!(check_header_block(&prepare_headers(headers.clone())).is_ok());
+
+        headers.set_raw("x-larger", vec![vec![b'a'; 1_000]]);
+        match check_header_block(&prepare_headers(headers)) {
+            Err(::Error::TooLarge) => (),
+            other => panic!("expected TooLarge, got {:?}", other),
+        }
+    }
+
+    /// Tests that a response of only headers, as gRPC sends for errors, is read, with its
+    /// headers kept as they were sent.
+    #[test]
+    fn test_http2_response_trailers_only() {
+        let mut mock_connector = MockHttp2Connector::new();
+        let mut headers = Headers::new();
+        headers.set_raw("content-type", vec![b"application/grpc".to_vec()]);
+        headers.set_raw("grpc-status", vec![b"5".to_vec()]);
+        mock_connector.new_response_stream(b"200", &headers, None);
+        let protocol = Http2Protocol::with_connector(mock_connector);
+
+        let mut message = protocol.new_message("127.0.0.1", 1337, "http").unwrap();
+        message.set_outgoing(RequestHead {
+            headers: Headers::new(),
+            method: method::Method::Post,
+            url: Url::parse("http://127.0.0.1/pkg.Service/Method").unwrap(),
+        }).unwrap();
+        let resp = message.get_incoming().unwrap();
+
+        assert_eq!(resp.raw_status.0, 200);
+        assert_eq!(resp.headers.get_raw("grpc-status"), Some(&[b"5".to_vec()][..]));
+        assert_eq!(resp.headers.get_raw("content-type"),
+                   Some(&[b"application/grpc".to_vec()][..]));
+        assert!(resp.headers.get_raw(":status").is_none());
+        let mut body = Vec::new();
+        message.read_to_end(&mut body).unwrap();
+        assert!(body.is_empty());
+    }
+
     /// Tests that the headers of a response are correctly parsed when they include a `Set-Cookie`
     /// header.
     #[test]
@@ -820,4 +935,18 @@ mod tests {
         assert!(headers.has::<header::ContentLength>());
         assert_eq!(version, version::HttpVersion::Http20);
     }
+
+    /// Tests that a client request on an `Http2Message` is sent with HTTP/2.
+    #[test]
+    fn test_http2_request_version() {
+        let mut mock_connector = MockHttp2Connector::new();
+        mock_connector.new_response_stream(b"200", &Headers::new(), None);
+        let protocol = Http2Protocol::with_connector(mock_connector);
+
+        let message = protocol.new_message("127.0.0.1", 1337, "http").unwrap();
+        let req = Request::with_message(method::Method::Get,
+                                        Url::parse("http://127.0.0.1/hello").unwrap(),
+                                        message).unwrap();
+        assert_eq!(req.version, version::HttpVersion::Http20);
+    }
 }
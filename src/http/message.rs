@@ -3,11 +3,9 @@
 
 use std::any::{Any, TypeId};
 use std::fmt::Debug;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::mem;
 
-#[cfg(feature = "timeouts")]
-use std::io;
 #[cfg(feature = "timeouts")]
 use std::time::Duration;
 
@@ -15,6 +13,8 @@ use typeable::Typeable;
 
 use header::Headers;
 use http::RawStatus;
+use http::h1::Limits;
+use net::{AbortHandle, ConnectInfo};
 use url::Url;
 
 use method;
@@ -26,6 +26,9 @@ use traitobject;
 pub trait Protocol {
     /// Creates a fresh `HttpMessage` bound to the given host, based on the given protocol scheme.
     fn new_message(&self, host: &str, port: u16, scheme: &str) -> ::Result<Box<HttpMessage>>;
+
+    /// Closes the connections kept idle for reuse, if any are.
+    fn close_idle(&self) {}
 }
 
 /// Describes a request.
@@ -59,6 +62,15 @@ pub trait HttpMessage: Write + Read + Send + Any + Typeable + Debug {
     /// After this, the `HttpMessage` instance can be used as an `io::Write` in order to write the
     /// body of the request.
     fn set_outgoing(&mut self, head: RequestHead) -> ::Result<RequestHead>;
+    /// Initiates a new outgoing request, like `set_outgoing`, but writing
+    /// `raw` as its head byte for byte. `head` is what `raw` was parsed
+    /// into.
+    ///
+    /// The raw head of the response is kept, for `raw_head`.
+    fn set_outgoing_raw(&mut self, _head: RequestHead, _raw: &[u8]) -> ::Result<RequestHead> {
+        Err(::Error::Io(io::Error::new(io::ErrorKind::Other,
+                                       "writing a raw head is not supported")))
+    }
     /// Obtains the incoming response and returns its head (i.e. the `ResponseHead` struct)
     ///
     /// After this, the `HttpMessage` instance can be used as an `io::Read` in order to read out
@@ -70,8 +82,55 @@ pub trait HttpMessage: Write + Read + Send + Any + Typeable + Debug {
     /// Set the write timeout duration for this message.
     #[cfg(feature = "timeouts")]
     fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()>;
+    /// Waits up to `timeout` for a `100 Continue` to an outgoing request
+    /// sent with `Expect: 100-continue`, before its body is written.
+    ///
+    /// Returns whether to write the body, which is `false` once the final
+    /// response came instead.
+    #[cfg(feature = "timeouts")]
+    fn wait_for_continue(&mut self, _timeout: Duration) -> ::Result<bool> {
+        Ok(true)
+    }
     /// Closes the underlying HTTP connection.
     fn close_connection(&mut self) -> ::Result<()>;
+    /// Ends the outgoing message, and shuts down writing to the underlying
+    /// connection, so that the peer reads the end of the stream after it.
+    ///
+    /// The incoming message can still be read, but the connection can't be
+    /// reused after.
+    fn shutdown_write(&mut self) -> ::Result<()> {
+        Err(::Error::Io(io::Error::new(io::ErrorKind::Other,
+                                       "shutting down writing is not supported")))
+    }
+    /// The trailer fields of the incoming message, once its body has been
+    /// read to the end.
+    fn trailers(&self) -> Option<&Headers> {
+        None
+    }
+    /// Set the limits on the size of the incoming message, before it is
+    /// obtained with `get_incoming`.
+    ///
+    /// Messages that can't enforce them ignore them.
+    fn set_limits(&mut self, _limits: Limits) {}
+    /// The incoming message head exactly as it was received, if it was kept
+    /// for a request started with `set_outgoing_raw`.
+    fn raw_head(&self) -> Option<&[u8]> {
+        None
+    }
+    /// What is known about how the connection of this message was made, if
+    /// it was made for it.
+    fn connect_info(&self) -> Option<&ConnectInfo> {
+        None
+    }
+    /// Get a handle that shuts down the connection of this message from
+    /// another thread, if it can be.
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        None
+    }
+    /// The HTTP version the outgoing message is sent with.
+    fn version(&self) -> version::HttpVersion {
+        version::HttpVersion::Http11
+    }
     /// Returns whether the incoming message has a body.
     fn has_body(&self) -> bool;
 }
@@ -2,16 +2,20 @@
 use std::borrow::Cow;
 
 use header::Connection;
-use header::ConnectionOption::{KeepAlive, Close};
+use header::ConnectionOption::{self, KeepAlive, Close};
 use header::Headers;
 use version::HttpVersion;
-use version::HttpVersion::{Http10, Http11};
+use version::HttpVersion::{Http09, Http10, Http11, Http20};
 
 #[cfg(feature = "serde-serialization")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+pub use self::conn::{BufferPool, Conn, ConnInfo, Observer, ReadStatus, RequestInfo};
+pub use self::conn::ResponseBody;
 pub use self::message::{HttpMessage, RequestHead, ResponseHead, Protocol};
 
+pub mod conn;
+pub mod fallback;
 pub mod h1;
 pub mod h2;
 pub mod message;
@@ -39,11 +43,14 @@ impl Deserialize for RawStatus {
 #[inline]
 pub fn should_keep_alive(version: HttpVersion, headers: &Headers) -> bool {
     trace!("should_keep_alive( {:?}, {:?} )", version, headers.get::<Connection>());
-    match (version, headers.get::<Connection>()) {
-        (Http10, None) => false,
-        (Http10, Some(conn)) if !conn.contains(&KeepAlive) => false,
-        (Http11, Some(conn)) if conn.contains(&Close)  => false,
-        _ => true
+    let conn = headers.get::<Connection>();
+    let has = |option: &ConnectionOption| conn.map_or(false, |conn| conn.contains(option));
+    match version {
+        // HTTP/0.9 has no persistent connections
+        Http09 => false,
+        _ if has(&Close) => false,
+        Http10 => has(&KeepAlive),
+        Http11 | Http20 => true,
     }
 }
 
@@ -61,4 +68,12 @@ fn test_should_keep_alive() {
     headers.set(Connection::keep_alive());
     assert!(should_keep_alive(Http10, &headers));
     assert!(should_keep_alive(Http11, &headers));
+
+    headers.set(Connection(vec![KeepAlive, Close]));
+    assert!(!should_keep_alive(Http10, &headers));
+    assert!(!should_keep_alive(Http11, &headers));
+
+    headers.remove::<Connection>();
+    assert!(!should_keep_alive(Http09, &headers));
+    assert!(should_keep_alive(Http20, &headers));
 }
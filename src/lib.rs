@@ -131,6 +131,8 @@
 extern crate rustc_serialize as serialize;
 extern crate time;
 extern crate url;
+#[cfg(unix)]
+extern crate libc;
 #[cfg(feature = "openssl")]
 extern crate openssl;
 #[cfg(feature = "serde-serialization")]
@@ -183,13 +185,21 @@ macro_rules! inspect(
 mod mock;
 #[doc(hidden)]
 pub mod buffer;
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod client;
+pub mod clock;
+pub mod dump;
 pub mod error;
+pub mod fault;
 pub mod method;
 pub mod header;
 pub mod http;
+pub mod multipart;
 pub mod net;
+pub mod problem;
 pub mod server;
+pub mod signatures;
 pub mod status;
 pub mod uri;
 pub mod version;
@@ -211,4 +221,5 @@ fn _assert_send<T: Send>() {
 fn _assert_sync<T: Sync>() {
     _assert_sync::<Client>();
     _assert_sync::<error::Error>();
+    _assert_sync::<server::Server>();
 }
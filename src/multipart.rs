@@ -0,0 +1,780 @@
+//! `multipart/form-data` bodies, from RFC 7578.
+//!
+//! A `Form` builds the body of a request from text fields and files, which
+//! are read as the body is sent, not before:
+//!
+//! ```no_run
+//! use hyper::Client;
+//! use hyper::multipart::Form;
+//!
+//! let mut form = Form::new();
+//! form.add_text("title", "Holiday");
+//! form.add_file("photo", "beach.jpg").unwrap();
+//! let mut body = form.into_body();
+//! Client::new().post("http://example.domain/upload")
+//!     .header(body.content_type())
+//!     .body(body.as_body())
+//!     .send().unwrap();
+//! ```
+//!
+//! The body has a `Content-Length` if the length of every file is known,
+//! and is chunked otherwise.
+//!
+//! A `Multipart` reads the parts of a body one at a time, each part being
+//! a `Read` of its data, so that files can be written out as they arrive:
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io;
+//! use hyper::multipart::{self, Multipart};
+//! use hyper::server::{Request, Response};
+//! use hyper::status::StatusCode;
+//!
+//! fn upload(mut req: Request, mut res: Response) {
+//!     let boundary = match multipart::boundary(&req.headers) {
+//!         Some(boundary) => boundary,
+//!         None => {
+//!             *res.status_mut() = StatusCode::UnsupportedMediaType;
+//!             return;
+//!         }
+//!     };
+//!     let mut parts = Multipart::new(&mut req, &boundary);
+//!     while let Some(mut part) = parts.next_part().unwrap() {
+//!         if part.filename() == Some("beach.jpg") {
+//!             io::copy(&mut part, &mut File::create("upload.jpg").unwrap()).unwrap();
+//!         }
+//!     }
+//! }
+//! ```
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use httparse;
+use time;
+use unicase::UniCase;
+
+use client::Body;
+use header::{ContentType, Headers};
+use mime::{Attr, Mime, SubLevel, TopLevel, Value};
+
+// the most bytes the headers of a part can take
+const MAX_HEAD_SIZE: usize = 8192;
+// the most headers a part can have
+const MAX_HEADERS: usize = 32;
+
+/// The fields of a form, to send as a `multipart/form-data` body.
+pub struct Form<'a> {
+    boundary: String,
+    // the headers of each part, after the boundary line, and its data
+    parts: Vec<(String, Data<'a>)>,
+}
+
+enum Data<'a> {
+    Bytes(Vec<u8>),
+    Reader(Box<Read + 'a>, Option<u64>),
+}
+
+impl<'a> Form<'a> {
+    /// Creates a form without fields.
+    pub fn new() -> Form<'a> {
+        Form {
+            boundary: new_boundary("form"),
+            parts: Vec::new(),
+        }
+    }
+
+    /// Get the boundary between the parts of the body.
+    ///
+    /// A new one is picked when a field is added whose name, or text, holds
+    /// it. The data of streams isn't known before it is sent, so it isn't
+    /// checked.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Adds a text field.
+    pub fn add_text(&mut self, name: &str, value: &str) {
+        let head = head(name, None, None);
+        self.parts.push((head, Data::Bytes(value.as_bytes().to_vec())));
+        self.check_boundary();
+    }
+
+    /// Adds the file at `path`, sent as `application/octet-stream` with the
+    /// last component of the path as its file name.
+    pub fn add_file<P: AsRef<Path>>(&mut self, name: &str, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let file = try!(File::open(path));
+        let len = try!(file.metadata()).len();
+        let filename = path.file_name().map(|name| name.to_string_lossy().into_owned());
+        self.add_stream(name, file, Some(len), filename.as_ref().map(|name| &name[..]), None);
+        Ok(())
+    }
+
+    /// Adds a field whose value is read from `reader` as the body is sent.
+    ///
+    /// A `len` given must be the number of bytes `reader` has. A
+    /// `content_type` defaults to `application/octet-stream` for a field
+    /// with a `filename`.
+    pub fn add_stream<R: Read + 'a>(&mut self, name: &str, reader: R, len: Option<u64>,
+                                    filename: Option<&str>, content_type: Option<Mime>) {
+        let content_type = match (content_type, filename) {
+            (None, Some(_)) => Some(Mime(TopLevel::Application, SubLevel::Ext("octet-stream".to_owned()), vec![])),
+            (content_type, _) => content_type
+        };
+        let head = head(name, filename, content_type);
+        self.parts.push((head, Data::Reader(Box::new(reader), len)));
+        self.check_boundary();
+    }
+
+    /// Get the `Content-Type` of the body, with its boundary.
+    pub fn content_type(&self) -> ContentType {
+        ContentType(Mime(TopLevel::Multipart, SubLevel::FormData,
+                         vec![(Attr::Boundary, Value::Ext(self.boundary.clone()))]))
+    }
+
+    /// Get the length of the body, if the length of every field is known.
+    pub fn content_length(&self) -> Option<u64> {
+        let close = self.close();
+        self.parts.iter().enumerate().fold(Some(close.len() as u64), |len, (i, part)| {
+            let data = match part.1 {
+                Data::Bytes(ref bytes) => Some(bytes.len() as u64),
+                Data::Reader(_, len) => len,
+            };
+            let delimiter = self.delimiter(i).len() as u64;
+            match (len, data) {
+                (Some(len), Some(data)) => Some(len + delimiter + part.0.len() as u64 + data),
+                _ => None
+            }
+        })
+    }
+
+    /// Turns the form into a body to send.
+    pub fn into_body(self) -> FormBody<'a> {
+        let content_type = self.content_type();
+        let len = self.content_length();
+        let close = self.close();
+        let delimiters = (0..self.parts.len()).map(|i| self.delimiter(i)).collect::<Vec<_>>();
+        let mut segments = VecDeque::new();
+        for ((head, data), delimiter) in self.parts.into_iter().zip(delimiters) {
+            let head = delimiter + &head;
+            segments.push_back(Segment::Bytes(Cursor::new(head.into_bytes())));
+            segments.push_back(match data {
+                Data::Bytes(bytes) => Segment::Bytes(Cursor::new(bytes)),
+                Data::Reader(reader, len) => Segment::Reader(reader, len),
+            });
+        }
+        segments.push_back(Segment::Bytes(Cursor::new(close.into_bytes())));
+        FormBody {
+            content_type: content_type,
+            len: len,
+            segments: segments,
+        }
+    }
+
+    // Picks new boundaries until none of the fields known holds it.
+    fn check_boundary(&mut self) {
+        while self.parts.iter().any(|&(ref head, ref data)| {
+            let boundary = self.boundary.as_bytes();
+            contains(head.as_bytes(), boundary) || match *data {
+                Data::Bytes(ref bytes) => contains(bytes, boundary),
+                Data::Reader(..) => false,
+            }
+        }) {
+            debug!("form field holds boundary {:?}, picking another", self.boundary);
+            self.boundary = new_boundary("form");
+        }
+    }
+
+    // The line before the headers of the `i`th part.
+    fn delimiter(&self, i: usize) -> String {
+        if i == 0 {
+            format!("--{}\r\n", self.boundary)
+        } else {
+            format!("\r\n--{}\r\n", self.boundary)
+        }
+    }
+
+    fn close(&self) -> String {
+        if self.parts.is_empty() {
+            format!("--{}--\r\n", self.boundary)
+        } else {
+            format!("\r\n--{}--\r\n", self.boundary)
+        }
+    }
+}
+
+impl<'a> fmt::Debug for Form<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Form")
+            .field("boundary", &self.boundary)
+            .field("parts", &self.parts.len())
+            .finish()
+    }
+}
+
+impl<'a> Default for Form<'a> {
+    fn default() -> Form<'a> {
+        Form::new()
+    }
+}
+
+fn head(name: &str, filename: Option<&str>, content_type: Option<Mime>) -> String {
+    let mut head = format!("Content-Disposition: form-data; name=\"{}\"", escape(name));
+    if let Some(filename) = filename {
+        head.push_str(&format!("; filename=\"{}\"", escape(filename)));
+    }
+    if let Some(content_type) = content_type {
+        head.push_str(&format!("\r\nContent-Type: {}", content_type));
+    }
+    head.push_str("\r\n\r\n");
+    head
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+// Quotes in names are percent-encoded, as browsers do, and so are line
+// breaks, which would end the header.
+fn escape(s: &str) -> String {
+    s.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// A `multipart/form-data` body, made by `Form::into_body`.
+pub struct FormBody<'a> {
+    content_type: ContentType,
+    len: Option<u64>,
+    segments: VecDeque<Segment<'a>>,
+}
+
+enum Segment<'a> {
+    Bytes(Cursor<Vec<u8>>),
+    // the bytes left to read, if known
+    Reader(Box<Read + 'a>, Option<u64>),
+}
+
+impl<'a> FormBody<'a> {
+    /// Get the `Content-Type` of the body, with its boundary.
+    pub fn content_type(&self) -> ContentType {
+        self.content_type.clone()
+    }
+
+    /// Get the length of the body, if it is known.
+    pub fn content_length(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Borrows the body to send, sized if its length is known and chunked
+    /// otherwise.
+    pub fn as_body<'b>(&'b mut self) -> Body<'b> where 'a: 'b {
+        match self.len {
+            Some(len) => Body::SizedBody(self, len),
+            None => Body::ChunkedBody(self),
+        }
+    }
+}
+
+impl<'a> Read for FormBody<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(segment) = self.segments.front_mut() {
+            let n = match *segment {
+                Segment::Bytes(ref mut bytes) => try!(bytes.read(buf)),
+                Segment::Reader(ref mut reader, Some(ref mut left)) => {
+                    let len = min(*left, buf.len() as u64) as usize;
+                    let n = try!(reader.read(&mut buf[..len]));
+                    if n == 0 && *left > 0 {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                  "form field shorter than its length"));
+                    }
+                    *left -= n as u64;
+                    n
+                },
+                Segment::Reader(ref mut reader, None) => try!(reader.read(buf)),
+            };
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            self.segments.pop_front();
+        }
+        Ok(0)
+    }
+}
+
+impl<'a> fmt::Debug for FormBody<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FormBody")
+            .field("content_type", &self.content_type)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// The boundary of a `multipart` body, from the `Content-Type` in its
+/// headers.
+pub fn boundary(headers: &Headers) -> Option<String> {
+    match headers.get::<ContentType>() {
+        Some(&ContentType(Mime(TopLevel::Multipart, _, ref params))) => {
+            params.iter().filter_map(|&(ref attr, ref value)| match (attr, value) {
+                (&Attr::Boundary, &Value::Ext(ref boundary)) => Some(boundary.clone()),
+                _ => None
+            }).next()
+        },
+        _ => None
+    }
+}
+
+/// A boundary unlike any other made by this process.
+///
+/// It isn't random, so a part could hold it, such as a multipart body made
+/// by this process. Parts that are known up front should be checked.
+#[doc(hidden)]
+pub fn new_boundary(kind: &str) -> String {
+    static BOUNDARIES: AtomicUsize = ATOMIC_USIZE_INIT;
+    format!("hyper-{}-{:x}-{:x}",
+            kind, time::precise_time_ns(), BOUNDARIES.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Reads the parts of a `multipart` body, such as `multipart/form-data`.
+///
+/// Only a few kilobytes of the body are buffered at a time. What comes
+/// before the first part and after the last is skipped, as is what is left
+/// of a part when the next is read.
+pub struct Multipart<R> {
+    body: R,
+    // the CRLF and dashes before the boundary, ending each part
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    eof: bool,
+    state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    // in the data of a part, or before the first
+    Data,
+    // just past a delimiter
+    Boundary,
+    Done,
+}
+
+impl<R: Read> Multipart<R> {
+    /// Reads the parts of `body`, separated by `boundary`.
+    pub fn new(body: R, boundary: &str) -> Multipart<R> {
+        let delimiter = format!("\r\n--{}", boundary).into_bytes();
+        let mut buf = vec![0; max(MAX_HEAD_SIZE, delimiter.len() * 2)];
+        // the first boundary needn't follow a line break
+        buf[..2].copy_from_slice(b"\r\n");
+        Multipart {
+            body: body,
+            delimiter: delimiter,
+            buf: buf,
+            start: 0,
+            end: 2,
+            eof: false,
+            state: State::Data,
+        }
+    }
+
+    /// Reads the headers of the next part, returning `None` after the last.
+    pub fn next_part(&mut self) -> io::Result<Option<Part<R>>> {
+        let mut skipped = [0; 1024];
+        while self.state == State::Data {
+            try!(self.read_data(&mut skipped));
+        }
+        if self.state == State::Done {
+            return Ok(None);
+        }
+
+        // a delimiter is followed by dashes after the last part, and
+        // otherwise by a line break, maybe after some whitespace
+        try!(self.fill_or_eof(2));
+        if &self.buf[self.start..self.start + 2] == b"--" {
+            self.state = State::Done;
+            return Ok(None);
+        }
+        loop {
+            try!(self.fill_or_eof(1));
+            match self.buf[self.start] {
+                b' ' | b'\t' => self.start += 1,
+                _ => break
+            }
+        }
+        try!(self.fill_or_eof(2));
+        if &self.buf[self.start..self.start + 2] != b"\r\n" {
+            return Err(invalid("invalid multipart delimiter line"));
+        }
+        self.start += 2;
+
+        let headers;
+        loop {
+            let parsed = {
+                let mut raw = [httparse::EMPTY_HEADER; MAX_HEADERS];
+                match httparse::parse_headers(&self.buf[self.start..self.end], &mut raw) {
+                    Ok(httparse::Status::Complete((len, raw))) => Some((len, Headers::from_raw(raw))),
+                    Ok(httparse::Status::Partial) => None,
+                    Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
+                }
+            };
+            match parsed {
+                Some((len, parsed_headers)) => {
+                    self.start += len;
+                    headers = try!(parsed_headers.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)));
+                    break;
+                },
+                None => {
+                    let buffered = self.end - self.start;
+                    if buffered >= MAX_HEAD_SIZE {
+                        return Err(invalid("multipart headers too large"));
+                    }
+                    try!(self.fill_or_eof(buffered + 1));
+                }
+            }
+        }
+        self.state = State::Data;
+
+        let (name, filename) = match headers.get_raw("Content-Disposition") {
+            Some(raw) if !raw.is_empty() => {
+                let value = String::from_utf8_lossy(&raw[0]);
+                (disposition_param(&value, "name"), disposition_param(&value, "filename"))
+            },
+            _ => (None, None)
+        };
+        Ok(Some(Part {
+            headers: headers,
+            name: name,
+            filename: filename,
+            multipart: self,
+        }))
+    }
+
+    /// Get a reference to the body being read.
+    pub fn get_ref(&self) -> &R {
+        &self.body
+    }
+
+    /// Unwraps the body, dropping what was buffered of it.
+    pub fn into_inner(self) -> R {
+        self.body
+    }
+
+    // Reads data of the part being read, returning 0 once at its end.
+    fn read_data(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.state != State::Data || out.is_empty() {
+            return Ok(0);
+        }
+        let len = self.delimiter.len();
+        try!(self.fill(len));
+        let n = {
+            let buffered = &self.buf[self.start..self.end];
+            match buffered.windows(len).position(|w| w == &self.delimiter[..]) {
+                Some(0) => {
+                    self.start += len;
+                    self.state = State::Boundary;
+                    return Ok(0);
+                },
+                Some(n) => n,
+                // the end could be the start of a delimiter
+                None => buffered.len().saturating_sub(len - 1),
+            }
+        };
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      "multipart body ended within a part"));
+        }
+        let n = min(n, out.len());
+        out[..n].copy_from_slice(&self.buf[self.start..self.start + n]);
+        self.start += n;
+        Ok(n)
+    }
+
+    // Reads until `want` bytes are buffered, returning false if the body
+    // ended first. `want` is never more than fits in `buf`.
+    fn fill(&mut self, want: usize) -> io::Result<bool> {
+        while self.end - self.start < want {
+            if self.eof {
+                return Ok(false);
+            }
+            if self.end == self.buf.len() {
+                let len = self.buf.len();
+                self.buf.drain(..self.start);
+                self.buf.resize(len, 0);
+                self.end -= self.start;
+                self.start = 0;
+            }
+            match try!(self.body.read(&mut self.buf[self.end..])) {
+                0 => self.eof = true,
+                n => self.end += n,
+            }
+        }
+        Ok(true)
+    }
+
+    fn fill_or_eof(&mut self, want: usize) -> io::Result<()> {
+        if try!(self.fill(want)) {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::UnexpectedEof, "multipart body ended early"))
+        }
+    }
+}
+
+impl<R> fmt::Debug for Multipart<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Multipart")
+            .field("delimiter", &String::from_utf8_lossy(&self.delimiter))
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+// Finds a parameter of a `Content-Disposition`, unquoting its value.
+fn disposition_param(value: &str, param: &str) -> Option<String> {
+    let mut chars = value.chars().peekable();
+    // the disposition type
+    while let Some(c) = chars.next() {
+        if c == ';' {
+            break;
+        }
+    }
+    loop {
+        let mut name = String::new();
+        loop {
+            match chars.next() {
+                Some('=') => break,
+                Some(c) => name.push(c),
+                None => return None
+            }
+        }
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    // backslashes are kept, since old browsers send
+                    // Windows paths as file names
+                    Some('\\') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        value.push('"');
+                    },
+                    Some('"') | None => break,
+                    Some(c) => value.push(c),
+                }
+            }
+            while let Some(c) = chars.next() {
+                if c == ';' {
+                    break;
+                }
+            }
+        } else {
+            while let Some(c) = chars.next() {
+                if c == ';' {
+                    break;
+                }
+                value.push(c);
+            }
+            value = value.trim().to_owned();
+        }
+        if UniCase(name.trim()) == UniCase(param) {
+            return Some(value);
+        }
+    }
+}
+
+/// A part of a `multipart` body, and a `Read` of its data.
+pub struct Part<'a, R: 'a> {
+    /// The headers of the part.
+    pub headers: Headers,
+    name: Option<String>,
+    filename: Option<String>,
+    multipart: &'a mut Multipart<R>,
+}
+
+impl<'a, R: Read> Part<'a, R> {
+    /// Get the name of the form field, from the `Content-Disposition`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|name| &name[..])
+    }
+
+    /// Get the file name of an uploaded file, from the
+    /// `Content-Disposition`.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_ref().map(|name| &name[..])
+    }
+
+    /// Get the media type of the data, if the part has a `Content-Type`.
+    pub fn content_type(&self) -> Option<&Mime> {
+        self.headers.get::<ContentType>().map(|content_type| &content_type.0)
+    }
+}
+
+impl<'a, R: Read> Read for Part<'a, R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.multipart.read_data(buf)
+    }
+}
+
+impl<'a, R> fmt::Debug for Part<'a, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Part")
+            .field("headers", &self.headers)
+            .field("name", &self.name)
+            .field("filename", &self.filename)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read};
+
+    use header::{ContentType, Headers};
+
+    use super::{boundary, Form, Multipart};
+
+    // gives at most `step` bytes a read, like a socket might
+    struct Trickle<'a>(&'a [u8], usize);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = ::std::cmp::min(self.1, buf.len());
+            (&mut self.0).read(&mut buf[..n])
+        }
+    }
+
+    fn sample() -> Form<'static> {
+        let mut form = Form::new();
+        form.add_text("title", "Holiday");
+        form.add_stream("photo", Cursor::new(b"\r\n--not the end".to_vec()), Some(15),
+                        Some("beach \"1\".jpg"), None);
+        form
+    }
+
+    #[test]
+    fn test_form_body() {
+        let form = sample();
+        let b = form.boundary().to_owned();
+        let expected = format!("--{b}\r\nContent-Disposition: form-data; name=\"title\"\r\n\
+                                \r\nHoliday\r\n\
+                                --{b}\r\nContent-Disposition: form-data; name=\"photo\"; \
+                                filename=\"beach %221%22.jpg\"\r\n\
+                                Content-Type: application/octet-stream\r\n\
+                                \r\n\r\n--not the end\r\n\
+                                --{b}--\r\n", b = b);
+        assert_eq!(form.content_length(), Some(expected.len() as u64));
+        let mut body = form.into_body();
+        assert_eq!(boundary(&header(body.content_type())), Some(b));
+        let mut s = String::new();
+        body.read_to_string(&mut s).unwrap();
+        assert_eq!(s, expected);
+
+        // a stream without a length makes the body chunked
+        let mut form = Form::new();
+        form.add_stream("data", io::empty(), None, None, None);
+        assert_eq!(form.content_length(), None);
+
+        // a stream shorter than its length fails the body
+        let mut form = Form::new();
+        form.add_stream("data", io::empty(), Some(1), None, None);
+        assert!(form.into_body().read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_form_boundary_not_in_fields() {
+        let mut form = Form::new();
+        let first = form.boundary().to_owned();
+        form.add_text("echo", &format!("--{}--", first));
+        let second = form.boundary().to_owned();
+        assert!(second != first);
+        form.add_stream(&second, io::empty(), Some(0), None, None);
+        assert!(form.boundary() != first && form.boundary() != second);
+
+        let b = form.boundary().to_owned();
+        let mut encoded = Vec::new();
+        form.into_body().read_to_end(&mut encoded).unwrap();
+        let mut parts = Multipart::new(&encoded[..], &b);
+        let mut s = String::new();
+        parts.next_part().unwrap().unwrap().read_to_string(&mut s).unwrap();
+        assert_eq!(s, format!("--{}--", first));
+        assert_eq!(parts.next_part().unwrap().unwrap().name(), Some(&second[..]));
+        assert!(parts.next_part().unwrap().is_none());
+    }
+
+    fn header(content_type: ContentType) -> Headers {
+        let mut headers = Headers::new();
+        headers.set(content_type);
+        headers
+    }
+
+    #[test]
+    fn test_multipart_round_trip() {
+        let form = sample();
+        let b = form.boundary().to_owned();
+        let mut encoded = b"preamble\r\n".to_vec();
+        form.into_body().read_to_end(&mut encoded).unwrap();
+        encoded.extend_from_slice(b"epilogue");
+
+        for step in &[1, 3, 7, 1000] {
+            let mut parts = Multipart::new(Trickle(&encoded, *step), &b);
+            {
+                let mut part = parts.next_part().unwrap().unwrap();
+                assert_eq!(part.name(), Some("title"));
+                assert_eq!(part.filename(), None);
+                let mut s = String::new();
+                part.read_to_string(&mut s).unwrap();
+                assert_eq!(s, "Holiday");
+            }
+            {
+                let mut part = parts.next_part().unwrap().unwrap();
+                assert_eq!(part.name(), Some("photo"));
+                assert_eq!(part.filename(), Some("beach %221%22.jpg"));
+                assert_eq!(part.content_type().unwrap().to_string(), "application/octet-stream");
+                let mut s = String::new();
+                part.read_to_string(&mut s).unwrap();
+                assert_eq!(s, "\r\n--not the end");
+            }
+            assert!(parts.next_part().unwrap().is_none());
+            assert!(parts.next_part().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_multipart_parse() {
+        let body = b"--xyz  \r\n\
+                     Content-Disposition: form-data; name=\"a;b\"; filename=\"C:\\x\\\"y\\\"\"\r\n\
+                     \r\n\
+                     skipped\r\n\
+                     --xyz\r\n\
+                     Content-Disposition: form-data; name=c\r\n\
+                     \r\n\
+                     last";
+        let mut parts = Multipart::new(&body[..], "xyz");
+        {
+            let part = parts.next_part().unwrap().unwrap();
+            assert_eq!(part.name(), Some("a;b"));
+            assert_eq!(part.filename(), Some("C:\\x\"y\""));
+        }
+        // the rest of a part isn't needed to read the next
+        let mut part = parts.next_part().unwrap().unwrap();
+        assert_eq!(part.name(), Some("c"));
+        let err = part.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut parts = Multipart::new(&b"--xyz\r\nBad Header\r\n\r\n"[..], "xyz");
+        assert!(parts.next_part().is_err());
+    }
+}
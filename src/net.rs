@@ -1,16 +1,25 @@
 //! A collection of traits abstracting over Listeners and Streams.
 use std::any::{Any, TypeId};
+use std::collections::HashMap;
 use std::fmt;
 use std::io::{self, ErrorKind, Read, Write};
-use std::net::{SocketAddr, ToSocketAddrs, TcpStream, TcpListener, Shutdown};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs, TcpStream, TcpListener, Shutdown};
 use std::mem;
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
 
 #[cfg(feature = "openssl")]
 pub use self::openssl::Openssl;
+#[cfg(unix)]
+pub use self::unix::{PeerCredentials, UnixConnector, UnixListener, UnixStream};
+#[cfg(all(target_os = "linux", feature = "seqpacket"))]
+pub use self::unix::{SeqPacketConnector, SeqPacketListener, SeqPacketStream};
+#[cfg(all(target_os = "linux", feature = "vsock"))]
+pub use self::vsock::{VsockAddr, VsockConnector, VsockListener, VsockStream};
 
-#[cfg(feature = "timeouts")]
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use header::Headers;
 use typeable::Typeable;
 use traitobject;
 
@@ -47,6 +56,10 @@ impl<'a, N: NetworkListener + 'a> Iterator for NetworkConnections<'a, N> {
     }
 }
 
+/// Shuts a stream down when called, as returned by
+/// `NetworkStream::abort_handle`.
+pub type AbortHandle = Box<Fn() + Send + Sync>;
+
 /// An abstraction over streams that a Server can utilize.
 pub trait NetworkStream: Read + Write + Any + Send + Typeable {
     /// Get the remote address of the underlying connection.
@@ -66,6 +79,25 @@ pub trait NetworkStream: Read + Write + Any + Send + Typeable {
         Ok(())
     }
 
+    /// Get the headers to add to requests sent on this stream, if it goes
+    /// to an HTTP proxy that forwards them, rather than to the origin
+    /// server.
+    ///
+    /// Requests sent to such a proxy have their target in absolute form.
+    #[inline]
+    fn proxy_headers(&self) -> Option<&Headers> {
+        None
+    }
+
+    /// Get a handle that shuts this stream down from another thread, to
+    /// abort a read or write blocked on it.
+    ///
+    /// Streams that can't be shut down that way return `None`.
+    #[inline]
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        None
+    }
+
     // Unsure about name and implementation...
 
     #[doc(hidden)]
@@ -84,6 +116,72 @@ pub trait NetworkConnector {
 
     /// Connect to a remote address.
     fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<Self::Stream>;
+
+    /// Connect to a remote address, and return what is known about how the
+    /// connection was made along with it.
+    ///
+    /// By default, all of `connect` is timed as connecting. Connectors that
+    /// wrap another should call this on it rather than `connect`, so what
+    /// the inner one measured isn't lost.
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+            -> ::Result<(Self::Stream, ConnectInfo)> {
+        let start = Instant::now();
+        let stream = try!(self.connect(host, port, scheme));
+        Ok((stream, ConnectInfo { connect: Some(start.elapsed()), ..Default::default() }))
+    }
+
+    /// Closes the connections kept idle for reuse, if any are.
+    #[inline]
+    fn close_idle(&self) {}
+}
+
+/// What is known about how a client connection was made.
+///
+/// Connectors return it from `NetworkConnector::connect_with_info`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConnectInfo {
+    /// Whether an idle connection was reused, instead of making a new one.
+    pub reused: bool,
+    /// Time spent resolving the host, if measured apart from connecting.
+    pub dns: Option<Duration>,
+    /// Time spent establishing the TCP connection.
+    pub connect: Option<Duration>,
+    /// Time spent on the TLS handshake.
+    pub tls: Option<Duration>,
+    /// The negotiated TLS cipher, if the `Ssl` implementation reports it.
+    ///
+    /// The built-in `Openssl` doesn't, so this is never set with it.
+    pub cipher: Option<String>,
+}
+
+/// Hosts to connect to at a given address, instead of the addresses they
+/// resolve to, shared between its clones. See `Client::resolve_override`.
+///
+/// Hosts are matched without regard to case, on any port.
+#[derive(Debug, Clone, Default)]
+pub struct ResolveOverrides {
+    hosts: Arc<RwLock<HashMap<String, SocketAddr>>>,
+}
+
+impl ResolveOverrides {
+    /// Creates an empty set of overrides.
+    pub fn new() -> ResolveOverrides {
+        ResolveOverrides::default()
+    }
+
+    /// Connect to `addr` for `host`.
+    pub fn insert(&self, host: &str, addr: SocketAddr) {
+        self.hosts.write().unwrap().insert(host.to_lowercase(), addr);
+    }
+
+    /// The address to connect to for `host`, if it is overridden.
+    pub fn get(&self, host: &str) -> Option<SocketAddr> {
+        let hosts = self.hosts.read().unwrap();
+        if hosts.is_empty() {
+            return None;
+        }
+        hosts.get(&host.to_lowercase()).cloned()
+    }
 }
 
 impl<T: NetworkStream + Send> From<T> for Box<NetworkStream + Send> {
@@ -229,6 +327,26 @@ impl HttpListener {
     pub fn new<To: ToSocketAddrs>(addr: To) -> ::Result<HttpListener> {
         Ok(HttpListener(try!(TcpListener::bind(addr))))
     }
+
+    /// Accepts TCP Fast Open (RFC 7413), so clients connecting again can
+    /// send their request in the SYN, keeping up to `queue` such
+    /// connections that are waiting to be accepted.
+    ///
+    /// Only Linux supports this, and elsewhere it returns an error.
+    ///
+    /// ```no_run
+    /// use hyper::net::HttpListener;
+    /// use hyper::server::{Server, Request, Response};
+    ///
+    /// let listener = HttpListener::new("0.0.0.0:8080").unwrap();
+    /// listener.set_fastopen(256).unwrap();
+    /// Server::new(listener).handle(|_: Request, res: Response| {
+    ///     res.send(b"ok").unwrap();
+    /// }).unwrap();
+    /// ```
+    pub fn set_fastopen(&self, queue: u32) -> io::Result<()> {
+        fastopen::listen(&self.0, queue)
+    }
 }
 
 impl NetworkListener for HttpListener {
@@ -362,6 +480,15 @@ impl NetworkStream for HttpStream {
             err => err
         }
     }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        match self.0.try_clone() {
+            Ok(stream) => Some(Box::new(move || {
+                let _ = stream.shutdown(Shutdown::Both);
+            })),
+            Err(_) => None
+        }
+    }
 }
 
 /// A connector that will produce HttpStreams.
@@ -372,17 +499,334 @@ impl NetworkConnector for HttpConnector {
     type Stream = HttpStream;
 
     fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<HttpStream> {
-        let addr = &(host, port);
-        Ok(try!(match scheme {
+        ResolvingConnector::new(SystemResolver).connect(host, port, scheme)
+    }
+
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+            -> ::Result<(HttpStream, ConnectInfo)> {
+        ResolvingConnector::new(SystemResolver).connect_with_info(host, port, scheme)
+    }
+}
+
+/// Resolves host names to the addresses to connect to.
+///
+/// Closures taking a host and port are resolvers, so a static map of hosts
+/// is simply:
+///
+/// ```
+/// use std::io;
+/// use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+/// use hyper::Client;
+///
+/// let client = Client::with_resolver(|host: &str, port: u16| match host {
+///     "example.domain" => Ok(vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port)]),
+///     _ => Err(io::Error::new(io::ErrorKind::NotFound, "unknown host")),
+/// });
+/// ```
+pub trait Resolver: Send + Sync {
+    /// Resolves `host` to addresses with `port`, in the order to try them.
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
+}
+
+impl<F> Resolver for F where F: Fn(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + Sync {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        (*self)(host, port)
+    }
+}
+
+/// Resolves hosts with the system resolver, `getaddrinfo` on most platforms.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok(try!((host, port).to_socket_addrs()).collect())
+    }
+}
+
+/// A connector that produces `HttpStream`s, resolving hosts with a
+/// `Resolver`, and racing their addresses with `connect_any`.
+#[derive(Debug, Clone)]
+pub struct ResolvingConnector<R: Resolver = SystemResolver> {
+    resolver: R,
+    delay: Duration,
+    overrides: ResolveOverrides,
+    fastopen: bool,
+}
+
+impl<R: Resolver> ResolvingConnector<R> {
+    /// Creates a connector using the given resolver, starting another
+    /// attempt every 250 milliseconds.
+    pub fn new(resolver: R) -> ResolvingConnector<R> {
+        ResolvingConnector {
+            resolver: resolver,
+            delay: Duration::from_millis(250),
+            overrides: ResolveOverrides::new(),
+            fastopen: false,
+        }
+    }
+
+    /// Sets how long an attempt to connect gets before the next address is
+    /// tried alongside it.
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+
+    /// Get the resolver this connector uses.
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+
+    /// Sets the hosts to connect to without resolving them.
+    pub fn set_overrides(&mut self, overrides: ResolveOverrides) {
+        self.overrides = overrides;
+    }
+
+    /// Get the hosts this connector connects to without resolving them.
+    pub fn overrides(&self) -> &ResolveOverrides {
+        &self.overrides
+    }
+
+    /// Sets whether to connect with TCP Fast Open (RFC 7413), where the
+    /// platform supports it, which is only Linux. The request head is then
+    /// sent in the SYN to servers that were connected to before.
+    ///
+    /// Such a connect returns before the server answers, so the first
+    /// address reachable or not wins the race, and a failure is only seen
+    /// when the request is written.
+    ///
+    /// ```no_run
+    /// use hyper::Client;
+    /// use hyper::net::{ResolvingConnector, SystemResolver};
+    ///
+    /// let mut connector = ResolvingConnector::new(SystemResolver);
+    /// connector.set_fastopen(true);
+    /// let client = Client::with_connector(connector);
+    /// ```
+    pub fn set_fastopen(&mut self, fastopen: bool) {
+        self.fastopen = fastopen;
+    }
+}
+
+impl<R: Resolver> NetworkConnector for ResolvingConnector<R> {
+    type Stream = HttpStream;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<HttpStream> {
+        self.connect_with_info(host, port, scheme).map(|(stream, _)| stream)
+    }
+
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+            -> ::Result<(HttpStream, ConnectInfo)> {
+        match scheme {
             "http" => {
                 debug!("http scheme");
-                Ok(HttpStream(try!(TcpStream::connect(addr))))
+                let mut info = ConnectInfo::default();
+                let start = Instant::now();
+                let addrs = match (self.overrides.get(host), ip_literal(host, port)) {
+                    (Some(addr), _) => vec![addr],
+                    (None, Some(addr)) => vec![try!(addr)],
+                    (None, None) => try!(self.resolver.resolve(host, port)),
+                };
+                info.dns = Some(start.elapsed());
+                let start = Instant::now();
+                let stream = try!(race(&addrs, self.delay, self.fastopen));
+                info.connect = Some(start.elapsed());
+                Ok((HttpStream(stream), info))
+            },
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                    "Invalid scheme for Http").into())
+        }
+    }
+}
+
+/// The address of `host`, if it is an IP address literal rather than a
+/// name to resolve.
+///
+/// IPv6 addresses can be in brackets, as in URIs and `Host` headers, and
+/// can have a zone ID (RFC 6874), written `%25` and the zone in brackets,
+/// or `%` and the zone without. Zones other than numbers are looked up as
+/// interface names by the system resolver.
+pub fn ip_literal(host: &str, port: u16) -> Option<io::Result<SocketAddr>> {
+    let (ip, bracketed) = if host.starts_with('[') && host.ends_with(']') {
+        (&host[1..host.len() - 1], true)
+    } else {
+        (host, false)
+    };
+    if let Ok(ip) = ip.parse::<IpAddr>() {
+        return Some(Ok(SocketAddr::new(ip, port)));
+    }
+    let (ip, zone) = match ip.find('%') {
+        Some(i) => (&ip[..i], &ip[i + 1..]),
+        None => return None
+    };
+    let ip = match ip.parse::<Ipv6Addr>() {
+        Ok(ip) => ip,
+        Err(_) => return None
+    };
+    let zone = if bracketed && zone.starts_with("25") { &zone[2..] } else { zone };
+    if zone.is_empty() {
+        return Some(Err(io::Error::new(io::ErrorKind::InvalidInput, "empty IPv6 zone ID")));
+    }
+    Some(match zone.parse::<u32>() {
+        Ok(scope_id) => Ok(SocketAddr::V6(SocketAddrV6::new(ip, port, 0, scope_id))),
+        Err(_) => (&*format!("{}%{}", ip, zone), port).to_socket_addrs().and_then(|mut addrs| {
+            addrs.next().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "unknown IPv6 zone ID")
+            })
+        })
+    })
+}
+
+/// Connects to one of `addrs`, as "Happy Eyeballs" (RFC 6555) does.
+///
+/// Addresses are tried alternating between IPv6 and IPv4, starting with the
+/// family of the first, and otherwise in order. Each attempt gets `delay`
+/// before the next is started alongside it, or less if it fails, and the
+/// first to connect wins. Attempts still running then are closed once they
+/// finish. If all fail, the error of the last is returned.
+pub fn connect_any(addrs: &[SocketAddr], delay: Duration) -> io::Result<TcpStream> {
+    race(addrs, delay, false)
+}
+
+fn race(addrs: &[SocketAddr], delay: Duration, fastopen: bool) -> io::Result<TcpStream> {
+    if addrs.len() == 1 {
+        return fastopen::connect(addrs[0], fastopen);
+    }
+    let mut addrs = interleave(addrs).into_iter();
+    let (tx, rx) = mpsc::channel();
+    let mut pending = 0;
+    let mut err = None;
+    loop {
+        if let Some(addr) = addrs.next() {
+            trace!("connecting to {}", addr);
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let _ = tx.send((addr, fastopen::connect(addr, fastopen)));
+            });
+            pending += 1;
+        }
+        if pending == 0 {
+            return Err(err.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to")
+            }));
+        }
+        let result = if addrs.len() > 0 {
+            match rx.recv_timeout(delay) {
+                Ok(result) => result,
+                Err(_) => continue,
+            }
+        } else {
+            // `tx` is still held, so this waits for an attempt
+            rx.recv().unwrap()
+        };
+        pending -= 1;
+        match result {
+            (_, Ok(stream)) => return Ok(stream),
+            (addr, Err(e)) => {
+                debug!("connecting to {} failed: {:?}", addr, e);
+                err = Some(e);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod fastopen {
+    use std::io;
+    use std::mem;
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+    use libc;
+
+    fn set(fd: RawFd, opt: libc::c_int, value: libc::c_int) -> io::Result<()> {
+        let ret = unsafe {
+            libc::setsockopt(fd, libc::IPPROTO_TCP, opt,
+                             &value as *const libc::c_int as *const libc::c_void,
+                             mem::size_of::<libc::c_int>() as libc::socklen_t)
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn listen(listener: &TcpListener, queue: u32) -> io::Result<()> {
+        set(listener.as_raw_fd(), libc::TCP_FASTOPEN, queue as libc::c_int)
+    }
+
+    // std connects before the option could be set, so the socket is made
+    // here instead.
+    pub fn connect(addr: SocketAddr, fastopen: bool) -> io::Result<TcpStream> {
+        if !fastopen {
+            return TcpStream::connect(addr);
+        }
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let (family, len) = match addr {
+            SocketAddr::V4(ref addr) => {
+                let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+                (libc::AF_INET, mem::size_of::<libc::sockaddr_in>())
             },
-            _ => {
-                Err(io::Error::new(io::ErrorKind::InvalidInput,
-                                "Invalid scheme for Http"))
+            SocketAddr::V6(ref addr) => {
+                let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.sin6_addr.s6_addr = addr.ip().octets();
+                sin6.sin6_scope_id = addr.scope_id();
+                (libc::AF_INET6, mem::size_of::<libc::sockaddr_in6>())
+            }
+        };
+        let fd = unsafe { libc::socket(family, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let stream = unsafe { TcpStream::from_raw_fd(fd) };
+        try!(set(fd, libc::TCP_FASTOPEN_CONNECT, 1));
+        let ret = unsafe {
+            libc::connect(fd, &storage as *const _ as *const libc::sockaddr,
+                          len as libc::socklen_t)
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(stream)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod fastopen {
+    use std::io;
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+
+    pub fn listen(_: &TcpListener, _: u32) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other,
+                           "TCP Fast Open isn't supported on this platform"))
+    }
+
+    pub fn connect(addr: SocketAddr, _: bool) -> io::Result<TcpStream> {
+        TcpStream::connect(addr)
+    }
+}
+
+fn interleave(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first_v6 = addrs.first().map_or(false, |addr| addr.is_ipv6());
+    let (first, second): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs.iter()
+        .partition(|addr| addr.is_ipv6() == first_v6);
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    let mut interleaved = Vec::with_capacity(addrs.len());
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return interleaved,
+            (a, b) => {
+                interleaved.extend(a);
+                interleaved.extend(b);
             }
-        }))
+        }
     }
 }
 
@@ -423,6 +867,13 @@ pub trait Ssl {
     fn wrap_client(&self, stream: HttpStream, host: &str) -> ::Result<Self::Stream>;
     /// Wrap a server stream with SSL.
     fn wrap_server(&self, stream: HttpStream) -> ::Result<Self::Stream>;
+
+    /// Get the cipher negotiated for a stream returned by `wrap_client`, if
+    /// the implementation can tell.
+    #[inline]
+    fn cipher(&self, _stream: &Self::Stream) -> Option<String> {
+        None
+    }
 }
 
 /// A stream over the HTTP protocol, possibly protected by SSL.
@@ -496,6 +947,14 @@ impl<S: NetworkStream> NetworkStream for HttpsStream<S> {
             HttpsStream::Https(ref mut s) => s.close(how)
         }
     }
+
+    #[inline]
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        match *self {
+            HttpsStream::Http(ref s) => s.abort_handle(),
+            HttpsStream::Https(ref s) => s.abort_handle()
+        }
+    }
 }
 
 /// A Http Listener over SSL.
@@ -538,31 +997,57 @@ impl<S: Ssl + Clone> NetworkListener for HttpsListener<S> {
 }
 
 /// A connector that can protect HTTP streams using SSL.
+///
+/// The plain TCP connection is made by another connector, `HttpConnector`
+/// by default.
 #[derive(Debug, Default)]
-pub struct HttpsConnector<S: Ssl> {
-    ssl: S
+pub struct HttpsConnector<S: Ssl, C = HttpConnector> {
+    ssl: S,
+    connector: C,
 }
 
-impl<S: Ssl> HttpsConnector<S> {
+impl<S: Ssl> HttpsConnector<S, HttpConnector> {
     /// Create a new connector using the provided SSL implementation.
-    pub fn new(s: S) -> HttpsConnector<S> {
-        HttpsConnector { ssl: s }
+    pub fn new(s: S) -> HttpsConnector<S, HttpConnector> {
+        HttpsConnector::with_connector(s, HttpConnector)
+    }
+}
+
+impl<S: Ssl, C: NetworkConnector<Stream=HttpStream>> HttpsConnector<S, C> {
+    /// Create a new connector using the provided SSL implementation, making
+    /// TCP connections with the given connector.
+    pub fn with_connector(s: S, connector: C) -> HttpsConnector<S, C> {
+        HttpsConnector { ssl: s, connector: connector }
     }
 }
 
-impl<S: Ssl> NetworkConnector for HttpsConnector<S> {
+impl<S: Ssl, C: NetworkConnector<Stream=HttpStream>> NetworkConnector for HttpsConnector<S, C> {
     type Stream = HttpsStream<S::Stream>;
 
     fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<Self::Stream> {
-        let addr = &(host, port);
+        self.connect_with_info(host, port, scheme).map(|(stream, _)| stream)
+    }
+
+    fn connect_with_info(&self, host: &str, port: u16, scheme: &str)
+            -> ::Result<(Self::Stream, ConnectInfo)> {
         if scheme == "https" {
             debug!("https scheme");
-            let stream = HttpStream(try!(TcpStream::connect(addr)));
-            self.ssl.wrap_client(stream, host).map(HttpsStream::Https)
+            let (stream, mut info) = try!(self.connector.connect_with_info(host, port, "http"));
+            let start = Instant::now();
+            let stream = try!(self.ssl.wrap_client(stream, host));
+            info.tls = Some(start.elapsed());
+            info.cipher = self.ssl.cipher(&stream);
+            Ok((HttpsStream::Https(stream), info))
         } else {
-            HttpConnector.connect(host, port, scheme).map(HttpsStream::Http)
+            let (stream, info) = try!(self.connector.connect_with_info(host, port, scheme));
+            Ok((HttpsStream::Http(stream), info))
         }
     }
+
+    #[inline]
+    fn close_idle(&self) {
+        self.connector.close_idle()
+    }
 }
 
 
@@ -587,7 +1072,7 @@ mod openssl {
     use openssl::ssl::error::StreamError as SslIoError;
     use openssl::ssl::error::SslError;
     use openssl::x509::X509FileType;
-    use super::{NetworkStream, HttpStream};
+    use super::{AbortHandle, NetworkStream, HttpStream};
 
     /// An implementation of `Ssl` for OpenSSL.
     ///
@@ -675,13 +1160,1075 @@ mod openssl {
         fn close(&mut self, how: Shutdown) -> io::Result<()> {
             self.get_mut().close(how)
         }
+
+        fn abort_handle(&self) -> Option<AbortHandle> {
+            self.get_ref().abort_handle()
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use mock::MockStream;
-    use super::{NetworkStream};
+// A Unix domain socket has a path instead of an IP address and port, and a
+// vsock socket a context ID, so this is what `peer_addr` and `local_addr`
+// give for one.
+#[cfg(unix)]
+fn unspecified_addr() -> SocketAddr {
+    "0.0.0.0:0".parse().unwrap()
+}
+
+#[cfg(unix)]
+mod unix {
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    use std::cmp;
+    use std::fmt;
+    use std::io::{self, ErrorKind, Read, Write};
+    use std::mem;
+    use std::net::{SocketAddr, Shutdown};
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+    use std::os::unix::net;
+    use std::path::{Path, PathBuf};
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    use std::ptr;
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    use std::sync::{Arc, Mutex};
+    #[cfg(feature = "timeouts")]
+    use std::time::Duration;
+
+    use libc;
+
+    use super::{AbortHandle, NetworkConnector, NetworkListener, NetworkStream};
+    use super::unspecified_addr;
+
+    // The name of an abstract socket, for a path starting with a NUL byte.
+    #[cfg(target_os = "linux")]
+    fn abstract_addr(path: &Path) -> Option<io::Result<net::SocketAddr>> {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::ffi::OsStrExt;
+
+        let bytes = path.as_os_str().as_bytes();
+        if bytes.first() != Some(&0) {
+            return None;
+        }
+        Some(net::SocketAddr::from_abstract_name(&bytes[1..]))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn bind(path: &Path) -> io::Result<net::UnixListener> {
+        match abstract_addr(path) {
+            Some(addr) => net::UnixListener::bind_addr(&try!(addr)),
+            None => net::UnixListener::bind(path)
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn bind(path: &Path) -> io::Result<net::UnixListener> {
+        net::UnixListener::bind(path)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn connect(path: &Path) -> io::Result<net::UnixStream> {
+        match abstract_addr(path) {
+            Some(addr) => net::UnixStream::connect_addr(&try!(addr)),
+            None => net::UnixStream::connect(path)
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn connect(path: &Path) -> io::Result<net::UnixStream> {
+        net::UnixStream::connect(path)
+    }
+
+    /// A `NetworkListener` for `UnixStream`s, listening on a Unix domain
+    /// socket.
+    ///
+    /// Its `local_addr` is `0.0.0.0:0`, as the socket has a path instead.
+    ///
+    /// On Linux, a path starting with a NUL byte, such as `"\0hyper"`, is
+    /// the name of an abstract socket, which has no file and goes away with
+    /// the last socket using it. `UnixConnector` connects to the same
+    /// paths.
+    pub struct UnixListener(net::UnixListener);
+
+    impl Clone for UnixListener {
+        #[inline]
+        fn clone(&self) -> UnixListener {
+            UnixListener(self.0.try_clone().unwrap())
+        }
+    }
+
+    impl From<net::UnixListener> for UnixListener {
+        fn from(listener: net::UnixListener) -> UnixListener {
+            UnixListener(listener)
+        }
+    }
+
+    impl UnixListener {
+        /// Start listening on a socket at `path`, which must not exist yet.
+        pub fn new<P: AsRef<Path>>(path: P) -> ::Result<UnixListener> {
+            Ok(UnixListener(try!(bind(path.as_ref()))))
+        }
+    }
+
+    impl NetworkListener for UnixListener {
+        type Stream = UnixStream;
+
+        #[inline]
+        fn accept(&mut self) -> ::Result<UnixStream> {
+            Ok(UnixStream(try!(self.0.accept()).0))
+        }
+
+        #[inline]
+        fn local_addr(&mut self) -> io::Result<SocketAddr> {
+            Ok(unspecified_addr())
+        }
+    }
+
+    impl AsRawFd for UnixListener {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+
+    impl FromRawFd for UnixListener {
+        unsafe fn from_raw_fd(fd: RawFd) -> UnixListener {
+            UnixListener(net::UnixListener::from_raw_fd(fd))
+        }
+    }
+
+    /// A wrapper around a Unix domain socket stream.
+    ///
+    /// Its `peer_addr` is `0.0.0.0:0`. A server handler can get the stream
+    /// with `Request::downcast_ref::<UnixStream>()` to learn more about the
+    /// peer, such as its `peer_credentials`:
+    ///
+    /// ```no_run
+    /// use hyper::net::UnixStream;
+    /// use hyper::server::{Request, Response};
+    /// use hyper::status::StatusCode;
+    ///
+    /// fn local_api(req: Request, mut res: Response) {
+    ///     let uid = req.downcast_ref::<UnixStream>()
+    ///         .and_then(|stream| stream.peer_credentials().ok())
+    ///         .map(|creds| creds.uid);
+    ///     if uid != Some(0) {
+    ///         *res.status_mut() = StatusCode::Forbidden;
+    ///     }
+    /// }
+    /// ```
+    pub struct UnixStream(pub net::UnixStream);
+
+    /// The identity of the process at the other end of a Unix domain
+    /// socket, as it was when the socket was connected.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PeerCredentials {
+        /// The effective user ID.
+        pub uid: u32,
+        /// The effective group ID.
+        pub gid: u32,
+        /// The process ID, on systems that tell it (Linux and Android).
+        pub pid: Option<i32>,
+    }
+
+    impl UnixStream {
+        /// Get the credentials of the peer process, which the system
+        /// vouches for, so they can be used to authorize it.
+        pub fn peer_credentials(&self) -> io::Result<PeerCredentials> {
+            peer_credentials(self.0.as_raw_fd())
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn peer_credentials(fd: RawFd) -> io::Result<PeerCredentials> {
+        let mut cred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+        let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(fd, libc::SOL_SOCKET, libc::SO_PEERCRED,
+                             &mut cred as *mut libc::ucred as *mut libc::c_void, &mut len)
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(PeerCredentials {
+            uid: cred.uid,
+            gid: cred.gid,
+            pid: Some(cred.pid),
+        })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn peer_credentials(fd: RawFd) -> io::Result<PeerCredentials> {
+        let mut uid = 0;
+        let mut gid = 0;
+        if unsafe { libc::getpeereid(fd, &mut uid, &mut gid) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(PeerCredentials {
+            uid: uid,
+            gid: gid,
+            pid: None,
+        })
+    }
+
+    impl Clone for UnixStream {
+        #[inline]
+        fn clone(&self) -> UnixStream {
+            UnixStream(self.0.try_clone().unwrap())
+        }
+    }
+
+    impl fmt::Debug for UnixStream {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("UnixStream(_)")
+        }
+    }
+
+    impl Read for UnixStream {
+        #[inline]
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for UnixStream {
+        #[inline]
+        fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
+            self.0.write(msg)
+        }
+        #[inline]
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    impl AsRawFd for UnixStream {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+
+    impl FromRawFd for UnixStream {
+        unsafe fn from_raw_fd(fd: RawFd) -> UnixStream {
+            UnixStream(net::UnixStream::from_raw_fd(fd))
+        }
+    }
+
+    impl NetworkStream for UnixStream {
+        #[inline]
+        fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+            Ok(unspecified_addr())
+        }
+
+        #[cfg(feature = "timeouts")]
+        #[inline]
+        fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+            self.0.set_read_timeout(dur)
+        }
+
+        #[cfg(feature = "timeouts")]
+        #[inline]
+        fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+            self.0.set_write_timeout(dur)
+        }
+
+        #[inline]
+        fn close(&mut self, how: Shutdown) -> io::Result<()> {
+            match self.0.shutdown(how) {
+                Ok(_) => Ok(()),
+                Err(ref e) if e.kind() == ErrorKind::NotConnected => Ok(()),
+                err => err
+            }
+        }
+
+        fn abort_handle(&self) -> Option<AbortHandle> {
+            match self.0.try_clone() {
+                Ok(stream) => Some(Box::new(move || {
+                    let _ = stream.shutdown(Shutdown::Both);
+                })),
+                Err(_) => None
+            }
+        }
+    }
+
+    /// A `NetworkListener` for `SeqPacketStream`s, listening on a Unix
+    /// domain socket of type `SOCK_SEQPACKET`.
+    ///
+    /// Such a socket is connected like a stream, but keeps the boundaries
+    /// of what is written, as some container runtimes expect. It takes the
+    /// same paths as `UnixListener`, including abstract names.
+    ///
+    /// ```no_run
+    /// use hyper::net::SeqPacketListener;
+    /// use hyper::server::{Server, Request, Response};
+    ///
+    /// let listener = SeqPacketListener::new("\0runtime").unwrap();
+    /// Server::new(listener).handle(|_: Request, res: Response| {
+    ///     res.send(b"ok").unwrap();
+    /// }).unwrap();
+    /// ```
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    pub struct SeqPacketListener(net::UnixListener);
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl Clone for SeqPacketListener {
+        #[inline]
+        fn clone(&self) -> SeqPacketListener {
+            SeqPacketListener(self.0.try_clone().unwrap())
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl SeqPacketListener {
+        /// Start listening on a socket at `path`, which must not exist yet.
+        pub fn new<P: AsRef<Path>>(path: P) -> ::Result<SeqPacketListener> {
+            let (addr, len) = try!(sockaddr(path.as_ref()));
+            let listener = unsafe { net::UnixListener::from_raw_fd(try!(seqpacket_socket())) };
+            let fd = listener.as_raw_fd();
+            let addr = &addr as *const libc::sockaddr_un as *const libc::sockaddr;
+            if unsafe { libc::bind(fd, addr, len) } != 0 ||
+                unsafe { libc::listen(fd, 128) } != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(SeqPacketListener(listener))
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl NetworkListener for SeqPacketListener {
+        type Stream = SeqPacketStream;
+
+        #[inline]
+        fn accept(&mut self) -> ::Result<SeqPacketStream> {
+            Ok(SeqPacketStream::new(try!(self.0.accept()).0))
+        }
+
+        #[inline]
+        fn local_addr(&mut self) -> io::Result<SocketAddr> {
+            Ok(unspecified_addr())
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl AsRawFd for SeqPacketListener {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+
+    /// A wrapper around a Unix domain socket of type `SOCK_SEQPACKET`.
+    ///
+    /// Each write is sent as one record, of at most 64KiB. A read takes a
+    /// whole record from the socket, and gives out what doesn't fit in the
+    /// buffer on the next reads, so nothing is cut off. An empty record
+    /// reads as the end of the stream. Clones share the record being read.
+    ///
+    /// Its `peer_addr` is `0.0.0.0:0`, as with `UnixStream`.
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    #[derive(Clone)]
+    pub struct SeqPacketStream {
+        sock: Arc<net::UnixStream>,
+        record: Arc<Mutex<Record>>,
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    struct Record {
+        buf: Vec<u8>,
+        pos: usize,
+    }
+
+    // Records bigger than this can be refused by the default send buffer.
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    const MAX_RECORD: usize = 64 * 1024;
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl SeqPacketStream {
+        fn new(sock: net::UnixStream) -> SeqPacketStream {
+            SeqPacketStream {
+                sock: Arc::new(sock),
+                record: Arc::new(Mutex::new(Record { buf: Vec::new(), pos: 0 })),
+            }
+        }
+
+        /// Get the credentials of the peer process, as with
+        /// `UnixStream::peer_credentials`.
+        pub fn peer_credentials(&self) -> io::Result<PeerCredentials> {
+            peer_credentials(self.sock.as_raw_fd())
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl fmt::Debug for SeqPacketStream {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("SeqPacketStream(_)")
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl Read for SeqPacketStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut record = self.record.lock().unwrap();
+            if record.pos == record.buf.len() {
+                // MSG_TRUNC makes this the length of the whole next record
+                let len = unsafe {
+                    libc::recv(self.sock.as_raw_fd(), ptr::null_mut(), 0,
+                               libc::MSG_PEEK | libc::MSG_TRUNC)
+                };
+                if len < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                record.buf = vec![0; len as usize];
+                record.pos = 0;
+                let n = try!((&*self.sock).read(&mut record.buf));
+                record.buf.truncate(n);
+            }
+            let n = cmp::min(buf.len(), record.buf.len() - record.pos);
+            buf[..n].copy_from_slice(&record.buf[record.pos..record.pos + n]);
+            record.pos += n;
+            Ok(n)
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl Write for SeqPacketStream {
+        #[inline]
+        fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
+            let len = cmp::min(msg.len(), MAX_RECORD);
+            (&*self.sock).write(&msg[..len])
+        }
+        #[inline]
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl AsRawFd for SeqPacketStream {
+        fn as_raw_fd(&self) -> RawFd {
+            self.sock.as_raw_fd()
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl NetworkStream for SeqPacketStream {
+        #[inline]
+        fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+            Ok(unspecified_addr())
+        }
+
+        #[cfg(feature = "timeouts")]
+        #[inline]
+        fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+            self.sock.set_read_timeout(dur)
+        }
+
+        #[cfg(feature = "timeouts")]
+        #[inline]
+        fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+            self.sock.set_write_timeout(dur)
+        }
+
+        #[inline]
+        fn close(&mut self, how: Shutdown) -> io::Result<()> {
+            match self.sock.shutdown(how) {
+                Ok(_) => Ok(()),
+                Err(ref e) if e.kind() == ErrorKind::NotConnected => Ok(()),
+                err => err
+            }
+        }
+
+        fn abort_handle(&self) -> Option<AbortHandle> {
+            let sock = self.sock.clone();
+            Some(Box::new(move || {
+                let _ = sock.shutdown(Shutdown::Both);
+            }))
+        }
+    }
+
+    /// A connector that makes every connection to one Unix domain socket
+    /// of type `SOCK_SEQPACKET`, as `UnixConnector` does for streams.
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    #[derive(Debug, Clone)]
+    pub struct SeqPacketConnector {
+        path: PathBuf,
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl SeqPacketConnector {
+        /// Creates a connector to the socket at `path`.
+        pub fn new<P: AsRef<Path>>(path: P) -> SeqPacketConnector {
+            SeqPacketConnector { path: path.as_ref().to_owned() }
+        }
+
+        /// The path of the socket connected to.
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    impl NetworkConnector for SeqPacketConnector {
+        type Stream = SeqPacketStream;
+
+        fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<SeqPacketStream> {
+            if scheme != "http" {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "Invalid scheme for a Unix socket").into());
+            }
+            debug!("connecting to {}:{} over {:?}", host, port, self.path);
+            let (addr, len) = try!(sockaddr(&self.path));
+            let sock = unsafe { net::UnixStream::from_raw_fd(try!(seqpacket_socket())) };
+            let addr = &addr as *const libc::sockaddr_un as *const libc::sockaddr;
+            if unsafe { libc::connect(sock.as_raw_fd(), addr, len) } != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(SeqPacketStream::new(sock))
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    fn seqpacket_socket() -> io::Result<RawFd> {
+        let fd = unsafe {
+            libc::socket(libc::AF_UNIX, libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC, 0)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd)
+    }
+
+    // The address of the socket at `path`, or of the abstract socket named
+    // by a path starting with a NUL byte.
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    fn sockaddr(path: &Path) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        let bytes = path.as_os_str().as_bytes();
+        // an abstract name is used as it is, and a path ends with a NUL
+        let is_abstract = bytes.first() == Some(&0);
+        let len = if is_abstract { bytes.len() } else { bytes.len() + 1 };
+        if len > addr.sun_path.len() || (!is_abstract && bytes.contains(&0)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "path can't be used for a Unix socket"));
+        }
+        for (dst, src) in addr.sun_path.iter_mut().zip(bytes) {
+            *dst = *src as libc::c_char;
+        }
+        let offset = &addr.sun_path as *const _ as usize - &addr as *const _ as usize;
+        Ok((addr, (offset + len) as libc::socklen_t))
+    }
+
+    /// A connector that makes every connection to one Unix domain socket.
+    ///
+    /// The host and port of a URL are only sent in the `Host` header, so a
+    /// `Client` using it can request `http://localhost/containers/json` of
+    /// a server listening on a socket, for example.
+    ///
+    /// ```no_run
+    /// use hyper::Client;
+    /// use hyper::net::UnixConnector;
+    ///
+    /// let client = Client::with_connector(UnixConnector::new("/var/run/docker.sock"));
+    /// client.get("http://localhost/containers/json").send().unwrap();
+    /// ```
+    #[derive(Debug, Clone)]
+    pub struct UnixConnector {
+        path: PathBuf,
+    }
+
+    impl UnixConnector {
+        /// Creates a connector to the socket at `path`.
+        pub fn new<P: AsRef<Path>>(path: P) -> UnixConnector {
+            UnixConnector { path: path.as_ref().to_owned() }
+        }
+
+        /// The path of the socket connected to.
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl NetworkConnector for UnixConnector {
+        type Stream = UnixStream;
+
+        fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<UnixStream> {
+            match scheme {
+                "http" => {
+                    debug!("connecting to {}:{} over {:?}", host, port, self.path);
+                    Ok(UnixStream(try!(connect(&self.path))))
+                },
+                _ => {
+                    Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                       "Invalid scheme for a Unix socket").into())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "vsock"))]
+mod vsock {
+    use std::fmt;
+    use std::io::{self, ErrorKind, Read, Write};
+    use std::mem;
+    use std::net::{SocketAddr, Shutdown};
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+    // std has no vsock sockets, but what its UnixStream does with the
+    // descriptor, such as reading, shutting down and setting timeouts,
+    // works the same for any stream socket.
+    use std::os::unix::net::UnixStream as Socket;
+    #[cfg(feature = "timeouts")]
+    use std::time::Duration;
+
+    use libc;
+
+    use super::{AbortHandle, NetworkConnector, NetworkListener, NetworkStream};
+    use super::unspecified_addr;
+
+    /// The address of a vsock socket: the context ID (CID) of a VM or of
+    /// the host, which is `2`, and a port.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct VsockAddr {
+        /// The context ID.
+        pub cid: u32,
+        /// The port.
+        pub port: u32,
+    }
+
+    impl VsockAddr {
+        /// The address of `port` on every context ID this machine has, to
+        /// listen on.
+        pub fn any(port: u32) -> VsockAddr {
+            VsockAddr { cid: libc::VMADDR_CID_ANY, port: port }
+        }
+
+        fn to_raw(&self) -> libc::sockaddr_vm {
+            let mut addr: libc::sockaddr_vm = unsafe { mem::zeroed() };
+            addr.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+            addr.svm_cid = self.cid;
+            addr.svm_port = self.port;
+            addr
+        }
+
+        fn from_raw(addr: &libc::sockaddr_vm) -> VsockAddr {
+            VsockAddr { cid: addr.svm_cid, port: addr.svm_port }
+        }
+    }
+
+    fn socket() -> io::Result<Socket> {
+        let fd = unsafe {
+            libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { Socket::from_raw_fd(fd) })
+    }
+
+    /// A `NetworkListener` for `VsockStream`s, so a server in a VM can be
+    /// reached from its host, or the other way around, without a network.
+    ///
+    /// Its `local_addr` is `0.0.0.0:0`, as the socket has a context ID and
+    /// port instead.
+    ///
+    /// ```no_run
+    /// use hyper::net::VsockListener;
+    /// use hyper::server::{Server, Request, Response};
+    ///
+    /// let listener = VsockListener::new(8000).unwrap();
+    /// Server::new(listener).handle(|_: Request, res: Response| {
+    ///     res.send(b"ok").unwrap();
+    /// }).unwrap();
+    /// ```
+    pub struct VsockListener(Socket);
+
+    impl Clone for VsockListener {
+        #[inline]
+        fn clone(&self) -> VsockListener {
+            VsockListener(self.0.try_clone().unwrap())
+        }
+    }
+
+    impl VsockListener {
+        /// Start listening on `port` of every context ID.
+        pub fn new(port: u32) -> ::Result<VsockListener> {
+            VsockListener::bind(VsockAddr::any(port))
+        }
+
+        /// Start listening on `addr`.
+        pub fn bind(addr: VsockAddr) -> ::Result<VsockListener> {
+            let sock = try!(socket());
+            let addr = addr.to_raw();
+            let len = mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+            let addr = &addr as *const libc::sockaddr_vm as *const libc::sockaddr;
+            if unsafe { libc::bind(sock.as_raw_fd(), addr, len) } != 0 ||
+                unsafe { libc::listen(sock.as_raw_fd(), 128) } != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(VsockListener(sock))
+        }
+
+        /// The address listened on, with the port picked if it was
+        /// `VMADDR_PORT_ANY`.
+        pub fn local_vsock_addr(&self) -> io::Result<VsockAddr> {
+            let mut addr: libc::sockaddr_vm = unsafe { mem::zeroed() };
+            let mut len = mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+            let ret = unsafe {
+                libc::getsockname(self.0.as_raw_fd(),
+                                  &mut addr as *mut libc::sockaddr_vm as *mut libc::sockaddr,
+                                  &mut len)
+            };
+            if ret != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(VsockAddr::from_raw(&addr))
+        }
+    }
+
+    impl NetworkListener for VsockListener {
+        type Stream = VsockStream;
+
+        fn accept(&mut self) -> ::Result<VsockStream> {
+            let mut addr: libc::sockaddr_vm = unsafe { mem::zeroed() };
+            let mut len = mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+            let fd = unsafe {
+                libc::accept4(self.0.as_raw_fd(),
+                              &mut addr as *mut libc::sockaddr_vm as *mut libc::sockaddr,
+                              &mut len, libc::SOCK_CLOEXEC)
+            };
+            if fd < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(VsockStream {
+                sock: unsafe { Socket::from_raw_fd(fd) },
+                peer: VsockAddr::from_raw(&addr),
+            })
+        }
+
+        #[inline]
+        fn local_addr(&mut self) -> io::Result<SocketAddr> {
+            Ok(unspecified_addr())
+        }
+    }
+
+    impl AsRawFd for VsockListener {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+
+    /// A wrapper around a vsock stream.
+    ///
+    /// Its `peer_addr` is `0.0.0.0:0`. A server handler can get the stream
+    /// with `Request::downcast_ref::<VsockStream>()` for the
+    /// `peer_vsock_addr` instead.
+    pub struct VsockStream {
+        sock: Socket,
+        peer: VsockAddr,
+    }
+
+    impl VsockStream {
+        /// The context ID and port of the other end.
+        pub fn peer_vsock_addr(&self) -> VsockAddr {
+            self.peer
+        }
+    }
+
+    impl Clone for VsockStream {
+        #[inline]
+        fn clone(&self) -> VsockStream {
+            VsockStream { sock: self.sock.try_clone().unwrap(), peer: self.peer }
+        }
+    }
+
+    impl fmt::Debug for VsockStream {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "VsockStream({}:{})", self.peer.cid, self.peer.port)
+        }
+    }
+
+    impl Read for VsockStream {
+        #[inline]
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.sock.read(buf)
+        }
+    }
+
+    impl Write for VsockStream {
+        #[inline]
+        fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
+            self.sock.write(msg)
+        }
+        #[inline]
+        fn flush(&mut self) -> io::Result<()> {
+            self.sock.flush()
+        }
+    }
+
+    impl AsRawFd for VsockStream {
+        fn as_raw_fd(&self) -> RawFd {
+            self.sock.as_raw_fd()
+        }
+    }
+
+    impl NetworkStream for VsockStream {
+        #[inline]
+        fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+            Ok(unspecified_addr())
+        }
+
+        #[cfg(feature = "timeouts")]
+        #[inline]
+        fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+            self.sock.set_read_timeout(dur)
+        }
+
+        #[cfg(feature = "timeouts")]
+        #[inline]
+        fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+            self.sock.set_write_timeout(dur)
+        }
+
+        #[inline]
+        fn close(&mut self, how: Shutdown) -> io::Result<()> {
+            match self.sock.shutdown(how) {
+                Ok(_) => Ok(()),
+                Err(ref e) if e.kind() == ErrorKind::NotConnected => Ok(()),
+                err => err
+            }
+        }
+
+        fn abort_handle(&self) -> Option<AbortHandle> {
+            match self.sock.try_clone() {
+                Ok(sock) => Some(Box::new(move || {
+                    let _ = sock.shutdown(Shutdown::Both);
+                })),
+                Err(_) => None
+            }
+        }
+    }
+
+    /// A connector that makes every connection to one vsock address, such
+    /// as an agent listening in a VM.
+    ///
+    /// As with `UnixConnector`, the host and port of a URL are only sent in
+    /// the `Host` header.
+    ///
+    /// ```no_run
+    /// use hyper::Client;
+    /// use hyper::net::{VsockAddr, VsockConnector};
+    ///
+    /// let agent = VsockConnector::new(VsockAddr { cid: 3, port: 8000 });
+    /// Client::with_connector(agent).get("http://localhost/health").send().unwrap();
+    /// ```
+    #[derive(Debug, Clone)]
+    pub struct VsockConnector {
+        addr: VsockAddr,
+    }
+
+    impl VsockConnector {
+        /// Creates a connector to `addr`.
+        pub fn new(addr: VsockAddr) -> VsockConnector {
+            VsockConnector { addr: addr }
+        }
+
+        /// The address connected to.
+        pub fn addr(&self) -> VsockAddr {
+            self.addr
+        }
+    }
+
+    impl NetworkConnector for VsockConnector {
+        type Stream = VsockStream;
+
+        fn connect(&self, host: &str, port: u16, scheme: &str) -> ::Result<VsockStream> {
+            if scheme != "http" {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "Invalid scheme for a vsock socket").into());
+            }
+            debug!("connecting to {}:{} over {:?}", host, port, self.addr);
+            let sock = try!(socket());
+            let addr = self.addr.to_raw();
+            let len = mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+            let addr = &addr as *const libc::sockaddr_vm as *const libc::sockaddr;
+            if unsafe { libc::connect(sock.as_raw_fd(), addr, len) } != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(VsockStream { sock: sock, peer: self.addr })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Duration;
+    use mock::MockStream;
+    use super::{NetworkConnector, NetworkStream, ResolveOverrides, ResolvingConnector};
+    use super::{connect_any, interleave, ip_literal};
+
+    #[test]
+    fn test_connect_info() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connector = |_: &str, _: u16, _: &str| ::std::net::TcpStream::connect(addr);
+        let (_, info) = connector.connect_with_info("127.0.0.1", 1, "http").unwrap();
+        assert!(info.connect.is_some());
+        assert!(!info.reused && info.dns.is_none() && info.tls.is_none());
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    #[test]
+    fn test_seqpacket_records() {
+        use std::io::{Read, Write};
+        use super::{NetworkListener, SeqPacketConnector, SeqPacketListener};
+
+        let name = format!("\0hyper-records-{}", ::std::process::id());
+        let mut listener = SeqPacketListener::new(&name).unwrap();
+        let mut client = SeqPacketConnector::new(&name).connect("localhost", 80, "http").unwrap();
+        let mut server = listener.accept().unwrap();
+        client.write_all(b"hello world").unwrap();
+        client.write_all(b"!").unwrap();
+
+        // a record is never cut short by a small buffer
+        let mut buf = [0; 4];
+        let mut reads = Vec::new();
+        for _ in 0..4 {
+            let n = server.read(&mut buf).unwrap();
+            reads.push(buf[..n].to_vec());
+        }
+        assert_eq!(reads, vec![b"hell".to_vec(), b"o wo".to_vec(), b"rld".to_vec(), b"!".to_vec()]);
+        drop(client);
+        assert_eq!(server.read(&mut buf).unwrap(), 0);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "vsock"))]
+    #[test]
+    fn test_vsock() {
+        use std::io::{Read, Write};
+        use libc;
+        use super::{NetworkListener, VsockAddr, VsockConnector, VsockListener};
+
+        let local = VsockAddr { cid: libc::VMADDR_CID_LOCAL, port: libc::VMADDR_PORT_ANY };
+        let mut listener = match VsockListener::bind(local) {
+            Ok(listener) => listener,
+            // there is no vsock loopback to test with here
+            Err(_) => return
+        };
+        let addr = listener.local_vsock_addr().unwrap();
+        assert!(addr.port != libc::VMADDR_PORT_ANY);
+        let mut client = VsockConnector::new(addr).connect("localhost", 80, "http").unwrap();
+        let mut server = listener.accept().unwrap();
+        assert_eq!(server.peer_vsock_addr().cid, libc::VMADDR_CID_LOCAL);
+        client.write_all(b"ping").unwrap();
+        let mut buf = [0; 4];
+        server.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[test]
+    fn test_interleave() {
+        let addrs = ["[::1]:1", "[::1]:2", "[::1]:3", "127.0.0.1:4", "127.0.0.1:5"].iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect::<Vec<SocketAddr>>();
+        let ports = interleave(&addrs).iter().map(|addr| addr.port()).collect::<Vec<_>>();
+        assert_eq!(ports, vec![1, 4, 2, 5, 3]);
+        let ports = interleave(&addrs[2..]).iter().map(|addr| addr.port()).collect::<Vec<_>>();
+        assert_eq!(ports, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_ip_literal() {
+        fn literal(host: &str) -> Option<String> {
+            ip_literal(host, 80).map(|addr| match addr.unwrap() {
+                SocketAddr::V6(addr) => format!("{} {} {}", addr.ip(), addr.port(), addr.scope_id()),
+                addr => addr.to_string()
+            })
+        }
+        assert_eq!(literal("127.0.0.1"), Some("127.0.0.1:80".to_owned()));
+        assert_eq!(literal("[::1]"), Some("::1 80 0".to_owned()));
+        assert_eq!(literal("::1"), Some("::1 80 0".to_owned()));
+        assert_eq!(literal("[fe80::1%253]"), Some("fe80::1 80 3".to_owned()));
+        assert_eq!(literal("fe80::1%25"), Some("fe80::1 80 25".to_owned()));
+        assert_eq!(literal("example.domain"), None);
+        assert_eq!(literal("[example.domain]"), None);
+        assert!(ip_literal("[fe80::1%25]", 80).unwrap().is_err());
+    }
+
+    #[test]
+    fn test_connect_any() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let addrs = vec![refused, refused, listener.local_addr().unwrap()];
+        let stream = connect_any(&addrs, Duration::from_secs(60)).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+
+        assert!(connect_any(&[refused, refused], Duration::from_millis(1)).is_err());
+        assert_eq!(connect_any(&[], Duration::from_millis(1)).unwrap_err().kind(),
+                   io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_fastopen() {
+        use std::io::{Read, Write};
+        use super::{HttpListener, NetworkListener};
+
+        let mut listener = HttpListener::new("127.0.0.1:0").unwrap();
+        listener.set_fastopen(16).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut connector = ResolvingConnector::new(super::SystemResolver);
+        connector.set_fastopen(true);
+        for _ in 0..2 {
+            let mut client = connector.connect("127.0.0.1", addr.port(), "http").unwrap();
+            client.write_all(b"ping").unwrap();
+            let mut server = listener.accept().unwrap();
+            let mut buf = [0; 4];
+            server.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"ping");
+        }
+    }
+
+    #[test]
+    fn test_resolving_connector() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connector = ResolvingConnector::new(move |host: &str, _port: u16| match host {
+            "example.domain" => Ok(vec![addr]),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "unknown host")),
+        });
+        let (mut stream, info) = connector.connect_with_info("example.domain", 1, "http").unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+        assert!(info.dns.is_some() && info.connect.is_some());
+        assert!(connector.connect("missing.domain", 1, "http").is_err());
+
+        // literals aren't resolved
+        let mut stream = connector.connect("127.0.0.1", addr.port(), "http").unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+
+        // nor are overridden hosts
+        let mut connector = connector;
+        connector.set_overrides(ResolveOverrides::new());
+        connector.overrides().insert("Missing.Domain", addr);
+        let mut stream = connector.connect("missing.domain", 1, "http").unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_peer_credentials() {
+        use std::os::unix::net;
+        use libc;
+        use super::UnixStream;
+
+        let (a, _b) = net::UnixStream::pair().unwrap();
+        let creds = UnixStream(a).peer_credentials().unwrap();
+        assert_eq!(creds.uid, unsafe { libc::geteuid() });
+        assert_eq!(creds.gid, unsafe { libc::getegid() });
+        if cfg!(any(target_os = "linux", target_os = "android")) {
+            assert_eq!(creds.pid, Some(::std::process::id() as i32));
+        }
+    }
 
     #[test]
     fn test_downcast_box_stream() {
@@ -0,0 +1,262 @@
+//! Problem Details for HTTP APIs, from RFC 9457.
+//!
+//! A `Problem` is a machine readable error body, sent with the
+//! `application/problem+json` media type. Servers can answer with one, and
+//! clients can read one from an error response:
+//!
+//! ```no_run
+//! use hyper::server::{Server, Request, Response};
+//! use hyper::status::StatusCode;
+//! use hyper::problem::Problem;
+//!
+//! Server::http("0.0.0.0:0").unwrap().handle(|_: Request, res: Response| {
+//!     let mut problem = Problem::new(StatusCode::Forbidden);
+//!     problem.problem_type = "https://example.domain/probs/out-of-credit".to_owned();
+//!     problem.detail = Some("Your current balance is 30, but that costs 50.".to_owned());
+//!     problem.set_extension("balance", 30);
+//!     problem.send(res).unwrap();
+//! }).unwrap();
+//! ```
+//!
+//! ```no_run
+//! use hyper::Client;
+//! use hyper::problem::Problem;
+//!
+//! let client = Client::new();
+//! let mut res = client.get("http://example.domain/account").send().unwrap();
+//! if let Some(problem) = Problem::from_response(&mut res).unwrap() {
+//!     println!("{}: {:?}", problem.problem_type, problem.detail);
+//! }
+//! ```
+//!
+//! Extension members are `rustc_serialize` JSON values, the JSON library
+//! hyper already depends on.
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+
+use serialize::json::{self, Json, ToJson};
+
+use client::Response as ClientResponse;
+use header::ContentType;
+use mime::{Mime, TopLevel, SubLevel};
+use net::Fresh;
+use server::Response as ServerResponse;
+use status::StatusCode;
+
+const MEDIA_TYPE: &'static str = "problem+json";
+const MEMBERS: [&'static str; 5] = ["type", "title", "status", "detail", "instance"];
+
+/// A problem details object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    /// A URI reference identifying the problem type. `about:blank` means
+    /// there is nothing more to the problem than its status code.
+    pub problem_type: String,
+    /// A short summary of the problem type.
+    pub title: Option<String>,
+    /// The status code of the response.
+    pub status: Option<StatusCode>,
+    /// An explanation of this occurrence of the problem.
+    pub detail: Option<String>,
+    /// A URI reference identifying this occurrence of the problem.
+    pub instance: Option<String>,
+    /// Any other members.
+    pub extensions: BTreeMap<String, Json>,
+}
+
+impl Problem {
+    /// Creates an `about:blank` problem for a status code, titled with its
+    /// canonical reason.
+    pub fn new(status: StatusCode) -> Problem {
+        Problem {
+            problem_type: "about:blank".to_owned(),
+            title: status.canonical_reason().map(|reason| reason.to_owned()),
+            status: Some(status),
+            detail: None,
+            instance: None,
+            extensions: BTreeMap::new(),
+        }
+    }
+
+    /// Sets an extension member, replacing any with the same name.
+    ///
+    /// The names of the standard members are ignored.
+    pub fn set_extension<T: ToJson>(&mut self, name: &str, value: T) {
+        if !MEMBERS.contains(&name) {
+            self.extensions.insert(name.to_owned(), value.to_json());
+        }
+    }
+
+    /// Get an extension member.
+    pub fn extension(&self, name: &str) -> Option<&Json> {
+        self.extensions.get(name)
+    }
+
+    /// Parses a problem from a JSON object.
+    ///
+    /// As the RFC asks, standard members with the wrong type are ignored.
+    pub fn from_json(text: &str) -> Option<Problem> {
+        let mut object = match Json::from_str(text) {
+            Ok(Json::Object(object)) => object,
+            _ => return None,
+        };
+        let mut string = |name: &str| match object.remove(name) {
+            Some(Json::String(s)) => Some(s),
+            _ => None,
+        };
+        let problem_type = string("type").unwrap_or_else(|| "about:blank".to_owned());
+        let title = string("title");
+        let detail = string("detail");
+        let instance = string("instance");
+        let status = match object.remove("status") {
+            Some(Json::U64(n)) if n >= 100 && n < 600 => Some(StatusCode::from_u16(n as u16)),
+            _ => None,
+        };
+        Some(Problem {
+            problem_type: problem_type,
+            title: title,
+            status: status,
+            detail: detail,
+            instance: instance,
+            extensions: object,
+        })
+    }
+
+    /// Reads a problem from a client response.
+    ///
+    /// Returns `None` without reading the body if the response isn't
+    /// `application/problem+json`, and an error if it is, but the body isn't
+    /// a JSON object.
+    pub fn from_response(res: &mut ClientResponse) -> ::Result<Option<Problem>> {
+        if !is_problem(res.headers.get::<ContentType>()) {
+            return Ok(None);
+        }
+        let mut body = String::new();
+        try!(res.read_to_string(&mut body));
+        match Problem::from_json(&body) {
+            Some(problem) => Ok(Some(problem)),
+            None => Err(::Error::Io(io::Error::new(io::ErrorKind::InvalidData,
+                                                   "invalid problem details body"))),
+        }
+    }
+
+    /// Sends the problem as a server response, with its status code.
+    pub fn send(&self, mut res: ServerResponse<Fresh>) -> io::Result<()> {
+        if let Some(status) = self.status {
+            *res.status_mut() = status;
+        }
+        res.headers_mut().set(ContentType(problem_mime()));
+        res.send(self.to_string().as_bytes())
+    }
+}
+
+impl ToJson for Problem {
+    fn to_json(&self) -> Json {
+        let mut object = self.extensions.clone();
+        object.insert("type".to_owned(), self.problem_type.to_json());
+        if let Some(ref title) = self.title {
+            object.insert("title".to_owned(), title.to_json());
+        }
+        if let Some(status) = self.status {
+            object.insert("status".to_owned(), status.to_u16().to_json());
+        }
+        if let Some(ref detail) = self.detail {
+            object.insert("detail".to_owned(), detail.to_json());
+        }
+        if let Some(ref instance) = self.instance {
+            object.insert("instance".to_owned(), instance.to_json());
+        }
+        Json::Object(object)
+    }
+}
+
+impl ::std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{}", json::as_json(&self.to_json()))
+    }
+}
+
+fn problem_mime() -> Mime {
+    Mime(TopLevel::Application, SubLevel::Ext(MEDIA_TYPE.to_owned()), vec![])
+}
+
+fn is_problem(content_type: Option<&ContentType>) -> bool {
+    match content_type {
+        Some(&ContentType(Mime(TopLevel::Application, SubLevel::Ext(ref sub), _))) => {
+            sub == MEDIA_TYPE
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::from_utf8;
+
+    use header::Headers;
+    use mock::MockStream;
+    use serialize::json::Json;
+    use client::Response as ClientResponse;
+    use server::Response as ServerResponse;
+    use status::StatusCode;
+
+    use super::Problem;
+
+    fn out_of_credit() -> Problem {
+        let mut problem = Problem::new(StatusCode::Forbidden);
+        problem.problem_type = "https://example.domain/probs/out-of-credit".to_owned();
+        problem.title = Some("You do not have enough credit.".to_owned());
+        problem.instance = Some("/account/12345/msgs/abc".to_owned());
+        problem.set_extension("balance", 30u64);
+        problem.set_extension("status", "ignored".to_owned());
+        problem
+    }
+
+    #[test]
+    fn test_new() {
+        let problem = Problem::new(StatusCode::NotFound);
+        assert_eq!(problem.to_string(),
+                   r#"{"status":404,"title":"Not Found","type":"about:blank"}"#);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let problem = out_of_credit();
+        let json = problem.to_string();
+        assert!(json.contains(r#""balance":30"#));
+        assert!(json.contains(r#""status":403"#));
+        assert_eq!(Problem::from_json(&json), Some(problem));
+    }
+
+    #[test]
+    fn test_from_json_wrong_types() {
+        let problem = Problem::from_json(r#"{"status":"500","title":7,"x":[1]}"#).unwrap();
+        assert_eq!(problem.problem_type, "about:blank");
+        assert_eq!(problem.status, None);
+        assert_eq!(problem.title, None);
+        assert_eq!(problem.extension("x"), Some(&Json::Array(vec![Json::U64(1)])));
+        assert_eq!(Problem::from_json("[]"), None);
+    }
+
+    #[test]
+    fn test_send_and_read() {
+        let mut headers = Headers::new();
+        let mut stream = MockStream::new();
+        out_of_credit().send(ServerResponse::new(&mut stream, &mut headers)).unwrap();
+        let raw = from_utf8(&stream.write).unwrap().to_owned();
+        assert!(raw.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+        assert!(raw.contains("Content-Type: application/problem+json\r\n"));
+
+        let stream = MockStream::with_input(raw.as_bytes());
+        let url = "http://example.domain".parse().unwrap();
+        let mut res = ClientResponse::new(url, Box::new(stream)).unwrap();
+        assert_eq!(Problem::from_response(&mut res).unwrap(), Some(out_of_credit()));
+
+        let stream = MockStream::with_input(b"HTTP/1.1 403 Forbidden\r\n\
+                                              Content-Type: application/json\r\n\
+                                              Content-Length: 2\r\n\r\n{}");
+        let url = "http://example.domain".parse().unwrap();
+        let mut res = ClientResponse::new(url, Box::new(stream)).unwrap();
+        assert_eq!(Problem::from_response(&mut res).unwrap(), None);
+    }
+}
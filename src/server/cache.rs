@@ -0,0 +1,642 @@
+//! A micro-cache for hot server responses.
+//!
+//! `Cache` wraps another `Handler`, and keeps the responses it writes for
+//! `GET` requests around for a short, fixed time. Requests arriving while an
+//! entry is fresh are answered from memory, without calling the wrapped
+//! handler at all.
+//!
+//! Entries are keyed by the method, host and path of the request, plus the
+//! values of any request headers the response listed in its `Vary` header.
+//! The host is that of an absolute request-target if there is one, and the
+//! `Host` header otherwise, so that the hosts behind a `VHost` never share
+//! entries.
+//!
+//! A single byte `Range` requested of a cached `200 OK` is answered from
+//! the cached body with a `206 Partial Content`, or a `416` if it cannot be
+//! satisfied. Range requests with no cached entity go to the handler, and
+//! their responses are not cached.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use hyper::server::{Server, Request, Response};
+//! use hyper::server::cache::Cache;
+//!
+//! fn expensive(_: Request, res: Response) {
+//!     res.send(b"computed").unwrap();
+//! }
+//!
+//! let cache = Cache::new(expensive, Duration::from_secs(1));
+//! let invalidator = cache.invalidator();
+//! Server::http("0.0.0.0:0").unwrap().handle(cache).unwrap();
+//! // later, when the underlying data changes:
+//! invalidator.invalidate("/");
+//! ```
+use std::ascii::AsciiExt;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use buffer::BufReader;
+use clock::{self, Clock};
+use header::{self, Headers, CacheControl, CacheDirective, ContentLength, TransferEncoding, Vary};
+use header::{ContentRange, ContentRangeSpec, Host, Range};
+use header::Encoding::Chunked;
+use http::h1;
+use http::h1::HttpReader::{SizedReader, ChunkedReader, EmptyReader};
+use http::h1::HttpWriter::ThroughWriter;
+use method::Method;
+use net::Fresh;
+use server::{range, Handler, RawHead, Request, Response};
+use status::StatusCode;
+use uri::RequestUri;
+
+const DEFAULT_MAX_ENTRIES: usize = 1024;
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// A `Handler` that caches the responses of the `Handler` it wraps.
+pub struct Cache<H: Handler> {
+    handler: H,
+    ttl: Duration,
+    max_body_size: usize,
+    store: Arc<Mutex<Store>>,
+    clock: Arc<Clock>,
+}
+
+/// A handle that can drop entries from a `Cache`.
+///
+/// This stays usable after the `Cache` itself has been given to a `Server`.
+#[derive(Clone)]
+pub struct Invalidator {
+    store: Arc<Mutex<Store>>,
+}
+
+struct Store {
+    entries: HashMap<Key, Vec<Entry>>,
+    len: usize,
+    max_entries: usize,
+}
+
+// The method, the host and port, and the path and query.
+type Key = (Method, Option<String>, String);
+
+fn key(req: &Request) -> Key {
+    match req.uri {
+        RequestUri::AbsoluteUri(ref url) => {
+            let host = url.serialize_host().map(|host| match url.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host
+            });
+            let mut path = url.serialize_path().unwrap_or_else(|| "/".to_owned());
+            if let Some(ref query) = url.query {
+                path.push('?');
+                path.push_str(query);
+            }
+            (req.method.clone(), host.map(|host| host.to_ascii_lowercase()), path)
+        },
+        ref uri => {
+            let host = req.headers.get::<Host>().map(|host| match host.port {
+                Some(port) => format!("{}:{}", host.hostname, port),
+                None => host.hostname.clone()
+            });
+            (req.method.clone(), host.map(|host| host.to_ascii_lowercase()), uri.to_string())
+        }
+    }
+}
+
+struct Entry {
+    vary: Vec<(String, Option<Vec<Vec<u8>>>)>,
+    status: StatusCode,
+    headers: Headers,
+    body: Vec<u8>,
+    expires: Instant,
+}
+
+impl<H: Handler> Cache<H> {
+    /// Wraps a handler, caching its responses for `ttl`.
+    pub fn new(handler: H, ttl: Duration) -> Cache<H> {
+        Cache {
+            handler: handler,
+            ttl: ttl,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            store: Arc::new(Mutex::new(Store {
+                entries: HashMap::new(),
+                len: 0,
+                max_entries: DEFAULT_MAX_ENTRIES,
+            })),
+            clock: clock::system(),
+        }
+    }
+
+    /// Sets the maximum number of responses kept at once.
+    ///
+    /// Once full, expired entries are purged to make room, and if there are
+    /// none, new responses are simply not cached.
+    pub fn set_max_entries(&mut self, max: usize) {
+        self.store.lock().unwrap().max_entries = max;
+    }
+
+    /// Sets the largest response body, in bytes, that will be cached.
+    pub fn set_max_body_size(&mut self, max: usize) {
+        self.max_body_size = max;
+    }
+
+    /// Sets the clock by which entries expire.
+    pub fn set_clock(&mut self, clock: Arc<Clock>) {
+        self.clock = clock;
+    }
+
+    /// Get an `Invalidator` for this cache.
+    pub fn invalidator(&self) -> Invalidator {
+        Invalidator { store: self.store.clone() }
+    }
+
+    /// Drops all cached responses for a path, such as `/index.html`, on
+    /// every host.
+    pub fn invalidate(&self, uri: &str) {
+        self.invalidator().invalidate(uri)
+    }
+
+    /// Drops every cached response.
+    pub fn clear(&self) {
+        self.invalidator().clear()
+    }
+
+    fn lookup<'a>(&self, key: &Key, headers: &Headers, res: Response<'a, Fresh>)
+              -> Option<Response<'a, Fresh>> {
+        let store = self.store.lock().unwrap();
+        let now = self.clock.now();
+        let entry = store.entries.get(key).and_then(|variants| {
+            variants.iter().find(|entry| entry.expires > now && entry.matches(headers))
+        });
+        match entry {
+            Some(entry) => {
+                trace!("cache hit {:?}", key);
+                entry.replay(headers, res);
+                None
+            },
+            None => Some(res)
+        }
+    }
+
+    fn insert(&self, key: Key, headers: &Headers, raw: &[u8]) {
+        let (status, mut res_headers, body) = match parse(raw) {
+            Ok(parts) => parts,
+            Err(e) => {
+                debug!("cache could not parse response: {:?}", e);
+                return;
+            }
+        };
+
+        if !is_cacheable(status, &res_headers) {
+            trace!("response not cacheable {:?}", key);
+            return;
+        }
+
+        let vary = match res_headers.get::<Vary>() {
+            Some(&Vary::Items(ref names)) => names.iter().map(|name| {
+                (name.to_string(), headers.get_raw(name).map(|raw| raw.to_vec()))
+            }).collect(),
+            _ => vec![],
+        };
+
+        res_headers.remove::<header::Date>();
+        res_headers.remove::<header::Connection>();
+        res_headers.remove::<ContentLength>();
+        res_headers.remove::<TransferEncoding>();
+
+        let entry = Entry {
+            vary: vary,
+            status: status,
+            headers: res_headers,
+            body: body,
+            expires: self.clock.now() + self.ttl,
+        };
+        self.store.lock().unwrap().insert(key, entry, self.clock.now());
+    }
+}
+
+impl Invalidator {
+    /// Drops all cached responses for a path, such as `/index.html`, on
+    /// every host.
+    pub fn invalidate(&self, uri: &str) {
+        let mut store = self.store.lock().unwrap();
+        let before = store.entries.len();
+        let mut removed = 0;
+        store.entries.retain(|key, variants| {
+            if key.2 == uri {
+                removed += variants.len();
+                false
+            } else {
+                true
+            }
+        });
+        store.len -= removed;
+        debug!("invalidated {} ({} of {} keys)", uri, before - store.entries.len(), before);
+    }
+
+    /// Drops every cached response.
+    pub fn clear(&self) {
+        let mut store = self.store.lock().unwrap();
+        store.entries.clear();
+        store.len = 0;
+    }
+}
+
+impl Store {
+    fn insert(&mut self, key: Key, entry: Entry, now: Instant) {
+        if self.len >= self.max_entries {
+            self.purge(now);
+            if self.len >= self.max_entries {
+                debug!("cache full, not caching {:?}", key);
+                return;
+            }
+        }
+        let variants = self.entries.entry(key).or_insert(vec![]);
+        let before = variants.len();
+        variants.retain(|existing| existing.vary != entry.vary);
+        self.len = self.len + 1 + variants.len() - before;
+        variants.push(entry);
+    }
+
+    fn purge(&mut self, now: Instant) {
+        let mut len = 0;
+        for variants in self.entries.values_mut() {
+            variants.retain(|entry| entry.expires > now);
+            len += variants.len();
+        }
+        self.entries.retain(|_, variants| !variants.is_empty());
+        self.len = len;
+    }
+}
+
+impl Entry {
+    fn matches(&self, headers: &Headers) -> bool {
+        self.vary.iter().all(|&(ref name, ref value)| {
+            headers.get_raw(name) == value.as_ref().map(|v| &v[..])
+        })
+    }
+
+    fn replay(&self, headers: &Headers, mut res: Response<Fresh>) {
+        *res.status_mut() = self.status;
+        res.headers_mut().extend(self.headers.iter());
+
+        let spec = match headers.get::<Range>() {
+            Some(&Range::Bytes(ref specs)) if specs.len() == 1 && self.status == StatusCode::Ok
+                && range::if_range(headers, &self.headers) => Some(specs[0].clone()),
+            _ => None,
+        };
+
+        let len = self.body.len() as u64;
+        let result = match spec.map(|spec| spec.to_satisfiable_range(len)) {
+            Some(Some((from, to))) => {
+                *res.status_mut() = StatusCode::PartialContent;
+                res.headers_mut().set(ContentRange(ContentRangeSpec::Bytes {
+                    range: Some((from, to)),
+                    instance_length: Some(len)
+                }));
+                res.send(&self.body[from as usize..to as usize + 1])
+            },
+            Some(None) => {
+                *res.status_mut() = StatusCode::RangeNotSatisfiable;
+                res.headers_mut().set(ContentRange(ContentRangeSpec::Bytes {
+                    range: None,
+                    instance_length: Some(len)
+                }));
+                res.send(b"")
+            },
+            None => res.send(&self.body)
+        };
+        if let Err(e) = result {
+            debug!("error writing cached response: {:?}", e);
+        }
+    }
+}
+
+impl<H: Handler> Handler for Cache<H> {
+    fn handle<'a, 'k>(&'a self, req: Request<'a, 'k>, res: Response<'a, Fresh>) {
+        if req.method != Method::Get || req.headers.get_raw("Authorization").is_some() {
+            return self.handler.handle(req, res);
+        }
+
+        let key = key(&req);
+        let res = match self.lookup(&key, &req.headers, res) {
+            Some(res) => res,
+            None => return,
+        };
+
+        // Partial responses aren't cached, so a Range request that missed is
+        // passed on to the handler untouched.
+        if req.headers.has::<Range>() {
+            return self.handler.handle(req, res);
+        }
+
+        let headers = req.headers.clone();
+        let (version, body, status, res_headers) = res.deconstruct();
+        let mut tee = Tee {
+            inner: body.into_inner(),
+            captured: Vec::new(),
+            max: self.max_body_size,
+            overflowed: false,
+        };
+        {
+            let res = Response::<Fresh>::construct(version, ThroughWriter(&mut tee),
+                                                   status, res_headers);
+            self.handler.handle(req, res);
+        }
+
+        if !tee.overflowed {
+            self.insert(key, &headers, &tee.captured);
+        }
+    }
+
+    fn check_continue(&self, head: (&Method, &::uri::RequestUri, &Headers)) -> StatusCode {
+        self.handler.check_continue(head)
+    }
+
+    fn on_raw_head(&self, head: &RawHead, addr: SocketAddr) -> bool {
+        self.handler.on_raw_head(head, addr)
+    }
+
+    fn on_head(&self, req: &Request, res: &mut Headers) -> Option<StatusCode> {
+        self.handler.on_head(req, res)
+    }
+
+    fn on_connection_start(&self) {
+        self.handler.on_connection_start()
+    }
+
+    fn on_connection_end(&self) {
+        self.handler.on_connection_end()
+    }
+}
+
+/// Writes through to the real stream, keeping a copy of what was written.
+struct Tee<'a> {
+    inner: &'a mut (Write + 'a),
+    captured: Vec<u8>,
+    max: usize,
+    overflowed: bool,
+}
+
+impl<'a> Write for Tee<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = try!(self.inner.write(buf));
+        if !self.overflowed {
+            if self.captured.len() + n > self.max {
+                self.overflowed = true;
+                self.captured = Vec::new();
+            } else {
+                self.captured.extend_from_slice(&buf[..n]);
+            }
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn parse(raw: &[u8]) -> ::Result<(StatusCode, Headers, Vec<u8>)> {
+    let mut rdr = BufReader::new(raw);
+    let head = try!(h1::parse_response(&mut rdr));
+    let mut reader = match head.headers.get::<TransferEncoding>() {
+        Some(&TransferEncoding(ref codings)) if codings.last() == Some(&Chunked) => {
+            ChunkedReader(rdr, None)
+        },
+        _ => match head.headers.get::<ContentLength>() {
+            Some(&ContentLength(len)) => SizedReader(rdr, len),
+            None => EmptyReader(rdr),
+        }
+    };
+    let mut body = Vec::new();
+    try!(reader.read_to_end(&mut body));
+    Ok((StatusCode::from_u16(head.subject.0), head.headers, body))
+}
+
+fn is_cacheable(status: StatusCode, headers: &Headers) -> bool {
+    match status {
+        StatusCode::Ok | StatusCode::NonAuthoritativeInformation |
+        StatusCode::NoContent | StatusCode::MultipleChoices |
+        StatusCode::MovedPermanently | StatusCode::NotFound |
+        StatusCode::MethodNotAllowed | StatusCode::Gone => (),
+        _ => return false,
+    }
+
+    if headers.has::<header::SetCookie>() || headers.get::<Vary>() == Some(&Vary::Any) {
+        return false;
+    }
+
+    match headers.get::<CacheControl>() {
+        Some(cc) => !cc.iter().any(|directive| match *directive {
+            CacheDirective::NoStore | CacheDirective::NoCache | CacheDirective::Private => true,
+            _ => false
+        }),
+        None => true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use clock::ManualClock;
+    use header::{CacheControl, CacheDirective, EntityTag, ETag, Vary};
+    use server::{Handler, Request, Response};
+    use server::testing::render_str;
+    use unicase::UniCase;
+
+    use super::Cache;
+
+    struct Counting(Arc<AtomicUsize>);
+
+    impl Handler for Counting {
+        fn handle<'a, 'k>(&'a self, _: Request<'a, 'k>, res: Response<'a>) {
+            let n = self.0.fetch_add(1, Ordering::SeqCst);
+            res.send(format!("call {}", n).as_bytes()).unwrap();
+        }
+    }
+
+    fn counting(calls: &Arc<AtomicUsize>) -> Counting {
+        Counting(calls.clone())
+    }
+
+    #[test]
+    fn test_cache_hit() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = Cache::new(counting(&calls), Duration::from_secs(60));
+
+        let first = render_str(&cache, b"GET /hot HTTP/1.1\r\nHost: example.domain\r\n\r\n");
+        let second = render_str(&cache, b"GET /hot HTTP/1.1\r\nHost: example.domain\r\n\r\n");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(first.ends_with("call 0"));
+        assert!(second.ends_with("call 0"));
+        assert!(second.contains("Content-Length: 6\r\n"));
+        assert!(!second.contains("Transfer-Encoding"));
+    }
+
+    #[test]
+    fn test_cache_hosts() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = Cache::new(counting(&calls), Duration::from_secs(60));
+
+        let a = render_str(&cache, b"GET /hot HTTP/1.1\r\nHost: a.example.domain\r\n\r\n");
+        let b = render_str(&cache, b"GET /hot HTTP/1.1\r\nHost: b.example.domain\r\n\r\n");
+        assert!(a.ends_with("call 0"));
+        assert!(b.ends_with("call 1"));
+        // the same host, named by an absolute request-target
+        let again = render_str(&cache, b"GET http://B.example.domain/hot HTTP/1.1\r\n\r\n");
+        assert!(again.ends_with("call 1"));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_cache_skips_post() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = Cache::new(counting(&calls), Duration::from_secs(60));
+
+        render_str(&cache, b"POST /hot HTTP/1.1\r\nContent-Length: 0\r\n\r\n");
+        render_str(&cache, b"POST /hot HTTP/1.1\r\nContent-Length: 0\r\n\r\n");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_cache_expires() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = Cache::new(counting(&calls), Duration::from_millis(0));
+
+        render_str(&cache, b"GET /hot HTTP/1.1\r\n\r\n");
+        let second = render_str(&cache, b"GET /hot HTTP/1.1\r\n\r\n");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(second.ends_with("call 1"));
+    }
+
+    #[test]
+    fn test_cache_clock() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let clock = ManualClock::new();
+        let mut cache = Cache::new(counting(&calls), Duration::from_secs(60));
+        cache.set_clock(Arc::new(clock.clone()));
+
+        render_str(&cache, b"GET /hot HTTP/1.1\r\n\r\n");
+        clock.advance(Duration::from_secs(59));
+        assert!(render_str(&cache, b"GET /hot HTTP/1.1\r\n\r\n").ends_with("call 0"));
+        clock.advance(Duration::from_secs(1));
+        assert!(render_str(&cache, b"GET /hot HTTP/1.1\r\n\r\n").ends_with("call 1"));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_cache_invalidate() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = Cache::new(counting(&calls), Duration::from_secs(60));
+        let invalidator = cache.invalidator();
+
+        render_str(&cache, b"GET /hot HTTP/1.1\r\n\r\n");
+        render_str(&cache, b"GET /other HTTP/1.1\r\n\r\n");
+        invalidator.invalidate("/hot");
+        render_str(&cache, b"GET /hot HTTP/1.1\r\n\r\n");
+        render_str(&cache, b"GET /other HTTP/1.1\r\n\r\n");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        cache.clear();
+        render_str(&cache, b"GET /other HTTP/1.1\r\n\r\n");
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_cache_vary() {
+        fn handle(req: Request, mut res: Response) {
+            let lang = String::from_utf8(req.headers.get_raw("Accept-Language")
+                .map(|raw| raw[0].clone()).unwrap_or(vec![])).unwrap();
+            res.headers_mut().set(Vary::Items(vec![UniCase("Accept-Language".to_owned())]));
+            res.send(lang.as_bytes()).unwrap();
+        }
+        let cache = Cache::new(handle, Duration::from_secs(60));
+
+        render_str(&cache, b"GET / HTTP/1.1\r\nAccept-Language: en\r\n\r\n");
+        render_str(&cache, b"GET / HTTP/1.1\r\nAccept-Language: de\r\n\r\n");
+        let en = render_str(&cache, b"GET / HTTP/1.1\r\nAccept-Language: en\r\n\r\n");
+        let de = render_str(&cache, b"GET / HTTP/1.1\r\nAccept-Language: de\r\n\r\n");
+        assert!(en.ends_with("\r\n\r\nen"));
+        assert!(de.ends_with("\r\n\r\nde"));
+        assert_eq!(cache.store.lock().unwrap().len, 2);
+    }
+
+    #[test]
+    fn test_cache_no_store() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let inner = calls.clone();
+        let cache = Cache::new(move |_: Request, mut res: Response| {
+            inner.fetch_add(1, Ordering::SeqCst);
+            res.headers_mut().set(CacheControl(vec![CacheDirective::NoStore]));
+            res.send(b"secret").unwrap();
+        }, Duration::from_secs(60));
+
+        render_str(&cache, b"GET / HTTP/1.1\r\n\r\n");
+        render_str(&cache, b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_cache_range() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = Cache::new(counting(&calls), Duration::from_secs(60));
+
+        // a miss is passed through, and not cached
+        render_str(&cache, b"GET / HTTP/1.1\r\nRange: bytes=0-1\r\n\r\n");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        render_str(&cache, b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let partial = render_str(&cache, b"GET / HTTP/1.1\r\nRange: bytes=2-\r\n\r\n");
+        assert!(partial.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(partial.contains("Content-Range: bytes 2-5/6\r\n"));
+        assert!(partial.ends_with("\r\n\r\nll 1"));
+
+        let suffix = render_str(&cache, b"GET / HTTP/1.1\r\nRange: bytes=-1\r\n\r\n");
+        assert!(suffix.contains("Content-Range: bytes 5-5/6\r\n"));
+        assert!(suffix.ends_with("\r\n\r\n1"));
+
+        let unsatisfiable = render_str(&cache, b"GET / HTTP/1.1\r\nRange: bytes=6-\r\n\r\n");
+        assert!(unsatisfiable.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"));
+        assert!(unsatisfiable.contains("Content-Range: bytes */6\r\n"));
+
+        let multi = render_str(&cache, b"GET / HTTP/1.1\r\nRange: bytes=0-1,3-4\r\n\r\n");
+        assert!(multi.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(multi.ends_with("\r\n\r\ncall 1"));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_cache_if_range() {
+        let cache = Cache::new(|_: Request, mut res: Response| {
+            res.headers_mut().set(ETag(EntityTag::strong("v1".to_owned())));
+            res.send(b"abcdef").unwrap();
+        }, Duration::from_secs(60));
+
+        render_str(&cache, b"GET / HTTP/1.1\r\n\r\n");
+        let current = render_str(&cache,
+            b"GET / HTTP/1.1\r\nRange: bytes=0-1\r\nIf-Range: \"v1\"\r\n\r\n");
+        assert!(current.ends_with("\r\n\r\nab"));
+        let stale = render_str(&cache,
+            b"GET / HTTP/1.1\r\nRange: bytes=0-1\r\nIf-Range: \"v0\"\r\n\r\n");
+        assert!(stale.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(stale.ends_with("\r\n\r\nabcdef"));
+    }
+
+    #[test]
+    fn test_cache_max_body_size() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut cache = Cache::new(counting(&calls), Duration::from_secs(60));
+        cache.set_max_body_size(4);
+
+        render_str(&cache, b"GET / HTTP/1.1\r\n\r\n");
+        render_str(&cache, b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
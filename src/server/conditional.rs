@@ -0,0 +1,207 @@
+//! Answering conditional requests.
+//!
+//! `evaluate` checks the `If-Match`, `If-Unmodified-Since`, `If-None-Match`
+//! and `If-Modified-Since` headers of a request against the `ETag` and
+//! `Last-Modified` of the response about to be sent, in the order RFC 7232
+//! gives them. `respond` then sends a `304 Not Modified` or a
+//! `412 Precondition Failed` in place of the response if it should be:
+//!
+//! ```no_run
+//! use hyper::header::{EntityTag, ETag};
+//! use hyper::server::{Request, Response};
+//! use hyper::server::conditional;
+//!
+//! fn page(req: Request, mut res: Response) {
+//!     res.headers_mut().set(ETag(EntityTag::strong("v42".to_owned())));
+//!     if let Some(res) = conditional::respond(&req, res).unwrap() {
+//!         res.send(b"the page").unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! `If-Range` is left to the `range` module.
+use std::io;
+
+use header::{ContentEncoding, ContentLanguage, ContentLength, ContentType};
+use header::{ETag, Headers, HttpDate, IfMatch, IfModifiedSince, IfNoneMatch};
+use header::{IfUnmodifiedSince, LastModified};
+use method::Method;
+use net::Fresh;
+use server::{Request, Response};
+use status::StatusCode;
+
+/// What a request's preconditions call for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// Send the response as it is.
+    Proceed,
+    /// The client's copy is current, so send `304 Not Modified` instead.
+    NotModified,
+    /// A precondition failed, so send `412 Precondition Failed` instead.
+    Failed,
+}
+
+/// Evaluates the preconditions of a request, with headers `req`, against
+/// the validators in the headers `res` of its response.
+///
+/// A request with preconditions is taken to be for a resource that exists.
+pub fn evaluate(method: &Method, req: &Headers, res: &Headers) -> Precondition {
+    let etag = res.get::<ETag>().map(|etag| &etag.0);
+    let modified = res.get::<LastModified>().map(|modified| modified.0);
+    let safe = *method == Method::Get || *method == Method::Head;
+
+    if let Some(if_match) = req.get::<IfMatch>() {
+        let matched = match *if_match {
+            IfMatch::Any => true,
+            IfMatch::Items(ref tags) => etag.map_or(false, |etag| {
+                tags.iter().any(|tag| tag.strong_eq(etag))
+            }),
+        };
+        if !matched {
+            return Precondition::Failed;
+        }
+    } else if let Some(&IfUnmodifiedSince(since)) = req.get::<IfUnmodifiedSince>() {
+        if modified.map_or(false, |modified| after(modified, since)) {
+            return Precondition::Failed;
+        }
+    }
+
+    if let Some(if_none_match) = req.get::<IfNoneMatch>() {
+        let matched = match *if_none_match {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Items(ref tags) => etag.map_or(false, |etag| {
+                tags.iter().any(|tag| tag.weak_eq(etag))
+            }),
+        };
+        if matched {
+            return if safe { Precondition::NotModified } else { Precondition::Failed };
+        }
+    } else if let Some(&IfModifiedSince(since)) = req.get::<IfModifiedSince>() {
+        if safe && modified.map_or(false, |modified| !after(modified, since)) {
+            return Precondition::NotModified;
+        }
+    }
+
+    Precondition::Proceed
+}
+
+// Compares as instants, since `HttpDate`s can be in different time zones.
+fn after(a: HttpDate, b: HttpDate) -> bool {
+    a.0.to_timespec() > b.0.to_timespec()
+}
+
+/// Sends a `304 Not Modified` or `412 Precondition Failed` if `req` calls
+/// for one, and otherwise gives `res` back to send as usual.
+///
+/// Headers already set on `res`, such as `ETag` and `Cache-Control`, are
+/// sent along, except those describing a body, which isn't sent.
+pub fn respond<'a>(req: &Request, mut res: Response<'a, Fresh>)
+        -> io::Result<Option<Response<'a, Fresh>>> {
+    let status = match evaluate(&req.method, &req.headers, res.headers()) {
+        Precondition::Proceed => return Ok(Some(res)),
+        Precondition::NotModified => StatusCode::NotModified,
+        Precondition::Failed => StatusCode::PreconditionFailed,
+    };
+    *res.status_mut() = status;
+    {
+        let headers = res.headers_mut();
+        headers.remove::<ContentType>();
+        headers.remove::<ContentEncoding>();
+        headers.remove::<ContentLanguage>();
+        headers.remove::<ContentLength>();
+    }
+    try!(if status == StatusCode::NotModified {
+        try!(res.start()).end()
+    } else {
+        res.send(b"")
+    });
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Headers, IfMatch, IfModifiedSince, IfNoneMatch, IfUnmodifiedSince};
+    use method::Method;
+    use server::{Request, Response};
+    use server::testing::render;
+
+    use super::{evaluate, respond, Precondition};
+
+    const MODIFIED: &'static str = "Sun, 07 Nov 1994 08:48:37 GMT";
+    const BEFORE: &'static str = "Sun, 07 Nov 1994 08:48:36 GMT";
+
+    fn validators() -> Headers {
+        let mut headers = Headers::new();
+        headers.set_raw("ETag", vec![b"W/\"v1\"".to_vec()]);
+        headers.set_raw("Last-Modified", vec![MODIFIED.as_bytes().to_vec()]);
+        headers
+    }
+
+    fn check(method: Method, name: &str, value: &str) -> Precondition {
+        let mut req = Headers::new();
+        req.set_raw(name.to_owned(), vec![value.as_bytes().to_vec()]);
+        evaluate(&method, &req, &validators())
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(evaluate(&Method::Get, &Headers::new(), &validators()), Precondition::Proceed);
+
+        assert_eq!(check(Method::Get, "If-None-Match", "\"v0\", \"v1\""), Precondition::NotModified);
+        assert_eq!(check(Method::Get, "If-None-Match", "\"v2\""), Precondition::Proceed);
+        assert_eq!(check(Method::Put, "If-None-Match", "*"), Precondition::Failed);
+
+        assert_eq!(check(Method::Get, "If-Modified-Since", MODIFIED), Precondition::NotModified);
+        assert_eq!(check(Method::Get, "If-Modified-Since", BEFORE), Precondition::Proceed);
+        assert_eq!(check(Method::Post, "If-Modified-Since", MODIFIED), Precondition::Proceed);
+
+        // weak tags never match strongly
+        assert_eq!(check(Method::Put, "If-Match", "W/\"v1\""), Precondition::Failed);
+        assert_eq!(check(Method::Put, "If-Match", "*"), Precondition::Proceed);
+        assert_eq!(check(Method::Put, "If-Unmodified-Since", BEFORE), Precondition::Failed);
+        assert_eq!(check(Method::Put, "If-Unmodified-Since", MODIFIED), Precondition::Proceed);
+
+        // If-None-Match takes the place of If-Modified-Since, and If-Match
+        // that of If-Unmodified-Since
+        let mut req = Headers::new();
+        req.set(IfNoneMatch::Items(vec![]));
+        req.set_raw("If-Modified-Since", vec![MODIFIED.as_bytes().to_vec()]);
+        assert_eq!(evaluate(&Method::Get, &req, &validators()), Precondition::Proceed);
+        req.remove::<IfNoneMatch>();
+        req.remove::<IfModifiedSince>();
+        req.set(IfMatch::Any);
+        req.set_raw("If-Unmodified-Since", vec![BEFORE.as_bytes().to_vec()]);
+        assert!(req.has::<IfUnmodifiedSince>());
+        assert_eq!(evaluate(&Method::Put, &req, &validators()), Precondition::Proceed);
+    }
+
+    fn page(req: Request, mut res: Response) {
+        res.headers_mut().set_raw("ETag", vec![b"\"v1\"".to_vec()]);
+        res.headers_mut().set_raw("Content-Type", vec![b"text/plain".to_vec()]);
+        if let Some(res) = respond(&req, res).unwrap() {
+            res.send(b"the page").unwrap();
+        }
+    }
+
+    fn get(headers: &str) -> String {
+        let req = format!("GET / HTTP/1.1\r\n{}\r\n", headers);
+        String::from_utf8(render(&page, req.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_respond() {
+        let out = get("");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", out);
+        assert!(out.ends_with("\r\n\r\nthe page"));
+
+        let out = get("If-None-Match: \"v1\"\r\n");
+        assert!(out.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{:?}", out);
+        assert!(out.contains("ETag: \"v1\"\r\n"));
+        assert!(!out.contains("Content-Type") && !out.contains("Content-Length"), "{:?}", out);
+        assert!(out.ends_with("\r\n\r\n"));
+
+        let out = get("If-Match: \"v0\"\r\n");
+        assert!(out.starts_with("HTTP/1.1 412 Precondition Failed\r\n"), "{:?}", out);
+        assert!(out.contains("Content-Length: 0\r\n"));
+    }
+}
@@ -0,0 +1,232 @@
+//! Health and readiness endpoints.
+//!
+//! `Health` wraps another `Handler`, and answers `GET /healthz` and
+//! `GET /readyz` itself, passing every other request on.
+//!
+//! `/healthz` says whether the server is alive, and is always `200 OK`
+//! while it can answer at all. `/readyz` says whether it should be sent
+//! traffic: it runs the checks added with `add_check`, and is `200 OK` if
+//! all pass, or `503 Service Unavailable` with the failures in the body.
+//!
+//! A `Readiness` added to the server's `ShutdownHandle` is set to not ready
+//! when the server starts shutting down, so that load balancers stop
+//! sending it requests while those in flight finish.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use hyper::server::{Server, Request, Response};
+//! use hyper::server::health::Health;
+//!
+//! fn app(_: Request, res: Response) {
+//!     res.send(b"hello").unwrap();
+//! }
+//!
+//! let mut health = Health::new(app);
+//! health.add_check("database", || Ok(()));
+//! let readiness = health.readiness();
+//! let mut listening = Server::http("0.0.0.0:0").unwrap().handle(health).unwrap();
+//! listening.shutdown_handle().add_readiness(readiness);
+//!
+//! // when asked to shut down:
+//! listening.shutdown(Duration::from_secs(30));
+//! ```
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use header::{ContentType, Headers};
+use method::Method;
+use net::Fresh;
+use server::{Handler, RawHead, Request, Response};
+use status::StatusCode;
+use uri::RequestUri;
+
+const LIVE_PATH: &'static str = "/healthz";
+const READY_PATH: &'static str = "/readyz";
+
+type Check = Box<Fn() -> Result<(), String> + Send + Sync>;
+
+/// A `Handler` that answers health and readiness probes.
+pub struct Health<H: Handler> {
+    handler: H,
+    checks: Vec<(String, Check)>,
+    ready: Arc<AtomicBool>,
+}
+
+/// A handle that can mark a `Health` as not ready, such as while a server
+/// is draining before it is closed. See `ShutdownHandle::add_readiness`.
+#[derive(Debug, Clone)]
+pub struct Readiness {
+    ready: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    /// Sets whether `/readyz` can succeed.
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::SeqCst);
+    }
+
+    /// Whether `/readyz` can succeed, if its checks pass.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Probe {
+    Live,
+    Ready,
+}
+
+impl<H: Handler> Health<H> {
+    /// Wraps a handler, which gets the requests that aren't probes.
+    pub fn new(handler: H) -> Health<H> {
+        Health {
+            handler: handler,
+            checks: Vec::new(),
+            ready: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Adds a check run by `/readyz`, which returns why it failed if it
+    /// did.
+    pub fn add_check<F>(&mut self, name: &str, check: F)
+    where F: Fn() -> Result<(), String> + Send + Sync + 'static {
+        self.checks.push((name.to_owned(), Box::new(check)));
+    }
+
+    /// Get a `Readiness` for this handler.
+    pub fn readiness(&self) -> Readiness {
+        Readiness { ready: self.ready.clone() }
+    }
+
+    fn probe(&self, method: &Method, uri: &RequestUri) -> Option<Probe> {
+        if *method != Method::Get {
+            return None;
+        }
+        let path = match *uri {
+            RequestUri::AbsolutePath(ref path) => match path.find('?') {
+                Some(query) => &path[..query],
+                None => &path[..]
+            },
+            _ => return None
+        };
+        match path {
+            LIVE_PATH => Some(Probe::Live),
+            READY_PATH => Some(Probe::Ready),
+            _ => None
+        }
+    }
+
+    fn ready(&self) -> Result<(), String> {
+        let mut failures = String::new();
+        if !self.ready.load(Ordering::SeqCst) {
+            failures.push_str("not ready\n");
+        }
+        for &(ref name, ref check) in &self.checks {
+            if let Err(e) = check() {
+                failures.push_str(&format!("{}: {}\n", name, e));
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
+
+impl<H: Handler> Handler for Health<H> {
+    fn handle<'a, 'k>(&'a self, req: Request<'a, 'k>, mut res: Response<'a, Fresh>) {
+        let result = match self.probe(&req.method, &req.uri) {
+            Some(Probe::Live) => Ok(()),
+            Some(Probe::Ready) => self.ready(),
+            None => return self.handler.handle(req, res),
+        };
+        let body = match result {
+            Ok(()) => "ok\n".to_owned(),
+            Err(failures) => {
+                debug!("{} failed: {:?}", req.uri, failures);
+                *res.status_mut() = StatusCode::ServiceUnavailable;
+                failures
+            }
+        };
+        res.headers_mut().set(ContentType::plaintext());
+        if let Err(e) = res.send(body.as_bytes()) {
+            debug!("error sending probe response: {:?}", e);
+        }
+    }
+
+    fn check_continue(&self, head: (&Method, &RequestUri, &Headers)) -> StatusCode {
+        self.handler.check_continue(head)
+    }
+
+    fn on_raw_head(&self, head: &RawHead, addr: SocketAddr) -> bool {
+        self.handler.on_raw_head(head, addr)
+    }
+
+    fn on_head(&self, req: &Request, res: &mut Headers) -> Option<StatusCode> {
+        // probes don't reach the wrapped handler
+        match self.probe(&req.method, &req.uri) {
+            Some(_) => None,
+            None => self.handler.on_head(req, res)
+        }
+    }
+
+    fn on_connection_start(&self) {
+        self.handler.on_connection_start()
+    }
+
+    fn on_connection_end(&self) {
+        self.handler.on_connection_end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use server::{Request, Response};
+    use server::testing::render_str;
+
+    use super::Health;
+
+    fn app(_: Request, res: Response) {
+        res.send(b"app").unwrap();
+    }
+
+    #[test]
+    fn test_health() {
+        let db_up = Arc::new(AtomicBool::new(true));
+        let mut health = Health::new(app);
+        let up = db_up.clone();
+        health.add_check("database", move || {
+            if up.load(Ordering::SeqCst) { Ok(()) } else { Err("unreachable".to_owned()) }
+        });
+        let readiness = health.readiness();
+
+        let out = render_str(&health, b"GET /readyz HTTP/1.1\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", out);
+        assert!(out.ends_with("ok\n"));
+        let out = render_str(&health, b"GET /index HTTP/1.1\r\n\r\n");
+        assert!(out.ends_with("app"));
+
+        db_up.store(false, Ordering::SeqCst);
+        let out = render_str(&health, b"GET /readyz?verbose HTTP/1.1\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 503 Service Unavailable\r\n"), "{:?}", out);
+        assert!(out.ends_with("database: unreachable\n"));
+
+        // draining is not ready, but still alive
+        db_up.store(true, Ordering::SeqCst);
+        readiness.set_ready(false);
+        let out = render_str(&health, b"GET /readyz HTTP/1.1\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 503 Service Unavailable\r\n"), "{:?}", out);
+        assert!(out.ends_with("not ready\n"));
+        let out = render_str(&health, b"GET /healthz HTTP/1.1\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", out);
+
+        let out = render_str(&health, b"POST /healthz HTTP/1.1\r\nContent-Length: 0\r\n\r\n");
+        assert!(out.ends_with("app"));
+    }
+}
@@ -13,13 +13,14 @@ impl<A: NetworkListener + Send + 'static> ListenerPool<A> {
         ListenerPool { acceptor: acceptor }
     }
 
-    /// Runs the acceptor pool. Blocks until the acceptors are closed.
+    /// Runs the acceptor pool. Blocks until every thread has stopped, when
+    /// `work` returns `false` for a stream it was given.
     ///
     /// ## Panics
     ///
     /// Panics if threads == 0.
     pub fn accept<F>(self, work: F, threads: usize)
-        where F: Fn(A::Stream) + Send + Sync + 'static {
+        where F: Fn(A::Stream) -> bool + Send + Sync + 'static {
         assert!(threads != 0, "Can't accept on 0 threads.");
 
         let (super_tx, supervisor_rx) = mpsc::channel();
@@ -31,23 +32,32 @@ impl<A: NetworkListener + Send + 'static> ListenerPool<A> {
             spawn_with(super_tx.clone(), work.clone(), self.acceptor.clone())
         }
 
-        // Monitor for panics.
-        // FIXME(reem): This won't ever exit since we still have a super_tx handle.
-        for _ in supervisor_rx.iter() {
-            spawn_with(super_tx.clone(), work.clone(), self.acceptor.clone());
+        // Monitor for panics, until all threads have stopped.
+        let mut running = threads;
+        for panicked in supervisor_rx.iter() {
+            if panicked {
+                spawn_with(super_tx.clone(), work.clone(), self.acceptor.clone());
+            } else {
+                running -= 1;
+                if running == 0 {
+                    break;
+                }
+            }
         }
     }
 }
 
-fn spawn_with<A, F>(supervisor: mpsc::Sender<()>, work: Arc<F>, mut acceptor: A)
+fn spawn_with<A, F>(supervisor: mpsc::Sender<bool>, work: Arc<F>, mut acceptor: A)
 where A: NetworkListener + Send + 'static,
-      F: Fn(<A as NetworkListener>::Stream) + Send + Sync + 'static {
+      F: Fn(<A as NetworkListener>::Stream) -> bool + Send + Sync + 'static {
     thread::spawn(move || {
-        let _sentinel = Sentinel::new(supervisor, ());
+        let _sentinel = Sentinel::new(supervisor);
 
         loop {
             match acceptor.accept() {
-                Ok(stream) => work(stream),
+                Ok(stream) => if !work(stream) {
+                    break;
+                },
                 Err(e) => {
                     error!("Connection failed: {}", e);
                 }
@@ -56,24 +66,22 @@ where A: NetworkListener + Send + 'static,
     });
 }
 
-struct Sentinel<T: Send + 'static> {
-    value: Option<T>,
-    supervisor: mpsc::Sender<T>,
+// Tells the supervisor when its thread ends, and whether by a panic.
+struct Sentinel {
+    supervisor: mpsc::Sender<bool>,
 }
 
-impl<T: Send + 'static> Sentinel<T> {
-    fn new(channel: mpsc::Sender<T>, data: T) -> Sentinel<T> {
+impl Sentinel {
+    fn new(channel: mpsc::Sender<bool>) -> Sentinel {
         Sentinel {
-            value: Some(data),
             supervisor: channel,
         }
     }
 }
 
-impl<T: Send + 'static> Drop for Sentinel<T> {
+impl Drop for Sentinel {
     fn drop(&mut self) {
-        // Respawn ourselves
-        let _ = self.supervisor.send(self.value.take().unwrap());
+        // Respawn ourselves if we panicked
+        let _ = self.supervisor.send(thread::panicking());
     }
 }
-
@@ -61,6 +61,34 @@
 //! }).unwrap();
 //! ```
 //!
+//! # Handlers per connection
+//!
+//! To make a handler for each connection instead, pass a `HandlerFactory`
+//! to `handle_factory`. It is called with every accepted connection, and
+//! can refuse it, or make a handler that keeps state for just that
+//! connection. Closures taking a `&ConnectionInfo` are factories too.
+//!
+//! ```no_run
+//! use std::net::SocketAddr;
+//! use hyper::server::{ConnectionInfo, Handler, Server, Request, Response};
+//!
+//! struct Peer(SocketAddr);
+//!
+//! impl Handler for Peer {
+//!     fn handle(&self, _: Request, res: Response) {
+//!         res.send(format!("hello {}", self.0).as_bytes()).unwrap();
+//!     }
+//! }
+//!
+//! Server::http("0.0.0.0:0").unwrap().handle_factory(|conn: &ConnectionInfo| {
+//!     if conn.remote_addr.ip().is_loopback() {
+//!         Some(Peer(conn.remote_addr))
+//!     } else {
+//!         None
+//!     }
+//! }).unwrap();
+//! ```
+//!
 //! # The `Request` and `Response` pair
 //!
 //! A `Handler` receives a pair of arguments, a `Request` and a `Response`. The
@@ -109,31 +137,53 @@
 //! implement `Write`.
 use std::fmt;
 use std::io::{self, ErrorKind, BufWriter, Write};
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use num_cpus;
 
 pub use self::request::Request;
 pub use self::response::Response;
 
+pub use http::h1::{RawHead, RawHeaders, ColonWhitespace, Limits};
 pub use net::{Fresh, Streaming};
 
 use Error;
 use buffer::BufReader;
-use header::{Headers, Expect, Connection};
+use clock::{self, Clock};
+use header::{Headers, Expect, Connection, Date, HttpDate};
+use header::Server as ServerHeader;
 use http;
+#[cfg(feature = "timeouts")]
+use http::h1;
 use method::Method;
 use net::{NetworkListener, NetworkStream, HttpListener, HttpsListener, Ssl};
+#[cfg(unix)]
+use net::UnixListener;
 use status::StatusCode;
 use uri::RequestUri;
 use version::HttpVersion::Http11;
 
+use self::health::Readiness;
 use self::listener::ListenerPool;
 
 pub mod request;
 pub mod response;
+pub mod cache;
+pub mod conditional;
+pub mod health;
+pub mod range;
+pub mod redirect;
+pub mod shed;
+pub mod sse;
+pub mod testing;
+pub mod tunnel;
+pub mod vhost;
 
 mod listener;
 
@@ -141,10 +191,102 @@ mod listener;
 ///
 /// Once listening, it will create a `Request`/`Response` pair for each
 /// incoming connection, and hand them to the provided handler.
+///
+/// More listeners, such as one for HTTPS next to the plaintext one, can be
+/// added with `add_listener`, to share the handler and `Listening` handle.
 #[derive(Debug)]
 pub struct Server<L = HttpListener> {
     listener: L,
-    timeouts: Timeouts,
+    listeners: Listeners,
+    config: WorkerConfig,
+}
+
+// The listeners added with `add_listener`, of any type.
+struct Listeners(Vec<Box<Bind>>);
+
+impl fmt::Debug for Listeners {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Listeners({})", self.0.len())
+    }
+}
+
+// A listener that can start accepting connections for a shared factory.
+trait Bind: Send + Sync {
+    fn local_addr(&mut self) -> io::Result<SocketAddr>;
+    fn start(self: Box<Self>, worker: Arc<Worker<Erased>>, threads: usize) -> JoinHandle<()>;
+}
+
+impl<L: NetworkListener + Send + Sync + 'static> Bind for L {
+    fn local_addr(&mut self) -> io::Result<SocketAddr> {
+        NetworkListener::local_addr(self)
+    }
+
+    fn start(self: Box<Self>, worker: Arc<Worker<Erased>>, threads: usize) -> JoinHandle<()> {
+        let pool = ListenerPool::new(*self);
+        let work = move |mut stream| worker.work(&mut stream);
+        thread::spawn(move || pool.accept(work, threads))
+    }
+}
+
+// A handler shared by several connections.
+struct SharedHandler<H: Handler + ?Sized>(Arc<H>);
+
+impl<H: Handler + ?Sized> Handler for SharedHandler<H> {
+    fn handle<'a, 'k>(&'a self, req: Request<'a, 'k>, res: Response<'a, Fresh>) {
+        self.0.handle(req, res)
+    }
+
+    fn check_continue(&self, head: (&Method, &RequestUri, &Headers)) -> StatusCode {
+        self.0.check_continue(head)
+    }
+
+    fn on_raw_head(&self, head: &RawHead, addr: SocketAddr) -> bool {
+        self.0.on_raw_head(head, addr)
+    }
+
+    fn on_head(&self, req: &Request, res: &mut Headers) -> Option<StatusCode> {
+        self.0.on_head(req, res)
+    }
+
+    fn on_connection_start(&self) {
+        self.0.on_connection_start()
+    }
+
+    fn on_connection_end(&self) {
+        self.0.on_connection_end()
+    }
+}
+
+// The factory behind `Server::handle`, giving every connection the same handler.
+struct Single<H: Handler>(Arc<H>);
+
+impl<H: Handler> HandlerFactory for Single<H> {
+    type Handler = SharedHandler<H>;
+
+    fn on_connection(&self, _: &ConnectionInfo) -> Option<SharedHandler<H>> {
+        Some(SharedHandler(self.0.clone()))
+    }
+}
+
+// A factory of any type, shared by the listeners of a server.
+struct Erased(Box<DynFactory>);
+
+trait DynFactory: Sync + Send {
+    fn shared_handler(&self, conn: &ConnectionInfo) -> Option<Arc<Handler>>;
+}
+
+impl<F: HandlerFactory> DynFactory for F where F::Handler: 'static {
+    fn shared_handler(&self, conn: &ConnectionInfo) -> Option<Arc<Handler>> {
+        self.on_connection(conn).map(|handler| Arc::new(handler) as Arc<Handler>)
+    }
+}
+
+impl HandlerFactory for Erased {
+    type Handler = SharedHandler<Handler>;
+
+    fn on_connection(&self, conn: &ConnectionInfo) -> Option<SharedHandler<Handler>> {
+        self.0.shared_handler(conn).map(SharedHandler)
+    }
 }
 
 #[derive(Clone, Copy, Default, Debug)]
@@ -152,6 +294,9 @@ struct Timeouts {
     read: Option<Duration>,
     write: Option<Duration>,
     keep_alive: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    #[cfg(feature = "timeouts")]
+    head: Option<Duration>,
 }
 
 macro_rules! try_option(
@@ -169,10 +314,20 @@ impl<L: NetworkListener> Server<L> {
     pub fn new(listener: L) -> Server<L> {
         Server {
             listener: listener,
-            timeouts: Timeouts::default(),
+            listeners: Listeners(Vec::new()),
+            config: WorkerConfig::default(),
         }
     }
 
+    /// Adds another listener, which can be of another type, such as an
+    /// `HttpsListener` for a server made with `Server::http`.
+    ///
+    /// Connections to every listener are handled by the same handler, and
+    /// each listener gets its own threads.
+    pub fn add_listener<A: NetworkListener + Send + Sync + 'static>(&mut self, listener: A) {
+        self.listeners.0.push(Box::new(listener));
+    }
+
     /// Enables keep-alive for this server.
     ///
     /// The timeout duration passed will be used to determine how long
@@ -182,17 +337,88 @@ impl<L: NetworkListener> Server<L> {
     /// is enabled for hyper, and rustc is 1.4 or greater.
     #[inline]
     pub fn keep_alive(&mut self, timeout: Duration) {
-        self.timeouts.keep_alive = Some(timeout);
+        self.config.timeouts.keep_alive = Some(timeout);
+    }
+
+    /// Sets how long after it was accepted a connection is kept alive for.
+    /// The response to the first request after that closes it, so that
+    /// clients behind a load balancer move on to other servers.
+    #[inline]
+    pub fn set_max_connection_lifetime(&mut self, lifetime: Option<Duration>) {
+        self.config.timeouts.max_lifetime = lifetime;
+    }
+
+    /// Sets a `Server` header to send with every response. Handlers can
+    /// still replace or remove it.
+    #[inline]
+    pub fn set_server_header(&mut self, server: Option<ServerHeader>) {
+        self.config.server_header = server;
+    }
+
+    /// Sets the clock giving the time for `Date` headers, and by which the
+    /// head timeout runs out.
+    #[inline]
+    pub fn set_clock(&mut self, clock: Arc<Clock>) {
+        self.config.clock = clock;
+    }
+
+    /// Sets whether to refuse requests with header lines that can't be
+    /// parsed, by closing the connection.
+    ///
+    /// By default these lines are kept out of the headers, and can be seen
+    /// with `Headers::get_raw_invalid`.
+    #[inline]
+    pub fn set_strict_headers(&mut self, strict: bool) {
+        self.config.strict_headers = strict;
+    }
+
+    /// Sets what to do with whitespace between a header name and its colon.
+    ///
+    /// The default, `ColonWhitespace::Reject`, closes the connection.
+    #[inline]
+    pub fn set_colon_whitespace(&mut self, colon: ColonWhitespace) {
+        self.config.colon_whitespace = colon;
+    }
+
+    /// Sets the limits on the size of requests.
+    ///
+    /// A request over them is answered with `414 URI Too Long`,
+    /// `431 Request Header Fields Too Large` or `413 Payload Too Large`,
+    /// and the connection is closed.
+    #[inline]
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.config.limits = limits;
+    }
+
+    /// Sets whether to keep the head of each request exactly as it was
+    /// received, for `Request::raw_head`, as a gateway forwarding heads
+    /// byte for byte needs.
+    ///
+    /// This costs a copy of every head, so it is off by default.
+    #[inline]
+    pub fn set_raw_heads(&mut self, raw_heads: bool) {
+        self.config.raw_heads = raw_heads;
     }
 
     #[cfg(feature = "timeouts")]
     pub fn set_read_timeout(&mut self, dur: Option<Duration>) {
-        self.timeouts.read = dur;
+        self.config.timeouts.read = dur;
     }
 
     #[cfg(feature = "timeouts")]
     pub fn set_write_timeout(&mut self, dur: Option<Duration>) {
-        self.timeouts.write = dur;
+        self.config.timeouts.write = dur;
+    }
+
+    /// Sets how long a client has to send the whole head of a request,
+    /// from its first byte, before the connection is closed.
+    ///
+    /// The read timeout only limits each read, so without this a client
+    /// sending a byte at a time can hold a connection open for as long as
+    /// it likes.
+    #[cfg(feature = "timeouts")]
+    pub fn set_head_timeout(&mut self, dur: Option<Duration>) {
+        self.config.timeouts.head = dur;
     }
 }
 
@@ -203,6 +429,18 @@ impl Server<HttpListener> {
     }
 }
 
+#[cfg(unix)]
+impl Server<UnixListener> {
+    /// Creates a new server that will handle `UnixStream`s, from a Unix
+    /// domain socket at `path`.
+    ///
+    /// The path must not exist yet. A `Listening` for it has the socket
+    /// address `0.0.0.0:0`, and so has the `remote_addr` of each request.
+    pub fn unix<P: AsRef<Path>>(path: P) -> ::Result<Server<UnixListener>> {
+        UnixListener::new(path).map(Server::new)
+    }
+}
+
 impl<S: Ssl + Clone + Send> Server<HttpsListener<S>> {
     /// Creates a new server that will handle `HttpStream`s over SSL.
     ///
@@ -212,7 +450,7 @@ impl<S: Ssl + Clone + Send> Server<HttpsListener<S>> {
     }
 }
 
-impl<L: NetworkListener + Send + 'static> Server<L> {
+impl<L: NetworkListener + Send + Sync + 'static> Server<L> {
     /// Binds to a socket and starts handling connections.
     pub fn handle<H: Handler + 'static>(self, handler: H) -> ::Result<Listening> {
         self.handle_threads(handler, num_cpus::get() * 5 / 4)
@@ -222,45 +460,146 @@ impl<L: NetworkListener + Send + 'static> Server<L> {
     /// number of threads.
     pub fn handle_threads<H: Handler + 'static>(self, handler: H,
             threads: usize) -> ::Result<Listening> {
-        handle(self, handler, threads)
+        handle(self, Single(Arc::new(handler)), threads)
+    }
+
+    /// Binds to a socket and starts handling connections, each with a
+    /// handler made for it by the factory.
+    pub fn handle_factory<F>(self, factory: F) -> ::Result<Listening>
+    where F: HandlerFactory + 'static, F::Handler: 'static {
+        self.handle_factory_threads(factory, num_cpus::get() * 5 / 4)
+    }
+
+    /// Binds to a socket and starts handling connections, each with a
+    /// handler made for it by the factory, with the provided number of
+    /// threads.
+    pub fn handle_factory_threads<F>(self, factory: F, threads: usize) -> ::Result<Listening>
+    where F: HandlerFactory + 'static, F::Handler: 'static {
+        handle(self, factory, threads)
     }
 }
 
-fn handle<H, L>(mut server: Server<L>, handler: H, threads: usize) -> ::Result<Listening>
-where H: Handler + 'static, L: NetworkListener + Send + 'static {
+fn handle<F, L>(mut server: Server<L>, factory: F, threads: usize) -> ::Result<Listening>
+where F: HandlerFactory + 'static, F::Handler: 'static, L: NetworkListener + Send + Sync + 'static {
     let socket = try!(server.listener.local_addr());
-
     debug!("threads = {:?}", threads);
+
+    if !server.listeners.0.is_empty() {
+        return handle_shared(server, Erased(Box::new(factory)), threads, socket);
+    }
+
     let pool = ListenerPool::new(server.listener);
-    let worker = Worker::new(handler, server.timeouts);
-    let work = move |mut stream| worker.handle_connection(&mut stream);
+    let worker = Worker::configured(factory, server.config);
+    let drain = worker.drain.clone();
+    let work = move |mut stream| worker.work(&mut stream);
 
     let guard = thread::spawn(move || pool.accept(work, threads));
 
     Ok(Listening {
         _guard: Some(guard),
+        _guards: Vec::new(),
         socket: socket,
+        sockets: vec![socket],
+        shutdown: ShutdownHandle {
+            drain: drain,
+            sockets: vec![socket],
+            threads: threads,
+        },
     })
 }
 
-struct Worker<H: Handler + 'static> {
-    handler: H,
+fn handle_shared<L>(server: Server<L>, factory: Erased, threads: usize, socket: SocketAddr)
+    -> ::Result<Listening>
+where L: NetworkListener + Send + Sync + 'static {
+    let Server { listener, listeners, config } = server;
+    let mut binds = listeners.0;
+    binds.insert(0, Box::new(listener));
+    let mut sockets = vec![socket];
+    for bind in &mut binds[1..] {
+        sockets.push(try!(bind.local_addr()));
+    }
+
+    let worker = Arc::new(Worker::configured(factory, config));
+    let guards = binds.into_iter().map(|bind| bind.start(worker.clone(), threads)).collect();
+
+    Ok(Listening {
+        _guard: None,
+        _guards: guards,
+        socket: socket,
+        sockets: sockets.clone(),
+        shutdown: ShutdownHandle {
+            drain: worker.drain.clone(),
+            sockets: sockets,
+            threads: threads,
+        },
+    })
+}
+
+// How a Server handles each connection, set with its `set_*` methods.
+#[derive(Debug)]
+struct WorkerConfig {
     timeouts: Timeouts,
+    server_header: Option<ServerHeader>,
+    strict_headers: bool,
+    colon_whitespace: ColonWhitespace,
+    limits: Limits,
+    raw_heads: bool,
+    clock: Arc<Clock>,
 }
 
-impl<H: Handler + 'static> Worker<H> {
-    fn new(handler: H, timeouts: Timeouts) -> Worker<H> {
-        Worker {
-            handler: handler,
+impl Default for WorkerConfig {
+    fn default() -> WorkerConfig {
+        WorkerConfig {
+            timeouts: Timeouts::default(),
+            server_header: None,
+            strict_headers: false,
+            colon_whitespace: ColonWhitespace::Reject,
+            limits: Limits::default(),
+            raw_heads: false,
+            clock: clock::system(),
+        }
+    }
+}
+
+struct Worker<F: HandlerFactory + 'static> {
+    factory: F,
+    config: WorkerConfig,
+    drain: Arc<Drain>,
+}
+
+#[cfg(test)]
+impl<H: Handler + 'static> Worker<Single<H>> {
+    fn new(handler: H, timeouts: Timeouts) -> Worker<Single<H>> {
+        Worker::configured(Single(Arc::new(handler)), WorkerConfig {
             timeouts: timeouts,
+            ..WorkerConfig::default()
+        })
+    }
+}
+
+impl<F: HandlerFactory + 'static> Worker<F> {
+    fn configured(factory: F, config: WorkerConfig) -> Worker<F> {
+        Worker {
+            factory: factory,
+            config: config,
+            drain: Arc::new(Drain::new()),
         }
     }
 
+    // Handles a connection accepted by a listener, and returns whether to
+    // keep accepting more.
+    fn work<S>(&self, stream: &mut S) -> bool where S: NetworkStream + Clone {
+        let _active = self.drain.enter();
+        if self.drain.is_closing() {
+            return false;
+        }
+        self.handle_connection(stream);
+        !self.drain.is_closing()
+    }
+
     fn handle_connection<S>(&self, mut stream: &mut S) where S: NetworkStream + Clone {
         debug!("Incoming stream");
 
-        self.handler.on_connection_start();
-
         if let Err(e) = self.set_timeouts(&*stream) {
             error!("set_timeouts error: {:?}", e);
             return;
@@ -274,26 +613,47 @@ impl<H: Handler + 'static> Worker<H> {
             }
         };
 
+        let handler = match self.factory.on_connection(&ConnectionInfo {
+            remote_addr: addr,
+            stream: &*stream,
+        }) {
+            Some(handler) => handler,
+            None => {
+                debug!("connection from {} refused by the handler factory", addr);
+                return;
+            }
+        };
+
+        handler.on_connection_start();
+
         // FIXME: Use Type ascription
         let stream_clone: &mut NetworkStream = &mut stream.clone();
         let mut rdr = BufReader::new(stream_clone);
         let mut wrt = BufWriter::new(stream);
 
-        while self.keep_alive_loop(&mut rdr, &mut wrt, addr) {
-            if let Err(e) = self.set_read_timeout(*rdr.get_ref(), self.timeouts.keep_alive) {
+        let accepted = self.config.clock.now();
+        while self.keep_alive_loop(&handler, &mut rdr, &mut wrt, addr, accepted) {
+            if let Err(e) = self.set_read_timeout(*rdr.get_ref(), self.config.timeouts.keep_alive) {
                 error!("set_read_timeout keep_alive {:?}", e);
                 break;
             }
         }
 
-        self.handler.on_connection_end();
+        // Shut down writing before the stream is dropped, so that the client
+        // reads the end of the last response, even if closing the connection
+        // with its next request still unread would reset it.
+        if let Err(e) = wrt.flush().and_then(|_| wrt.get_mut().close(Shutdown::Write)) {
+            debug!("error shutting down writing to {}: {:?}", addr, e);
+        }
+
+        handler.on_connection_end();
 
         debug!("keep_alive loop ending for {}", addr);
     }
 
     fn set_timeouts(&self, s: &NetworkStream) -> io::Result<()> {
-        try!(self.set_read_timeout(s, self.timeouts.read));
-        self.set_write_timeout(s, self.timeouts.write)
+        try!(self.set_read_timeout(s, self.config.timeouts.read));
+        self.set_write_timeout(s, self.config.timeouts.write)
     }
 
     #[cfg(not(feature = "timeouts"))]
@@ -316,9 +676,23 @@ impl<H: Handler + 'static> Worker<H> {
         s.set_read_timeout(timeout)
     }
 
-    fn keep_alive_loop<W: Write>(&self, mut rdr: &mut BufReader<&mut NetworkStream>,
-            wrt: &mut W, addr: SocketAddr) -> bool {
-        let req = match Request::new(rdr, addr) {
+    fn keep_alive_loop<W: Write>(&self, handler: &F::Handler,
+            mut rdr: &mut BufReader<&mut NetworkStream>, wrt: &mut W, addr: SocketAddr,
+            accepted: Instant) -> bool {
+        if let Err(e) = self.read_head(rdr) {
+            debug!("error reading head from {}: {:?}", addr, e);
+            return false;
+        }
+
+        let mut inspected = true;
+        let mut raw_head = None;
+        let mut req = match Request::with_limits(rdr, addr, self.config.colon_whitespace, &self.config.limits,
+                                                 |head| {
+            inspected = handler.on_raw_head(head, addr);
+            if self.config.raw_heads {
+                raw_head = Some(head.as_bytes().to_vec());
+            }
+        }) {
             Ok(req) => req,
             Err(Error::Io(ref e)) if e.kind() == ErrorKind::ConnectionAborted => {
                 trace!("tcp closed, cancelling keep-alive loop");
@@ -329,32 +703,84 @@ impl<H: Handler + 'static> Worker<H> {
                 return false;
             }
             Err(e) => {
-                //TODO: send a 400 response
+                let status = match e {
+                    Error::UriTooLong => Some(StatusCode::UriTooLong),
+                    Error::TooLarge => Some(StatusCode::RequestHeaderFieldsTooLarge),
+                    Error::BodyTooLarge => Some(StatusCode::PayloadTooLarge),
+                    //TODO: send a 400 response
+                    _ => None
+                };
                 error!("request error = {:?}", e);
+                if let Some(status) = status {
+                    self.refuse(wrt, status);
+                }
                 return false;
             }
         };
 
-        if !self.handle_expect(&req, wrt) {
+        if !inspected {
+            debug!("request from {} refused by on_raw_head", addr);
             return false;
         }
+        if let Some(head) = raw_head {
+            req.set_raw_head(head);
+        }
 
-        if let Err(e) = req.set_read_timeout(self.timeouts.read) {
-            error!("set_read_timeout {:?}", e);
+        if self.config.strict_headers && !req.headers.get_raw_invalid().is_empty() {
+            debug!("request from {} has invalid header lines", addr);
             return false;
         }
 
-        let mut keep_alive = self.timeouts.keep_alive.is_some() &&
-            http::should_keep_alive(req.version, &req.headers);
+        let expired = self.config.timeouts.max_lifetime.map_or(false, |max| {
+            self.config.clock.now() - accepted >= max
+        });
+        if expired {
+            debug!("connection from {} lived past its max lifetime", addr);
+        }
+        let mut keep_alive = self.config.timeouts.keep_alive.is_some() && !expired &&
+            http::should_keep_alive(req.version, &req.headers) && !self.drain.is_closing();
         let version = req.version;
         let mut res_headers = Headers::new();
+        res_headers.set(Date(HttpDate(self.config.clock.now_utc())));
+        if let Some(ref server) = self.config.server_header {
+            res_headers.set(server.clone());
+        }
+
+        if let Some(status) = handler.on_head(&req, &mut res_headers) {
+            debug!("request from {} answered by on_head with {}", addr, status);
+            // the body is left unread, so the connection can't be reused
+            // unless there isn't one
+            let keep_alive = keep_alive && !req.has_body() &&
+                http::should_keep_alive(version, &res_headers);
+            if !keep_alive {
+                res_headers.set(Connection::close());
+            }
+            let mut res = Response::new(wrt, &mut res_headers);
+            res.version = version;
+            *res.status_mut() = status;
+            if let Err(e) = res.send(b"") {
+                debug!("error sending on_head response: {:?}", e);
+                return false;
+            }
+            return keep_alive;
+        }
+
+        if !self.handle_expect(handler, &req, wrt) {
+            return false;
+        }
+
+        if let Err(e) = req.set_read_timeout(self.config.timeouts.read) {
+            error!("set_read_timeout {:?}", e);
+            return false;
+        }
+
         if !keep_alive {
             res_headers.set(Connection::close());
         }
         {
             let mut res = Response::new(wrt, &mut res_headers);
             res.version = version;
-            self.handler.handle(req, res);
+            handler.handle(req, res);
         }
 
         // if the request was keep-alive, we need to check that the server agrees
@@ -362,14 +788,71 @@ impl<H: Handler + 'static> Worker<H> {
         if keep_alive {
             keep_alive = http::should_keep_alive(version, &res_headers);
         }
+        // the response was begun before the server started shutting down
+        if self.drain.is_closing() {
+            keep_alive = false;
+        }
 
         debug!("keep_alive = {:?} for {}", keep_alive, addr);
         keep_alive
     }
 
-    fn handle_expect<W: Write>(&self, req: &Request, wrt: &mut W) -> bool {
+    // Answers a request refused for its size, and closes the connection.
+    fn refuse<W: Write>(&self, wrt: &mut W, status: StatusCode) {
+        let mut headers = Headers::new();
+        headers.set(Date(HttpDate(self.config.clock.now_utc())));
+        if let Some(ref server) = self.config.server_header {
+            headers.set(server.clone());
+        }
+        headers.set(Connection::close());
+        let mut res = Response::new(wrt, &mut headers);
+        *res.status_mut() = status;
+        if let Err(e) = res.send(b"") {
+            debug!("error sending {} response: {:?}", status, e);
+        }
+    }
+
+    #[cfg(not(feature = "timeouts"))]
+    fn read_head(&self, _rdr: &mut BufReader<&mut NetworkStream>) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Reads until the buffer holds a whole head, shortening the read timeout
+    // so that the reads all end by the head timeout. Parsing it is left to
+    // `Request::with_inspect`, as are a closed connection and an invalid head.
+    #[cfg(feature = "timeouts")]
+    fn read_head(&self, rdr: &mut BufReader<&mut NetworkStream>) -> io::Result<()> {
+        let timeout = match self.config.timeouts.head {
+            Some(timeout) => timeout,
+            None => return Ok(())
+        };
+        // waiting for a request to start is up to the keep-alive timeout
+        if rdr.get_buf().is_empty() && try!(rdr.read_into_buf()) == 0 {
+            return Ok(());
+        }
+        let deadline = self.config.clock.now() + timeout;
+        while let Ok(None) = h1::try_parse_request(rdr.get_buf(), self.config.colon_whitespace) {
+            let now = self.config.clock.now();
+            if now >= deadline {
+                return Err(io::Error::new(ErrorKind::TimedOut, "request head took too long"));
+            }
+            let left = deadline - now;
+            let dur = match self.config.timeouts.read {
+                Some(read) if read < left => read,
+                _ => left
+            };
+            try!(rdr.get_ref().set_read_timeout(Some(dur)));
+            if try!(rdr.read_into_buf()) == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_expect<W: Write>(&self, handler: &F::Handler, req: &Request, wrt: &mut W)
+            -> bool {
          if req.version == Http11 && req.headers.get() == Some(&Expect::Continue) {
-            let status = self.handler.check_continue((&req.method, &req.uri, &req.headers));
+            let status = handler.check_continue((&req.method, &req.uri, &req.headers));
             match write!(wrt, "{} {}\r\n\r\n", Http11, status) {
                 Ok(..) => (),
                 Err(e) => {
@@ -391,29 +874,186 @@ impl<H: Handler + 'static> Worker<H> {
 /// A listening server, which can later be closed.
 pub struct Listening {
     _guard: Option<JoinHandle<()>>,
+    _guards: Vec<JoinHandle<()>>,
     /// The socket addresses that the server is bound to.
     pub socket: SocketAddr,
+    /// The socket addresses of all the server's listeners, starting with
+    /// `socket`.
+    pub sockets: Vec<SocketAddr>,
+    shutdown: ShutdownHandle,
+}
+
+/// A handle that can shut down a server gracefully, from any thread.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    drain: Arc<Drain>,
+    sockets: Vec<SocketAddr>,
+    threads: usize,
+}
+
+impl fmt::Debug for ShutdownHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ShutdownHandle {{ sockets: {:?} }}", self.sockets)
+    }
+}
+
+impl ShutdownHandle {
+    /// Stops accepting connections, and waits up to `timeout` for the open
+    /// ones to finish. Returns whether they all did.
+    ///
+    /// Requests being handled are finished, and their connections closed
+    /// after their responses. Requests read after this get their response
+    /// with `Connection: close`. Idle keep-alive connections are closed once
+    /// their next request is answered, or their keep-alive timeout runs out.
+    ///
+    /// Listeners are stopped by connecting to their socket addresses. One
+    /// that can't be connected to, such as a Unix domain socket, stops at
+    /// its next connection instead.
+    ///
+    /// Every `Readiness` added with `add_readiness` is set to not ready
+    /// first, so that `/readyz` fails while the server drains.
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        debug!("shutting down {:?}", self.sockets);
+        self.drain.close();
+        for &addr in &self.sockets {
+            wake(addr, self.threads);
+        }
+        self.drain.wait(timeout)
+    }
+
+    /// Sets `readiness` to not ready once the server starts shutting down.
+    pub fn add_readiness(&self, readiness: Readiness) {
+        if self.drain.is_closing() {
+            readiness.set_ready(false);
+        }
+        self.drain.readiness.lock().unwrap().push(readiness);
+    }
+}
+
+// Connects to a listener once for each of its threads, so that those waiting
+// to accept a connection see that the server is shutting down.
+fn wake(addr: SocketAddr, threads: usize) {
+    if addr.port() == 0 {
+        return;
+    }
+    let addr = match addr {
+        SocketAddr::V4(ref v4) if v4.ip().is_unspecified() => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), addr.port())
+        },
+        SocketAddr::V6(ref v6) if v6.ip().is_unspecified() => {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), addr.port())
+        },
+        _ => addr
+    };
+    for _ in 0..threads {
+        if let Err(e) = TcpStream::connect(addr) {
+            debug!("error waking listener {}: {:?}", addr, e);
+            return;
+        }
+    }
+}
+
+// Whether a server is shutting down, and how many connections it has open.
+struct Drain {
+    closing: AtomicBool,
+    active: Mutex<usize>,
+    idle: Condvar,
+    readiness: Mutex<Vec<Readiness>>,
+}
+
+impl Drain {
+    fn new() -> Drain {
+        Drain {
+            closing: AtomicBool::new(false),
+            active: Mutex::new(0),
+            idle: Condvar::new(),
+            readiness: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
+
+    fn close(&self) {
+        for readiness in self.readiness.lock().unwrap().iter() {
+            readiness.set_ready(false);
+        }
+        self.closing.store(true, Ordering::SeqCst);
+    }
+
+    fn enter(&self) -> Active {
+        *self.active.lock().unwrap() += 1;
+        Active(self)
+    }
+
+    fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut active = self.active.lock().unwrap();
+        while *active > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                debug!("{} connections still open", *active);
+                return false;
+            }
+            active = self.idle.wait_timeout(active, deadline - now).unwrap().0;
+        }
+        true
+    }
+}
+
+// Counts a connection as open until it is dropped.
+struct Active<'a>(&'a Drain);
+
+impl<'a> Drop for Active<'a> {
+    fn drop(&mut self) {
+        let mut active = self.0.active.lock().unwrap();
+        *active -= 1;
+        if *active == 0 {
+            self.0.idle.notify_all();
+        }
+    }
 }
 
 impl fmt::Debug for Listening {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Listening {{ socket: {:?} }}", self.socket)
+        write!(f, "Listening {{ sockets: {:?} }}", self.sockets)
     }
 }
 
 impl Drop for Listening {
     fn drop(&mut self) {
         let _ = self._guard.take().map(|g| g.join());
+        for guard in self._guards.drain(..) {
+            let _ = guard.join();
+        }
     }
 }
 
 impl Listening {
-    /// Stop the server from listening to its socket address.
+    /// Stop the server from listening to its socket addresses.
     pub fn close(&mut self) -> ::Result<()> {
         let _ = self._guard.take();
+        self._guards.clear();
         debug!("closing server");
         Ok(())
     }
+
+    /// Shuts the server down gracefully, waiting up to `timeout` for open
+    /// connections to finish. Returns whether they all did.
+    ///
+    /// See `ShutdownHandle::shutdown`.
+    pub fn shutdown(&mut self, timeout: Duration) -> bool {
+        let drained = self.shutdown.shutdown(timeout);
+        let _ = self._guard.take();
+        self._guards.clear();
+        drained
+    }
+
+    /// Get a handle to shut the server down with from another thread.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
 }
 
 /// A handler that can handle incoming requests for a server.
@@ -431,6 +1071,27 @@ pub trait Handler: Sync + Send {
         StatusCode::Continue
     }
 
+    /// This is run with the head of each request as it was received, before
+    /// its headers are parsed and normalized, such as to fingerprint clients
+    /// by the order and case of their headers.
+    ///
+    /// Returning `false` closes the connection without handling the request.
+    fn on_raw_head(&self, _: &RawHead, _: SocketAddr) -> bool {
+        true
+    }
+
+    /// This is run as soon as the head of a request is parsed, before any of
+    /// its body is read, such as to refuse a large upload to a path that
+    /// doesn't exist, or that needs authorization, without reading it.
+    ///
+    /// Returning a status code answers the request with it, and the headers
+    /// set on `res`, and an empty body, instead of calling `handle`. If the
+    /// request has a body, the connection is closed after, since it wasn't
+    /// read.
+    fn on_head(&self, _: &Request, _: &mut Headers) -> Option<StatusCode> {
+        None
+    }
+
     /// This is run after a connection is received, on a per-connection basis (not a
     /// per-request basis, as a connection with keep-alive may handle multiple
     /// requests)
@@ -448,6 +1109,62 @@ impl<F> Handler for F where F: Fn(Request, Response<Fresh>), F: Sync + Send {
     }
 }
 
+/// Makes a handler for each connection a server accepts.
+///
+/// Unlike a `Handler` given to `Server::handle`, which is shared by every
+/// connection, the handler made here lives only as long as its connection,
+/// so it can keep state for it, such as who the peer authenticated as.
+pub trait HandlerFactory: Sync + Send {
+    /// The handler made for each connection.
+    type Handler: Handler;
+
+    /// Called with each accepted connection, before any of it is read.
+    ///
+    /// Returning `None` closes the connection.
+    fn on_connection(&self, conn: &ConnectionInfo) -> Option<Self::Handler>;
+}
+
+impl<F, H> HandlerFactory for F
+where F: Fn(&ConnectionInfo) -> Option<H> + Sync + Send, H: Handler {
+    type Handler = H;
+
+    fn on_connection(&self, conn: &ConnectionInfo) -> Option<H> {
+        self(conn)
+    }
+}
+
+/// A connection that a `HandlerFactory` is making a handler for.
+pub struct ConnectionInfo<'a> {
+    /// The remote address of the connection.
+    pub remote_addr: SocketAddr,
+    stream: &'a NetworkStream,
+}
+
+impl<'a> ConnectionInfo<'a> {
+    /// Get a reference to the underlying `NetworkStream`.
+    #[inline]
+    pub fn downcast_ref<T: NetworkStream>(&self) -> Option<&T> {
+        self.stream.downcast_ref()
+    }
+
+    /// Get a reference to the underlying Ssl stream, if connected
+    /// over HTTPS, such as to check the peer's certificate.
+    #[inline]
+    pub fn ssl<T: NetworkStream>(&self) -> Option<&T> {
+        use ::net::HttpsStream;
+        match self.downcast_ref() {
+            Some(&HttpsStream::Https(ref s)) => Some(s),
+            _ => None
+        }
+    }
+}
+
+impl<'a> fmt::Debug for ConnectionInfo<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ConnectionInfo {{ remote_addr: {:?} }}", self.remote_addr)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use header::Headers;
@@ -505,4 +1222,546 @@ mod tests {
         Worker::new(Reject, Default::default()).handle_connection(&mut mock);
         assert_eq!(mock.write, &b"HTTP/1.1 417 Expectation Failed\r\n\r\n"[..]);
     }
+
+    #[test]
+    fn test_on_raw_head() {
+        use std::net::SocketAddr;
+        use std::sync::Mutex;
+        use super::RawHead;
+
+        struct Fingerprint(Mutex<Vec<Vec<u8>>>);
+        impl Handler for Fingerprint {
+            fn handle<'a, 'k>(&'a self, _: Request<'a, 'k>, res: Response<'a, Fresh>) {
+                res.start().unwrap().end().unwrap();
+            }
+
+            fn on_raw_head(&self, head: &RawHead, _: SocketAddr) -> bool {
+                let names = head.headers().map(|(name, _)| name.to_vec()).collect::<Vec<_>>();
+                let allow = names.first().map_or(false, |name| name == b"Host");
+                self.0.lock().unwrap().extend(names);
+                allow
+            }
+        }
+
+        let mut mock = MockStream::with_input(b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            user-agent: a\r\n\
+            \r\n\
+            GET / HTTP/1.1\r\n\
+            user-agent: b\r\n\
+            Host: example.domain\r\n\
+            \r\n\
+        ");
+        let handler = Fingerprint(Mutex::new(Vec::new()));
+        let mut worker = Worker::new(handler, Default::default());
+        worker.config.timeouts.keep_alive = Some(::std::time::Duration::from_secs(5));
+        worker.handle_connection(&mut mock);
+
+        // the first request is handled, and the second refused
+        assert_eq!(mock.write.windows(8).filter(|w| w == b"HTTP/1.1").count(), 1);
+        let names = (worker.factory.0).0.lock().unwrap();
+        assert_eq!(*names, vec![b"Host".to_vec(), b"user-agent".to_vec(),
+                                b"user-agent".to_vec(), b"Host".to_vec()]);
+    }
+
+    #[test]
+    fn test_handler_factory() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use super::ConnectionInfo;
+
+        // counts the requests on its connection
+        struct Counter(AtomicUsize);
+        impl Handler for Counter {
+            fn handle<'a, 'k>(&'a self, _: Request<'a, 'k>, res: Response<'a, Fresh>) {
+                let count = self.0.fetch_add(1, Ordering::Relaxed) + 1;
+                res.send(count.to_string().as_bytes()).unwrap();
+            }
+        }
+
+        let input = b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            \r\n\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ";
+        let factory = |conn: &ConnectionInfo| {
+            assert_eq!(conn.remote_addr, "127.0.0.1:1337".parse().unwrap());
+            assert!(conn.ssl::<MockStream>().is_none());
+            assert!(conn.downcast_ref::<MockStream>().is_some());
+            Some(Counter(AtomicUsize::new(0)))
+        };
+        let mut worker = Worker::configured(factory, Default::default());
+        worker.config.timeouts.keep_alive = Some(::std::time::Duration::from_secs(5));
+        for _ in 0..2 {
+            let mut mock = MockStream::with_input(input);
+            worker.handle_connection(&mut mock);
+            let raw = String::from_utf8(mock.write).unwrap();
+            assert!(raw.ends_with("\r\n\r\n2"), "{:?}", raw);
+            assert_eq!(raw.matches("\r\n\r\n1").count(), 1);
+        }
+
+        let refuse = |_: &ConnectionInfo| None::<Counter>;
+        let worker = Worker::configured(refuse, Default::default());
+        let mut mock = MockStream::with_input(input);
+        worker.handle_connection(&mut mock);
+        assert!(mock.write.is_empty());
+    }
+
+    #[test]
+    fn test_on_head() {
+        use header::Authorization;
+
+        struct Auth;
+        impl Handler for Auth {
+            fn handle<'a, 'k>(&'a self, _: Request<'a, 'k>, res: Response<'a, Fresh>) {
+                res.send(b"handled").unwrap();
+            }
+
+            fn on_head(&self, req: &Request, res: &mut Headers) -> Option<StatusCode> {
+                if req.uri == RequestUri::AbsolutePath("/missing".to_owned()) {
+                    Some(StatusCode::NotFound)
+                } else if !req.headers.has::<Authorization<String>>() {
+                    res.set_raw("WWW-Authenticate", vec![b"Basic".to_vec()]);
+                    Some(StatusCode::Unauthorized)
+                } else {
+                    None
+                }
+            }
+        }
+
+        let mut worker = Worker::new(Auth, Default::default());
+        worker.config.timeouts.keep_alive = Some(::std::time::Duration::from_secs(5));
+
+        // without a body, the connection is kept
+        let mut mock = MockStream::with_input(b"\
+            GET /missing HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            \r\n\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Authorization: x\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ");
+        worker.handle_connection(&mut mock);
+        let raw = String::from_utf8(mock.write).unwrap();
+        assert!(raw.starts_with("HTTP/1.1 404 Not Found\r\n"), "{:?}", raw);
+        assert!(raw.ends_with("handled"));
+
+        // with an unread body, it is closed, and no 100 Continue is sent
+        let mut mock = MockStream::with_input(b"\
+            POST /upload HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Expect: 100-continue\r\n\
+            Content-Length: 10\r\n\
+            \r\n\
+            1234567890\
+        ");
+        worker.handle_connection(&mut mock);
+        let raw = String::from_utf8(mock.write).unwrap();
+        assert!(raw.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{:?}", raw);
+        assert!(raw.contains("WWW-Authenticate: Basic\r\n"));
+        assert!(raw.contains("Connection: close\r\n"));
+        assert!(raw.contains("Content-Length: 0\r\n"));
+        assert!(raw.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_invalid_header_lines() {
+        fn handle(req: Request, res: Response<Fresh>) {
+            assert_eq!(req.headers.get_raw("host"), Some(&[b"example.domain".to_vec()][..]));
+            assert_eq!(req.headers.get_raw_invalid(), &[b"Bad Header: x".to_vec()][..]);
+            res.start().unwrap().end().unwrap();
+        }
+
+        let input = b"GET / HTTP/1.1\r\nBad Header: x\r\nHost: example.domain\r\n\r\n";
+        let mut mock = MockStream::with_input(input);
+        Worker::new(handle, Default::default()).handle_connection(&mut mock);
+        assert!(mock.write.starts_with(b"HTTP/1.1 200 OK\r\n"));
+
+        let mut mock = MockStream::with_input(input);
+        let mut worker = Worker::new(handle, Default::default());
+        worker.config.strict_headers = true;
+        worker.handle_connection(&mut mock);
+        assert!(mock.write.is_empty());
+    }
+
+    #[test]
+    fn test_limits() {
+        use std::io::Read;
+        use super::Limits;
+
+        fn handle(mut req: Request, res: Response<Fresh>) {
+            let mut body = Vec::new();
+            match req.read_to_end(&mut body) {
+                Ok(_) => res.send(b"ok").unwrap(),
+                Err(_) => res.send(b"too large").unwrap()
+            }
+        }
+
+        let mut worker = Worker::new(handle, Default::default());
+        worker.config.limits = Limits { max_uri_len: 16, max_head_size: 128, max_headers: 4,
+                                 max_body_size: Some(8) };
+        let respond = |input: &[u8]| {
+            let mut mock = MockStream::with_input(input);
+            worker.handle_connection(&mut mock);
+            String::from_utf8(mock.write).unwrap()
+        };
+
+        let raw = respond(b"POST /upload HTTP/1.1\r\nContent-Length: 8\r\n\r\n12345678");
+        assert!(raw.starts_with("HTTP/1.1 200 OK\r\n") && raw.ends_with("ok"), "{:?}", raw);
+
+        let raw = respond(b"GET /a/very/long/path HTTP/1.1\r\n\r\n");
+        assert!(raw.starts_with("HTTP/1.1 414 URI Too Long\r\n"), "{:?}", raw);
+        assert!(raw.contains("Connection: close\r\n"));
+
+        let raw = respond(b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\nE: 5\r\n\r\n");
+        assert!(raw.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"), "{:?}", raw);
+
+        let raw = respond(b"POST /upload HTTP/1.1\r\nContent-Length: 9\r\n\r\n123456789");
+        assert!(raw.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{:?}", raw);
+        assert!(raw.contains("Content-Length: 0\r\n"));
+
+        // a chunked body can only be refused once it is read
+        let raw = respond(b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                            9\r\n123456789\r\n0\r\n\r\n");
+        assert!(raw.ends_with("too large"), "{:?}", raw);
+    }
+
+    #[test]
+    fn test_raw_heads() {
+        use std::io::Write;
+
+        const REQUEST: &'static [u8] = b"GET / HTTP/1.1\r\nhost: a\r\nX-Dup: 1\r\nx-dup: 2\r\n\r\n";
+        const RESPONSE: &'static [u8] = b"HTTP/1.1 200 OK\r\nx-b: 1\r\nX-A:  2\r\nX-B: 3\r\n\
+                                          Content-Length: 2\r\n\r\n";
+
+        fn handle(req: Request, res: Response<Fresh>) {
+            match req.raw_head() {
+                Some(head) => {
+                    assert_eq!(head.as_bytes(), REQUEST);
+                    let mut res = res.start_raw(RESPONSE).unwrap();
+                    res.write_all(b"ok").unwrap();
+                },
+                None => res.send(b"cooked").unwrap()
+            }
+        }
+
+        let mut worker = Worker::new(handle, Default::default());
+        let mut mock = MockStream::with_input(REQUEST);
+        worker.handle_connection(&mut mock);
+        assert!(mock.write.ends_with(b"cooked"));
+
+        worker.config.raw_heads = true;
+        let mut mock = MockStream::with_input(REQUEST);
+        worker.handle_connection(&mut mock);
+        let mut expected = RESPONSE.to_vec();
+        expected.extend_from_slice(b"ok");
+        assert_eq!(mock.write, expected);
+    }
+
+    #[test]
+    fn test_add_listener() {
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+        use net::HttpListener;
+        use super::Server;
+
+        fn hello(req: Request, res: Response) {
+            res.send(format!("hello {}", req.uri).as_bytes()).unwrap();
+        }
+
+        let mut server = Server::http("127.0.0.1:0").unwrap();
+        server.add_listener(HttpListener::new("127.0.0.1:0").unwrap());
+        let mut listening = server.handle_threads(hello, 1).unwrap();
+        assert_eq!(listening.sockets.len(), 2);
+        assert_eq!(listening.sockets[0], listening.socket);
+
+        for addr in listening.sockets.clone() {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(b"GET /x HTTP/1.0\r\n\r\n").unwrap();
+            let mut res = String::new();
+            stream.read_to_string(&mut res).unwrap();
+            assert!(res.starts_with("HTTP/1.0 200 OK\r\n"), "{:?}", res);
+            assert!(res.ends_with("hello /x"));
+        }
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown() {
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+        use std::sync::Mutex;
+        use std::sync::mpsc::{channel, Receiver, Sender};
+        use std::thread;
+        use std::time::Duration;
+        use super::Server;
+        use super::health::Health;
+
+        struct Slow {
+            started: Mutex<Sender<()>>,
+            release: Mutex<Receiver<()>>,
+        }
+
+        impl Handler for Slow {
+            fn handle(&self, _: Request, res: Response) {
+                self.started.lock().unwrap().send(()).unwrap();
+                self.release.lock().unwrap().recv().unwrap();
+                res.send(b"done").unwrap();
+            }
+        }
+
+        fn read_response(stream: &mut TcpStream) -> String {
+            let mut res = Vec::new();
+            let mut buf = [0; 256];
+            while !res.ends_with(b"done") {
+                let n = stream.read(&mut buf).unwrap();
+                assert!(n > 0, "{:?}", String::from_utf8_lossy(&res));
+                res.extend_from_slice(&buf[..n]);
+            }
+            String::from_utf8(res).unwrap()
+        }
+
+        let (started_tx, started_rx) = channel();
+        let (release_tx, release_rx) = channel();
+        let mut server = Server::http("127.0.0.1:0").unwrap();
+        server.keep_alive(Duration::from_secs(5));
+        let mut listening = server.handle_threads(Slow {
+            started: Mutex::new(started_tx),
+            release: Mutex::new(release_rx),
+        }, 2).unwrap();
+
+        let request = b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n";
+        let mut kept = TcpStream::connect(listening.socket).unwrap();
+        kept.write_all(request).unwrap();
+        started_rx.recv().unwrap();
+        release_tx.send(()).unwrap();
+        assert!(!read_response(&mut kept).contains("Connection: close"));
+        // let its thread go back to waiting for the next request
+        thread::sleep(Duration::from_millis(50));
+
+        let mut busy = TcpStream::connect(listening.socket).unwrap();
+        busy.write_all(request).unwrap();
+        started_rx.recv().unwrap();
+
+        let readiness = Health::new(|_: Request, _: Response| ()).readiness();
+        listening.shutdown_handle().add_readiness(readiness.clone());
+        assert!(readiness.is_ready());
+
+        // the open connections aren't done in time, but are still finished
+        assert!(!listening.shutdown_handle().shutdown(Duration::from_millis(10)));
+        assert!(!readiness.is_ready());
+        release_tx.send(()).unwrap();
+        assert!(read_response(&mut busy).ends_with("done"));
+        assert_eq!(busy.read(&mut [0; 16]).unwrap(), 0);
+
+        kept.write_all(request).unwrap();
+        started_rx.recv().unwrap();
+        release_tx.send(()).unwrap();
+        let res = read_response(&mut kept);
+        assert!(res.contains("Connection: close\r\n"), "{:?}", res);
+        assert_eq!(kept.read(&mut [0; 16]).unwrap(), 0);
+
+        assert!(listening.shutdown(Duration::from_secs(5)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix() {
+        use std::env;
+        use std::fs;
+        use std::io::Read;
+        use client::Client;
+        use net::UnixConnector;
+        use super::Server;
+
+        fn hello(req: Request, res: Response) {
+            res.send(format!("hello {} from {}", req.uri, req.remote_addr).as_bytes()).unwrap();
+        }
+
+        let path = env::temp_dir().join(format!("hyper-test-{}.sock", ::std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut listening = Server::unix(&path).unwrap().handle_threads(hello, 1).unwrap();
+
+        let client = Client::with_connector(UnixConnector::new(&path));
+        for _ in 0..2 {
+            let mut res = client.get("http://localhost/x").send().unwrap();
+            let mut body = String::new();
+            res.read_to_string(&mut body).unwrap();
+            assert_eq!(body, "hello /x from 0.0.0.0:0");
+        }
+        listening.close().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_unix_abstract() {
+        use std::io::Read;
+        use client::Client;
+        use net::UnixConnector;
+        use super::Server;
+
+        fn hello(_: Request, res: Response) {
+            res.send(b"hello").unwrap();
+        }
+
+        let name = format!("\0hyper-test-{}", ::std::process::id());
+        let mut listening = Server::unix(&name).unwrap().handle_threads(hello, 1).unwrap();
+        assert!(Server::unix(&name).is_err());
+
+        let client = Client::with_connector(UnixConnector::new(&name));
+        let mut body = String::new();
+        client.get("http://localhost/").send().unwrap().read_to_string(&mut body).unwrap();
+        assert_eq!(body, "hello");
+        listening.close().unwrap();
+    }
+
+    #[cfg(all(target_os = "linux", feature = "seqpacket"))]
+    #[test]
+    fn test_unix_seqpacket() {
+        use std::io::Read;
+        use client::Client;
+        use net::{SeqPacketConnector, SeqPacketListener};
+        use super::Server;
+
+        fn big(_: Request, res: Response) {
+            res.send(&vec![b'x'; 200 * 1024]).unwrap();
+        }
+
+        let name = format!("\0hyper-seqpacket-{}", ::std::process::id());
+        let listener = SeqPacketListener::new(&name).unwrap();
+        let mut listening = Server::new(listener).handle_threads(big, 1).unwrap();
+
+        let client = Client::with_connector(SeqPacketConnector::new(&name));
+        for _ in 0..2 {
+            let mut body = Vec::new();
+            client.get("http://localhost/").send().unwrap().read_to_end(&mut body).unwrap();
+            assert_eq!(body.len(), 200 * 1024);
+        }
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_server_header() {
+        use std::str::from_utf8;
+        use header::Server;
+
+        fn handle(req: Request, mut res: Response<Fresh>) {
+            if req.uri == RequestUri::AbsolutePath("/own".to_owned()) {
+                res.headers_mut().set(Server::new("handler", "2"));
+            }
+            res.start().unwrap().end().unwrap();
+        }
+
+        for &(path, expected) in &[("/", "Server: myserver/1.2\r\n"),
+                                   ("/own", "Server: handler/2\r\n")] {
+            let input = format!("GET {} HTTP/1.1\r\nHost: example.domain\r\n\r\n", path);
+            let mut mock = MockStream::with_input(input.as_bytes());
+            let mut worker = Worker::new(handle, Default::default());
+            worker.config.server_header = Some(Server::new("myserver", "1.2"));
+            worker.handle_connection(&mut mock);
+            let raw = from_utf8(&mock.write).unwrap();
+            assert!(raw.contains(expected), "{:?}", raw);
+            assert_eq!(raw.matches("Server:").count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_clock() {
+        use std::str::from_utf8;
+        use std::sync::Arc;
+        use std::time::Duration;
+        use clock::ManualClock;
+
+        fn handle(_: Request, res: Response<Fresh>) {
+            res.send(b"").unwrap();
+        }
+
+        let clock = ManualClock::new();
+        clock.advance(Duration::from_secs(24 * 60 * 60));
+        let mut worker = Worker::new(handle, Default::default());
+        worker.config.clock = Arc::new(clock);
+        let mut mock = MockStream::with_input(b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n");
+        worker.handle_connection(&mut mock);
+        let raw = from_utf8(&mock.write).unwrap();
+        assert!(raw.contains("Date: Fri, 02 Jan 1970 00:00:00 GMT\r\n"), "{:?}", raw);
+    }
+
+    #[test]
+    fn test_max_connection_lifetime() {
+        use std::sync::Arc;
+        use std::time::Duration;
+        use clock::ManualClock;
+
+        let clock = ManualClock::new();
+        let passing = clock.clone();
+        let handle = move |_: Request, res: Response<Fresh>| {
+            passing.advance(Duration::from_secs(40));
+            res.send(b"ok").unwrap();
+        };
+        let mut worker = Worker::new(handle, Default::default());
+        worker.config.timeouts.keep_alive = Some(Duration::from_secs(5));
+        worker.config.timeouts.max_lifetime = Some(Duration::from_secs(60));
+        worker.config.clock = Arc::new(clock);
+        let mut mock = MockStream::with_input(b"\
+            GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n\
+            GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n\
+            GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n\
+        ");
+        worker.handle_connection(&mut mock);
+
+        // the second request starts 40s in, the third after the lifetime
+        let raw = String::from_utf8(mock.write).unwrap();
+        assert_eq!(raw.matches("HTTP/1.1 200 OK\r\n").count(), 3, "{:?}", raw);
+        assert_eq!(raw.matches("Connection: close\r\n").count(), 1);
+        assert!(raw.rsplit("HTTP/1.1").next().unwrap().contains("Connection: close"));
+    }
+
+    #[test]
+    fn test_shutdown_write_after_last_response() {
+        let mut mock = MockStream::with_input(b"\
+            GET / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Connection: close\r\n\
+            \r\n\
+        ");
+
+        fn handle(_: Request, res: Response<Fresh>) {
+            res.send(b"bye").unwrap();
+        }
+
+        Worker::new(handle, Default::default()).handle_connection(&mut mock);
+        assert!(mock.write.ends_with(b"bye"));
+        assert!(mock.is_closed);
+    }
+
+    #[cfg(feature = "timeouts")]
+    #[test]
+    fn test_head_timeout() {
+        use std::time::Duration;
+
+        fn handle(_: Request, res: Response<Fresh>) {
+            res.send(b"hello").unwrap();
+        }
+
+        let head: Vec<&[u8]> = vec![b"GET / HTTP/1.1\r\n", b"Host: example.domain\r\n\r\n"];
+
+        let mut worker = Worker::new(handle, Default::default());
+        worker.config.timeouts.head = Some(Duration::from_secs(10));
+        let mut mock = MockStream::with_responses(head.clone());
+        worker.handle_connection(&mut mock);
+        assert!(mock.write.ends_with(b"hello"));
+
+        // the rest of the head comes too late
+        worker.config.timeouts.head = Some(Duration::new(0, 0));
+        let mut mock = MockStream::with_responses(head);
+        worker.handle_connection(&mut mock);
+        assert_eq!(mock.write, b"");
+    }
 }
@@ -0,0 +1,293 @@
+//! Answering `Range` requests.
+//!
+//! `send` writes a body of known length as the response to a request, and
+//! honors its `Range` header: a single range gets a `206 Partial Content`
+//! of just those bytes, several get a `206` with a `multipart/byteranges`
+//! body holding each of them, and a `Range` none of whose ranges are in the
+//! body gets a `416 Range Not Satisfiable`. Requests that aren't `GET`, or
+//! whose `If-Range` doesn't match the `ETag` or `Last-Modified` of the
+//! response, get the whole body, as do ranges of units other than bytes.
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use hyper::server::{Request, Response};
+//! use hyper::server::range;
+//!
+//! fn download(req: Request, res: Response) {
+//!     let file = File::open("archive.tar").unwrap();
+//!     let len = file.metadata().unwrap().len();
+//!     range::send(&req, res, file, len).unwrap();
+//! }
+//! ```
+//!
+//! Overlapping and adjacent ranges are merged, so each byte is sent at most
+//! once, and parts are sent in the order they have in the body.
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use mime::{Attr, Mime, SubLevel, TopLevel, Value};
+
+use header::{AcceptRanges, ContentLength, ContentRange, ContentRangeSpec, ContentType};
+use header::{ETag, Headers, IfRange, LastModified, Range, RangeUnit};
+use method::Method;
+use multipart::new_boundary;
+use net::Fresh;
+use server::{Request, Response};
+use status::StatusCode;
+
+/// The parts of a body a `Range` asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ranges {
+    /// All of it.
+    Whole,
+    /// These end-inclusive ranges of it, in ascending order, with none
+    /// overlapping or adjacent.
+    Parts(Vec<(u64, u64)>),
+    /// None of the ranges asked for are in it.
+    Unsatisfiable,
+}
+
+/// Resolves the ranges `range` asks for, of a body `len` bytes long.
+pub fn resolve(range: &Range, len: u64) -> Ranges {
+    let specs = match *range {
+        Range::Bytes(ref specs) => specs,
+        Range::Unregistered(..) => return Ranges::Whole
+    };
+    let mut ranges = specs.iter()
+        .filter_map(|spec| spec.to_satisfiable_range(len))
+        .collect::<Vec<_>>();
+    if ranges.is_empty() {
+        return Ranges::Unsatisfiable;
+    }
+    ranges.sort();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (from, to) in ranges {
+        match merged.last_mut() {
+            Some(last) if from <= last.1 + 1 => {
+                if to > last.1 {
+                    last.1 = to;
+                }
+                continue;
+            },
+            _ => ()
+        }
+        merged.push((from, to));
+    }
+    Ranges::Parts(merged)
+}
+
+/// Whether the `If-Range` of a request, if it has one, matches the `ETag`
+/// or `Last-Modified` of the response, so that its `Range` is honored.
+pub fn if_range(req: &Headers, res: &Headers) -> bool {
+    match req.get::<IfRange>() {
+        Some(&IfRange::EntityTag(ref tag)) => match res.get::<ETag>() {
+            Some(&ETag(ref etag)) => tag.strong_eq(etag),
+            None => false,
+        },
+        Some(&IfRange::Date(ref date)) => match res.get::<LastModified>() {
+            Some(&LastModified(ref modified)) => date == modified,
+            None => false,
+        },
+        None => true,
+    }
+}
+
+/// Sends the parts of `body` that `req` asks for, or all of it.
+///
+/// `body` must be `len` bytes long. Headers already set on `res`, such as
+/// `Content-Type` or `ETag`, are sent along, and a `Content-Type` is moved
+/// into each part of a `multipart/byteranges` body.
+pub fn send<R: Read + Seek>(req: &Request, mut res: Response<Fresh>, mut body: R, len: u64)
+        -> io::Result<()> {
+    res.headers_mut().set(AcceptRanges(vec![RangeUnit::Bytes]));
+    let ranges = match req.headers.get::<Range>() {
+        Some(range) if req.method == Method::Get && if_range(&req.headers, res.headers()) => {
+            resolve(range, len)
+        },
+        _ => Ranges::Whole
+    };
+
+    match ranges {
+        Ranges::Whole => {
+            res.headers_mut().set(ContentLength(len));
+            let mut res = try!(res.start());
+            try!(copy_range(&mut body, &mut res, 0, len));
+            res.end()
+        },
+        Ranges::Unsatisfiable => {
+            *res.status_mut() = StatusCode::RangeNotSatisfiable;
+            res.headers_mut().set(ContentRange(ContentRangeSpec::Bytes {
+                range: None,
+                instance_length: Some(len)
+            }));
+            res.headers_mut().remove::<ContentType>();
+            res.send(b"")
+        },
+        Ranges::Parts(ref parts) if parts.len() == 1 => {
+            let (from, to) = parts[0];
+            *res.status_mut() = StatusCode::PartialContent;
+            res.headers_mut().set(ContentRange(ContentRangeSpec::Bytes {
+                range: Some((from, to)),
+                instance_length: Some(len)
+            }));
+            res.headers_mut().set(ContentLength(to - from + 1));
+            let mut res = try!(res.start());
+            try!(copy_range(&mut body, &mut res, from, to - from + 1));
+            res.end()
+        },
+        Ranges::Parts(parts) => {
+            let mut boundary = new_boundary("byteranges");
+            while try!(holds(&mut body, &parts, boundary.as_bytes())) {
+                debug!("range holds boundary {:?}, picking another", boundary);
+                boundary = new_boundary("byteranges");
+            }
+            let content_type = res.headers().get::<ContentType>().map(|ct| ct.to_string());
+            let heads = parts.iter().enumerate().map(|(i, &(from, to))| {
+                let content_type = match content_type {
+                    Some(ref ct) => format!("Content-Type: {}\r\n", ct),
+                    None => String::new()
+                };
+                format!("{}--{}\r\n{}Content-Range: bytes {}-{}/{}\r\n\r\n",
+                        if i == 0 { "" } else { "\r\n" }, boundary, content_type, from, to, len)
+            }).collect::<Vec<_>>();
+            let close = format!("\r\n--{}--\r\n", boundary);
+            let body_len = parts.iter().zip(&heads)
+                .fold(close.len() as u64, |n, (&(from, to), head)| {
+                    n + head.len() as u64 + to - from + 1
+                });
+
+            *res.status_mut() = StatusCode::PartialContent;
+            res.headers_mut().set(ContentType(Mime(TopLevel::Multipart,
+                                                   SubLevel::Ext("byteranges".to_owned()),
+                                                   vec![(Attr::Boundary, Value::Ext(boundary))])));
+            res.headers_mut().set(ContentLength(body_len));
+            let mut res = try!(res.start());
+            for (&(from, to), head) in parts.iter().zip(&heads) {
+                try!(res.write_all(head.as_bytes()));
+                try!(copy_range(&mut body, &mut res, from, to - from + 1));
+            }
+            try!(res.write_all(close.as_bytes()));
+            res.end()
+        }
+    }
+}
+
+fn copy_range<R: Read + Seek, W: Write>(body: &mut R, out: &mut W, from: u64, len: u64)
+        -> io::Result<()> {
+    try!(body.seek(SeekFrom::Start(from)));
+    let copied = try!(io::copy(&mut body.take(len), out));
+    if copied < len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "body shorter than its length"));
+    }
+    Ok(())
+}
+
+// Whether any of `parts` of `body` holds `needle`, which is read a chunk
+// at a time, keeping the end of each chunk in case it starts there.
+fn holds<R: Read + Seek>(body: &mut R, parts: &[(u64, u64)], needle: &[u8])
+        -> io::Result<bool> {
+    let mut chunk = [0; 8192];
+    for &(from, to) in parts {
+        try!(body.seek(SeekFrom::Start(from)));
+        let mut part = body.by_ref().take(to - from + 1);
+        let mut window = Vec::with_capacity(chunk.len() + needle.len());
+        loop {
+            let n = try!(part.read(&mut chunk));
+            if n == 0 {
+                break;
+            }
+            window.extend_from_slice(&chunk[..n]);
+            if window.windows(needle.len()).any(|w| w == needle) {
+                return Ok(true);
+            }
+            let keep = window.len().saturating_sub(needle.len() - 1);
+            window.drain(..keep);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use header::{ByteRangeSpec, Range};
+    use server::{Request, Response};
+    use server::testing::render;
+
+    use super::{holds, resolve, send, Ranges};
+
+    #[test]
+    fn test_resolve() {
+        let range = Range::Bytes(vec![ByteRangeSpec::FromTo(10, 19), ByteRangeSpec::Last(5),
+                                      ByteRangeSpec::FromTo(0, 4), ByteRangeSpec::AllFrom(5),
+                                      ByteRangeSpec::AllFrom(100)]);
+        assert_eq!(resolve(&range, 100), Ranges::Parts(vec![(0, 99)]));
+        assert_eq!(resolve(&Range::bytes_multi(vec![(10, 19), (0, 4), (5, 5), (30, 40)]), 35),
+                   Ranges::Parts(vec![(0, 5), (10, 19), (30, 34)]));
+        assert_eq!(resolve(&Range::bytes(50, 60), 50), Ranges::Unsatisfiable);
+        assert_eq!(resolve(&Range::Unregistered("lines".to_owned(), "1-2".to_owned()), 50),
+                   Ranges::Whole);
+    }
+
+    fn alphabet(req: Request, mut res: Response) {
+        res.headers_mut().set_raw("Content-Type", vec![b"text/plain".to_vec()]);
+        res.headers_mut().set_raw("ETag", vec![b"\"abc\"".to_vec()]);
+        send(&req, res, Cursor::new(&b"abcdefghijklmnopqrstuvwxyz"[..]), 26).unwrap();
+    }
+
+    fn get(headers: &str) -> String {
+        let req = format!("GET / HTTP/1.1\r\n{}\r\n", headers);
+        String::from_utf8(render(&alphabet, req.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_send() {
+        let out = get("");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\n\
+                                 Content-Length: 26\r\n"), "{:?}", out);
+        assert!(out.ends_with("\r\n\r\nabcdefghijklmnopqrstuvwxyz"));
+
+        let out = get("Range: bytes=-3\r\n");
+        assert!(out.starts_with("HTTP/1.1 206 Partial Content\r\n"), "{:?}", out);
+        assert!(out.contains("Content-Range: bytes 23-25/26\r\n"));
+        assert!(out.ends_with("\r\n\r\nxyz"));
+
+        // a stale If-Range gets the whole body
+        let out = get("Range: bytes=0-0\r\nIf-Range: \"old\"\r\n");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", out);
+        let out = get("Range: bytes=0-0\r\nIf-Range: \"abc\"\r\n");
+        assert!(out.ends_with("\r\n\r\na"), "{:?}", out);
+
+        let out = get("Range: bytes=30-\r\n");
+        assert!(out.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"), "{:?}", out);
+        assert!(out.contains("Content-Range: bytes */26\r\n"));
+        assert!(!out.contains("Content-Type"));
+    }
+
+    #[test]
+    fn test_send_multipart() {
+        let out = get("Range: bytes=0-1,-2\r\n");
+        assert!(out.starts_with("HTTP/1.1 206 Partial Content\r\n"), "{:?}", out);
+        let boundary = out.split("boundary=").nth(1).unwrap().split("\r\n").next().unwrap();
+        let body = format!("--{b}\r\nContent-Type: text/plain\r\n\
+                            Content-Range: bytes 0-1/26\r\n\r\nab\r\n\
+                            --{b}\r\nContent-Type: text/plain\r\n\
+                            Content-Range: bytes 24-25/26\r\n\r\nyz\r\n\
+                            --{b}--\r\n", b = boundary);
+        assert!(out.contains(&format!("Content-Length: {}\r\n", body.len())), "{:?}", out);
+        assert!(out.contains("Content-Type: multipart/byteranges; boundary="));
+        assert!(out.ends_with(&format!("\r\n\r\n{}", body)), "{:?}", out);
+    }
+
+    #[test]
+    fn test_holds() {
+        let mut body = vec![b'x'; 20000];
+        body[8190..8196].copy_from_slice(b"--edge");
+        let mut body = Cursor::new(body);
+        // found across chunks, and only within the parts
+        assert!(holds(&mut body, &[(0, 19999)], b"--edge").unwrap());
+        assert!(holds(&mut body, &[(0, 99), (8000, 8200)], b"--edge").unwrap());
+        assert!(!holds(&mut body, &[(0, 8193), (8194, 19999)], b"--edge").unwrap());
+        assert!(!holds(&mut body, &[(0, 19999)], b"--other").unwrap());
+    }
+}
@@ -0,0 +1,238 @@
+//! Redirects from plaintext HTTP to HTTPS.
+//!
+//! `HttpsRedirect` wraps the `Handler` of a plaintext listener, and sends
+//! requests on to the `https` URL of the same resource. `GET` and `HEAD`
+//! requests get a `301 Moved Permanently`, and other methods a
+//! `308 Permanent Redirect`, which keeps the method and body.
+//!
+//! By default every request is redirected. With `always` switched off, only
+//! those sending `Upgrade-Insecure-Requests: 1` are, and the rest go to the
+//! wrapped handler.
+//!
+//! To have browsers go to HTTPS directly next time, the responses of the
+//! HTTPS listener send `Strict-Transport-Security`. Browsers ignore it when
+//! it comes over plaintext HTTP (RFC 6797, section 8.1), so a redirect
+//! sending it isn't enough.
+//!
+//! ```no_run
+//! use hyper::header::StrictTransportSecurity;
+//! use hyper::server::{Server, Request, Response};
+//! use hyper::server::redirect::HttpsRedirect;
+//! use hyper::status::StatusCode;
+//!
+//! // only requests without a usable Host header get here
+//! fn unredirectable(_: Request, mut res: Response) {
+//!     *res.status_mut() = StatusCode::BadRequest;
+//! }
+//!
+//! // the handler of the HTTPS listener
+//! fn secure(_: Request, mut res: Response) {
+//!     res.headers_mut().set(StrictTransportSecurity::excluding_subdomains(31536000));
+//!     res.send(b"Hello World!").unwrap();
+//! }
+//!
+//! let redirect = HttpsRedirect::new(unredirectable, Default::default());
+//! Server::http("0.0.0.0:80").unwrap().handle(redirect).unwrap();
+//! ```
+use std::net::SocketAddr;
+
+use header::{Headers, Host, Location, StrictTransportSecurity, UpgradeInsecureRequests, Vary};
+use method::Method;
+use net::Fresh;
+use server::{Handler, RawHead, Request, Response};
+use status::StatusCode;
+use unicase::UniCase;
+use uri::RequestUri;
+
+/// Config options for an `HttpsRedirect`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Redirect every request, and not only those that send
+    /// `Upgrade-Insecure-Requests: 1`.
+    pub always: bool,
+    /// The port HTTPS is served on.
+    pub https_port: u16,
+    /// The `Strict-Transport-Security` header to send with redirects, if
+    /// any. None by default, since browsers ignore it on plaintext
+    /// responses: it is sent by the HTTPS listener instead.
+    pub hsts: Option<StrictTransportSecurity>,
+}
+
+impl Default for Config {
+    #[inline]
+    fn default() -> Config {
+        Config {
+            always: true,
+            https_port: 443,
+            hsts: None,
+        }
+    }
+}
+
+/// A `Handler` that redirects requests to HTTPS.
+pub struct HttpsRedirect<H: Handler> {
+    handler: H,
+    config: Config,
+}
+
+impl<H: Handler> HttpsRedirect<H> {
+    /// Wraps a handler, which gets the requests that aren't redirected.
+    pub fn new(handler: H, config: Config) -> HttpsRedirect<H> {
+        HttpsRedirect {
+            handler: handler,
+            config: config,
+        }
+    }
+
+    /// Get the `https` URL to redirect a request to, if it should be.
+    ///
+    /// Requests without a target path, or a host to redirect to, aren't.
+    pub fn target(&self, req: &Request) -> Option<String> {
+        if !self.config.always && !req.headers.has::<UpgradeInsecureRequests>() {
+            return None;
+        }
+        let (host, path) = match req.uri {
+            RequestUri::AbsolutePath(ref path) => match req.headers.get::<Host>() {
+                Some(host) => (host.hostname.clone(), path.clone()),
+                None => return None
+            },
+            RequestUri::AbsoluteUri(ref url) => {
+                let mut path = url.serialize_path().unwrap_or_else(|| "/".to_owned());
+                if let Some(ref query) = url.query {
+                    path.push('?');
+                    path.push_str(query);
+                }
+                match url.serialize_host() {
+                    Some(host) => (host, path),
+                    None => return None
+                }
+            },
+            _ => return None
+        };
+        Some(match self.config.https_port {
+            443 => format!("https://{}{}", host, path),
+            port => format!("https://{}:{}{}", host, port, path),
+        })
+    }
+}
+
+fn vary(headers: &mut Headers) {
+    let name = UniCase("Upgrade-Insecure-Requests".to_owned());
+    match headers.get_mut::<Vary>() {
+        Some(&mut Vary::Items(ref mut names)) => {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+            return;
+        },
+        Some(&mut Vary::Any) => return,
+        None => ()
+    }
+    headers.set(Vary::Items(vec![name]));
+}
+
+impl<H: Handler> Handler for HttpsRedirect<H> {
+    fn handle<'a, 'k>(&'a self, req: Request<'a, 'k>, mut res: Response<'a, Fresh>) {
+        let target = match self.target(&req) {
+            Some(target) => target,
+            None => {
+                if !self.config.always {
+                    vary(res.headers_mut());
+                }
+                return self.handler.handle(req, res);
+            }
+        };
+        debug!("redirecting {} to {}", req.uri, target);
+        *res.status_mut() = match req.method {
+            Method::Get | Method::Head => StatusCode::MovedPermanently,
+            _ => StatusCode::PermanentRedirect,
+        };
+        {
+            let headers = res.headers_mut();
+            headers.set(Location(target));
+            if let Some(ref hsts) = self.config.hsts {
+                headers.set(hsts.clone());
+            }
+            if !self.config.always {
+                vary(headers);
+            }
+        }
+        if let Err(e) = res.send(b"") {
+            debug!("error sending redirect: {:?}", e);
+        }
+    }
+
+    fn check_continue(&self, head: (&Method, &RequestUri, &Headers)) -> StatusCode {
+        self.handler.check_continue(head)
+    }
+
+    fn on_raw_head(&self, head: &RawHead, addr: SocketAddr) -> bool {
+        self.handler.on_raw_head(head, addr)
+    }
+
+    fn on_head(&self, req: &Request, res: &mut Headers) -> Option<StatusCode> {
+        // requests that will be redirected don't reach the wrapped handler
+        match self.target(req) {
+            Some(_) => None,
+            None => self.handler.on_head(req, res)
+        }
+    }
+
+    fn on_connection_start(&self) {
+        self.handler.on_connection_start()
+    }
+
+    fn on_connection_end(&self) {
+        self.handler.on_connection_end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::StrictTransportSecurity;
+    use server::{Request, Response};
+    use server::testing::render_str;
+
+    use super::{Config, HttpsRedirect};
+
+    fn inner(_: Request, res: Response) {
+        res.send(b"plaintext").unwrap();
+    }
+
+    #[test]
+    fn test_always() {
+        let redirect = HttpsRedirect::new(inner, Default::default());
+        let out = render_str(&redirect, b"GET /a?b=c HTTP/1.1\r\nHost: example.domain:80\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 301 Moved Permanently\r\n"));
+        assert!(out.contains("Location: https://example.domain/a?b=c\r\n"));
+        assert!(!out.contains("Strict-Transport-Security"));
+        assert!(!out.contains("Vary"));
+
+        let out = render_str(&redirect, b"POST /form HTTP/1.1\r\nHost: example.domain\r\n\
+                                         Content-Length: 0\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 308 Permanent Redirect\r\n"));
+
+        let out = render_str(&redirect, b"GET / HTTP/1.0\r\n\r\n");
+        assert!(out.ends_with("plaintext"));
+    }
+
+    #[test]
+    fn test_upgrade_insecure_requests() {
+        let redirect = HttpsRedirect::new(inner, Config {
+            always: false,
+            https_port: 8443,
+            hsts: Some(StrictTransportSecurity::excluding_subdomains(31536000)),
+        });
+        let out = render_str(&redirect, b"GET /a HTTP/1.1\r\nHost: example.domain\r\n\
+                                         Upgrade-Insecure-Requests: 1\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 301 Moved Permanently\r\n"));
+        assert!(out.contains("Location: https://example.domain:8443/a\r\n"));
+        assert!(out.contains("Vary: Upgrade-Insecure-Requests\r\n"));
+        assert!(out.contains("Strict-Transport-Security: max-age=31536000\r\n"));
+
+        let out = render_str(&redirect, b"GET /a HTTP/1.1\r\nHost: example.domain\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out.contains("Vary: Upgrade-Insecure-Requests\r\n"));
+        assert!(out.ends_with("plaintext"));
+    }
+}
@@ -11,7 +11,7 @@ use net::NetworkStream;
 use version::{HttpVersion};
 use method::Method::{self, Get, Head};
 use header::{Headers, ContentLength, TransferEncoding};
-use http::h1::{self, Incoming, HttpReader};
+use http::h1::{self, Incoming, HttpReader, RawHead, ColonWhitespace, Limits};
 use http::h1::HttpReader::{SizedReader, ChunkedReader, EmptyReader};
 use uri::RequestUri;
 
@@ -27,17 +27,45 @@ pub struct Request<'a, 'b: 'a> {
     pub uri: RequestUri,
     /// The version of HTTP for this request.
     pub version: HttpVersion,
-    body: HttpReader<&'a mut BufReader<&'b mut NetworkStream>>
+    body: HttpReader<&'a mut BufReader<&'b mut NetworkStream>>,
+    trailers: Option<Headers>,
+    limits: Limits,
+    // bytes of the body read so far
+    body_read: u64,
+    raw_head: Option<Vec<u8>>,
 }
 
 
 impl<'a, 'b: 'a> Request<'a, 'b> {
     /// Create a new Request, reading the StartLine and Headers so they are
     /// immediately useful.
-    pub fn new(mut stream: &'a mut BufReader<&'b mut NetworkStream>, addr: SocketAddr)
+    pub fn new(stream: &'a mut BufReader<&'b mut NetworkStream>, addr: SocketAddr)
         -> ::Result<Request<'a, 'b>> {
+        Request::with_inspect(stream, addr, ColonWhitespace::Reject, |_| ())
+    }
+
+    /// Create a new Request, like `new`, but choosing what to do with
+    /// whitespace before header colons, and first passing the raw head of
+    /// the request to `inspect`, before it is parsed into `Headers`.
+    pub fn with_inspect<F>(stream: &'a mut BufReader<&'b mut NetworkStream>,
+                           addr: SocketAddr, colon: ColonWhitespace, inspect: F)
+        -> ::Result<Request<'a, 'b>>
+    where F: FnOnce(&RawHead) {
+        Request::with_limits(stream, addr, colon, &Limits::default(), inspect)
+    }
 
-        let Incoming { version, subject: (method, uri), headers } = try!(h1::parse_request(stream));
+    /// Create a new Request, like `with_inspect`, but within `limits`.
+    ///
+    /// Fails with `Error::BodyTooLarge` if the `Content-Length` of the
+    /// request is over `max_body_size`, and reading a chunked body fails
+    /// with an error of kind `InvalidData` once it goes over.
+    pub fn with_limits<F>(mut stream: &'a mut BufReader<&'b mut NetworkStream>,
+                          addr: SocketAddr, colon: ColonWhitespace, limits: &Limits,
+                          inspect: F)
+        -> ::Result<Request<'a, 'b>>
+    where F: FnOnce(&RawHead) {
+        let Incoming { version, subject: (method, uri), headers } =
+            try!(h1::parse_request_limited(stream, colon, limits, inspect));
         debug!("Request Line: {:?} {:?} {:?}", method, uri, version);
         debug!("{:?}", headers);
 
@@ -45,7 +73,11 @@ impl<'a, 'b: 'a> Request<'a, 'b> {
             EmptyReader(stream)
         } else if headers.has::<ContentLength>() {
             match headers.get::<ContentLength>() {
-                Some(&ContentLength(len)) => SizedReader(stream, len),
+                Some(&ContentLength(0)) => EmptyReader(stream),
+                Some(&ContentLength(len)) => {
+                    try!(limits.check_body(len));
+                    SizedReader(stream, len)
+                },
                 None => unreachable!()
             }
         } else if headers.has::<TransferEncoding>() {
@@ -61,7 +93,11 @@ impl<'a, 'b: 'a> Request<'a, 'b> {
             uri: uri,
             headers: headers,
             version: version,
-            body: body
+            body: body,
+            trailers: None,
+            limits: *limits,
+            body_read: 0,
+            raw_head: None,
         })
     }
 
@@ -78,6 +114,41 @@ impl<'a, 'b: 'a> Request<'a, 'b> {
     pub fn set_read_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
         Ok(())
     }
+    /// Whether the request has a body left to read, going by its framing
+    /// headers.
+    #[doc(hidden)]
+    pub fn has_body(&self) -> bool {
+        match self.body {
+            EmptyReader(..) | SizedReader(_, 0) => false,
+            _ => true
+        }
+    }
+
+    /// The head of the request exactly as it was received, keeping the
+    /// order, case and repetition of its header fields, so that it can be
+    /// forwarded unchanged with `client::Request::start_raw`.
+    ///
+    /// It is only kept by a server with `Server::set_raw_heads`.
+    #[inline]
+    pub fn raw_head(&self) -> Option<RawHead> {
+        self.raw_head.as_ref().map(|head| RawHead::new(head))
+    }
+
+    /// Keeps the raw head of the request, for `raw_head`.
+    #[doc(hidden)]
+    pub fn set_raw_head(&mut self, head: Vec<u8>) {
+        self.raw_head = Some(head);
+    }
+
+    /// The trailer fields sent after a chunked body, such as a checksum of
+    /// it, once the body has been read to the end.
+    ///
+    /// Bodies that aren't chunked have none.
+    #[inline]
+    pub fn trailers(&self) -> Option<&Headers> {
+        self.trailers.as_ref()
+    }
+
     /// Get a reference to the underlying `NetworkStream`.
     #[inline]
     pub fn downcast_ref<T: NetworkStream>(&self) -> Option<&T> {
@@ -124,7 +195,13 @@ impl<'a, 'b: 'a> Request<'a, 'b> {
 impl<'a, 'b> Read for Request<'a, 'b> {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.body.read(buf)
+        let n = try!(self.body.read(buf));
+        if n == 0 && !buf.is_empty() && self.trailers.is_none() {
+            self.trailers = Some(try!(self.body.read_trailers()));
+        }
+        self.body_read += n as u64;
+        try!(self.limits.check_body_read(self.body_read));
+        Ok(n)
     }
 }
 
@@ -132,6 +209,7 @@ impl<'a, 'b> Read for Request<'a, 'b> {
 mod tests {
     use buffer::BufReader;
     use header::{Host, TransferEncoding, Encoding};
+    use http::h1::HttpReader::EmptyReader;
     use net::NetworkStream;
     use mock::MockStream;
     use super::Request;
@@ -200,6 +278,29 @@ mod tests {
         assert_eq!(read_to_string(req).unwrap(), "".to_owned());
     }
 
+    #[test]
+    fn test_post_zero_length_body() {
+        let mut mock = MockStream::with_input(b"\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            GET / HTTP/1.1\r\n\
+        ");
+
+        // FIXME: Use Type ascription
+        let mock: &mut NetworkStream = &mut mock;
+        let mut stream = BufReader::new(mock);
+
+        let req = Request::new(&mut stream, sock("127.0.0.1:80")).unwrap();
+        assert!(!req.has_body());
+        match req.body {
+            EmptyReader(..) => (),
+            ref body => panic!("expected EmptyReader, got {:?}", body),
+        }
+        assert_eq!(read_to_string(req).unwrap(), "".to_owned());
+    }
+
     #[test]
     fn test_parse_chunked_request() {
         let mut mock = MockStream::with_input(b"\
@@ -241,6 +342,42 @@ mod tests {
         assert_eq!(read_to_string(req).unwrap(), "qwert".to_owned());
     }
 
+    #[test]
+    fn test_chunked_request_trailers() {
+        let mut mock = MockStream::with_input(b"\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Transfer-Encoding: chunked\r\n\
+            Trailer: Content-MD5\r\n\
+            \r\n\
+            3\r\n\
+            foo\r\n\
+            0\r\n\
+            Content-MD5: rL0Y20zC+Fzt72VPzMSk2A==\r\n\
+            \r\n\
+            GET / HTTP/1.1\r\n"
+        );
+
+        // FIXME: Use Type ascription
+        let mock: &mut NetworkStream = &mut mock;
+        let mut stream = BufReader::new(mock);
+        {
+            let mut req = Request::new(&mut stream, sock("127.0.0.1:80")).unwrap();
+            assert_eq!(req.trailers(), None);
+            let mut s = String::new();
+            req.read_to_string(&mut s).unwrap();
+            assert_eq!(s, "foo");
+            let trailers = req.trailers().unwrap();
+            assert_eq!(trailers.get_raw("Content-MD5").unwrap(),
+                       &[b"rL0Y20zC+Fzt72VPzMSk2A==".to_vec()][..]);
+        }
+
+        // the next request starts right after them
+        let mut rest = String::new();
+        stream.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "GET / HTTP/1.1\r\n");
+    }
+
     /// Tests that when a chunk size is not a valid radix-16 number, an error
     /// is returned.
     #[test]
@@ -3,16 +3,19 @@
 //! These are responses sent by a `hyper::Server` to clients, after
 //! receiving a request.
 use std::any::{Any, TypeId};
+use std::fmt;
 use std::marker::PhantomData;
 use std::mem;
 use std::io::{self, Write};
 use std::ptr;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
 use std::thread;
+use std::time::Duration;
 
 use time::now_utc;
 
 use header;
-use http::h1::{CR, LF, LINE_ENDING, HttpWriter};
+use http::h1::{CR, LF, LINE_ENDING, HttpWriter, HeadParser, Incoming};
 use http::h1::HttpWriter::{ThroughWriter, ChunkedWriter, SizedWriter, EmptyWriter};
 use status;
 use net::{Fresh, Streaming};
@@ -36,6 +39,10 @@ pub struct Response<'a, W: Any = Fresh> {
     status: status::StatusCode,
     // The outgoing headers on this response.
     headers: &'a mut header::Headers,
+    // Writes are gathered into chunks of at least this many bytes.
+    min_chunk_size: usize,
+    // Bytes written, waiting to be a chunk.
+    chunk_buf: Vec<u8>,
 
     _writing: PhantomData<W>
 }
@@ -49,6 +56,18 @@ impl<'a, W: Any> Response<'a, W> {
     #[inline]
     pub fn headers(&self) -> &header::Headers { &*self.headers }
 
+    /// Gathers writes to a chunked body into chunks of at least `size`
+    /// bytes, instead of sending a chunk for each write.
+    ///
+    /// This is for bodies written a few bytes at a time, which would
+    /// otherwise spend more on chunk framing than on the body. What has been
+    /// gathered is still sent by `flush` and `end`. `0`, the default, sends
+    /// every write as it is.
+    #[inline]
+    pub fn set_min_chunk_size(&mut self, size: usize) {
+        self.min_chunk_size = size;
+    }
+
     /// Construct a Response from its constituent parts.
     #[inline]
     pub fn construct(version: version::HttpVersion,
@@ -60,14 +79,21 @@ impl<'a, W: Any> Response<'a, W> {
             version: version,
             body: body,
             headers: headers,
+            min_chunk_size: 0,
+            chunk_buf: Vec::new(),
             _writing: PhantomData,
         }
     }
 
     /// Deconstruct this Response into its constituent parts.
+    ///
+    /// Bytes gathered for a chunk, and not yet written, are dropped, so a
+    /// response with a minimum chunk size should be flushed first.
     #[inline]
-    pub fn deconstruct(self) -> (version::HttpVersion, HttpWriter<&'a mut (Write + 'a)>,
-                                 status::StatusCode, &'a mut header::Headers) {
+    pub fn deconstruct(mut self) -> (version::HttpVersion, HttpWriter<&'a mut (Write + 'a)>,
+                                     status::StatusCode, &'a mut header::Headers) {
+        // not forgotten with the rest
+        drop(mem::replace(&mut self.chunk_buf, Vec::new()));
         unsafe {
             let parts = (
                 self.version,
@@ -93,7 +119,7 @@ impl<'a, W: Any> Response<'a, W> {
             status::StatusCode::NoContent | status::StatusCode::NotModified => Body::Empty,
             c if c.class() == status::StatusClass::Informational => Body::Empty,
             _ => if let Some(cl) = self.headers.get::<header::ContentLength>() {
-                Body::Sized(**cl)
+                if **cl == 0 { Body::Empty } else { Body::Sized(**cl) }
             } else {
                 Body::Chunked
             }
@@ -135,6 +161,8 @@ impl<'a> Response<'a, Fresh> {
             version: version::HttpVersion::Http11,
             headers: headers,
             body: ThroughWriter(stream),
+            min_chunk_size: 0,
+            chunk_buf: Vec::new(),
             _writing: PhantomData,
         }
     }
@@ -178,6 +206,7 @@ impl<'a> Response<'a, Fresh> {
     /// creating a Response<Streaming>
     pub fn start(mut self) -> io::Result<Response<'a, Streaming>> {
         let body_type = try!(self.write_head());
+        let min_chunk_size = self.min_chunk_size;
         let (version, body, status, headers) = self.deconstruct();
         let stream = match body_type {
             Body::Chunked => ChunkedWriter(body.into_inner()),
@@ -191,9 +220,118 @@ impl<'a> Response<'a, Fresh> {
             body: stream,
             status: status,
             headers: headers,
+            min_chunk_size: min_chunk_size,
+            chunk_buf: Vec::new(),
+            _writing: PhantomData,
+        })
+    }
+
+    /// Consume this Response<Fresh>, writing `head` as its head byte for
+    /// byte, and creating a Response<Streaming>.
+    ///
+    /// This is for gateways that forward heads unchanged, such as the
+    /// `raw_head` of a `client::Response`. `head` is a whole response head,
+    /// ending with an empty line. Its status and headers take the place of
+    /// the response's, and decide how the body is written: chunked if
+    /// `Transfer-Encoding` ends with `chunked`, with `Content-Length` bytes,
+    /// or else until the connection is closed. Nothing is added to it, not
+    /// even a `Date`.
+    pub fn start_raw(mut self, head: &[u8]) -> io::Result<Response<'a, Streaming>> {
+        let parsed = HeadParser::response().parse_whole(head);
+        let Incoming { version, subject, headers } = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))
+        };
+        debug!("writing raw head: {:?}", String::from_utf8_lossy(head));
+        try!(self.body.write_all(head));
+
+        let status = status::StatusCode::from_u16(subject.0);
+        let chunked = match headers.get::<header::TransferEncoding>() {
+            Some(&header::TransferEncoding(ref codings)) => {
+                codings.last() == Some(&header::Encoding::Chunked)
+            },
+            None => false
+        };
+        let len = headers.get::<header::ContentLength>().map(|len| **len);
+        *self.headers = headers;
+        self.version = version;
+        self.status = status;
+
+        let min_chunk_size = self.min_chunk_size;
+        let (version, body, status, headers) = self.deconstruct();
+        let stream = match len {
+            _ if status == status::StatusCode::NoContent ||
+                 status == status::StatusCode::NotModified ||
+                 status.class() == status::StatusClass::Informational => {
+                EmptyWriter(body.into_inner())
+            },
+            _ if chunked => ChunkedWriter(body.into_inner()),
+            Some(0) => EmptyWriter(body.into_inner()),
+            Some(len) => SizedWriter(body.into_inner(), len),
+            None => {
+                // the body ends when the connection does
+                headers.set(header::Connection::close());
+                ThroughWriter(body.into_inner())
+            }
+        };
+
+        Ok(Response {
+            version: version,
+            body: stream,
+            status: status,
+            headers: headers,
+            min_chunk_size: min_chunk_size,
+            chunk_buf: Vec::new(),
             _writing: PhantomData,
         })
     }
+
+    /// Starts the response, and writes each chunk of `chunks` to the body as
+    /// it is computed, flushing after each.
+    ///
+    /// The next chunk is only computed once the last one has been written,
+    /// so a slow client slows the iterator down, instead of chunks piling up
+    /// in memory. The body is chunked, unless a `Content-Length` was set.
+    ///
+    /// ```
+    /// # use hyper::server::Response;
+    /// fn handler(res: Response) {
+    ///     let rows = (0..100).map(|n| format!("{},{}\n", n, n * n));
+    ///     res.send_chunks(rows).unwrap();
+    /// }
+    /// ```
+    pub fn send_chunks<I>(self, chunks: I) -> io::Result<()>
+    where I: IntoIterator, I::Item: AsRef<[u8]> {
+        let mut stream = try!(self.start());
+        for chunk in chunks {
+            let chunk = chunk.as_ref();
+            // an empty chunk would end a chunked body
+            if chunk.is_empty() {
+                continue;
+            }
+            try!(stream.write_all(chunk));
+            try!(stream.flush());
+        }
+        stream.end()
+    }
+
+    /// Starts a long-poll response, whose body is written once one of its
+    /// `Waker`s is woken, such as by another thread when an event happens.
+    ///
+    /// The head is sent right away. While any `Waker` for the response
+    /// exists, `LongPoll::wait` waits for it without a deadline, so the
+    /// connection stays open however long the event takes.
+    pub fn long_poll(self) -> io::Result<(LongPoll<'a>, Waker)> {
+        let mut res = try!(self.start());
+        try!(res.flush());
+        let (tx, rx) = channel();
+        Ok((LongPoll {
+            res: res,
+            wakes: rx,
+            keep_alive: None,
+        }, Waker(tx)))
+    }
+
     /// Get a mutable reference to the status.
     #[inline]
     pub fn status_mut(&mut self) -> &mut status::StatusCode { &mut self.status }
@@ -207,27 +345,161 @@ impl<'a> Response<'a, Fresh> {
 impl<'a> Response<'a, Streaming> {
     /// Flushes all writing of a response to the client.
     #[inline]
-    pub fn end(self) -> io::Result<()> {
+    pub fn end(mut self) -> io::Result<()> {
         trace!("ending");
+        try!(self.write_chunk());
         let (_, body, _, _) = self.deconstruct();
         try!(body.end());
         Ok(())
     }
+
+    /// Ends a chunked response with the `trailers`, then flushes it.
+    ///
+    /// The fields should be named in a `Trailer` header before the response
+    /// is started. Fails if the body isn't chunked, which is when it has a
+    /// `Content-Length`.
+    pub fn end_with_trailers(mut self, trailers: &header::Headers) -> io::Result<()> {
+        trace!("ending with trailers");
+        try!(self.write_chunk());
+        let (_, body, _, _) = self.deconstruct();
+        try!(body.end_with_trailers(trailers));
+        Ok(())
+    }
+
+    /// Ends the response, like `end`, then calls `on_flushed`.
+    ///
+    /// Ending flushes the connection, so once `on_flushed` is called every
+    /// byte of the response has been handed to the kernel, and anything the
+    /// body was streamed from can be released. It isn't called if ending
+    /// fails, in which case the error is returned.
+    pub fn finish<F: FnOnce()>(self, on_flushed: F) -> io::Result<()> {
+        try!(self.end());
+        trace!("flushed");
+        on_flushed();
+        Ok(())
+    }
+
+    fn write_chunk(&mut self) -> io::Result<()> {
+        if !self.chunk_buf.is_empty() {
+            trace!("writing gathered chunk of {} bytes", self.chunk_buf.len());
+            try!(self.body.write_all(&self.chunk_buf));
+            self.chunk_buf.clear();
+        }
+        Ok(())
+    }
 }
 
 impl<'a> Write for Response<'a, Streaming> {
     #[inline]
     fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
         debug!("write {:?} bytes", msg.len());
-        self.body.write(msg)
+        let gather = match self.body {
+            ChunkedWriter(..) => self.chunk_buf.len() + msg.len() < self.min_chunk_size ||
+                                 !self.chunk_buf.is_empty(),
+            _ => false
+        };
+        if !gather {
+            return self.body.write(msg);
+        }
+        self.chunk_buf.extend_from_slice(msg);
+        if self.chunk_buf.len() >= self.min_chunk_size {
+            try!(self.write_chunk());
+        }
+        Ok(msg.len())
     }
 
     #[inline]
     fn flush(&mut self) -> io::Result<()> {
+        try!(self.write_chunk());
         self.body.flush()
     }
 }
 
+/// A long-poll response, made by `Response::long_poll`.
+///
+/// It implements `Write`, to write the body once woken.
+pub struct LongPoll<'a> {
+    res: Response<'a, Streaming>,
+    wakes: Receiver<()>,
+    keep_alive: Option<(Duration, &'static [u8])>,
+}
+
+impl<'a> LongPoll<'a> {
+    /// Writes `bytes` to the body every `interval` spent waiting, so that
+    /// proxies don't close the connection as idle.
+    ///
+    /// The bytes must be something the client ignores, such as `b" "` in
+    /// JSON, or a `b":\n"` comment in a `text/event-stream`.
+    #[inline]
+    pub fn set_keep_alive(&mut self, interval: Duration, bytes: &'static [u8]) {
+        self.keep_alive = Some((interval, bytes));
+    }
+
+    /// Waits until a `Waker` is woken, writing any keep-alive meanwhile.
+    ///
+    /// Returns `false` once every `Waker` has been dropped without waking,
+    /// after which the response should be ended. Wakes that happen while
+    /// not waiting are counted as one.
+    pub fn wait(&mut self) -> io::Result<bool> {
+        loop {
+            let woken = match self.keep_alive {
+                Some((interval, _)) => self.wakes.recv_timeout(interval),
+                None => self.wakes.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match woken {
+                Ok(()) => {
+                    while let Ok(()) = self.wakes.try_recv() {}
+                    return Ok(true);
+                },
+                Err(RecvTimeoutError::Disconnected) => return Ok(false),
+                Err(RecvTimeoutError::Timeout) => {
+                    let bytes = self.keep_alive.map_or(&b""[..], |(_, bytes)| bytes);
+                    trace!("long-poll keep-alive");
+                    try!(self.res.write_all(bytes));
+                    try!(self.res.flush());
+                }
+            }
+        }
+    }
+
+    /// Flushes all writing of the response to the client.
+    #[inline]
+    pub fn end(self) -> io::Result<()> {
+        self.res.end()
+    }
+}
+
+impl<'a> fmt::Debug for LongPoll<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LongPoll {{ keep_alive: {:?} }}", self.keep_alive.map(|(dur, _)| dur))
+    }
+}
+
+impl<'a> Write for LongPoll<'a> {
+    #[inline]
+    fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
+        self.res.write(msg)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.res.flush()
+    }
+}
+
+/// Wakes the `LongPoll` it was made with. It can be cloned and sent to
+/// other threads.
+#[derive(Clone, Debug)]
+pub struct Waker(Sender<()>);
+
+impl Waker {
+    /// Wakes the long-poll. Returns `false` if the response is gone.
+    #[inline]
+    pub fn wake(&self) -> bool {
+        self.0.send(()).is_ok()
+    }
+}
+
 #[derive(PartialEq)]
 enum Body {
     Chunked,
@@ -253,6 +525,12 @@ impl<'a, T: Any> Drop for Response<'a, T> {
             };
             end(&mut body);
         } else {
+            if !self.chunk_buf.is_empty() {
+                if let Err(e) = self.body.write_all(&self.chunk_buf) {
+                    debug!("error dropping request: {:?}", e);
+                    return;
+                }
+            }
             end(&mut self.body);
         };
 
@@ -328,6 +606,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_streaming_finish() {
+        use std::io::{BufWriter, Write};
+
+        let mut headers = Headers::new();
+        let mut stream = MockStream::new();
+        let mut flushed = false;
+        {
+            let mut buf = BufWriter::new(&mut stream);
+            let res = Response::new(&mut buf, &mut headers);
+            let mut res = res.start().unwrap();
+            res.write_all(b"foo").unwrap();
+            res.finish(|| flushed = true).unwrap();
+            assert!(buf.get_ref().write.ends_with(b"0\r\n\r\n"));
+        }
+        assert!(flushed);
+
+        let mut stream = MockStream::new();
+        stream.error_on_write = true;
+        let mut flushed = false;
+        {
+            let mut buf = BufWriter::new(&mut stream);
+            let res = Response::new(&mut buf, &mut headers);
+            assert!(res.start().unwrap().finish(|| flushed = true).is_err());
+        }
+        assert!(!flushed);
+    }
+
+    #[test]
+    fn test_send_chunks() {
+        let mut headers = Headers::new();
+        let mut stream = MockStream::new();
+        {
+            let res = Response::new(&mut stream, &mut headers);
+            res.send_chunks(vec![b"foo".to_vec(), vec![], b"quux".to_vec()]).unwrap();
+        }
+
+        lines! { stream =
+            "HTTP/1.1 200 OK",
+            _date,
+            _transfer_encoding,
+            "",
+            "3",
+            "foo",
+            "4",
+            "quux",
+            "0",
+            ""
+        }
+    }
+
+    #[test]
+    fn test_min_chunk_size() {
+        use std::io::Write;
+
+        let mut headers = Headers::new();
+        let mut stream = MockStream::new();
+        {
+            let mut res = Response::new(&mut stream, &mut headers);
+            res.set_min_chunk_size(4);
+            let mut res = res.start().unwrap();
+            for b in b"abcdef".chunks(1) {
+                res.write_all(b).unwrap();
+            }
+            res.write_all(b"ghijk").unwrap();
+            res.write_all(b"l").unwrap();
+            res.end().unwrap();
+        }
+
+        lines! { stream =
+            "HTTP/1.1 200 OK",
+            _date,
+            _transfer_encoding,
+            "",
+            "4",
+            "abcd",
+            "7",
+            "efghijk",
+            "1",
+            "l",
+            "0",
+            ""
+        }
+    }
+
     #[test]
     fn test_fresh_drop() {
         use status::StatusCode;
@@ -410,6 +773,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_long_poll() {
+        use std::io::Write;
+        use std::thread;
+        use std::time::Duration;
+
+        let mut headers = Headers::new();
+        let mut stream = MockStream::new();
+        {
+            let res = Response::new(&mut stream, &mut headers);
+            let (mut poll, waker) = res.long_poll().unwrap();
+            poll.set_keep_alive(Duration::from_millis(5), b" ");
+            let waking = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                assert!(waker.wake());
+            });
+            assert!(poll.wait().unwrap());
+            poll.write_all(b"foo").unwrap();
+            waking.join().unwrap();
+            assert!(!poll.wait().unwrap());
+            poll.end().unwrap();
+        }
+
+        let s = String::from_utf8(stream.write).unwrap();
+        assert!(s.contains("\r\n\r\n1\r\n \r\n"), "{:?}", s);
+        assert!(s.ends_with("3\r\nfoo\r\n0\r\n\r\n"), "{:?}", s);
+    }
+
     #[test]
     fn test_no_content() {
         use std::io::Write;
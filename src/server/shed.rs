@@ -0,0 +1,214 @@
+//! Shedding load when a server is overloaded.
+//!
+//! `LoadShed` wraps another `Handler`, and counts the requests it is
+//! handling at once. Once there are as many as allowed, further requests
+//! are answered right away with a `503 Service Unavailable` and a
+//! `Retry-After` header, instead of waiting their turn, so that the ones
+//! accepted still get answered in good time.
+//!
+//! The requests counted are those inside the wrapped handler's `handle`.
+//! The count is checked in `on_head`, before a request is counted, so
+//! requests arriving together can go somewhat over the limit.
+//!
+//! How long requests waited before being handled isn't taken into account.
+//! A `Server` accepts each connection on the thread that will handle it,
+//! so connections waiting for a free thread wait in the listener's
+//! backlog, unseen, for however long it takes. Keep `max_in_flight` below
+//! the number of threads given to `handle_threads`, so that threads are
+//! left free to take waiting requests up and refuse them quickly while the
+//! rest are busy.
+//!
+//! ```no_run
+//! use hyper::server::{Server, Request, Response};
+//! use hyper::server::shed::{Config, LoadShed};
+//!
+//! fn slow(_: Request, res: Response) {
+//!     res.send(b"done").unwrap();
+//! }
+//!
+//! let shed = LoadShed::new(slow, Config {
+//!     max_in_flight: 32,
+//!     retry_after: Some(5),
+//! });
+//! Server::http("0.0.0.0:0").unwrap().handle_threads(shed, 64).unwrap();
+//! ```
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use header::{Headers, RetryAfter};
+use method::Method;
+use net::Fresh;
+use server::{Handler, RawHead, Request, Response};
+use status::StatusCode;
+use uri::RequestUri;
+
+/// Config options for a `LoadShed`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The most requests to handle at once, which should be fewer than the
+    /// threads of the `Server`.
+    pub max_in_flight: usize,
+    /// The seconds to tell refused clients to wait, if any.
+    pub retry_after: Option<u64>,
+}
+
+impl Default for Config {
+    #[inline]
+    fn default() -> Config {
+        Config {
+            max_in_flight: 256,
+            retry_after: Some(1),
+        }
+    }
+}
+
+/// A `Handler` that refuses requests while too many are being handled.
+pub struct LoadShed<H: Handler> {
+    handler: H,
+    config: Config,
+    in_flight: AtomicUsize,
+}
+
+impl<H: Handler> LoadShed<H> {
+    /// Wraps a handler, which gets the requests that aren't refused.
+    pub fn new(handler: H, config: Config) -> LoadShed<H> {
+        LoadShed {
+            handler: handler,
+            config: config,
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of requests being handled now.
+    #[inline]
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    fn overloaded(&self) -> bool {
+        self.in_flight() >= self.config.max_in_flight
+    }
+}
+
+// Counts a request in flight until it is dropped, even by a panic.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl<'a> Drop for InFlight<'a> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<H: Handler> Handler for LoadShed<H> {
+    fn handle<'a, 'k>(&'a self, req: Request<'a, 'k>, res: Response<'a, Fresh>) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let _in_flight = InFlight(&self.in_flight);
+        self.handler.handle(req, res)
+    }
+
+    fn check_continue(&self, head: (&Method, &RequestUri, &Headers)) -> StatusCode {
+        if self.overloaded() {
+            return StatusCode::ServiceUnavailable;
+        }
+        self.handler.check_continue(head)
+    }
+
+    fn on_raw_head(&self, head: &RawHead, addr: SocketAddr) -> bool {
+        self.handler.on_raw_head(head, addr)
+    }
+
+    fn on_head(&self, req: &Request, res: &mut Headers) -> Option<StatusCode> {
+        if self.overloaded() {
+            debug!("shedding {} {}, {} in flight", req.method, req.uri, self.in_flight());
+            if let Some(secs) = self.config.retry_after {
+                res.set(RetryAfter::Delay(secs));
+            }
+            return Some(StatusCode::ServiceUnavailable);
+        }
+        self.handler.on_head(req, res)
+    }
+
+    fn on_connection_start(&self) {
+        self.handler.on_connection_start()
+    }
+
+    fn on_connection_end(&self) {
+        self.handler.on_connection_end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use buffer::BufReader;
+    use header::{Headers, RetryAfter};
+    use mock::MockStream;
+    use net::NetworkStream;
+    use server::{Handler, Request, Response};
+    use status::StatusCode;
+
+    use super::{Config, LoadShed};
+
+    struct Blocking {
+        started: Mutex<Sender<()>>,
+        release: Mutex<Receiver<()>>,
+    }
+
+    impl Handler for Blocking {
+        fn handle(&self, _: Request, res: Response) {
+            self.started.lock().unwrap().send(()).unwrap();
+            self.release.lock().unwrap().recv().unwrap();
+            res.send(b"done").unwrap();
+        }
+    }
+
+    fn on_head<H: Handler>(handler: &H, headers: &mut Headers) -> Option<StatusCode> {
+        let mut mock = MockStream::with_input(b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n");
+        let mock: &mut NetworkStream = &mut mock;
+        let mut stream = BufReader::new(mock);
+        let req = Request::new(&mut stream, "127.0.0.1:80".parse().unwrap()).unwrap();
+        handler.on_head(&req, headers)
+    }
+
+    fn handle<H: Handler>(handler: &H) {
+        let mut mock = MockStream::with_input(b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n");
+        let mock: &mut NetworkStream = &mut mock;
+        let mut stream = BufReader::new(mock);
+        let req = Request::new(&mut stream, "127.0.0.1:80".parse().unwrap()).unwrap();
+        let mut out = Vec::new();
+        let mut headers = Headers::new();
+        handler.handle(req, Response::new(&mut out, &mut headers));
+    }
+
+    #[test]
+    fn test_load_shed() {
+        let (started_tx, started_rx) = channel();
+        let (release_tx, release_rx) = channel();
+        let shed = Arc::new(LoadShed::new(Blocking {
+            started: Mutex::new(started_tx),
+            release: Mutex::new(release_rx),
+        }, Config {
+            max_in_flight: 1,
+            retry_after: Some(3),
+        }));
+
+        let mut headers = Headers::new();
+        assert_eq!(on_head(&*shed, &mut headers), None);
+
+        let busy = shed.clone();
+        let guard = thread::spawn(move || handle(&*busy));
+        started_rx.recv().unwrap();
+        assert_eq!(shed.in_flight(), 1);
+
+        assert_eq!(on_head(&*shed, &mut headers), Some(StatusCode::ServiceUnavailable));
+        assert_eq!(headers.get::<RetryAfter>(), Some(&RetryAfter::Delay(3)));
+
+        release_tx.send(()).unwrap();
+        guard.join().unwrap();
+        assert_eq!(shed.in_flight(), 0);
+        assert_eq!(on_head(&*shed, &mut Headers::new()), None);
+    }
+}
@@ -0,0 +1,328 @@
+//! Server-Sent Events, as `text/event-stream` responses.
+//!
+//! `start` begins an event stream in place of a response, and returns it
+//! with an `EventSender`, which other threads can send `Event`s on. `run`
+//! then writes each event as it is sent, flushing it right away, and writes
+//! a comment every keep-alive interval spent waiting, so that proxies don't
+//! close the connection as idle:
+//!
+//! ```no_run
+//! use std::thread;
+//! use std::time::Duration;
+//! use hyper::server::{Request, Response};
+//! use hyper::server::sse::{self, Event};
+//!
+//! fn ticks(_: Request, res: Response) {
+//!     let (mut events, sender) = sse::start(res).unwrap();
+//!     events.set_keep_alive(Some(Duration::from_secs(15)));
+//!     thread::spawn(move || {
+//!         for n in 0.. {
+//!             // false once the client is gone
+//!             if !sender.send(Event::new(&n.to_string()).event("tick")) {
+//!                 break;
+//!             }
+//!             thread::sleep(Duration::from_secs(1));
+//!         }
+//!     });
+//!     let _ = events.run();
+//! }
+//! ```
+//!
+//! When the client disconnects, writing fails, `run` returns the error, and
+//! `EventSender::send` returns `false` from then on, so event sources can
+//! stop.
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use mime::{Mime, SubLevel, TopLevel};
+
+use header::{CacheControl, CacheDirective, ContentLength, ContentType};
+use net::{Fresh, Streaming};
+use server::{Request, Response};
+
+/// An event, written as a frame of `event:`, `data:`, `id:` and `retry:`
+/// fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    data: String,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// Creates an event of the default type, `message`, with `data`.
+    ///
+    /// Data of several lines is sent as a `data:` field for each.
+    pub fn new(data: &str) -> Event {
+        Event {
+            data: data.to_owned(),
+            event: None,
+            id: None,
+            retry: None,
+        }
+    }
+
+    /// Sets the type of the event.
+    pub fn event(mut self, event: &str) -> Event {
+        self.event = Some(event.to_owned());
+        self
+    }
+
+    /// Sets the id of the event, which the client sends back in a
+    /// `Last-Event-ID` header when it reconnects.
+    pub fn id(mut self, id: &str) -> Event {
+        self.id = Some(id.to_owned());
+        self
+    }
+
+    /// Sets how long the client waits before reconnecting.
+    pub fn retry(mut self, retry: Duration) -> Event {
+        self.retry = Some(retry);
+        self
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // line breaks would end the field early
+        if let Some(ref event) = self.event {
+            try!(write!(f, "event: {}\n", single_line(event)));
+        }
+        if let Some(ref id) = self.id {
+            try!(write!(f, "id: {}\n", single_line(id)));
+        }
+        if let Some(retry) = self.retry {
+            let millis = retry.as_secs() * 1000 + retry.subsec_nanos() as u64 / 1_000_000;
+            try!(write!(f, "retry: {}\n", millis));
+        }
+        for line in lines(&self.data) {
+            try!(write!(f, "data: {}\n", line));
+        }
+        f.write_str("\n")
+    }
+}
+
+fn single_line(s: &str) -> String {
+    s.chars().filter(|&c| c != '\r' && c != '\n').collect()
+}
+
+// Clients end a line at `\r\n`, `\r` or `\n`, so each is a line break here.
+fn lines(s: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut rest = s;
+    while let Some(end) = rest.find(|c| c == '\r' || c == '\n') {
+        lines.push(&s[start..start + end]);
+        let len = if rest[end..].starts_with("\r\n") { 2 } else { 1 };
+        start += end + len;
+        rest = &s[start..];
+    }
+    lines.push(rest);
+    lines
+}
+
+/// The id of the last event a reconnecting client received, from its
+/// `Last-Event-ID` header.
+pub fn last_event_id<'r>(req: &'r Request) -> Option<&'r str> {
+    req.headers.get_raw("Last-Event-ID")
+        .and_then(|values| values.first())
+        .and_then(|value| ::std::str::from_utf8(value).ok())
+}
+
+/// Starts an event stream in place of `res`, and returns it with an
+/// `EventSender` for it.
+///
+/// The head is sent right away, with a `Content-Type` of
+/// `text/event-stream` and `Cache-Control: no-cache`. Other headers set on
+/// `res` are sent as well, except a `Content-Length`.
+pub fn start<'a>(mut res: Response<'a, Fresh>) -> io::Result<(EventStream<'a>, EventSender)> {
+    {
+        let headers = res.headers_mut();
+        headers.remove::<ContentLength>();
+        headers.set(ContentType(Mime(TopLevel::Text,
+                                     SubLevel::Ext("event-stream".to_owned()), vec![])));
+        headers.set(CacheControl(vec![CacheDirective::NoCache]));
+    }
+    res.set_min_chunk_size(0);
+    let mut res = try!(res.start());
+    try!(res.flush());
+    let (tx, rx) = channel();
+    Ok((EventStream {
+        res: res,
+        events: rx,
+        keep_alive: None,
+    }, EventSender(tx)))
+}
+
+/// An event stream, made by `start`.
+pub struct EventStream<'a> {
+    res: Response<'a, Streaming>,
+    events: Receiver<Event>,
+    keep_alive: Option<Duration>,
+}
+
+impl<'a> EventStream<'a> {
+    /// Sets how long `run` waits for an event before writing a comment, to
+    /// keep the connection from looking idle. Default is to never write
+    /// one.
+    #[inline]
+    pub fn set_keep_alive(&mut self, interval: Option<Duration>) {
+        self.keep_alive = interval;
+    }
+
+    /// Writes `event` and flushes it to the client.
+    pub fn send(&mut self, event: &Event) -> io::Result<()> {
+        trace!("sending event {:?}", event);
+        // in one write, so that it is one chunk
+        try!(self.res.write_all(event.to_string().as_bytes()));
+        self.res.flush()
+    }
+
+    /// Writes a comment, which the client ignores, and flushes it.
+    pub fn comment(&mut self, text: &str) -> io::Result<()> {
+        let mut comment = String::new();
+        for line in lines(text) {
+            comment.push(':');
+            comment.push_str(line);
+            comment.push('\n');
+        }
+        try!(self.res.write_all(comment.as_bytes()));
+        self.res.flush()
+    }
+
+    /// Writes the events sent with the `EventSender`s of this stream as
+    /// they come, until every sender is dropped, and then ends the response.
+    ///
+    /// Fails once the client is gone, after which the senders' `send`
+    /// returns `false`.
+    pub fn run(mut self) -> io::Result<()> {
+        loop {
+            let next = match self.keep_alive {
+                Some(interval) => self.events.recv_timeout(interval),
+                None => self.events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match next {
+                Ok(event) => try!(self.send(&event)),
+                Err(RecvTimeoutError::Disconnected) => return self.end(),
+                Err(RecvTimeoutError::Timeout) => {
+                    trace!("event stream keep-alive");
+                    try!(self.comment(""));
+                }
+            }
+        }
+    }
+
+    /// Flushes all writing of the response to the client.
+    #[inline]
+    pub fn end(self) -> io::Result<()> {
+        self.res.end()
+    }
+}
+
+impl<'a> fmt::Debug for EventStream<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EventStream {{ keep_alive: {:?} }}", self.keep_alive)
+    }
+}
+
+/// Sends events to the `EventStream` it was made with. It can be cloned
+/// and sent to other threads.
+#[derive(Clone, Debug)]
+pub struct EventSender(Sender<Event>);
+
+impl EventSender {
+    /// Sends `event` to be written. Returns `false` if the stream is gone,
+    /// such as when the client disconnected.
+    #[inline]
+    pub fn send(&self, event: Event) -> bool {
+        self.0.send(event).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use header::Headers;
+    use mock::MockStream;
+    use server::Response;
+
+    use super::{start, Event};
+
+    #[test]
+    fn test_event_format() {
+        assert_eq!(Event::new("hello").to_string(), "data: hello\n\n");
+        let event = Event::new("one\r\ntwo\nthree")
+            .event("up\ndate")
+            .id("7")
+            .retry(Duration::from_millis(1500));
+        assert_eq!(event.to_string(),
+                   "event: update\nid: 7\nretry: 1500\ndata: one\ndata: two\ndata: three\n\n");
+        // a bare CR ends a line too, so it can't start another field
+        assert_eq!(Event::new("x\rid: evil").to_string(), "data: x\ndata: id: evil\n\n");
+        assert_eq!(Event::new("a\r\rb").to_string(), "data: a\ndata: \ndata: b\n\n");
+    }
+
+    #[test]
+    fn test_run() {
+        let mut headers = Headers::new();
+        let mut stream = MockStream::new();
+        {
+            let res = Response::new(&mut stream, &mut headers);
+            let (mut events, sender) = start(res).unwrap();
+            events.set_keep_alive(Some(Duration::from_millis(5)));
+            let sending = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                assert!(sender.send(Event::new("first")));
+                assert!(sender.send(Event::new("second").id("2")));
+            });
+            events.run().unwrap();
+            sending.join().unwrap();
+        }
+
+        let s = String::from_utf8(stream.write).unwrap();
+        assert!(s.contains("Content-Type: text/event-stream\r\n"), "{:?}", s);
+        assert!(s.contains("Cache-Control: no-cache\r\n"), "{:?}", s);
+        assert!(s.contains("\r\n2\r\n:\n\r\n"), "{:?}", s);
+        assert!(s.ends_with("\r\nD\r\ndata: first\n\n\r\n14\r\nid: 2\ndata: second\n\n\r\n0\r\n\r\n"),
+                "{:?}", s);
+    }
+
+    // A connection that breaks once `hung_up` is set.
+    struct Hangup(Arc<AtomicBool>);
+
+    impl Write for Hangup {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0.load(Ordering::SeqCst) {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "hung up"))
+            } else {
+                Ok(buf.len())
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_client_disconnect() {
+        let hung_up = Arc::new(AtomicBool::new(false));
+        let mut headers = Headers::new();
+        let mut stream = Hangup(hung_up.clone());
+        let res = Response::new(&mut stream, &mut headers);
+        let (events, sender) = start(res).unwrap();
+        hung_up.store(true, Ordering::SeqCst);
+        let check = sender.clone();
+        assert!(sender.send(Event::new("lost")));
+        assert!(events.run().is_err());
+        assert!(!check.send(Event::new("too late")));
+    }
+}
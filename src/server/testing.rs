@@ -0,0 +1,238 @@
+//! Running a `Handler` without a network, for golden tests.
+//!
+//! `render` passes the bytes of a request to a handler, taking the same
+//! steps a `Server` takes for it: `on_raw_head`, `on_head`, answering
+//! `Expect: 100-continue` with `check_continue`, and then `handle`. It
+//! returns every byte written back, interim responses included, which can
+//! be compared with a response saved from an earlier run.
+//!
+//! So that the output is the same on every run, the `Date` header is set
+//! to the Unix epoch unless the handler sets its own, and the header fields
+//! of each response are sorted by name. Unlike a `Server`, it doesn't add
+//! `Connection` or `Server` headers.
+//!
+//! ```
+//! use hyper::server::{Request, Response};
+//! use hyper::server::testing;
+//!
+//! fn hello(_: Request, res: Response) {
+//!     res.send(b"hello").unwrap();
+//! }
+//!
+//! let out = testing::render(&hello, b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n");
+//! assert_eq!(out.unwrap(), &b"HTTP/1.1 200 OK\r\n\
+//!                             Content-Length: 5\r\n\
+//!                             Date: Thu, 01 Jan 1970 00:00:00 GMT\r\n\
+//!                             \r\n\
+//!                             hello"[..]);
+//! ```
+use std::ascii::AsciiExt;
+use std::io::{self, Cursor, Read, Write};
+use std::net::SocketAddr;
+#[cfg(feature = "timeouts")]
+use std::time::Duration;
+
+use time::{self, Timespec};
+
+use buffer::BufReader;
+use header::{Date, Expect, Headers, HttpDate};
+use net::NetworkStream;
+use server::{ColonWhitespace, Handler, Request, Response};
+use status::StatusCode;
+use version::HttpVersion::Http11;
+
+/// The address requests passed to `render` are from.
+pub const REMOTE_ADDR: &'static str = "127.0.0.1:40000";
+
+// `render` as a string, for the tests of the handlers in this crate.
+#[cfg(test)]
+pub fn render_str<H: Handler>(handler: &H, request: &[u8]) -> String {
+    String::from_utf8(render(handler, request).unwrap()).unwrap()
+}
+
+/// Runs `handler` for a request, given as the bytes of its head and body,
+/// and returns the bytes of the response.
+///
+/// A request refused by `on_raw_head` gets no response, so the output is
+/// empty. An error is returned only if the request can't be parsed.
+pub fn render<H: Handler>(handler: &H, request: &[u8]) -> ::Result<Vec<u8>> {
+    let addr = REMOTE_ADDR.parse().unwrap();
+    let mut input = Input(Cursor::new(request.to_vec()));
+    let input: &mut NetworkStream = &mut input;
+    let mut rdr = BufReader::new(input);
+
+    let mut inspected = true;
+    let req = try!(Request::with_inspect(&mut rdr, addr, ColonWhitespace::Reject, |head| {
+        inspected = handler.on_raw_head(head, addr);
+    }));
+    let mut out = Vec::new();
+    if !inspected {
+        return Ok(out);
+    }
+
+    let version = req.version;
+    let mut headers = Headers::new();
+    headers.set(Date(HttpDate(time::at_utc(Timespec::new(0, 0)))));
+
+    if let Some(status) = handler.on_head(&req, &mut headers) {
+        {
+            let mut res = Response::new(&mut out, &mut headers);
+            res.version = version;
+            *res.status_mut() = status;
+            try!(res.send(b""));
+        }
+        return Ok(sort_fields(out));
+    }
+
+    if version == Http11 && req.headers.get() == Some(&Expect::Continue) {
+        let status = handler.check_continue((&req.method, &req.uri, &req.headers));
+        try!(write!(&mut out, "{} {}\r\n\r\n", Http11, status));
+        if status != StatusCode::Continue {
+            return Ok(sort_fields(out));
+        }
+    }
+
+    {
+        let mut res = Response::new(&mut out, &mut headers);
+        res.version = version;
+        handler.handle(req, res);
+    }
+    Ok(sort_fields(out))
+}
+
+// Sorts the header fields of the interim responses and the final response
+// in `out` by name, as `Headers` writes them in no particular order.
+fn sort_fields(out: Vec<u8>) -> Vec<u8> {
+    let mut sorted = Vec::with_capacity(out.len());
+    let mut rest = &out[..];
+    while let Some(end) = rest.windows(4).position(|w| w == b"\r\n\r\n") {
+        // each line of the head ends with CRLF
+        let mut lines = rest[..end + 2].split(|&b| b == b'\n')
+            .filter_map(|line| line.split_last().map(|(_, line)| line))
+            .collect::<Vec<_>>();
+        let interim = lines[0].starts_with(b"HTTP/1.1 1");
+        lines[1..].sort_by_key(|line| {
+            let name = line.split(|&b| b == b':').next().unwrap_or(line);
+            name.to_ascii_lowercase()
+        });
+        for line in lines {
+            sorted.extend_from_slice(line);
+            sorted.extend_from_slice(b"\r\n");
+        }
+        sorted.extend_from_slice(b"\r\n");
+        rest = &rest[end + 4..];
+        if !interim {
+            break;
+        }
+    }
+    sorted.extend_from_slice(rest);
+    sorted
+}
+
+// The stream a rendered request is read from. What is written to it is
+// dropped, since responses are written to the output directly.
+struct Input(Cursor<Vec<u8>>);
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for Input {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl NetworkStream for Input {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        Ok(REMOTE_ADDR.parse().unwrap())
+    }
+
+    #[cfg(feature = "timeouts")]
+    fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(feature = "timeouts")]
+    fn set_write_timeout(&self, _: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use header::{Headers, Server};
+    use method::Method;
+    use server::{Handler, Request, Response};
+    use status::StatusCode;
+    use uri::RequestUri;
+
+    use super::render;
+
+    struct Echo;
+
+    impl Handler for Echo {
+        fn handle(&self, mut req: Request, res: Response) {
+            let mut body = String::new();
+            req.read_to_string(&mut body).unwrap();
+            res.send(format!("{} from {}: {}", req.method, req.remote_addr, body)
+                     .as_bytes()).unwrap();
+        }
+
+        fn check_continue(&self, (method, _, _): (&Method, &RequestUri, &Headers))
+                -> StatusCode {
+            match *method {
+                Method::Put => StatusCode::MethodNotAllowed,
+                _ => StatusCode::Continue
+            }
+        }
+
+        fn on_head(&self, req: &Request, res: &mut Headers) -> Option<StatusCode> {
+            res.set(Server("echo".to_owned()));
+            match req.method {
+                Method::Delete => Some(StatusCode::Forbidden),
+                _ => None
+            }
+        }
+    }
+
+    fn render_str(raw: &[u8]) -> String {
+        String::from_utf8(render(&Echo, raw).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(render_str(b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nbody"),
+                   "HTTP/1.1 200 OK\r\n\
+                    Content-Length: 31\r\n\
+                    Date: Thu, 01 Jan 1970 00:00:00 GMT\r\n\
+                    Server: echo\r\n\
+                    \r\n\
+                    POST from 127.0.0.1:40000: body");
+
+        assert_eq!(render_str(b"DELETE / HTTP/1.0\r\n\r\n"),
+                   "HTTP/1.0 403 Forbidden\r\n\
+                    Content-Length: 0\r\n\
+                    Date: Thu, 01 Jan 1970 00:00:00 GMT\r\n\
+                    Server: echo\r\n\
+                    \r\n");
+
+        let out = render_str(b"POST / HTTP/1.1\r\nExpect: 100-continue\r\n\
+                               Content-Length: 2\r\n\r\nok");
+        assert!(out.starts_with("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n"), "{:?}", out);
+        assert!(out.ends_with("POST from 127.0.0.1:40000: ok"));
+        assert_eq!(render_str(b"PUT / HTTP/1.1\r\nExpect: 100-continue\r\n\
+                                Content-Length: 2\r\n\r\nok"),
+                   "HTTP/1.1 405 Method Not Allowed\r\n\r\n");
+
+        assert!(render(&Echo, b"not http\r\n\r\n").is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! Accepting `CONNECT` tunnels, for forward proxies.
+//!
+//! A `CONNECT` request asks for a tunnel to the host and port of its
+//! `RequestUri::Authority` target. Once connected there, a handler claims
+//! the connection of the request with `accept`, which answers it with a
+//! `2xx` and returns a `Tunnel`: what the client sends is read from it,
+//! including bytes sent before the answer, and what is written to it goes
+//! to the client. `Tunnel::splice` relays it to and from a `TcpStream`.
+//! The connection is closed once the handler returns.
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use hyper::method::Method;
+//! use hyper::server::{Request, Response};
+//! use hyper::server::tunnel;
+//! use hyper::status::StatusCode;
+//! use hyper::uri::RequestUri;
+//!
+//! fn proxy(req: Request, mut res: Response) {
+//!     let upstream = match (&req.method, &req.uri) {
+//!         (&Method::Connect, &RequestUri::Authority(ref authority)) => {
+//!             TcpStream::connect(&authority[..])
+//!         },
+//!         _ => {
+//!             *res.status_mut() = StatusCode::MethodNotAllowed;
+//!             return;
+//!         }
+//!     };
+//!     match upstream {
+//!         Ok(upstream) => {
+//!             let tunnel = tunnel::accept(req, res).unwrap();
+//!             let _ = tunnel.splice(upstream);
+//!         },
+//!         Err(_) => *res.status_mut() = StatusCode::BadGateway,
+//!     }
+//! }
+//! ```
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::thread;
+
+use buffer::BufReader;
+use header::{ContentLength, TransferEncoding};
+use method::Method;
+use net::{Fresh, HttpStream, NetworkStream, Streaming};
+use server::{Request, Response};
+
+/// Answers the `CONNECT` request `req` with `res`, and returns the tunnel
+/// its connection becomes.
+///
+/// The status of `res` must be a `2xx`, `200 OK` unless another was set.
+/// Its headers are sent, except for `Content-Length` and
+/// `Transfer-Encoding`, since the answer has no body.
+pub fn accept<'a, 'b, 'c>(req: Request<'a, 'b>, mut res: Response<'c, Fresh>)
+        -> io::Result<Tunnel<'a, 'b, 'c>> {
+    if req.method != Method::Connect {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "only a CONNECT request is tunneled"));
+    }
+    if !res.status().is_success() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "a tunnel is accepted with a 2xx status"));
+    }
+    {
+        let headers = res.headers_mut();
+        headers.remove::<ContentLength>();
+        headers.remove::<TransferEncoding>();
+    }
+    let head = format!("{} {}\r\n{}\r\n", res.version, res.status(), res.headers());
+    // without framing headers, the rest of the connection is the body
+    let mut res = try!(res.start_raw(head.as_bytes()));
+    try!(res.flush());
+    let (_, _, _, _, _, body) = req.deconstruct();
+    Ok(Tunnel {
+        client: body.into_inner(),
+        res: res,
+    })
+}
+
+/// The connection of an accepted `CONNECT` request.
+pub struct Tunnel<'a, 'b: 'a, 'c> {
+    client: &'a mut BufReader<&'b mut NetworkStream>,
+    res: Response<'c, Streaming>,
+}
+
+impl<'a, 'b, 'c> Tunnel<'a, 'b, 'c> {
+    /// Relays the tunnel to and from `upstream`, until each side has ended
+    /// what it sends, and returns how many bytes went each way, from the
+    /// client first.
+    ///
+    /// Bytes from `upstream` are written to the client on another thread,
+    /// so only a tunnel over a plain `HttpStream` can be spliced.
+    pub fn splice(mut self, upstream: TcpStream) -> io::Result<(u64, u64)> {
+        let client = match self.client.get_ref().downcast_ref::<HttpStream>() {
+            Some(stream) => try!(stream.0.try_clone()),
+            None => return Err(io::Error::new(io::ErrorKind::Other,
+                                              "only a tunnel over TCP can be spliced"))
+        };
+        try!(self.res.flush());
+        let mut from_upstream = try!(upstream.try_clone());
+        let down = thread::spawn(move || {
+            let mut client = client;
+            let copied = io::copy(&mut from_upstream, &mut client);
+            let _ = client.shutdown(Shutdown::Write);
+            copied
+        });
+
+        let mut to_upstream = upstream;
+        let up = io::copy(&mut self.client, &mut to_upstream);
+        let _ = to_upstream.shutdown(Shutdown::Write);
+        let down = match down.join() {
+            Ok(copied) => copied,
+            Err(_) => Err(io::Error::new(io::ErrorKind::Other, "splicing thread panicked"))
+        };
+        Ok((try!(up), try!(down)))
+    }
+}
+
+impl<'a, 'b, 'c> Read for Tunnel<'a, 'b, 'c> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.client.read(buf)
+    }
+}
+
+impl<'a, 'b, 'c> Write for Tunnel<'a, 'b, 'c> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.res.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.res.flush()
+    }
+}
+
+impl<'a, 'b, 'c> fmt::Debug for Tunnel<'a, 'b, 'c> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Tunnel {{ buffered: {} }}", self.client.get_buf().len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use method::Method;
+    use mock::MockStream;
+    use server::{Request, Response, Server, Worker};
+    use status::StatusCode;
+    use uri::RequestUri;
+
+    use super::accept;
+
+    #[test]
+    fn test_accept() {
+        fn handle(req: Request, res: Response) {
+            assert_eq!(req.method, Method::Connect);
+            assert_eq!(req.uri, RequestUri::Authority("example.domain:443".to_owned()));
+            let mut tunnel = accept(req, res).unwrap();
+            let mut early = [0; 5];
+            tunnel.read_exact(&mut early).unwrap();
+            assert_eq!(&early, b"hello");
+            tunnel.write_all(b"world").unwrap();
+        }
+
+        let mut mock = MockStream::with_input(b"\
+            CONNECT example.domain:443 HTTP/1.1\r\n\
+            Host: example.domain:443\r\n\
+            \r\n\
+            hello\
+        ");
+        Worker::new(handle, Default::default()).handle_connection(&mut mock);
+        let raw = String::from_utf8(mock.write).unwrap();
+        assert!(raw.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", raw);
+        assert!(!raw.contains("Content-Length") && !raw.contains("Transfer-Encoding"), "{:?}", raw);
+        assert!(raw.ends_with("\r\n\r\nworld"), "{:?}", raw);
+        assert!(mock.is_closed);
+
+        fn refuse(req: Request, mut res: Response) {
+            *res.status_mut() = StatusCode::Forbidden;
+            assert!(accept(req, res).is_err());
+        }
+        let mut mock = MockStream::with_input(b"CONNECT example.domain:443 HTTP/1.1\r\n\r\n");
+        Worker::new(refuse, Default::default()).handle_connection(&mut mock);
+    }
+
+    #[test]
+    fn test_splice() {
+        let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = upstream.local_addr().unwrap();
+        let echo = thread::spawn(move || {
+            let (mut stream, _) = upstream.accept().unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).unwrap();
+            stream.write_all(&received).unwrap();
+        });
+
+        let mut listening = Server::http("127.0.0.1:0").unwrap()
+            .handle(|req: Request, res: Response| {
+                let upstream = match req.uri {
+                    RequestUri::Authority(ref authority) => {
+                        TcpStream::connect(&authority[..]).unwrap()
+                    },
+                    _ => panic!("unexpected target {:?}", req.uri)
+                };
+                let (up, down) = accept(req, res).unwrap().splice(upstream).unwrap();
+                assert_eq!((up, down), (10, 10));
+            }).unwrap();
+
+        let mut client = TcpStream::connect(listening.socket).unwrap();
+        write!(client, "CONNECT {} HTTP/1.1\r\n\r\nearly", target).unwrap();
+        let mut head = Vec::new();
+        let mut byte = [0];
+        while !head.ends_with(b"\r\n\r\n") {
+            client.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        assert!(head.starts_with(b"HTTP/1.1 200 OK\r\n"));
+        client.write_all(b" late").unwrap();
+        client.shutdown(::std::net::Shutdown::Write).unwrap();
+        let mut echoed = String::new();
+        client.read_to_string(&mut echoed).unwrap();
+        assert_eq!(echoed, "early late");
+
+        echo.join().unwrap();
+        listening.close().unwrap();
+    }
+}
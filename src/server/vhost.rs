@@ -0,0 +1,245 @@
+//! Dispatch to handlers by the host a request is for.
+//!
+//! A `VHost` serves several domains from one server, passing each request
+//! to the handler added for its host. The host is taken from an absolute
+//! request-target if there is one, and from the `Host` header otherwise.
+//!
+//! A pattern is either a host name, matched without regard to case, or
+//! `*.` and a domain, which matches any name below that domain, but not the
+//! domain itself. Exact names are tried first, then wildcards in the order
+//! they were added. Requests for no known host go to the default handler,
+//! or get a `404 Not Found` if there isn't one.
+//!
+//! `on_raw_head` is passed on to the handler for the host found in the raw
+//! request-target and `Host` field, the same one the request goes to, so a
+//! host's handler can refuse requests before they are parsed.
+//!
+//! ```no_run
+//! use hyper::server::{Server, Request, Response};
+//! use hyper::server::vhost::VHost;
+//!
+//! fn api(_: Request, res: Response) {
+//!     res.send(b"api").unwrap();
+//! }
+//!
+//! fn site(_: Request, res: Response) {
+//!     res.send(b"site").unwrap();
+//! }
+//!
+//! let mut vhost = VHost::new();
+//! vhost.add("api.example.domain", api);
+//! vhost.add("*.example.domain", site);
+//! vhost.set_default(site);
+//! Server::http("0.0.0.0:0").unwrap().handle(vhost).unwrap();
+//! ```
+use std::ascii::AsciiExt;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str;
+
+use header::{Headers, Host};
+use method::Method;
+use net::Fresh;
+use server::{Handler, RawHead, Request, Response};
+use status::StatusCode;
+use uri::RequestUri;
+
+/// A `Handler` that passes each request to the handler for its host.
+pub struct VHost {
+    hosts: Vec<(String, Box<Handler>)>,
+    default: Option<Box<Handler>>,
+}
+
+impl VHost {
+    /// Creates a `VHost` without any hosts.
+    pub fn new() -> VHost {
+        VHost {
+            hosts: Vec::new(),
+            default: None,
+        }
+    }
+
+    /// Adds the handler for requests to hosts matching `pattern`.
+    pub fn add<H: Handler + 'static>(&mut self, pattern: &str, handler: H) {
+        let pattern = pattern.trim_right_matches('.').to_ascii_lowercase();
+        self.hosts.push((pattern, Box::new(handler)));
+    }
+
+    /// Sets the handler for requests to hosts that match no pattern, and
+    /// those without a host.
+    pub fn set_default<H: Handler + 'static>(&mut self, handler: H) {
+        self.default = Some(Box::new(handler));
+    }
+
+    /// Get the handler for a host name, if there is one.
+    pub fn handler_for(&self, host: Option<&str>) -> Option<&Handler> {
+        let host = host.map(|host| host.trim_right_matches('.').to_ascii_lowercase());
+        let found = host.and_then(|host| {
+            self.hosts.iter().find(|&&(ref pattern, _)| *pattern == host).or_else(|| {
+                self.hosts.iter().find(|&&(ref pattern, _)| {
+                    pattern.starts_with("*.") && host.ends_with(&pattern[1..])
+                })
+            })
+        });
+        match found {
+            Some(&(_, ref handler)) => Some(&**handler),
+            None => self.default.as_ref().map(|handler| &**handler)
+        }
+    }
+
+    fn route(&self, uri: &RequestUri, headers: &Headers) -> Option<&Handler> {
+        let host = match *uri {
+            RequestUri::AbsoluteUri(ref url) => url.serialize_host(),
+            _ => headers.get::<Host>().map(|host| host.hostname.clone())
+        };
+        self.handler_for(host.as_ref().map(|host| &host[..]))
+    }
+
+    // Like `route`, with the host taken from a head that isn't parsed yet.
+    fn route_raw(&self, head: &RawHead) -> Option<&Handler> {
+        let uri = head.start_line().split(|&b| b == b' ').nth(1)
+            .and_then(|target| str::from_utf8(target).ok())
+            .and_then(|target| target.parse::<RequestUri>().ok())
+            .unwrap_or(RequestUri::Star);
+        let mut headers = Headers::new();
+        let host = head.headers().find(|&(name, _)| name.eq_ignore_ascii_case(b"Host"));
+        if let Some((_, host)) = host {
+            headers.set_raw("Host", vec![host.to_vec()]);
+        }
+        self.route(&uri, &headers)
+    }
+}
+
+impl fmt::Debug for VHost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hosts = self.hosts.iter().map(|&(ref pattern, _)| pattern).collect::<Vec<_>>();
+        f.debug_struct("VHost")
+            .field("hosts", &hosts)
+            .field("default", &self.default.is_some())
+            .finish()
+    }
+}
+
+impl Handler for VHost {
+    fn handle<'a, 'k>(&'a self, req: Request<'a, 'k>, mut res: Response<'a, Fresh>) {
+        match self.route(&req.uri, &req.headers) {
+            Some(handler) => handler.handle(req, res),
+            None => {
+                debug!("no handler for the host of {}", req.uri);
+                *res.status_mut() = StatusCode::NotFound;
+                if let Err(e) = res.send(b"") {
+                    debug!("error sending not found: {:?}", e);
+                }
+            }
+        }
+    }
+
+    fn check_continue(&self, head: (&Method, &RequestUri, &Headers)) -> StatusCode {
+        match self.route(head.1, head.2) {
+            Some(handler) => handler.check_continue(head),
+            None => StatusCode::NotFound
+        }
+    }
+
+    fn on_raw_head(&self, head: &RawHead, addr: SocketAddr) -> bool {
+        // without a handler, the request gets a 404 Not Found
+        self.route_raw(head).map_or(true, |handler| handler.on_raw_head(head, addr))
+    }
+
+    fn on_head(&self, req: &Request, res: &mut Headers) -> Option<StatusCode> {
+        self.route(&req.uri, &req.headers).and_then(|handler| handler.on_head(req, res))
+    }
+
+    fn on_connection_start(&self) {
+        for &(_, ref handler) in &self.hosts {
+            handler.on_connection_start();
+        }
+        if let Some(ref handler) = self.default {
+            handler.on_connection_start();
+        }
+    }
+
+    fn on_connection_end(&self) {
+        for &(_, ref handler) in &self.hosts {
+            handler.on_connection_end();
+        }
+        if let Some(ref handler) = self.default {
+            handler.on_connection_end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use net::Fresh;
+    use server::{Handler, RawHead, Request, Response};
+    use server::testing::render_str;
+
+    use super::VHost;
+
+    fn api(_: Request, res: Response) {
+        res.send(b"api").unwrap();
+    }
+
+    fn site(_: Request, res: Response) {
+        res.send(b"site").unwrap();
+    }
+
+    fn fallback(_: Request, res: Response) {
+        res.send(b"fallback").unwrap();
+    }
+
+    #[test]
+    fn test_vhost() {
+        let mut vhost = VHost::new();
+        vhost.add("*.example.domain", site);
+        vhost.add("API.example.domain", api);
+
+        let out = render_str(&vhost, b"GET / HTTP/1.1\r\nHost: api.example.domain:8080\r\n\r\n");
+        assert!(out.ends_with("api"));
+        let out = render_str(&vhost, b"GET / HTTP/1.1\r\nHost: www.Example.domain.\r\n\r\n");
+        assert!(out.ends_with("site"));
+        let out = render_str(&vhost, b"GET http://api.example.domain/ HTTP/1.1\r\n\
+                                       Host: other.domain\r\n\r\n");
+        assert!(out.ends_with("api"));
+
+        // the wildcard is only for names below the domain
+        let out = render_str(&vhost, b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let out = render_str(&vhost, b"GET / HTTP/1.0\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.0 404 Not Found\r\n"));
+
+        vhost.set_default(fallback);
+        let out = render_str(&vhost, b"GET / HTTP/1.1\r\nHost: example.domain\r\n\r\n");
+        assert!(out.ends_with("fallback"));
+        let out = render_str(&vhost, b"GET / HTTP/1.0\r\n\r\n");
+        assert!(out.ends_with("fallback"));
+    }
+
+    struct Refuse;
+
+    impl Handler for Refuse {
+        fn handle<'a, 'k>(&'a self, _: Request<'a, 'k>, res: Response<'a, Fresh>) {
+            res.send(b"refused").unwrap();
+        }
+
+        fn on_raw_head(&self, _: &RawHead, _: SocketAddr) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_vhost_raw_head() {
+        let mut vhost = VHost::new();
+        vhost.add("api.example.domain", Refuse);
+        vhost.set_default(site);
+
+        assert_eq!(render_str(&vhost, b"GET / HTTP/1.1\r\nhost: API.example.domain\r\n\r\n"), "");
+        assert_eq!(render_str(&vhost, b"GET http://api.example.domain/ HTTP/1.1\r\n\
+                                        Host: other.domain\r\n\r\n"), "");
+        let out = render_str(&vhost, b"GET / HTTP/1.1\r\nHost: www.example.domain\r\n\r\n");
+        assert!(out.ends_with("site"));
+    }
+}
@@ -0,0 +1,242 @@
+//! Canonical requests, for signing schemes such as AWS Signature Version 4.
+//!
+//! Schemes that sign a request with an HMAC usually sign a *canonical*
+//! form of it, so that harmless differences in encoding or header order
+//! don't break the signature. A `CanonicalRequest` is that form:
+//!
+//! ```text
+//! GET
+//! /photos/2024
+//! list-type=2&prefix=a%20b
+//! host:example.domain
+//! x-amz-date:20240101T000000Z
+//!
+//! host;x-amz-date
+//! e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855
+//! ```
+//!
+//! hyper doesn't hash bodies, so the payload hash is passed in, such as the
+//! hex SHA-256 of the body, or `UNSIGNED-PAYLOAD`.
+//!
+//! ```
+//! use hyper::header::Headers;
+//! use hyper::method::Method;
+//! use hyper::signatures::canonical::CanonicalRequest;
+//! use hyper::Url;
+//!
+//! let url = Url::parse("http://example.domain/a b?z=1&a=2").unwrap();
+//! let mut headers = Headers::new();
+//! headers.set_raw("Host", vec![b"example.domain".to_vec()]);
+//! let canonical = CanonicalRequest::new(&Method::Get, &url, &headers, &["host"],
+//!                                       "UNSIGNED-PAYLOAD", Default::default());
+//! assert_eq!(canonical.path, "/a%2520b");
+//! assert_eq!(canonical.query, "a=2&z=1");
+//! assert_eq!(canonical.signed_headers(), "host");
+//! ```
+use std::fmt;
+
+use url::Url;
+use url::percent_encoding::percent_decode;
+
+use client::Request;
+use header::Headers;
+use method::Method;
+use net::Fresh;
+
+/// Config options for building a `CanonicalRequest`.
+///
+/// The defaults match most AWS services. Amazon S3 wants `double_encode`
+/// switched off.
+///
+/// `.` and `..` path segments are always removed, since `Url` does so
+/// already.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Encode the path segments a second time, after decoding and encoding
+    /// them once.
+    pub double_encode: bool,
+}
+
+impl Default for Config {
+    #[inline]
+    fn default() -> Config {
+        Config {
+            double_encode: true,
+        }
+    }
+}
+
+/// The canonical form of a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanonicalRequest {
+    /// The method, such as `GET`.
+    pub method: String,
+    /// The encoded path, always starting with `/`.
+    pub path: String,
+    /// The encoded query pairs, sorted by name and then value.
+    pub query: String,
+    /// The lowercase names and trimmed values of the signed headers, sorted
+    /// by name. Repeated headers are joined with commas.
+    pub headers: Vec<(String, String)>,
+    /// The hash of the payload, as passed in.
+    pub payload_hash: String,
+}
+
+impl CanonicalRequest {
+    /// Builds the canonical form from the parts of a request.
+    ///
+    /// Only the headers named in `signed` are included. Signed headers that
+    /// aren't in `headers` are left out.
+    pub fn new(method: &Method, url: &Url, headers: &Headers, signed: &[&str],
+               payload_hash: &str, config: Config) -> CanonicalRequest {
+        let path = url.serialize_path().unwrap_or_else(|| "/".to_owned());
+        CanonicalRequest {
+            method: method.to_string(),
+            path: canonical_path(&path, &config),
+            query: canonical_query(url.query.as_ref().map_or("", |q| &q[..])),
+            headers: canonical_headers(headers, signed),
+            payload_hash: payload_hash.to_owned(),
+        }
+    }
+
+    /// Builds the canonical form of an outgoing request.
+    pub fn from_request(req: &Request<Fresh>, signed: &[&str], payload_hash: &str,
+                        config: Config) -> CanonicalRequest {
+        CanonicalRequest::new(&req.method(), &req.url, req.headers(), signed,
+                              payload_hash, config)
+    }
+
+    /// Get the names of the signed headers, joined with `;`.
+    pub fn signed_headers(&self) -> String {
+        self.headers.iter().map(|&(ref name, _)| &name[..]).collect::<Vec<_>>().join(";")
+    }
+}
+
+impl fmt::Display for CanonicalRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}\n{}\n{}\n", self.method, self.path, self.query));
+        for &(ref name, ref value) in &self.headers {
+            try!(write!(f, "{}:{}\n", name, value));
+        }
+        write!(f, "\n{}\n{}", self.signed_headers(), self.payload_hash)
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986,
+/// and `/` if `keep_slash` is set.
+pub fn uri_encode(input: &[u8], keep_slash: bool) -> String {
+    let mut out = String::with_capacity(input.len());
+    for &b in input {
+        match b {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            },
+            b'/' if keep_slash => out.push('/'),
+            b => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn canonical_path(path: &str, config: &Config) -> String {
+    let mut out = String::from("/");
+    for (i, segment) in path.trim_left_matches('/').split('/').enumerate() {
+        if i != 0 {
+            out.push('/');
+        }
+        let mut encoded = uri_encode(&percent_decode(segment.as_bytes()), false);
+        if config.double_encode {
+            encoded = uri_encode(encoded.as_bytes(), false);
+        }
+        out.push_str(&encoded);
+    }
+    out
+}
+
+fn canonical_query(query: &str) -> String {
+    let mut pairs = query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let name = parts.next().unwrap_or("");
+            let value = parts.next().unwrap_or("");
+            (uri_encode(&percent_decode(name.as_bytes()), false),
+             uri_encode(&percent_decode(value.as_bytes()), false))
+        })
+        .collect::<Vec<_>>();
+    pairs.sort();
+    pairs.iter().map(|&(ref name, ref value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>().join("&")
+}
+
+fn canonical_headers(headers: &Headers, signed: &[&str]) -> Vec<(String, String)> {
+    let mut canonical = signed.iter().filter_map(|name| {
+        headers.get_raw(name).map(|lines| {
+            let value = lines.iter()
+                .map(|line| {
+                    // trim, and collapse runs of spaces into one
+                    String::from_utf8_lossy(line).split_whitespace()
+                        .collect::<Vec<_>>().join(" ")
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            (name.to_lowercase(), value)
+        })
+    }).collect::<Vec<_>>();
+    canonical.sort();
+    canonical.dedup_by(|a, b| a.0 == b.0);
+    canonical
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Headers;
+    use method::Method;
+    use url::Url;
+
+    use super::{CanonicalRequest, Config, uri_encode};
+
+    fn canonical(url: &str, config: Config) -> CanonicalRequest {
+        let url = Url::parse(url).unwrap();
+        let mut headers = Headers::new();
+        headers.set_raw("Host", vec![b"example.domain".to_vec()]);
+        headers.set_raw("X-Amz-Date", vec![b"20240101T000000Z".to_vec()]);
+        headers.set_raw("X-Multi", vec![b"  a   b ".to_vec(), b"c".to_vec()]);
+        headers.set_raw("X-Unsigned", vec![b"x".to_vec()]);
+        CanonicalRequest::new(&Method::Get, &url, &headers,
+                              &["X-Amz-Date", "host", "x-multi", "x-missing"],
+                              "UNSIGNED-PAYLOAD", config)
+    }
+
+    #[test]
+    fn test_canonical_request() {
+        let c = canonical("http://example.domain/photos/./2024/../a?b=2&a=%7e&a=1&c",
+                          Default::default());
+        assert_eq!(c.to_string(), "\
+            GET\n\
+            /photos/a\n\
+            a=1&a=~&b=2&c=\n\
+            host:example.domain\n\
+            x-amz-date:20240101T000000Z\n\
+            x-multi:a b,c\n\
+            \n\
+            host;x-amz-date;x-multi\n\
+            UNSIGNED-PAYLOAD");
+    }
+
+    #[test]
+    fn test_canonical_path_config() {
+        let url = "http://example.domain/a%20b/c%2Fd";
+        assert_eq!(canonical(url, Default::default()).path, "/a%2520b/c%252Fd");
+        let s3 = Config { double_encode: false };
+        assert_eq!(canonical(url, s3).path, "/a%20b/c%2Fd");
+        assert_eq!(canonical("http://example.domain", Default::default()).path, "/");
+    }
+
+    #[test]
+    fn test_uri_encode() {
+        assert_eq!(uri_encode(b"a-z_A.Z~0/9 +*", false), "a-z_A.Z~0%2F9%20%2B%2A");
+        assert_eq!(uri_encode(b"a/b", true), "a/b");
+        assert_eq!(uri_encode("\u{e9}".as_bytes(), false), "%C3%A9");
+    }
+}
@@ -0,0 +1,522 @@
+//! HTTP Message Signatures, from RFC 9421.
+//!
+//! A signature covers a chosen set of *components* of a request: derived
+//! ones such as `@method` and `@path`, and header fields by their lowercase
+//! name. `sign` adds `Signature-Input` and `Signature` headers to an
+//! outgoing request, and `verify` checks them on an incoming one.
+//!
+//! hyper doesn't do any cryptography itself. The signature base, the exact
+//! bytes that are signed, is passed to a callback, which signs or verifies
+//! it with whichever key and algorithm it picks, such as by the `keyid`.
+//!
+//! ```no_run
+//! use hyper::client::Request;
+//! use hyper::method::Method;
+//! use hyper::signatures::{self, SignatureParams};
+//! # fn hmac_sha256(_key: &[u8], _data: &[u8]) -> Vec<u8> { vec![] }
+//!
+//! let url = "http://example.domain/foo".parse().unwrap();
+//! let mut req = Request::new(Method::Post, url).unwrap();
+//! let mut params = SignatureParams::new(&["@method", "@authority", "@path", "content-type"]);
+//! params.key_id = Some("my-key".to_owned());
+//! signatures::sign(&mut req, "sig1", params, |_params, base| {
+//!     Ok(hmac_sha256(b"secret", base))
+//! }).unwrap();
+//! ```
+//!
+//! Only derived components of requests, and header fields without
+//! parameters such as `;sf` or `;key`, are supported.
+//!
+//! Schemes that sign a canonical form of the request instead, such as AWS
+//! Signature Version 4, can build it with the `canonical` module.
+use std::error::Error as StdError;
+use std::fmt::{self, Write};
+
+use time;
+
+use client::Request as ClientRequest;
+use header::{Header, HeaderFormat, Headers, Host};
+use header::sfv::{BareItem, Dictionary, InnerList, Item, Member};
+use method::Method;
+use net::Fresh;
+use server::Request as ServerRequest;
+use uri::RequestUri;
+
+pub mod canonical;
+
+/// The `Signature-Input` header, the parameters of each signature by label.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignatureInput(pub Dictionary);
+
+/// The `Signature` header, the signature bytes by label.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature(pub Dictionary);
+
+impl Header for SignatureInput {
+    fn header_name() -> &'static str {
+        "Signature-Input"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> ::Result<SignatureInput> {
+        Dictionary::from_raw(raw).map(SignatureInput)
+    }
+}
+
+impl HeaderFormat for SignatureInput {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl Header for Signature {
+    fn header_name() -> &'static str {
+        "Signature"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> ::Result<Signature> {
+        Dictionary::from_raw(raw).map(Signature)
+    }
+}
+
+impl HeaderFormat for Signature {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// What a signature covers, and its metadata.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct SignatureParams {
+    /// The covered components, such as `@method` or `content-type`.
+    pub components: Vec<String>,
+    /// When the signature was created, in seconds since the epoch.
+    pub created: Option<i64>,
+    /// When the signature expires, in seconds since the epoch.
+    pub expires: Option<i64>,
+    /// A random value, to detect replays.
+    pub nonce: Option<String>,
+    /// The algorithm used.
+    pub alg: Option<String>,
+    /// The key used.
+    pub key_id: Option<String>,
+    /// An application specific tag.
+    pub tag: Option<String>,
+}
+
+impl SignatureParams {
+    /// Creates params covering `components`, created now.
+    pub fn new(components: &[&str]) -> SignatureParams {
+        SignatureParams {
+            components: components.iter().map(|c| c.to_lowercase()).collect(),
+            created: Some(time::now_utc().to_timespec().sec),
+            ..Default::default()
+        }
+    }
+
+    fn to_inner_list(&self) -> InnerList {
+        let mut params = Vec::new();
+        if let Some(created) = self.created {
+            params.push(("created".to_owned(), BareItem::Integer(created)));
+        }
+        if let Some(expires) = self.expires {
+            params.push(("expires".to_owned(), BareItem::Integer(expires)));
+        }
+        let strings = [("nonce", &self.nonce), ("alg", &self.alg),
+                       ("keyid", &self.key_id), ("tag", &self.tag)];
+        for &(key, value) in &strings {
+            if let Some(ref value) = *value {
+                params.push((key.to_owned(), BareItem::String(value.clone())));
+            }
+        }
+        InnerList {
+            items: self.components.iter()
+                .map(|c| Item::new(BareItem::String(c.clone())))
+                .collect(),
+            params: params,
+        }
+    }
+
+    fn from_inner_list(list: &InnerList) -> Option<SignatureParams> {
+        let mut params = SignatureParams::default();
+        for item in &list.items {
+            match item.bare {
+                BareItem::String(ref c) if item.params.is_empty() => {
+                    params.components.push(c.clone())
+                },
+                _ => return None,
+            }
+        }
+        for &(ref key, ref value) in &list.params {
+            match (&key[..], value) {
+                ("created", &BareItem::Integer(n)) => params.created = Some(n),
+                ("expires", &BareItem::Integer(n)) => params.expires = Some(n),
+                ("nonce", &BareItem::String(ref s)) => params.nonce = Some(s.clone()),
+                ("alg", &BareItem::String(ref s)) => params.alg = Some(s.clone()),
+                ("keyid", &BareItem::String(ref s)) => params.key_id = Some(s.clone()),
+                ("tag", &BareItem::String(ref s)) => params.tag = Some(s.clone()),
+                ("created", _) | ("expires", _) | ("nonce", _) | ("alg", _) |
+                    ("keyid", _) | ("tag", _) => return None,
+                // unknown params are still part of the signature base
+                _ => (),
+            }
+        }
+        Some(params)
+    }
+}
+
+/// Why an incoming signature was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The signature, or its input, was not in the request.
+    Missing,
+    /// The signature headers could not be parsed, or cover a component
+    /// that isn't supported or isn't in the request.
+    Malformed,
+    /// The signature's `expires` time has passed.
+    Expired,
+    /// The callback rejected the signature.
+    Invalid,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl StdError for VerifyError {
+    fn description(&self) -> &str {
+        match *self {
+            VerifyError::Missing => "Signature is missing",
+            VerifyError::Malformed => "Signature is malformed",
+            VerifyError::Expired => "Signature has expired",
+            VerifyError::Invalid => "Signature is invalid",
+        }
+    }
+}
+
+/// The parts of a request that components are taken from.
+struct Target<'a> {
+    method: Method,
+    scheme: String,
+    authority: String,
+    path: String,
+    query: Option<String>,
+    headers: &'a Headers,
+}
+
+impl<'a> Target<'a> {
+    fn component(&self, name: &str) -> Option<String> {
+        match name {
+            "@method" => Some(self.method.to_string()),
+            "@target-uri" => Some(format!("{}://{}{}", self.scheme, self.authority,
+                                          self.request_target())),
+            "@authority" => Some(self.authority.clone()),
+            "@scheme" => Some(self.scheme.clone()),
+            "@request-target" => Some(self.request_target()),
+            "@path" => Some(self.path.clone()),
+            "@query" => Some(format!("?{}", self.query.as_ref().map_or("", |q| &q[..]))),
+            name if name.starts_with('@') => None,
+            name => self.headers.get_raw(name).map(|lines| {
+                lines.iter()
+                    .map(|line| String::from_utf8_lossy(line).trim().to_owned())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }),
+        }
+    }
+
+    fn request_target(&self) -> String {
+        match self.query {
+            Some(ref query) => format!("{}?{}", self.path, query),
+            None => self.path.clone(),
+        }
+    }
+
+    fn base(&self, list: &InnerList) -> Option<Vec<u8>> {
+        let mut base = String::new();
+        for item in &list.items {
+            let name = match item.bare {
+                BareItem::String(ref name) if item.params.is_empty() => name,
+                _ => return None,
+            };
+            let value = match self.component(name) {
+                Some(value) => value,
+                None => return None,
+            };
+            if write!(base, "\"{}\": {}\n", name, value).is_err() {
+                return None;
+            }
+        }
+        if write!(base, "\"@signature-params\": {}", list).is_err() {
+            return None;
+        }
+        Some(base.into_bytes())
+    }
+}
+
+fn authority(host: &str, port: Option<u16>, scheme: &str) -> String {
+    let default = match scheme {
+        "https" => Some(443),
+        "http" => Some(80),
+        _ => None,
+    };
+    match port {
+        Some(port) if Some(port) != default => format!("{}:{}", host, port),
+        _ => host.to_owned(),
+    }.to_lowercase()
+}
+
+/// Signs an outgoing request, adding the signature under `label`.
+///
+/// `sign` is called with the params and the signature base, and returns
+/// the signature. Any signatures already on the request are kept. Returns
+/// `Error::Header` if a covered component isn't in the request.
+pub fn sign<F>(req: &mut ClientRequest<Fresh>, label: &str, params: SignatureParams, sign: F)
+    -> ::Result<()>
+where F: FnOnce(&SignatureParams, &[u8]) -> ::Result<Vec<u8>> {
+    let list = params.to_inner_list();
+    let base = {
+        let url = &req.url;
+        let target = Target {
+            method: req.method(),
+            scheme: url.scheme.clone(),
+            authority: authority(&url.serialize_host().unwrap_or_else(String::new),
+                                 url.port_or_default(), &url.scheme),
+            path: url.serialize_path().unwrap_or_else(|| "/".to_owned()),
+            query: url.query.clone(),
+            headers: req.headers(),
+        };
+        match target.base(&list) {
+            Some(base) => base,
+            None => return Err(::Error::Header),
+        }
+    };
+    let signature = try!(sign(&params, &base));
+
+    let label = label.to_owned();
+    let mut input = req.headers().get::<SignatureInput>().map_or(Vec::new(), |h| (h.0).0.clone());
+    let mut signatures = req.headers().get::<Signature>().map_or(Vec::new(), |h| (h.0).0.clone());
+    input.retain(|&(ref l, _)| *l != label);
+    signatures.retain(|&(ref l, _)| *l != label);
+    input.push((label.clone(), Member::InnerList(list)));
+    signatures.push((label, Member::Item(Item::new(BareItem::ByteSequence(signature)))));
+
+    let input = Dictionary(input);
+    // HeaderFormat may not fail, so catch anything unrepresentable, such
+    // as a `keyid` with a newline in it, here
+    if write!(String::new(), "{}", input).is_err() {
+        return Err(::Error::Header);
+    }
+    req.headers_mut().set(SignatureInput(input));
+    req.headers_mut().set(Signature(Dictionary(signatures)));
+    Ok(())
+}
+
+/// Verifies a signature on an incoming request.
+///
+/// With a `label`, only that signature is checked. Without one, the first
+/// signature that `verify` accepts is used. `verify` is called with the
+/// params, the signature base and the signature, and returns whether the
+/// signature is valid.
+///
+/// The server doesn't know whether a request came in over TLS, so the
+/// `scheme` to use for `@scheme` and `@target-uri` must be passed in.
+pub fn verify<F>(req: &ServerRequest, scheme: &str, label: Option<&str>, mut verify: F)
+    -> Result<SignatureParams, VerifyError>
+where F: FnMut(&SignatureParams, &[u8], &[u8]) -> bool {
+    let (input, signatures) = match (req.headers.get::<SignatureInput>(),
+                                     req.headers.get::<Signature>()) {
+        (Some(input), Some(signatures)) => (&(input.0).0, &signatures.0),
+        (None, None) => return Err(VerifyError::Missing),
+        _ => return Err(VerifyError::Malformed),
+    };
+
+    let (path, query) = match req.uri {
+        RequestUri::AbsolutePath(ref s) => match s.find('?') {
+            Some(i) => (s[..i].to_owned(), Some(s[i + 1..].to_owned())),
+            None => (s.clone(), None),
+        },
+        RequestUri::AbsoluteUri(ref url) => {
+            (url.serialize_path().unwrap_or_else(|| "/".to_owned()), url.query.clone())
+        },
+        _ => return Err(VerifyError::Malformed),
+    };
+    let authority = match req.uri {
+        RequestUri::AbsoluteUri(ref url) => {
+            let host = url.serialize_host().unwrap_or_else(String::new);
+            authority(&host, url.port_or_default(), scheme)
+        },
+        _ => match req.headers.get::<Host>() {
+            Some(host) => authority(&host.hostname, host.port, scheme),
+            None => return Err(VerifyError::Malformed),
+        },
+    };
+    let target = Target {
+        method: req.method.clone(),
+        scheme: scheme.to_lowercase(),
+        authority: authority,
+        path: path,
+        query: query,
+        headers: &req.headers,
+    };
+
+    let mut result = Err(VerifyError::Missing);
+    for &(ref l, ref member) in input {
+        if label.map_or(false, |label| label != l) {
+            continue;
+        }
+        result = check(&target, member, signatures.get(l), &mut verify);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+fn check<F>(target: &Target, input: &Member, signature: Option<&Member>, verify: &mut F)
+    -> Result<SignatureParams, VerifyError>
+where F: FnMut(&SignatureParams, &[u8], &[u8]) -> bool {
+    let list = match *input {
+        Member::InnerList(ref list) => list,
+        Member::Item(..) => return Err(VerifyError::Malformed),
+    };
+    let signature = match signature.and_then(Member::as_item).map(|item| &item.bare) {
+        Some(&BareItem::ByteSequence(ref bytes)) => bytes,
+        Some(..) => return Err(VerifyError::Malformed),
+        None => return Err(VerifyError::Missing),
+    };
+    let params = match SignatureParams::from_inner_list(list) {
+        Some(params) => params,
+        None => return Err(VerifyError::Malformed),
+    };
+    if let Some(expires) = params.expires {
+        if expires <= time::now_utc().to_timespec().sec {
+            return Err(VerifyError::Expired);
+        }
+    }
+    let base = match target.base(list) {
+        Some(base) => base,
+        None => return Err(VerifyError::Malformed),
+    };
+    if verify(&params, &base, signature) {
+        Ok(params)
+    } else {
+        Err(VerifyError::Invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::from_utf8;
+
+    use buffer::BufReader;
+    use client::Request as ClientRequest;
+    use header::ContentType;
+    use method::Method;
+    use mock::{MockStream, MockConnector};
+    use net::NetworkStream;
+    use server::Request as ServerRequest;
+    use url::Url;
+
+    use super::{sign, verify, SignatureParams, SignatureInput, VerifyError};
+
+    // a stand-in for real cryptography
+    fn checksum(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let sum = data.iter().chain(key).fold(0u32, |sum, &b| sum.wrapping_mul(31) ^ b as u32);
+        vec![(sum >> 24) as u8, (sum >> 16) as u8, (sum >> 8) as u8, sum as u8]
+    }
+
+    fn params() -> SignatureParams {
+        let mut params = SignatureParams::new(&["@method", "@authority", "@path", "@query",
+                                                "Content-Type"]);
+        params.created = Some(1618884473);
+        params.key_id = Some("test-key".to_owned());
+        params
+    }
+
+    fn signed_request(params: SignatureParams) -> (Vec<u8>, Vec<u8>) {
+        let url = Url::parse("http://Example.dom:8080/foo?a=b").unwrap();
+        let mut req = ClientRequest::with_connector(Method::Post, url, &mut MockConnector)
+            .unwrap();
+        req.headers_mut().set(ContentType::json());
+        let mut signed_base = Vec::new();
+        sign(&mut req, "sig1", params, |params, base| {
+            assert_eq!(params.key_id, Some("test-key".to_owned()));
+            signed_base = base.to_vec();
+            Ok(checksum(b"secret", base))
+        }).unwrap();
+
+        let raw = format!("POST /foo?a=b HTTP/1.1\r\n{}Content-Length: 0\r\n\r\n",
+                          req.headers());
+        (signed_base, raw.into_bytes())
+    }
+
+    fn verify_raw(raw: &[u8], key: &'static [u8]) -> Result<SignatureParams, VerifyError> {
+        let mut mock = MockStream::with_input(raw);
+        let mock: &mut NetworkStream = &mut mock;
+        let mut stream = BufReader::new(mock);
+        let req = ServerRequest::new(&mut stream, "127.0.0.1:80".parse().unwrap()).unwrap();
+        verify(&req, "http", Some("sig1"), |_params, base, signature| {
+            checksum(key, base) == signature
+        })
+    }
+
+    #[test]
+    fn test_signature_base() {
+        let (base, raw) = signed_request(params());
+        assert_eq!(from_utf8(&base).unwrap(), "\
+            \"@method\": POST\n\
+            \"@authority\": example.dom:8080\n\
+            \"@path\": /foo\n\
+            \"@query\": ?a=b\n\
+            \"content-type\": application/json; charset=utf-8\n\
+            \"@signature-params\": (\"@method\" \"@authority\" \"@path\" \"@query\" \
+            \"content-type\");created=1618884473;keyid=\"test-key\"");
+        let raw = from_utf8(&raw).unwrap();
+        assert!(raw.contains("Signature-Input: sig1=(\"@method\" \"@authority\" \"@path\" \
+                              \"@query\" \"content-type\");created=1618884473;\
+                              keyid=\"test-key\"\r\n"));
+        assert!(raw.contains("Signature: sig1=:"));
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let (_, raw) = signed_request(params());
+        let params = verify_raw(&raw, b"secret").unwrap();
+        assert_eq!(params.created, Some(1618884473));
+        assert_eq!(params.components[0], "@method");
+        assert_eq!(verify_raw(&raw, b"wrong key"), Err(VerifyError::Invalid));
+
+        // a covered header that changed
+        let raw = from_utf8(&raw).unwrap().replace("application/json", "text/plain");
+        assert_eq!(verify_raw(raw.as_bytes(), b"secret"), Err(VerifyError::Invalid));
+    }
+
+    #[test]
+    fn test_verify_errors() {
+        let raw = b"GET / HTTP/1.1\r\nHost: example.dom\r\n\r\n";
+        assert_eq!(verify_raw(raw, b"secret"), Err(VerifyError::Missing));
+
+        let mut expired = params();
+        expired.expires = Some(1618884480);
+        let (_, raw) = signed_request(expired);
+        assert_eq!(verify_raw(&raw, b"secret"), Err(VerifyError::Expired));
+
+        // a covered header that isn't in the request
+        let (_, raw) = signed_request(params());
+        let raw = from_utf8(&raw).unwrap()
+            .replace("\"content-type\");", "\"content-type\" \"x-missing\");");
+        assert_eq!(verify_raw(raw.as_bytes(), b"secret"), Err(VerifyError::Malformed));
+    }
+
+    #[test]
+    fn test_sign_missing_component() {
+        let url = Url::parse("http://example.dom/").unwrap();
+        let mut req = ClientRequest::with_connector(Method::Get, url, &mut MockConnector)
+            .unwrap();
+        let params = SignatureParams::new(&["content-type"]);
+        assert!(sign(&mut req, "sig1", params, |_, _| Ok(vec![])).is_err());
+        assert!(req.headers().get::<SignatureInput>().is_none());
+    }
+}
@@ -1,10 +1,12 @@
 //! HTTP RequestUris
 use std::fmt::{Display, self};
+use std::net::Ipv6Addr;
 use std::str::FromStr;
 use url::Url;
 use url::ParseError as UrlError;
 
 use Error;
+use method::Method;
 
 /// The Request-URI of a Request's StartLine.
 ///
@@ -63,6 +65,8 @@ impl FromStr for RequestUri {
             Ok(RequestUri::AbsolutePath(s.to_owned()))
         } else if bytes.contains(&b'/') {
             Ok(RequestUri::AbsoluteUri(try!(Url::parse(s))))
+        } else if is_zoned_ipv6_authority(s) {
+            Ok(RequestUri::Authority(s.to_owned()))
         } else {
             let mut temp = "http://".to_owned();
             temp.push_str(s);
@@ -73,6 +77,97 @@ impl FromStr for RequestUri {
     }
 }
 
+impl RequestUri {
+    /// Parses the request-target of a request with `method`, which decides
+    /// the forms it may take.
+    ///
+    /// As [RFC7230](https://tools.ietf.org/html/rfc7230#section-5.3) says,
+    /// only a `CONNECT` has the authority form, a host and port, which it
+    /// must have, and only an `OPTIONS` may have the asterisk form. Targets
+    /// in another form than their method allows fail with `Error::UriForm`.
+    pub fn from_target(method: &Method, target: &str) -> Result<RequestUri, Error> {
+        match *method {
+            Method::Connect => {
+                try!(check_authority_form(target));
+                Ok(RequestUri::Authority(target.to_owned()))
+            },
+            Method::Options if target == "*" => Ok(RequestUri::Star),
+            _ => match try!(target.parse()) {
+                RequestUri::Authority(_) | RequestUri::Star => Err(Error::UriForm),
+                uri => Ok(uri)
+            }
+        }
+    }
+}
+
+// Checks that `s` is a host and port, without userinfo, as the target of a
+// `CONNECT` is.
+fn check_authority_form(s: &str) -> Result<(), Error> {
+    if s == "*" || s.contains('/') {
+        return Err(Error::UriForm);
+    }
+    if s.contains('@') {
+        return Err(Error::Uri(UrlError::InvalidAtSymbolInUser));
+    }
+    let colon = match s.rfind(':') {
+        Some(colon) => colon,
+        None => return Err(Error::Uri(UrlError::InvalidPort))
+    };
+    let (host, port) = (&s[..colon], &s[colon + 1..]);
+    let digits = |s: &str| s.bytes().all(|b| b'0' <= b && b <= b'9');
+    if port.is_empty() || !digits(port) || port.parse::<u16>().is_err() {
+        return Err(Error::Uri(UrlError::InvalidPort));
+    }
+    if host.is_empty() {
+        return Err(Error::Uri(UrlError::EmptyHost));
+    }
+    if host.starts_with('[') {
+        if host.ends_with(']') && (host[1..host.len() - 1].parse::<Ipv6Addr>().is_ok() ||
+                                   is_zoned_ipv6_authority(host)) {
+            return Ok(());
+        }
+        return Err(Error::Uri(UrlError::InvalidIpv6Address));
+    }
+    let valid = host.bytes().all(|b| {
+        match b {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' | b'%' |
+            b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => true,
+            _ => false
+        }
+    });
+    if !valid {
+        return Err(Error::Uri(UrlError::InvalidDomainCharacter));
+    }
+    Ok(())
+}
+
+// Whether `s` is a bracketed IPv6 address with a zone ID (RFC 6874), and
+// maybe a port, which `Url` doesn't parse.
+fn is_zoned_ipv6_authority(s: &str) -> bool {
+    let end = match s.find(']') {
+        Some(end) if s.starts_with('[') => end,
+        _ => return false
+    };
+    let port = &s[end + 1..];
+    let digits = |s: &str| s.bytes().all(|b| b'0' <= b && b <= b'9');
+    if !port.is_empty() && !(port.starts_with(':') && digits(&port[1..])) {
+        return false;
+    }
+    let ip = &s[1..end];
+    match ip.find("%25") {
+        Some(i) => {
+            let zone = &ip[i + 3..];
+            ip[..i].parse::<Ipv6Addr>().is_ok() && !zone.is_empty() && zone.bytes().all(|b| {
+                match b {
+                    b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => true,
+                    _ => false
+                }
+            })
+        },
+        None => false
+    }
+}
+
 impl Display for RequestUri {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -94,6 +189,51 @@ fn test_uri_fromstr() {
     read("http://hyper.rs/", RequestUri::AbsoluteUri(Url::parse("http://hyper.rs/").unwrap()));
     read("hyper.rs", RequestUri::Authority("hyper.rs".to_owned()));
     read("/", RequestUri::AbsolutePath("/".to_owned()));
+    read("[::1]:443", RequestUri::Authority("[::1]:443".to_owned()));
+    read("[fe80::1%25eth0]:443", RequestUri::Authority("[fe80::1%25eth0]:443".to_owned()));
+    read("http://[::1]:8080/", RequestUri::AbsoluteUri(Url::parse("http://[::1]:8080/").unwrap()));
+
+    for bad in &["[fe80::1%25]:443", "[fe80::1%25eth0]x", "[fe80::1%25e%0]", "[::1"] {
+        assert!(bad.parse::<RequestUri>().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_uri_from_target() {
+    fn read(method: Method, s: &str) -> Result<RequestUri, Error> {
+        RequestUri::from_target(&method, s)
+    }
+
+    assert_eq!(read(Method::Connect, "hyper.rs:443").unwrap(),
+               RequestUri::Authority("hyper.rs:443".to_owned()));
+    assert_eq!(read(Method::Connect, "[::1]:8080").unwrap(),
+               RequestUri::Authority("[::1]:8080".to_owned()));
+    assert_eq!(read(Method::Connect, "[fe80::1%25eth0]:22").unwrap(),
+               RequestUri::Authority("[fe80::1%25eth0]:22".to_owned()));
+    assert_eq!(read(Method::Options, "*").unwrap(), RequestUri::Star);
+    assert_eq!(read(Method::Options, "/").unwrap(), RequestUri::AbsolutePath("/".to_owned()));
+    assert_eq!(read(Method::Get, "http://hyper.rs/").unwrap(),
+               RequestUri::AbsoluteUri(Url::parse("http://hyper.rs/").unwrap()));
+
+    fn error(method: Method, s: &str) -> String {
+        match read(method, s) {
+            Err(Error::Uri(e)) => format!("{:?}", e),
+            Err(e) => format!("{:?}", e),
+            Ok(uri) => panic!("{} parsed as {:?}", s, uri)
+        }
+    }
+
+    for &(bad, expected) in &[("hyper.rs", "InvalidPort"), ("hyper.rs:", "InvalidPort"),
+                              ("hyper.rs:65536", "InvalidPort"), (":443", "EmptyHost"),
+                              ("user@hyper.rs:443", "InvalidAtSymbolInUser"),
+                              ("/", "UriForm"), ("http://hyper.rs:443/", "UriForm"),
+                              ("*", "UriForm"), ("[::1:443", "InvalidIpv6Address"),
+                              ("[not-ip]:443", "InvalidIpv6Address"),
+                              ("hyper rs:443", "InvalidDomainCharacter")] {
+        assert_eq!(error(Method::Connect, bad), expected, "{}", bad);
+    }
+    assert_eq!(error(Method::Get, "*"), "UriForm");
+    assert_eq!(error(Method::Get, "hyper.rs:443"), "UriForm");
 }
 
 #[test]